@@ -0,0 +1,148 @@
+// instructions-per-second for a few representative workloads
+//
+//   cargo bench --bench core                          print the numbers
+//   cargo bench --bench core -- --save <file>         record them as a baseline
+//   cargo bench --bench core -- --compare <file>      fail when a workload got more than 10% slower
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use brick_station::hardware::{bus::Bus, cpu::Cpu, device::Device, interfaces::DeviceOps, ram::Ram};
+
+const INSTRUCTIONS_PER_SAMPLE : usize = 2_000_000;
+const SAMPLES                 : usize = 5;
+const TOLERANCE               : f64 = 0.10;
+
+struct Workload {
+    name    : &'static str,
+    program : &'static [u8],
+}
+
+const WORKLOADS: [Workload; 3] = [
+    // loads, stores, alu, compares, stack and subroutine calls, the mix nestest spends its time in
+    Workload { name: "mixed_loop", program: &[
+        0xA2, 0x00,             // 8000 LDX #$00
+        0xA0, 0x10,             // 8002 LDY #$10
+        0xA9, 0x01,             // 8004 LDA #$01
+        0x18,                   // 8006 CLC
+        0x69, 0x03,             // 8007 ADC #$03
+        0x95, 0x10,             // 8009 STA $10,X
+        0xE8,                   // 800B INX
+        0x29, 0x7F,             // 800C AND #$7F
+        0x48,                   // 800E PHA
+        0x68,                   // 800F PLA
+        0x20, 0x20, 0x80,       // 8010 JSR $8020
+        0x88,                   // 8013 DEY
+        0xD0, 0xEE,             // 8014 BNE $8004
+        0x4C, 0x00, 0x80,       // 8016 JMP $8000
+        0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA, // padding
+        0xC9, 0x40,             // 8020 CMP #$40
+        0x60,                   // 8022 RTS
+    ]},
+    // page to page copies, the access pattern of a sprite dma done by hand each frame
+    Workload { name: "page_copy", program: &[
+        0xA2, 0x00,             // 8000 LDX #$00
+        0xBD, 0x00, 0x02,       // 8002 LDA $0200,X
+        0x9D, 0x00, 0x03,       // 8005 STA $0300,X
+        0xE8,                   // 8008 INX
+        0xD0, 0xF7,             // 8009 BNE $8002
+        0xEE, 0x00, 0x02,       // 800B INC $0200
+        0x4C, 0x00, 0x80,       // 800E JMP $8000
+    ]},
+    // read-modify-write instructions on zero page and absolute indexed operands
+    Workload { name: "rmw_loop", program: &[
+        0xA2, 0x00,             // 8000 LDX #$00
+        0xE6, 0x10,             // 8002 INC $10
+        0x06, 0x11,             // 8004 ASL $11
+        0x26, 0x12,             // 8006 ROL $12
+        0xFE, 0x00, 0x04,       // 8008 INC $0400,X
+        0xDE, 0x00, 0x05,       // 800B DEC $0500,X
+        0x4E, 0x00, 0x06,       // 800E LSR $0600
+        0xE8,                   // 8011 INX
+        0xD0, 0xEE,             // 8012 BNE $8002
+        0x4C, 0x00, 0x80,       // 8014 JMP $8000
+    ]},
+];
+
+fn machine<'a>(program: &[u8]) -> Cpu<'a> {
+    let bus = Rc::new(RefCell::new(Bus::new()));
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(Device::Ram(Ram::new()))));
+    for (i, byte) in program.iter().enumerate() {
+        bus.borrow_mut().write(0x8000 + i as u16, *byte);
+    }
+    bus.borrow_mut().write(0xFFFC, 0x00);
+    bus.borrow_mut().write(0xFFFD, 0x80);
+
+    let mut cpu = Cpu::new();
+    cpu.bus = Some(bus);
+    cpu.reset();
+    cpu
+}
+
+// best of a few samples, in instructions and cycles per second
+fn measure(workload: &Workload) -> (f64, f64) {
+    let mut best = (0.0, 0.0);
+    for _ in 0..SAMPLES {
+        let mut cpu = machine(workload.program);
+        let start = Instant::now();
+        for _ in 0..INSTRUCTIONS_PER_SAMPLE {
+            cpu.step();
+        }
+        let elapsed = start.elapsed().max(Duration::from_nanos(1)).as_secs_f64();
+        let sample = (INSTRUCTIONS_PER_SAMPLE as f64 / elapsed, cpu.total_cycles as f64 / elapsed);
+        if sample.0 > best.0 {
+            best = sample;
+        }
+    }
+    best
+}
+
+fn read_baseline(path: &str) -> HashMap<String, f64> {
+    let content = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("cannot read baseline {}: {}", path, err);
+        process::exit(2);
+    });
+    content.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?.to_string(), parts.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).cloned();
+    let save = option("--save");
+    let baseline = option("--compare").map(|path| read_baseline(&path));
+
+    let mut results = Vec::new();
+    let mut regressed = false;
+    for workload in WORKLOADS.iter() {
+        let (instructions, cycles) = measure(workload);
+        let mut line = format!("{:<12} {:>8.2} M instr/s {:>8.2} M cycles/s", workload.name, instructions / 1e6, cycles / 1e6);
+        if let Some(reference) = baseline.as_ref().and_then(|baseline| baseline.get(workload.name)) {
+            let change = instructions / reference - 1.0;
+            line.push_str(&format!(" {:>+7.1}%", change * 100.0));
+            if change < -TOLERANCE {
+                line.push_str(" REGRESSED");
+                regressed = true;
+            }
+        }
+        println!("{}", line);
+        results.push(format!("{} {:.0}", workload.name, instructions));
+    }
+
+    if let Some(path) = save {
+        if let Err(err) = fs::write(&path, results.join("\n") + "\n") {
+            eprintln!("cannot write baseline {}: {}", path, err);
+            process::exit(2);
+        }
+    }
+    if regressed {
+        process::exit(1);
+    }
+}
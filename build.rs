@@ -0,0 +1,46 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// src/instructions.txt, a line per opcode : `opcode, mnemonic, addressing mode, base cycles`, and
+// `unofficial` after them for the ones the cpu runs that are not part of the instruction set,
+// turned into the const table src/hardware/opcode_table.rs includes. opcodes it does not list
+// are illegal too and run as a 1 cycle implied nop
+fn main() {
+    println!("cargo:rerun-if-changed=src/instructions.txt");
+    let description = fs::read_to_string("src/instructions.txt").expect("src/instructions.txt");
+
+    let mut table: Vec<Option<(String, String, u8, bool)>> = vec![None; 256];
+    for (number, line) in description.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let fields = line.split(',').map(str::trim).collect::<Vec<&str>>();
+        let invalid = |reason: &str| -> ! { panic!("src/instructions.txt line {}: {}", number + 1, reason) };
+        let legal = match (fields.len(), fields.get(4)) {
+            (4, _) => true,
+            (5, Some(&"unofficial")) => false,
+            _ => invalid("expected opcode, mnemonic, addressing mode, base cycles and maybe unofficial"),
+        };
+        let opcode = fields[0].parse::<u8>().unwrap_or_else(|_| invalid("opcode is not a byte"));
+        let cycles = fields[3].parse::<u8>().unwrap_or_else(|_| invalid("cycles is not a byte"));
+        // IZX, ZP0 ... name the AddressMode variants Izx, Zp0 ...
+        let mode = fields[2].chars().enumerate()
+            .map(|(at, c)| if at == 0 { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+            .collect::<String>();
+        if table[opcode as usize].is_some() {
+            invalid("opcode listed twice");
+        }
+        table[opcode as usize] = Some((fields[1].to_string(), mode, cycles, legal));
+    }
+
+    let entries = table.iter().enumerate()
+        .map(|(opcode, entry)| match entry {
+            Some((mnemonic, mode, cycles, legal)) => format!(
+                "    Instructions {{ mnemonic: Opcode::{}, opcode: {}, cycles: {}, address_mode: AddressMode::{}, legal: {} }},",
+                mnemonic, opcode, cycles, mode, legal),
+            None => format!(
+                "    Instructions {{ mnemonic: Opcode::NOP, opcode: {}, cycles: 1, address_mode: AddressMode::Imp, legal: false }},",
+                opcode),
+        })
+        .collect::<Vec<String>>();
+    let generated = format!("pub const OPCODES: [Instructions; 256] = [\n{}\n];\n", entries.join("\n"));
+    fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("opcode_table.rs"), generated).expect("opcode_table.rs");
+}
@@ -0,0 +1,12 @@
+// random instruction streams, register values and ram contents through the cpu, see
+// hardware::fuzzing for the layout of an input and the invariants checked
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use brick_station::hardware::fuzzing::run_cpu;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(violation) = run_cpu(data) {
+        panic!("{}", violation);
+    }
+});
@@ -0,0 +1,76 @@
+/* the brick_station core as a c library, build with : cargo build --release --features ffi
+ * and link against target/release/libbrick_station.so (.dylib, .dll)
+ *
+ * a handle is used from one thread at a time, every call accepts a null handle and then
+ * does nothing. a panic inside the core does not reach the caller, the call fails as it
+ * would on a null handle (-1, null, 0). buttons are bits A, B, Select, Start, Up, Down, Left,
+ * Right from bit 0 */
+#ifndef BRICK_STATION_H
+#define BRICK_STATION_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BS_API_VERSION 1
+
+typedef struct BsMachine BsMachine;
+
+/* BS_API_VERSION of the library, compare it with the header's */
+uint32_t bs_api_version(void);
+/* the rate of the samples bs_audio returns */
+uint32_t bs_audio_sample_rate(void);
+
+BsMachine *bs_create(void);
+void bs_destroy(BsMachine *machine);
+/* an independent copy of the machine in its current state, null if machine is, destroyed
+ * with bs_destroy on its own */
+BsMachine *bs_fork(BsMachine *machine);
+
+/* a program image of any supported format, name (may be null) helps detecting it by
+ * extension, 0 on success, -1 when it cannot be loaded. disk images need the bios first */
+int bs_load_rom(BsMachine *machine, const uint8_t *data, size_t size, const char *name);
+int bs_set_disk_system_bios(BsMachine *machine, const uint8_t *data, size_t size);
+
+void bs_reset(BsMachine *machine);
+void bs_power_cycle(BsMachine *machine);
+/* runs until the next frame is complete */
+void bs_run_frame(BsMachine *machine);
+/* run-ahead : the framebuffer shows the frame this many frames after the one just run,
+ * with the current input, cutting that many frames of input latency (0, the default, off) */
+void bs_set_run_ahead(BsMachine *machine, uint32_t frames);
+/* pads 0 - 3, read by the program from the next controller strobe on */
+void bs_set_input(BsMachine *machine, uint32_t port, uint8_t buttons);
+/* the buttons held as turbo ones, same bits, pressed and released in turn from one frame
+ * to the next at the turbo rate, presses a second from 1 to 30 (15 by default) */
+void bs_set_turbo_input(BsMachine *machine, uint32_t port, uint8_t buttons);
+void bs_set_turbo_rate(BsMachine *machine, uint8_t rate);
+
+/* the last complete frame, width * height 0x00RRGGBB pixels row by row, valid until
+ * the next bs_framebuffer call or bs_destroy, width and height may be null */
+const uint32_t *bs_framebuffer(BsMachine *machine, size_t *width, size_t *height);
+/* mono samples in -1 .. 1 produced since the previous call, valid until the next
+ * bs_audio call or bs_destroy */
+const float *bs_audio(BsMachine *machine, size_t *count);
+/* dynamic rate control : `queued` of the `size` samples the output queue holds are still
+ * to be played, the sample rate is bent by up to 0.5% to keep the queue half full */
+void bs_audio_queued(BsMachine *machine, size_t queued, size_t size);
+
+/* memory through the bus, a peek has no side effects on the devices */
+uint8_t bs_peek(BsMachine *machine, uint16_t address);
+void bs_write(BsMachine *machine, uint16_t address, uint8_t value);
+
+/* the state's size, the state is written only when out holds capacity bytes or more,
+ * call with a null out to learn the size */
+size_t bs_save_state(BsMachine *machine, uint8_t *out, size_t capacity);
+/* 0 on success, -1 for a state of another program or version, the machine is untouched then */
+int bs_load_state(BsMachine *machine, const uint8_t *data, size_t size);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,195 @@
+"""The brick_station core from python, for scripts and reinforcement learning environments.
+
+Build the library with ``cargo build --release --features python``, then
+
+    from brick_station import Emulator, A, RIGHT
+    with Emulator() as emulator:
+        emulator.load("game.nes")
+        emulator.step(60, buttons=RIGHT | A)
+        frame = emulator.framebuffer()   # numpy array, height x width x 3 rgb bytes
+
+The library is looked for in BRICK_STATION_LIBRARY, then in target/release and target/debug
+next to this directory. It goes through the c abi of include/brick_station.h with ctypes,
+so nothing has to be compiled for python itself. numpy is only needed for framebuffer().
+"""
+
+import ctypes
+import os
+import sys
+
+API_VERSION = 1
+
+# buttons, as bits of a pad's byte
+A, B, SELECT, START, UP, DOWN, LEFT, RIGHT = (1 << bit for bit in range(8))
+
+_NAMES = {"linux": "libbrick_station.so", "darwin": "libbrick_station.dylib", "win32": "brick_station.dll"}
+
+
+def _library_path():
+    if "BRICK_STATION_LIBRARY" in os.environ:
+        return os.environ["BRICK_STATION_LIBRARY"]
+    name = _NAMES.get(sys.platform, _NAMES["linux"])
+    root = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+    for profile in ("release", "debug"):
+        path = os.path.join(root, "target", profile, name)
+        if os.path.exists(path):
+            return path
+    raise OSError("%s not found, build it with cargo build --release --features python "
+                  "or point BRICK_STATION_LIBRARY at it" % name)
+
+
+def _load_library(path):
+    library = ctypes.CDLL(path)
+    machine = ctypes.c_void_p
+    signatures = {
+        "bs_api_version": ([], ctypes.c_uint32),
+        "bs_audio_sample_rate": ([], ctypes.c_uint32),
+        "bs_create": ([], machine),
+        "bs_destroy": ([machine], None),
+        "bs_fork": ([machine], machine),
+        "bs_load_rom": ([machine, ctypes.c_char_p, ctypes.c_size_t, ctypes.c_char_p], ctypes.c_int),
+        "bs_set_disk_system_bios": ([machine, ctypes.c_char_p, ctypes.c_size_t], ctypes.c_int),
+        "bs_reset": ([machine], None),
+        "bs_power_cycle": ([machine], None),
+        "bs_run_frame": ([machine], None),
+        "bs_set_input": ([machine, ctypes.c_uint32, ctypes.c_uint8], None),
+        "bs_set_turbo_input": ([machine, ctypes.c_uint32, ctypes.c_uint8], None),
+        "bs_set_turbo_rate": ([machine, ctypes.c_uint8], None),
+        "bs_framebuffer": ([machine, ctypes.POINTER(ctypes.c_size_t), ctypes.POINTER(ctypes.c_size_t)], ctypes.POINTER(ctypes.c_uint32)),
+        "bs_audio": ([machine, ctypes.POINTER(ctypes.c_size_t)], ctypes.POINTER(ctypes.c_float)),
+        "bs_peek": ([machine, ctypes.c_uint16], ctypes.c_uint8),
+        "bs_write": ([machine, ctypes.c_uint16, ctypes.c_uint8], None),
+        "bs_save_state": ([machine, ctypes.c_char_p, ctypes.c_size_t], ctypes.c_size_t),
+        "bs_load_state": ([machine, ctypes.c_char_p, ctypes.c_size_t], ctypes.c_int),
+    }
+    for name, (arguments, result) in signatures.items():
+        function = getattr(library, name)
+        function.argtypes = arguments
+        function.restype = result
+    if library.bs_api_version() != API_VERSION:
+        raise OSError("%s is of api version %d, %d expected" % (path, library.bs_api_version(), API_VERSION))
+    return library
+
+
+class Emulator:
+    """A console of its own, any number run side by side. Calls fail with OSError when the
+    core refuses them (a program it cannot load, a state of another program)."""
+
+    def __init__(self, library=None):
+        self._library = _load_library(library or _library_path())
+        self._handle = self._library.bs_create()
+        if not self._handle:
+            raise OSError("the machine could not be created")
+
+    def close(self):
+        if getattr(self, "_handle", None):
+            self._library.bs_destroy(self._handle)
+            self._handle = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.close()
+
+    def __del__(self):
+        self.close()
+
+    def fork(self):
+        """A machine of its own in the same state, to try other input from here."""
+        forked = Emulator.__new__(Emulator)
+        forked._library = self._library
+        forked._handle = self._library.bs_fork(self._handle)
+        if not forked._handle:
+            raise OSError("the machine could not be forked")
+        return forked
+
+    def load(self, program, name=None):
+        """A path, or the bytes of an image with its file name to tell the format by."""
+        if not isinstance(program, (bytes, bytearray)):
+            name = name or os.path.basename(program)
+            with open(program, "rb") as file:
+                program = file.read()
+        encoded = name.encode() if name else None
+        if self._library.bs_load_rom(self._handle, bytes(program), len(program), encoded) != 0:
+            raise OSError("%s could not be loaded" % (name or "the program"))
+
+    def set_disk_system_bios(self, bios):
+        if self._library.bs_set_disk_system_bios(self._handle, bytes(bios), len(bios)) != 0:
+            raise OSError("not a disk system bios")
+
+    def reset(self):
+        self._library.bs_reset(self._handle)
+
+    def power_cycle(self):
+        self._library.bs_power_cycle(self._handle)
+
+    def set_input(self, port, buttons):
+        """Pads 0 - 3, buttons of A, B, SELECT, START, UP, DOWN, LEFT, RIGHT or'ed together."""
+        self._library.bs_set_input(self._handle, port, buttons)
+
+    def set_turbo_input(self, port, buttons, rate=None):
+        if rate is not None:
+            self._library.bs_set_turbo_rate(self._handle, rate)
+        self._library.bs_set_turbo_input(self._handle, port, buttons)
+
+    def step(self, frames=1, buttons=None, port=0):
+        """Runs that many frames, with the buttons held on the port when given."""
+        if buttons is not None:
+            self.set_input(port, buttons)
+        for _ in range(frames):
+            self._library.bs_run_frame(self._handle)
+
+    def peek(self, address):
+        """A byte through the bus, without the side effects of a read on the devices."""
+        return self._library.bs_peek(self._handle, address & 0xFFFF)
+
+    def read(self, address, length):
+        return bytes(self.peek(address + offset) for offset in range(length))
+
+    def write(self, address, data):
+        """A byte, or bytes from the address on."""
+        for offset, value in enumerate([data] if isinstance(data, int) else data):
+            self._library.bs_write(self._handle, (address + offset) & 0xFFFF, value)
+
+    def _pixels(self):
+        width, height = ctypes.c_size_t(), ctypes.c_size_t()
+        pixels = self._library.bs_framebuffer(self._handle, ctypes.byref(width), ctypes.byref(height))
+        if not pixels:
+            raise OSError("no framebuffer")
+        return width.value, height.value, pixels
+
+    def framebuffer(self):
+        """The last complete frame as a height x width x 3 numpy array of rgb bytes, a copy."""
+        import numpy
+        width, height, pixels = self._pixels()
+        words = numpy.ctypeslib.as_array(pixels, shape=(height, width))
+        return numpy.stack([words >> 16, words >> 8, words], axis=-1).astype(numpy.uint8)
+
+    def framebuffer_rgb(self):
+        """The same without numpy : width, height and the rgb bytes, row by row."""
+        width, height, pixels = self._pixels()
+        rgb = bytearray(width * height * 3)
+        for i in range(width * height):
+            pixel = pixels[i]
+            rgb[i * 3:i * 3 + 3] = ((pixel >> 16) & 0xFF, (pixel >> 8) & 0xFF, pixel & 0xFF)
+        return width, height, bytes(rgb)
+
+    def audio(self):
+        """Mono samples in -1 .. 1 produced since the previous call."""
+        count = ctypes.c_size_t()
+        samples = self._library.bs_audio(self._handle, ctypes.byref(count))
+        return [samples[i] for i in range(count.value)] if samples else []
+
+    def sample_rate(self):
+        return self._library.bs_audio_sample_rate()
+
+    def save_state(self):
+        size = self._library.bs_save_state(self._handle, None, 0)
+        state = ctypes.create_string_buffer(size)
+        self._library.bs_save_state(self._handle, state, size)
+        return state.raw
+
+    def load_state(self, state):
+        if self._library.bs_load_state(self._handle, bytes(state), len(state)) != 0:
+            raise OSError("a state of another program or version")
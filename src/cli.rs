@@ -0,0 +1,474 @@
+use std::io::{Error, ErrorKind};
+use std::thread;
+
+use crate::config::{Config, DEFAULT_CONFIG_PATH};
+use crate::debugger::netplay::Role;
+use crate::debugger::sanitizer::Action;
+use crate::debugger::session;
+use crate::debugger::trace_format::{self, Field};
+use crate::hardware::timing;
+use crate::loader::loader::{self, LoadOptions};
+
+pub struct Arguments {
+    pub program_path : String,
+    pub patches : Vec<String>,     // ips or bps, applied in order
+    pub load_address : Option<u16>,
+    pub patch_reset_vector : Option<bool>,
+    pub listing_path : Option<String>,
+    pub debug_info_path : Option<String>,
+    pub config_path : String,
+    pub fuzz_seed : Option<u64>,
+    pub alignment : Option<u8>,    // the ppu's dot at power on, see timing::ALIGNMENTS
+    pub record_audio : Option<String>,
+    pub record_seconds : Option<f64>,
+    pub stems : bool,
+    pub record_frames : Option<String>,
+    pub run_frames : Option<u64>,
+    pub screenshot : Option<String>,
+    pub netplay : Option<Role>,
+    pub input_delay : Option<u8>,
+    pub remote : Option<u16>,
+    pub machine : Option<String>,
+    pub trace_states : Option<String>,
+    pub compare : Option<String>,  // the config of a reference machine run alongside, see comparison
+    pub sanitize : Option<Action>,
+    pub log : Option<String>,      // levels, e.g. warn,bus=debug
+    pub log_file : Option<String>,
+}
+
+impl Arguments {
+    pub fn usage() -> &'static str {
+        "usage : brick_station [<program>] [--patch <file.ips|file.bps>]... [--load-address <hex>] [--no-reset-vector] [--listing <file>] [--dbg <file>] [--config <file>] [--fuzz-seed <n>] [--alignment <0-2>] [--record-audio <file> [--record-seconds <n>] [--stems]] [--record-frames <dir/ or clip.png>] [--run-frames <n>] [--screenshot <file.png>] [--host <port> | --connect <address:port>] [--input-delay <frames>] [--remote <port>] [--machine <nes|file.toml>] [--trace-states <file>] [--compare <config file>] [--sanitize <off|log|break>] [--log <level,subsystem=level,...>] [--log-file <file>]"
+    }
+
+    pub fn parse(args: &[String]) -> Result<Arguments, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidInput, format!("{}\n{}", reason, Arguments::usage()));
+
+        let mut program_path = None;
+        let mut patches = Vec::new();
+        let mut load_address = None;
+        let mut patch_reset_vector = None;
+        let mut listing_path = None;
+        let mut debug_info_path = None;
+        let mut config_path = DEFAULT_CONFIG_PATH.to_string();
+        let mut fuzz_seed = None;
+        let mut alignment = None;
+        let mut record_audio = None;
+        let mut record_seconds = None;
+        let mut stems = false;
+        let mut record_frames = None;
+        let mut run_frames = None;
+        let mut screenshot = None;
+        let mut netplay = None;
+        let mut input_delay = None;
+        let mut remote = None;
+        let mut machine = None;
+        let mut trace_states = None;
+        let mut compare = None;
+        let mut sanitize = None;
+        let mut log = None;
+        let mut log_file = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--patch" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    patches.push(value.clone());
+                },
+                "--load-address" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    load_address = Some(loader::parse_address(value)
+                        .ok_or_else(|| invalid(format!("Invalid address: {}", value)))?);
+                },
+                "--listing" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    listing_path = Some(value.clone());
+                },
+                "--dbg" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    debug_info_path = Some(value.clone());
+                },
+                "--config" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    config_path = value.clone();
+                },
+                "--fuzz-seed" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    fuzz_seed = Some(value.parse::<u64>().map_err(|_| invalid(format!("Invalid seed: {}", value)))?);
+                },
+                "--alignment" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    alignment = Some(value.parse::<u8>().ok().filter(|alignment| *alignment < timing::ALIGNMENTS).ok_or_else(|| invalid(format!("Invalid alignment: {}, expected 0 to {}", value, timing::ALIGNMENTS - 1)))?);
+                },
+                "--record-audio" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    record_audio = Some(value.clone());
+                },
+                "--record-seconds" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    record_seconds = Some(value.parse::<f64>().ok().filter(|seconds| *seconds > 0.0).ok_or_else(|| invalid(format!("Invalid duration: {}", value)))?);
+                },
+                "--stems" => stems = true,
+                "--record-frames" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    record_frames = Some(value.clone());
+                },
+                "--run-frames" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    run_frames = Some(value.parse::<u64>().map_err(|_| invalid(format!("Invalid frame count: {}", value)))?);
+                },
+                "--screenshot" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    screenshot = Some(value.clone());
+                },
+                "--host" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    netplay = Some(Role::Host(value.parse::<u16>().map_err(|_| invalid(format!("Invalid port: {}", value)))?));
+                },
+                "--connect" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    netplay = Some(Role::Connect(value.clone()));
+                },
+                "--input-delay" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    input_delay = Some(value.parse::<u8>().map_err(|_| invalid(format!("Invalid input delay: {}", value)))?);
+                },
+                "--remote" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    remote = Some(value.parse::<u16>().map_err(|_| invalid(format!("Invalid port: {}", value)))?);
+                },
+                "--machine" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    machine = Some(value.clone());
+                },
+                "--trace-states" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    trace_states = Some(value.clone());
+                },
+                "--compare" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    compare = Some(value.clone());
+                },
+                "--sanitize" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    sanitize = Some(value.parse::<Action>().map_err(|err| invalid(err.to_string()))?);
+                },
+                "--log" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    log = Some(value.clone());
+                },
+                "--log-file" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    log_file = Some(value.clone());
+                },
+                "--no-reset-vector" => patch_reset_vector = Some(false),
+                "--reset-vector"    => patch_reset_vector = Some(true),
+                _ if arg.starts_with("--") => return Err(invalid(format!("Unknown option: {}", arg))),
+                _ => program_path = Some(arg.clone()),
+            }
+        }
+
+        // without one, the program the last session ran, and without that the interface
+        // starts in its program browser. a headless run needs one
+        let headless = run_frames.is_some() || screenshot.is_some();
+        let program_path = program_path
+            .or_else(|| session::last_program(&Config::load(&config_path).unwrap_or_default()))
+            .or_else(|| if headless { None } else { Some(String::new()) })
+            .ok_or_else(|| invalid("Missing program path".to_string()))?;
+
+        Ok(Arguments {
+            program_path,
+            patches,
+            load_address,
+            patch_reset_vector,
+            listing_path,
+            debug_info_path,
+            config_path,
+            fuzz_seed,
+            alignment,
+            record_audio,
+            record_seconds,
+            stems,
+            record_frames,
+            run_frames,
+            screenshot,
+            netplay,
+            input_delay,
+            remote,
+            machine,
+            trace_states,
+            compare,
+            sanitize,
+            log,
+            log_file,
+        })
+    }
+
+    // flags given on the command line win over whatever the base options say
+    pub fn load_options(&self, base: LoadOptions) -> LoadOptions {
+        LoadOptions {
+            load_address       : self.load_address.unwrap_or(base.load_address),
+            patch_reset_vector : self.patch_reset_vector.unwrap_or(base.patch_reset_vector),
+        }
+    }
+}
+
+// `brick_station nsf <file>` : plays a tune collection instead of debugging a program
+pub struct NsfArguments {
+    pub path     : String,
+    pub track    : Option<u8>, // 1 based, the file's start song when absent
+    pub seconds  : f64,        // length of wav output
+    pub wav_path : Option<String>,
+    pub stems    : bool, // each channel to its own wav next to the mixed one
+}
+
+impl NsfArguments {
+    pub fn usage() -> &'static str {
+        "usage : brick_station nsf <file> [--track <n>] [--seconds <n>] [--wav <file>] [--stems]"
+    }
+
+    pub fn parse(args: &[String]) -> Result<NsfArguments, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidInput, format!("{}\n{}", reason, NsfArguments::usage()));
+
+        let mut path = None;
+        let mut track = None;
+        let mut seconds = 150.0;
+        let mut wav_path = None;
+        let mut stems = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--track" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    track = Some(value.parse::<u8>().ok().filter(|track| *track > 0).ok_or_else(|| invalid(format!("Invalid track: {}", value)))?);
+                },
+                "--seconds" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    seconds = value.parse::<f64>().ok().filter(|seconds| *seconds > 0.0).ok_or_else(|| invalid(format!("Invalid duration: {}", value)))?;
+                },
+                "--wav" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    wav_path = Some(value.clone());
+                },
+                "--stems" => stems = true,
+                _ if arg.starts_with("--") => return Err(invalid(format!("Unknown option: {}", arg))),
+                _ => path = Some(arg.clone()),
+            }
+        }
+
+        Ok(NsfArguments {
+            path : path.ok_or_else(|| invalid("Missing nsf path".to_string()))?,
+            track,
+            seconds,
+            wav_path,
+            stems,
+        })
+    }
+}
+
+// `brick_station trace-diff <program>` : runs the program and compares its instruction
+// trace with a second configuration or a reference log
+pub struct TraceDiffArguments {
+    pub program_path : String,
+    pub options      : Vec<String>, // debugger flags for this side (--config, --machine, ...)
+    pub against      : Vec<String>, // the same for the other side, when no reference is given
+    pub reference    : Option<String>, // a log written by --write, nestest or Mesen
+    pub write        : Option<String>, // write this side's trace there instead of comparing
+    pub fields       : Vec<Field>,     // what a .jsonl or .csv trace written has, all by default
+    pub instructions : u64,
+    pub context      : usize, // lines shown around the first difference
+}
+
+impl TraceDiffArguments {
+    pub fn usage() -> &'static str {
+        "usage : brick_station trace-diff <program> [--patch <file>]... [--config <file>] [--machine <nes|file.toml>] [--load-address <hex>] [--fuzz-seed <n>] [--against-config <file>] [--against-machine <nes|file.toml>] [--against-fuzz-seed <n>] [--reference <log> | --write <log> [--fields <a,b,...>]] [--instructions <n>] [--context <n>]"
+    }
+
+    pub fn parse(args: &[String]) -> Result<TraceDiffArguments, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidInput, format!("{}\n{}", reason, TraceDiffArguments::usage()));
+
+        let mut program_path = None;
+        let mut options = Vec::new();
+        let mut against = Vec::new();
+        let mut reference = None;
+        let mut write = None;
+        let mut fields = Vec::new();
+        let mut instructions = 100_000;
+        let mut context = 5;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "--machine" | "--load-address" | "--fuzz-seed" | "--patch" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    options.extend([arg.clone(), value.clone()]);
+                },
+                "--against-config" | "--against-machine" | "--against-fuzz-seed" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    against.extend([arg.replace("--against-", "--"), value.clone()]);
+                },
+                "--reference" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    reference = Some(value.clone());
+                },
+                "--write" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    write = Some(value.clone());
+                },
+                "--fields" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    fields = trace_format::parse_fields(value).map_err(|err| invalid(err.to_string()))?;
+                },
+                "--instructions" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    instructions = value.parse::<u64>().ok().filter(|count| *count > 0).ok_or_else(|| invalid(format!("Invalid instruction count: {}", value)))?;
+                },
+                "--context" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    context = value.parse::<usize>().map_err(|_| invalid(format!("Invalid context: {}", value)))?;
+                },
+                _ if arg.starts_with("--") => return Err(invalid(format!("Unknown option: {}", arg))),
+                _ => program_path = Some(arg.clone()),
+            }
+        }
+        if reference.is_none() && write.is_none() && against.is_empty() {
+            return Err(invalid("Nothing to compare with : give --reference, --write or an --against- option".to_string()));
+        }
+
+        Ok(TraceDiffArguments {
+            program_path : program_path.ok_or_else(|| invalid("Missing program path".to_string()))?,
+            options,
+            against,
+            reference,
+            write,
+            fields,
+            instructions,
+            context,
+        })
+    }
+
+    // the debugger arguments one side runs with
+    pub fn side(&self, options: &[String]) -> Result<Arguments, Error> {
+        let mut args = vec![self.program_path.clone()];
+        args.extend(options.iter().cloned());
+        Arguments::parse(&args)
+    }
+}
+
+// `brick_station determinism <program>` : runs the program several times in the same
+// process with the same input and reports where the runs stop agreeing
+pub struct DeterminismArguments {
+    pub program_path : String,
+    pub options      : Vec<String>, // debugger flags the runs share (--config, --machine, ...)
+    pub frames       : u64,
+    pub inputs       : Option<String>, // an input macro played on the first pad, see macros
+}
+
+impl DeterminismArguments {
+    pub fn usage() -> &'static str {
+        "usage : brick_station determinism <program> [--patch <file>]... [--config <file>] [--machine <nes|file.toml>] [--load-address <hex>] [--fuzz-seed <n>] [--frames <n>] [--inputs <macro file>]"
+    }
+
+    pub fn parse(args: &[String]) -> Result<DeterminismArguments, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidInput, format!("{}\n{}", reason, DeterminismArguments::usage()));
+
+        let mut program_path = None;
+        let mut options = Vec::new();
+        let mut frames = 600;
+        let mut inputs = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "--machine" | "--load-address" | "--fuzz-seed" | "--patch" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    options.extend([arg.clone(), value.clone()]);
+                },
+                "--frames" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    frames = value.parse::<u64>().ok().filter(|count| *count > 0).ok_or_else(|| invalid(format!("Invalid frame count: {}", value)))?;
+                },
+                "--inputs" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    inputs = Some(value.clone());
+                },
+                _ if arg.starts_with("--") => return Err(invalid(format!("Unknown option: {}", arg))),
+                _ => program_path = Some(arg.clone()),
+            }
+        }
+
+        Ok(DeterminismArguments {
+            program_path : program_path.ok_or_else(|| invalid("Missing program path".to_string()))?,
+            options,
+            frames,
+            inputs,
+        })
+    }
+
+    // the debugger arguments a run is made with, `extra` after the shared ones
+    pub fn side(&self, extra: &[String]) -> Result<Arguments, Error> {
+        let mut args = vec![self.program_path.clone()];
+        args.extend(self.options.iter().cloned());
+        args.extend(extra.iter().cloned());
+        Arguments::parse(&args)
+    }
+}
+
+// `brick_station test <directory>` : the test roms of a directory and its manifest run
+// headlessly side by side, with a report for ci
+pub struct TestArguments {
+    pub directory : String,
+    pub jobs      : usize,          // roms run at the same time, one thread each
+    pub frames    : Option<u64>,    // the time limit of the roms the manifest gives none
+    pub junit     : Option<String>, // where the junit xml report goes
+    pub json      : Option<String>, // same for the json one
+}
+
+impl TestArguments {
+    pub fn usage() -> &'static str {
+        "usage : brick_station test <directory> [--jobs <n>] [--frames <n>] [--junit <file.xml>] [--json <file.json>]"
+    }
+
+    pub fn parse(args: &[String]) -> Result<TestArguments, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidInput, format!("{}\n{}", reason, TestArguments::usage()));
+
+        let mut directory = None;
+        let mut jobs = thread::available_parallelism().map_or(1, |jobs| jobs.get());
+        let mut frames = None;
+        let mut junit = None;
+        let mut json = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--jobs" | "--frames" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    let count = value.parse::<u64>().ok().filter(|count| *count > 0).ok_or_else(|| invalid(format!("Invalid count for {}: {}", arg, value)))?;
+                    match arg.as_str() {
+                        "--jobs" => jobs = count as usize,
+                        _ => frames = Some(count),
+                    }
+                },
+                "--junit" | "--json" => {
+                    let value = args.next().ok_or_else(|| invalid(format!("Missing value for {}", arg)))?;
+                    match arg.as_str() {
+                        "--junit" => junit = Some(value.clone()),
+                        _ => json = Some(value.clone()),
+                    }
+                },
+                _ if arg.starts_with("--") => return Err(invalid(format!("Unknown option: {}", arg))),
+                _ => directory = Some(arg.clone()),
+            }
+        }
+
+        Ok(TestArguments {
+            directory : directory.ok_or_else(|| invalid("Missing test directory".to_string()))?,
+            jobs,
+            frames,
+            junit,
+            json,
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const DEFAULT_CONFIG_PATH: &str = "brick_station.toml";
+
+// a flat view of a small toml subset : [section] headers, `key = value` pairs and # comments,
+// values are looked up as "section.key"
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config, Error> {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = Config::strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("config line {}: expected key = value", index + 1)))?;
+            let key = if section.is_empty() { key.trim().to_string() } else { format!("{}.{}", section, key.trim()) };
+            values.insert(key, value.trim().trim_matches('"').to_string());
+        }
+
+        Ok(Config { values })
+    }
+
+    fn strip_comment(line: &str) -> &str {
+        let mut quoted = false;
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '#' if !quoted => return &line[..i],
+                _ => {}
+            }
+        }
+        line
+    }
+
+    // a missing file is not an error, every setting has a default
+    pub fn load(path: &str) -> Result<Config, Error> {
+        if !Path::new(path).exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read(path)?;
+        Config::parse(&String::from_utf8_lossy(&content))
+    }
+
+    // values of other take precedence over the ones already present
+    pub fn overlay(&mut self, other: &Config) {
+        for (key, value) in other.values.iter() {
+            self.values.insert(key.clone(), value.clone());
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    pub fn get_or<T: std::str::FromStr>(&self, key: &str, default: T) -> T {
+        self.get(key).and_then(|value| value.parse().ok()).unwrap_or(default)
+    }
+}
@@ -1,2 +1,29 @@
+pub mod assembler;
+pub mod call;
 pub mod disassembler;
-pub mod debugger;
\ No newline at end of file
+pub mod edits;
+pub mod debugger;
+pub mod listing;
+pub mod debug_info;
+pub mod checkpoint;
+pub mod history;
+pub mod panes;
+pub mod preview;
+pub mod sanitizer;
+pub mod trace_filter;
+pub mod trace_diff;
+pub mod trace_format;
+pub mod savestates;
+pub mod battery;
+pub mod browser;
+pub mod explorer;
+pub mod session;
+pub mod teardown;
+pub mod netplay;
+pub mod macros;
+pub mod gamepad;
+pub mod comparison;
+pub mod determinism;
+pub mod regions;
+pub mod watchpoints;
+pub mod register_breaks;
\ No newline at end of file
@@ -0,0 +1,72 @@
+use std::io::{Error, ErrorKind};
+
+use crate::hardware::address_mode::AddressMode;
+use crate::hardware::opcode_table::OPCODES;
+
+// the operand's form, the width of its value picks zero page or absolute
+fn operand(text: &str) -> Option<(Vec<AddressMode>, u16)> {
+    let text = text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+    let value = |digits: &str| match digits.strip_prefix('$') {
+        Some(hex) if hex.len() == 2 || hex.len() == 4 => u16::from_str_radix(hex, 16).ok().map(|value| (value, hex.len() == 2)),
+        _ => None,
+    };
+    if text.is_empty() {
+        return Some((vec![AddressMode::Imp], 0));
+    }
+    if let Some(rest) = text.strip_prefix('#') {
+        return value(rest).filter(|(_, byte)| *byte).map(|(value, _)| (vec![AddressMode::Imm], value));
+    }
+    if let Some(rest) = text.strip_suffix(",X)").and_then(|rest| rest.strip_prefix('(')) {
+        return value(rest).filter(|(_, byte)| *byte).map(|(value, _)| (vec![AddressMode::Izx], value));
+    }
+    if let Some(rest) = text.strip_suffix("),Y").and_then(|rest| rest.strip_prefix('(')) {
+        return value(rest).filter(|(_, byte)| *byte).map(|(value, _)| (vec![AddressMode::Izy], value));
+    }
+    if let Some(rest) = text.strip_suffix(')').and_then(|rest| rest.strip_prefix('(')) {
+        return value(rest).filter(|(_, byte)| !*byte).map(|(value, _)| (vec![AddressMode::Ind], value));
+    }
+    let (rest, zero_page, absolute) = if let Some(rest) = text.strip_suffix(",X") {
+        (rest, AddressMode::Zpx, AddressMode::Abx)
+    } else if let Some(rest) = text.strip_suffix(",Y") {
+        (rest, AddressMode::Zpy, AddressMode::Aby)
+    } else {
+        (text.as_str(), AddressMode::Zp0, AddressMode::Abs)
+    };
+    // a bare word is a branch target too, the mnemonic decides
+    value(rest).map(|(value, byte)| match (byte, absolute) {
+        (true, _) => (vec![zero_page], value),
+        (false, AddressMode::Abs) => (vec![AddressMode::Abs, AddressMode::Rel], value),
+        (false, _) => (vec![absolute], value),
+    })
+}
+
+// one instruction as the disassembler writes it, `LDA #$01`, `STA ($10), Y`, `BNE $8010` or
+// `.byte $02`, at the address it goes to : branches take their target and store the offset
+pub fn assemble(line: &str, address: u16) -> Result<Vec<u8>, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidInput, format!("{}: {}", reason, line.trim()));
+    let line = line.trim();
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if mnemonic.eq_ignore_ascii_case(".byte") {
+        return match operand(rest) {
+            Some((modes, value)) if modes.contains(&AddressMode::Zp0) => Ok(vec![value as u8]),
+            _ => Err(invalid("Invalid byte")),
+        };
+    }
+    let (modes, value) = operand(rest).ok_or_else(|| invalid("Invalid operand"))?;
+    let instruction = OPCODES.iter()
+        .filter(|instruction| instruction.legal && instruction.mnemonic.to_string().eq_ignore_ascii_case(mnemonic))
+        .find(|instruction| modes.contains(&instruction.address_mode))
+        .ok_or_else(|| invalid("No such instruction"))?;
+    Ok(match instruction.address_mode {
+        AddressMode::Imp => vec![instruction.opcode],
+        AddressMode::Abs | AddressMode::Abx | AddressMode::Aby | AddressMode::Ind => vec![instruction.opcode, value as u8, (value >> 8) as u8],
+        AddressMode::Rel => {
+            let offset = value.wrapping_sub(address.wrapping_add(2)) as i16;
+            if !(-128..=127).contains(&offset) {
+                return Err(invalid("Branch out of range"));
+            }
+            vec![instruction.opcode, offset as u8]
+        },
+        _ => vec![instruction.opcode, value as u8],
+    })
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::hardware::machine::Machine;
+use crate::hardware::snapshot::{put_u16, put_u32, take_bytes, take_u16, take_u32};
+
+// `.sav` files start with a header so their format can change later, then the ram as the
+// board has it. files without the header are taken as the bare ram other emulators write
+const MAGIC   : &[u8; 4] = b"BSSV";
+const VERSION : u16 = 1;
+
+pub fn encode(ram: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ram.len() + 10);
+    out.extend_from_slice(MAGIC);
+    put_u16(&mut out, VERSION);
+    put_u32(&mut out, ram.len() as u32);
+    out.extend_from_slice(ram);
+    out
+}
+
+pub fn decode(content: &[u8]) -> Result<Vec<u8>, Error> {
+    if !content.starts_with(MAGIC) {
+        return Ok(content.to_vec());
+    }
+    let mut input = &content[MAGIC.len()..];
+    let version = take_u16(&mut input)?;
+    if version > VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Battery save of version {}, this build reads up to {}", version, VERSION)));
+    }
+    let length = take_u32(&mut input)? as usize;
+    Ok(take_bytes(&mut input, length)?.to_vec())
+}
+
+// written to a file next to it, then renamed over it : a crash halfway leaves the old
+// save whole, never half of each
+pub fn write_atomically(path: &Path, content: &[u8]) -> Result<(), Error> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let temporary = path.with_extension("sav.tmp");
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)
+}
+
+// the inserted board's battery backed ram and its file : the file is read into the board
+// when attached, and a change to the ram is written once it is `interval` old, so a game
+// writing its ram every frame does not rewrite the file every frame and a crash loses at
+// most that much. flush writes whatever changed at once, for quitting and switching programs
+#[derive(Debug, Clone)]
+pub struct BatterySave {
+    pub path   : PathBuf,
+    interval   : Duration,
+    written    : Vec<u8>,          // the ram as last read or written
+    changed_at : Option<Instant>,  // since when it differs from the file
+}
+
+fn with_ram<T>(machine: &Machine, action: impl FnOnce(Option<&[u8]>) -> T) -> T {
+    let bus = machine.bus.borrow();
+    let cartridge = bus.devices.iter()
+        .map(|mapping| mapping.device.borrow())
+        .find(|device| device.is_cartridge());
+    action(cartridge.as_ref().and_then(|device| device.prg_ram()))
+}
+
+impl BatterySave {
+    // None when the board has no ram to keep
+    pub fn attach(path: PathBuf, interval: Duration, machine: &Machine) -> Result<Option<BatterySave>, Error> {
+        if !with_ram(machine, |ram| ram.is_some()) {
+            return Ok(None);
+        }
+        match fs::read(&path) {
+            Ok(content) => {
+                let saved = decode(&content)?;
+                let bus = machine.bus.borrow();
+                if let Some(mapping) = bus.devices.iter().find(|mapping| mapping.device.borrow().is_cartridge()) {
+                    if let Some(ram) = mapping.device.borrow_mut().prg_ram_mut() {
+                        let length = ram.len().min(saved.len());
+                        ram[..length].copy_from_slice(&saved[..length]);
+                    }
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => {},
+            Err(err) => return Err(err),
+        }
+        let written = with_ram(machine, |ram| ram.map(<[u8]>::to_vec).unwrap_or_default());
+        Ok(Some(BatterySave { path, interval, written, changed_at : None }))
+    }
+
+    // often, every frame, true when the file was written
+    pub fn update(&mut self, machine: &Machine) -> Result<bool, Error> {
+        if !with_ram(machine, |ram| ram.is_some_and(|ram| ram != &self.written[..])) {
+            self.changed_at = None;
+            return Ok(false);
+        }
+        let changed_at = *self.changed_at.get_or_insert_with(Instant::now);
+        if changed_at.elapsed() < self.interval {
+            return Ok(false);
+        }
+        self.flush(machine)
+    }
+
+    pub fn flush(&mut self, machine: &Machine) -> Result<bool, Error> {
+        let ram = match with_ram(machine, |ram| ram.map(<[u8]>::to_vec)) {
+            Some(ram) if ram != self.written => ram,
+            _ => return Ok(false),
+        };
+        write_atomically(&self.path, &encode(&ram))?;
+        self.written = ram;
+        self.changed_at = None;
+        Ok(true)
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::loader::archive;
+
+// the program picker of the interface : the programs opened last, then a directory's
+// folders and files, walked with the arrows. a .zip is entered like a folder and lists the
+// programs it holds, so nothing is typed on the bottom line while the interface runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Recent(String),
+    Parent,
+    Directory(PathBuf),
+    Archive(PathBuf),
+    File(PathBuf),
+    Entry(String), // archive.zip#entry
+}
+
+#[derive(Debug, Clone)]
+pub struct Browser {
+    pub location : PathBuf, // a directory, or the archive being looked into
+    pub items    : Vec<Item>,
+    pub selected : usize,
+    pub error    : Option<String>, // why the location could not be listed
+    recent       : Vec<String>,
+}
+
+// what a terminal types for a file dropped on it, or a path pasted : quoted, with escaped
+// spaces, or a file:// url with %20s. the path as it would be given on the command line
+pub fn pasted_path(text: &str) -> String {
+    let text = text.trim();
+    let unquoted = ['\'', '"'].iter()
+        .find_map(|quote| text.strip_prefix(*quote).and_then(|text| text.strip_suffix(*quote)));
+    if let Some(url) = unquoted.unwrap_or(text).strip_prefix("file://") {
+        return percent_decoded(url.strip_prefix("localhost").unwrap_or(url));
+    }
+    if let Some(text) = unquoted {
+        return text.to_string();
+    }
+    let mut path = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && " '\"()[]&;!$".contains(next) => {
+                path.push(next);
+                chars.next();
+            },
+            _ => path.push(c),
+        }
+    }
+    path
+}
+
+fn percent_decoded(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.to_str().is_some_and(archive::is_archive)
+}
+
+impl Browser {
+    // at the directory of the program given, or the working directory
+    pub fn open(program_path: &str, recent: Vec<String>) -> Browser {
+        let (path, _) = archive::split(program_path);
+        let directory = Path::new(path).parent()
+            .filter(|parent| parent.is_dir())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+        let mut browser = Browser { location : PathBuf::new(), items : Vec::new(), selected : 0, error : None, recent };
+        browser.enter(directory);
+        browser
+    }
+
+    // straight into an archive, for one given on the command line holding several programs
+    pub fn open_archive(path: &str, recent: Vec<String>) -> Browser {
+        let mut browser = Browser::open(path, recent);
+        browser.enter(PathBuf::from(path.trim()));
+        browser
+    }
+
+    fn enter(&mut self, location: PathBuf) {
+        let location = location.canonicalize().unwrap_or(location);
+        let listed = if is_archive(&location) { Browser::list_archive(&location) } else { Browser::list_directory(&location) };
+        match listed {
+            Ok(listed) => {
+                self.items = self.recent.iter().cloned().map(Item::Recent)
+                    .chain(location.parent().map(|_| Item::Parent))
+                    .chain(listed)
+                    .collect();
+                self.location = location;
+                self.selected = 0;
+                self.error = None;
+            },
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    // folders first, then files, each by name, hidden ones left out
+    fn list_directory(directory: &Path) -> std::io::Result<Vec<Item>> {
+        let mut paths = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| !name.starts_with('.')))
+            .collect::<Vec<PathBuf>>();
+        paths.sort_by_key(|path| (!path.is_dir(), path.file_name().map(|name| name.to_ascii_lowercase())));
+        Ok(paths.into_iter().map(|path| if path.is_dir() {
+            Item::Directory(path)
+        } else if is_archive(&path) {
+            Item::Archive(path)
+        } else {
+            Item::File(path)
+        }).collect())
+    }
+
+    fn list_archive(path: &Path) -> std::io::Result<Vec<Item>> {
+        let entries = archive::entries(&fs::read(path)?)?;
+        Ok(archive::programs(&entries).iter()
+            .map(|entry| Item::Entry(format!("{}{}{}", path.display(), archive::SEPARATOR, entry.name)))
+            .collect())
+    }
+
+    pub fn label(item: &Item) -> String {
+        let name = |path: &Path| path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        match item {
+            Item::Recent(path) => format!("recent  {}", path),
+            Item::Parent => "../".to_string(),
+            Item::Directory(path) => format!("{}/", name(path)),
+            Item::Archive(path) => format!("{} [zip]", name(path)),
+            Item::File(path) => name(path),
+            Item::Entry(path) => archive::split(path).1.unwrap_or_default().to_string(),
+        }
+    }
+
+    pub fn select(&mut self, offset: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let last = self.items.len() as isize - 1;
+        self.selected = (self.selected as isize + offset).clamp(0, last) as usize;
+    }
+
+    pub fn leave(&mut self) {
+        if let Some(parent) = self.location.parent() {
+            self.enter(parent.to_path_buf());
+        }
+    }
+
+    // Enter on the selected item : folders and archives are entered, a program is returned
+    // for the caller to open. an archive holding one program opens it at once
+    pub fn activate(&mut self) -> Option<String> {
+        match self.items.get(self.selected).cloned()? {
+            Item::Recent(path) | Item::Entry(path) => Some(path),
+            Item::File(path) => Some(path.display().to_string()),
+            Item::Parent => {
+                self.leave();
+                None
+            },
+            Item::Directory(path) => {
+                self.enter(path);
+                None
+            },
+            Item::Archive(path) => {
+                match Browser::list_archive(&path).as_deref() {
+                    Ok([Item::Entry(only)]) => return Some(only.clone()),
+                    _ => self.enter(path),
+                }
+                None
+            },
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use super::edits::RegisterValue;
+use super::preview::Preview;
+
+// `8020` or `$8020 a=05 x=01`, the registers set before the routine is entered
+pub struct CallRequest {
+    pub target    : u16,
+    pub registers : Vec<RegisterValue>,
+}
+
+impl FromStr for CallRequest {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<CallRequest, Error> {
+        let mut parts = text.split_whitespace();
+        let target = parts.next()
+            .and_then(|target| u16::from_str_radix(target.trim_start_matches('$'), 16).ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid call: {}", text)))?;
+        Ok(CallRequest { target, registers: parts.map(str::parse).collect::<Result<Vec<RegisterValue>, Error>>()? })
+    }
+}
+
+// a routine run on a copy of the machine as if the program had called it there : the return
+// address pushed leads back to the pc, the routine is done once the pc is back with the stack
+// as it was, the machine itself is left as it is
+#[derive(Debug, Clone)]
+pub struct CallReport {
+    pub key       : (u64, usize), // the cycle and undo depth it was run at, as for the preview
+    pub target    : u16,
+    pub cycles    : Option<u64>, // None when it did not return within the limit
+    pub registers : Vec<String>,
+    pub writes    : Vec<(u16, u8, u8)>,
+}
+
+impl CallReport {
+    pub fn run(machine: &Machine, key: (u64, usize), request: &CallRequest, cycle_limit: u64) -> CallReport {
+        let copy = machine.fork();
+        while copy.cpu.borrow().cycle > 0 {
+            copy.tick();
+        }
+        let (before, back) = {
+            let mut cpu = copy.cpu.borrow_mut();
+            for RegisterValue(register, value) in request.registers.iter() {
+                register.set(&mut cpu.registers, *value);
+            }
+            let before = cpu.registers;
+            // as jsr would : the return address less one, high byte first
+            let back = before.pc.wrapping_sub(1);
+            cpu.write(0x0100 + before.sp as u16, (back >> 8) as u8);
+            cpu.write(0x0100 + before.sp.wrapping_sub(1) as u16, back as u8);
+            cpu.registers.sp = before.sp.wrapping_sub(2);
+            cpu.registers.pc = request.target;
+            (before, before.pc)
+        };
+        copy.bus.borrow_mut().recorded_writes = Some(Vec::new());
+
+        let started = copy.cpu.borrow().total_cycles;
+        let mut cycles = None;
+        while copy.cpu.borrow().total_cycles - started < cycle_limit {
+            copy.tick();
+            let cpu = copy.cpu.borrow();
+            if cpu.cycle == 0 && cpu.registers.pc == back && cpu.registers.sp == before.sp {
+                cycles = Some(cpu.total_cycles - started);
+                break;
+            }
+        }
+        let after = copy.cpu.borrow().registers;
+        let writes = copy.bus.borrow_mut().recorded_writes.take().unwrap_or_default();
+        CallReport {
+            key,
+            target    : request.target,
+            cycles,
+            registers : Preview::changed(&before, &after),
+            writes,
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![match self.cycles {
+            Some(cycles) => format!("CY: +{}", cycles),
+            None => "CY: no return".to_string(),
+        }];
+        lines.extend(self.registers.iter().cloned());
+        lines.extend(self.writes.iter().map(|(address, before, value)| format!("${:04X}: {:02X} > {:02X}", address, before, value)));
+        lines
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+use crate::config::Config;
+use super::debugger::State;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointPolicy {
+    pub interval_frames : u64,   // 0 disables automatic checkpoints
+    pub keep            : usize,
+}
+
+impl CheckpointPolicy {
+    pub fn from_config(config: &Config) -> CheckpointPolicy {
+        CheckpointPolicy {
+            interval_frames : config.get_or("checkpoints.interval_frames", 60),
+            keep            : config.get_or("checkpoints.keep", 100),
+        }
+    }
+}
+
+pub struct Checkpoint<'a> {
+    pub frame  : u64,
+    pub cycles : u64,
+    pub state  : State<'a>,
+}
+
+// snapshots taken every few frames while running, so rewinding far back
+// does not need a state per executed instruction
+pub struct Checkpoints<'a> {
+    pub policy     : CheckpointPolicy,
+    pub snapshots  : VecDeque<Checkpoint<'a>>,
+    pub next_frame : u64,
+}
+
+impl<'a> Checkpoints<'a> {
+    pub fn new(policy: CheckpointPolicy) -> Checkpoints<'a> {
+        Checkpoints {
+            policy,
+            snapshots  : VecDeque::new(),
+            next_frame : 0,
+        }
+    }
+
+    pub fn is_due(&self, frame: u64) -> bool {
+        self.policy.interval_frames != 0 && self.policy.keep != 0 && frame >= self.next_frame
+    }
+
+    pub fn record(&mut self, frame: u64, cycles: u64, state: State<'a>) {
+        self.snapshots.push_back(Checkpoint { frame, cycles, state });
+        while self.snapshots.len() > self.policy.keep {
+            self.snapshots.pop_front();
+        }
+        self.next_frame = frame + self.policy.interval_frames;
+    }
+
+    // the newest checkpoint strictly older than `cycles`, anything newer is discarded
+    pub fn rewind(&mut self, cycles: u64) -> Option<&Checkpoint<'a>> {
+        while self.snapshots.back().is_some_and(|checkpoint| checkpoint.cycles >= cycles) {
+            self.snapshots.pop_back();
+        }
+        let checkpoint = self.snapshots.back()?;
+        self.next_frame = checkpoint.frame + self.policy.interval_frames;
+        Some(checkpoint)
+    }
+}
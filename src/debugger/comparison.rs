@@ -0,0 +1,180 @@
+use std::io::Error;
+
+use crate::config::Config;
+use crate::hardware::controller::Peripheral;
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use crate::hardware::ppu::Decay;
+use crate::hardware::profile::MachineProfile;
+use crate::hardware::ram::PowerOnPattern;
+use crate::hardware::timing;
+
+// the cpu's internal ram, compared once a frame, the registers are at every instruction
+pub const COMPARED_RAM: u16 = 0x0800;
+// ram bytes listed at most in the comparison pane
+const LISTED_BYTES: usize = 16;
+
+// how a machine is built apart from its board : what memory holds at power on, the second
+// port and the timing and accuracy settings, the config's [power], [input] and [ppu]
+#[derive(Debug, Clone, Copy)]
+pub struct MachineSettings {
+    pub power_on    : PowerOnPattern,
+    pub peripheral  : Peripheral,
+    pub alignment   : u8,
+    pub decay       : Decay,
+    pub read_glitch : bool,
+}
+
+impl MachineSettings {
+    pub fn from_config(config: &Config) -> MachineSettings {
+        MachineSettings {
+            power_on    : config.get_or("power.ram", PowerOnPattern::Fill(0)),
+            peripheral  : config.get_or("input.port2", Peripheral::Controller),
+            alignment   : config.get_or("power.alignment", 0) % timing::ALIGNMENTS,
+            decay       : Decay {
+                open_bus : config.get_or("ppu.open_bus_decay", Decay::default().open_bus),
+                oam      : config.get_or("ppu.oam_decay", Decay::default().oam),
+            },
+            read_glitch : config.get_or("input.dmc_read_glitch", true),
+        }
+    }
+
+    pub fn build<'a>(&self, profile: &MachineProfile) -> Machine<'a> {
+        let machine = Machine::build(profile, self.power_on);
+        machine.align(self.alignment);
+        machine.set_decay(self.decay);
+        machine.set_read_glitch(self.read_glitch);
+        if profile.nes {
+            machine.connect(self.peripheral);
+        }
+        machine
+    }
+}
+
+// a/b comparison : a reference machine built from another config runs the same program in
+// lockstep with the debugged one, a run pauses at the first instruction they disagree on.
+// the reference is kept with each state, so stepping back and rewinding take it along
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub name       : String, // the config file the reference was built from
+    pub settings   : MachineSettings,
+    pub divergence : Option<Divergence>,
+}
+
+impl Comparison {
+    // the debugged machine's config with the other file's values over it
+    pub fn new(config: &Config, path: &str) -> Result<Comparison, Error> {
+        let mut overlaid = config.clone();
+        overlaid.overlay(&Config::load(path)?);
+        Ok(Comparison {
+            name       : path.to_string(),
+            settings   : MachineSettings::from_config(&overlaid),
+            divergence : None,
+        })
+    }
+
+    // the reference runs until it is at the cycle the machine is at, unless it waits at a
+    // divergence. the cpu executes a whole instruction on its first cycle so both show the
+    // registers they have after it
+    pub fn catch_up(&self, this: &Machine, reference: &Machine) {
+        let cycles = this.cpu.borrow().total_cycles;
+        while self.divergence.is_none() && reference.cpu.borrow().total_cycles < cycles {
+            reference.tick();
+        }
+    }
+
+    // the reference catches up with the machine and is compared with it, the ram too when
+    // asked, the reason to stop when they disagree. a divergence is only found once, the
+    // reference waits where it was from then on
+    pub fn check(&mut self, this: &Machine, reference: Option<&Machine>, ram: bool) -> Option<String> {
+        let reference = reference?;
+        if self.divergence.is_some() {
+            return None;
+        }
+        self.catch_up(this, reference);
+        self.divergence = Divergence::find(this, reference, ram);
+        let divergence = self.divergence.as_ref()?;
+        Some(format!("a/b {} differ from {}", divergence.describe(), self.name))
+    }
+}
+
+
+// what the two machines disagree on at a cycle : registers, the cycles left of the
+// instruction being run, and the ram bytes, each with this machine's value first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub cycles    : u64,
+    pub registers : Vec<(&'static str, u16, u16)>,
+    pub ram       : Vec<(u16, u8, u8)>,
+}
+
+impl Divergence {
+    pub fn find(this: &Machine, reference: &Machine, ram: bool) -> Option<Divergence> {
+        let divergence = Divergence {
+            cycles    : this.cpu.borrow().total_cycles,
+            registers : compare_registers(this, reference),
+            ram       : if ram { compare_ram(this, reference) } else { Vec::new() },
+        };
+        if divergence.registers.is_empty() && divergence.ram.is_empty() {
+            return None;
+        }
+        Some(divergence)
+    }
+
+    // "A, P, ram $0012 +2"
+    pub fn describe(&self) -> String {
+        let mut parts = self.registers.iter().map(|(name, _, _)| name.to_string()).collect::<Vec<String>>();
+        if let Some((address, _, _)) = self.ram.first() {
+            parts.push(match self.ram.len() {
+                1 => format!("ram ${:04X}", address),
+                count => format!("ram ${:04X} +{}", address, count - 1),
+            });
+        }
+        parts.join(", ")
+    }
+}
+
+fn registers(machine: &Machine) -> [(&'static str, u16); 7] {
+    let cpu = machine.cpu.borrow();
+    let registers = cpu.registers;
+    [("PC", registers.pc), ("A", registers.a as u16), ("X", registers.x as u16), ("Y", registers.y as u16),
+     ("SP", registers.sp as u16), ("P", registers.flags as u16), ("LEFT", cpu.cycle as u16)]
+}
+
+fn compare_registers(this: &Machine, reference: &Machine) -> Vec<(&'static str, u16, u16)> {
+    registers(this).iter().zip(registers(reference).iter())
+        .filter(|((_, this), (_, other))| this != other)
+        .map(|((name, this), (_, other))| (*name, *this, *other))
+        .collect()
+}
+
+fn compare_ram(this: &Machine, reference: &Machine) -> Vec<(u16, u8, u8)> {
+    let (this, reference) = (this.bus.borrow(), reference.bus.borrow());
+    (0..COMPARED_RAM)
+        .map(|address| (address, this.peek(address), reference.peek(address)))
+        .filter(|(_, this, other)| this != other)
+        .collect()
+}
+
+// both machines as they are now, a row each : the registers, then the ram bytes that differ
+pub fn lines(this: &Machine, reference: &Machine) -> Vec<String> {
+    let mut lines = vec![format!("{:<8}{:>8}{:>12}", "", "this", "reference")];
+    for ((name, this), (_, other)) in registers(this).iter().zip(registers(reference).iter()) {
+        let marker = if this != other { ">" } else { " " };
+        let width = if *name == "PC" { 4 } else { 2 };
+        lines.push(format!("{} {:<6}{:>8}{:>12}", marker, name, format!("{:0width$X}", this, width = width), format!("{:0width$X}", other, width = width)));
+    }
+    let cycles = (this.cpu.borrow().total_cycles, reference.cpu.borrow().total_cycles);
+    lines.push(format!("{} {:<6}{:>8}{:>12}", if cycles.0 != cycles.1 { ">" } else { " " }, "CYCLES", cycles.0, cycles.1));
+    let ram = compare_ram(this, reference);
+    if ram.is_empty() {
+        lines.push(format!("  ram ${:04X}-${:04X} the same", 0, COMPARED_RAM - 1));
+    }
+    for (address, this, other) in ram.iter().take(LISTED_BYTES) {
+        lines.push(format!("> ${:04X}{:>9}{:>12}", address, format!("{:02X}", this), format!("{:02X}", other)));
+    }
+    if ram.len() > LISTED_BYTES {
+        lines.push(format!("  {} more ram bytes differ", ram.len() - LISTED_BYTES));
+    }
+    lines
+}
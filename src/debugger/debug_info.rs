@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceLocation {
+    pub file : usize,
+    pub line : usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name  : String,
+    pub lines : Vec<String>, // empty when the file could not be found next to the .dbg
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    pub files     : HashMap<usize, SourceFile>,
+    pub locations : HashMap<u16, SourceLocation>,
+    pub addresses : HashMap<(usize, usize), u16>, // first address emitted by a file:line
+    pub symbols   : HashMap<u16, String>,         // labels by address
+}
+
+struct Record<'a> {
+    kind   : &'a str,
+    fields : HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn parse(line: &'a str) -> Option<Record<'a>> {
+        let mut parts = line.splitn(2, |c: char| c.is_whitespace());
+        let kind = parts.next()?;
+        let fields = parts.next()?.trim()
+            .split(',')
+            .filter_map(|field| {
+                let mut pair = field.splitn(2, '=');
+                Some((pair.next()?, pair.next()?))
+            })
+            .collect();
+        Some(Record { kind, fields })
+    }
+
+    fn number(&self, key: &str) -> Option<usize> {
+        let value = self.fields.get(key)?;
+        match value.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+
+    fn text(&self, key: &str) -> Option<String> {
+        Some(self.fields.get(key)?.trim_matches('"').to_string())
+    }
+
+    fn ids(&self, key: &str) -> Vec<usize> {
+        self.fields.get(key)
+            .map(|value| value.split('+').filter_map(|id| id.parse().ok()).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl DebugInfo {
+    pub fn load(path: &str) -> Result<DebugInfo, Error> {
+        let path = Path::new(path.trim());
+        let content = fs::read(path)?;
+        let mut info = DebugInfo::parse(&String::from_utf8_lossy(&content))?;
+
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        for file in info.files.values_mut() {
+            if let Ok(source) = fs::read(directory.join(&file.name)) {
+                file.lines = String::from_utf8_lossy(&source).lines().map(str::to_string).collect();
+            }
+        }
+        Ok(info)
+    }
+
+    // ld65 --dbgfile output, only file, seg, span and line records are needed for line mapping,
+    // sym records give the labels
+    pub fn parse(content: &str) -> Result<DebugInfo, Error> {
+        let records = content.lines().filter_map(Record::parse).collect::<Vec<Record>>();
+        if !records.iter().any(|record| record.kind == "version") {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a ca65 debug info file"));
+        }
+
+        let mut files = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut symbols = HashMap::new();
+        for record in records.iter() {
+            match record.kind {
+                "file" => if let (Some(id), Some(name)) = (record.number("id"), record.text("name")) {
+                    files.insert(id, SourceFile { name, lines: Vec::new() });
+                },
+                "seg" => if let (Some(id), Some(start)) = (record.number("id"), record.number("start")) {
+                    segments.insert(id, start);
+                },
+                "span" => if let (Some(id), Some(seg), Some(start), Some(size)) = (record.number("id"), record.number("seg"), record.number("start"), record.number("size")) {
+                    spans.insert(id, (seg, start, size));
+                },
+                "sym" => if let (Some(name), Some(value), Some("lab")) = (record.text("name"), record.number("val"), record.fields.get("type").copied()) {
+                    symbols.entry(value as u16).or_insert(name);
+                },
+                _ => {}
+            }
+        }
+
+        let mut locations = HashMap::new();
+        let mut addresses = HashMap::new();
+        // assembler lines (type 0) win over macro expansions and C lines mapped to the same bytes
+        let mut lines = records.iter().filter(|record| record.kind == "line").collect::<Vec<&Record>>();
+        lines.sort_by_key(|record| record.number("type").unwrap_or(0) != 0);
+        for record in lines {
+            let (file, line) = match (record.number("file"), record.number("line")) {
+                (Some(file), Some(line)) => (file, line),
+                _ => continue,
+            };
+            for span in record.ids("span") {
+                if let Some((seg, start, size)) = spans.get(&span) {
+                    let base = segments.get(seg).copied().unwrap_or(0) + start;
+                    for address in base..base + size {
+                        locations.entry(address as u16).or_insert(SourceLocation { file, line });
+                    }
+                    let first = addresses.entry((file, line)).or_insert(base as u16);
+                    *first = (*first).min(base as u16);
+                }
+            }
+        }
+
+        Ok(DebugInfo { files, locations, addresses, symbols })
+    }
+
+    pub fn symbol(&self, address: u16) -> Option<&str> {
+        self.symbols.get(&address).map(String::as_str)
+    }
+
+    pub fn location(&self, address: u16) -> Option<SourceLocation> {
+        self.locations.get(&address).copied()
+    }
+
+    // `main.s:12`, the file is matched by full name or by its last path component
+    pub fn resolve(&self, file_and_line: &str) -> Option<u16> {
+        let (name, line) = file_and_line.trim().rsplit_once(':')?;
+        let line = line.trim().parse::<usize>().ok()?;
+        let file = self.files.iter()
+            .find(|(_, file)| file.name == name || Path::new(&file.name).file_name().and_then(|f| f.to_str()) == Some(name))
+            .map(|(id, _)| *id)?;
+        self.addresses.get(&(file, line)).copied()
+    }
+}
@@ -1,18 +1,52 @@
-use crate::hardware::device::Device;
+use crate::cli::Arguments;
+use crate::config::Config;
 use crate::hardware::interfaces::DeviceOps;
 use crate::hardware::bus::*;
+use crate::hardware::fds;
 use crate::hardware::cpu::*;
-use crate::hardware::ram::*;
+use crate::hardware::registers::Flag;
+use crate::hardware::controller::{self, Button, Peripheral, Turbo};
+use crate::hardware::machine::Machine;
+use crate::hardware::profile::MachineProfile;
+use crate::hardware::ram::PowerOnPattern;
+use crate::hardware::ppu::Decay;
+use crate::hardware::palette::Palette;
+use crate::hardware::inspect::{self, StateNode};
+use crate::hardware::sanitizer::Sanitizer;
+use crate::hardware::watches::{Access, Watches};
+use crate::hardware::snapshot::Snapshot;
+use crate::hardware::state_hash::StateHash;
+use crate::hardware::timing::{self, RasterPosition};
+use crate::loader::{archive, loader};
+use crate::loader::loader::LoadOptions;
+use crate::loader::program::Program;
+use crate::loader::rom_database::{crc32, GameSettings};
+use crate::hardware::audio::SAMPLE_RATE;
+use crate::player::wav::WavRecording;
+use crate::player::frames::{self, FrameRecorder};
+use crate::player::png;
+use crate::player::video::{Filter, VideoOptions};
+use crate::remote::server;
+use crate::emulator;
+use crate::logging;
+use crate::test_rom;
 
 use std::cell::RefCell;
-use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::io::ErrorKind;
-use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::rc::Rc;
-use std::{io, io::Error};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{io, io::{BufWriter, Error, Write}};
+use crossterm::cursor::{Hide, MoveTo, Show};
 use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType};
 use tui::Frame;
 use tui::backend::Backend;
 use tui::{backend::CrosstermBackend, Terminal};
@@ -28,15 +62,144 @@ use crossterm::{
 };
 
 use super::disassembler::Disassembler;
+use super::listing::Listing;
+use super::debug_info::DebugInfo;
+use super::checkpoint::{CheckpointPolicy, Checkpoints};
+use super::history::{Executed, History, HistoryPolicy, Intervention};
+use super::sanitizer::{SanitizerPolicy, SanitizerReport};
+use super::trace_filter::TraceFilter;
+use super::trace_format::{TraceRecord, TraceWriter};
+use super::savestates::{self, Bookmark, SaveLibrary, SlotInfo};
+use super::battery::BatterySave;
+use super::macros::{InputMacro, MacroTake};
+use super::regions::{self, Region};
+use super::watchpoints::{self, Watchpoint};
+use super::register_breaks::{self, RegisterBreak};
+use super::gamepad::Gamepads;
+use super::browser::{self, Browser};
+use super::explorer::Explorer;
+use super::netplay::{Netplay, Role, DEFAULT_INPUT_DELAY};
+use super::panes::{Divider, Panes};
+use super::teardown::TerminalGuard;
+use super::preview::Preview;
+use super::call::{CallReport, CallRequest};
+use super::edits::{self, Edit, EditLog, Register, RegisterValue};
+use super::session::{self, Session};
+use super::comparison::{self, Comparison, MachineSettings};
+
+// upper bound for a single run (about a hundred frames), keeps a runaway loop from freezing the debugger
+const RUN_CYCLE_LIMIT: usize = 3_000_000;
+// breakpoint classes by name, runs stop after the instruction or interrupt sequence doing it
+const BREAK_CLASSES: [(&str, u8, &str); 6] = [
+    ("nmi", EVENT_NMI, "nmi entry"),
+    ("irq", EVENT_IRQ, "irq entry"),
+    ("brk", EVENT_BRK, "brk"),
+    ("rti", EVENT_RTI, "rti"),
+    ("vectors", EVENT_VECTOR_READ, "vector table read"),
+    ("stack", EVENT_STACK_WRAP, "stack wrap"),
+];
+// a history entry as shown : the line, the registers it kept, the label of the region its
+// operand names and the region's color
+pub type HistoryLine = (Executed, String, Option<String>, Option<(String, Color)>);
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// while in turbo the screen is only refreshed this often
+const TURBO_REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_SPEED: u32 = 8;
+// how long a paused interface waits for a key before looking at the gamepad being mapped
+const GAMEPAD_MAPPING_POLL_MS: u64 = 20;
+// set by a prompt, the interface then draws the whole screen again
+static PROMPTED: AtomicBool = AtomicBool::new(false);
+// seconds a change to battery backed ram waits before it is written
+const DEFAULT_BATTERY_FLUSH: f64 = 2.0;
+// milliseconds of sound the mixer holds at most, anything older is dropped
+const DEFAULT_AUDIO_LATENCY: u32 = 100;
+// rows Page Up / Page Down move in the program browser and the state explorer
+const BROWSER_PAGE: isize = 10;
+const MACHINE_MENU: [&str; 5] = ["Load program", "Eject program", "Soft reset", "Power cycle", "Palette"];
 
 pub struct State<'a> {
-    pub bus: Rc<RefCell<Bus<'a>>>,
-    pub cpu: Rc<RefCell<Cpu<'a>>>,
-    pub dis: Disassembler
+    pub machine: Machine<'a>,
+    pub dis: Disassembler,
+    pub reference: Option<Machine<'a>>, // the machine an a/b comparison runs alongside, see comparison
 }
 
 pub struct App<'a> {
     pub memory_page_index: i32,
+    pub memory_row: i32,               // the memory pane starts this many lines of 16 bytes into the page
+    pub selected_address: Option<u16>, // the memory cell last clicked
+    pub program_view: Option<usize>,   // the first disassembly line shown, None follows the pc
+    pub split: f32,                    // the left panes' share of the width
+    pub program_top: f32,              // where the program pane starts, as a share of the height
+    pub dragging: Option<Divider>,
+    pub panes: std::cell::Cell<Panes>,
+    pub config: Config,
+    pub program_path: String,
+    pub patches: Vec<String>,          // --patch, applied over program_path when it is loaded
+    pub load_options: LoadOptions,
+    pub game: Option<GameSettings>,
+    pub program: Program,
+    pub listing: Option<Listing>,
+    pub debug_info: Option<DebugInfo>,
+    pub breakpoints: BTreeSet<u16>,
+    pub watchpoints: Vec<Watchpoint>, // data breakpoints on reads and writes, kept with the session
+    pub register_breaks: BTreeSet<RegisterBreak>, // the ppu and apu registers reads or writes of stop, same
+    pub show_source: bool,
+    pub show_terminal: bool,
+    pub show_log: bool,
+    pub show_history: bool,
+    pub show_comparison: bool,
+    pub checkpoints: Checkpoints<'a>,
+    pub history: History,
+    pub trace_filter: TraceFilter,
+    pub return_break: Option<usize>, // stop once the call stack is back to this depth
+    pub break_events: u8,            // the cpu events of the breakpoint classes set
+    pub stop_reason: Option<String>, // why the last run stopped early, when it was not a plain breakpoint
+    pub stack_floor: u8,             // with the stack class set, pushes below $0100 + this stop too
+    pub sanitizer: SanitizerReport,
+    pub comparison: Option<Comparison>,    // --compare, the settings of the reference and where it diverged
+    pub preview: RefCell<Option<Preview>>, // what the next step does, while paused
+    pub keep_session: bool,                // breakpoints and layout are saved and restored per program
+    pub edits: EditLog,
+    pub selected_register: Option<Register>, // the register last clicked
+    pub call: Option<CallReport>,            // the last routine called from the debugger, shown until something changes
+    pub test_rom_seen: bool,                 // the terminal pane was opened for a test rom of the $6000 protocol
+    pub running: bool,
+    pub turbo: bool,
+    pub speed: u32,
+    pub held_buttons: [u8; 4],
+    pub held_turbo: [u8; 4],           // the buttons held as turbo ones, pressed and released in turn
+    pub auto_fire: Turbo,
+    pub input_port: usize,
+    pub pad_bindings: HashMap<char, (usize, Button, bool)>,
+    pub macros: HashMap<char, InputMacro>,   // played on the selected pad by their key, kept with the session
+    pub regions: Vec<Region>,                // named addresses, kept with the session
+    pub macro_recording: Option<MacroTake>,
+    pub macro_playing: Option<MacroTake>,
+    pub gamepads: Gamepads,
+    pub peripheral: Peripheral,
+    pub profile: MachineProfile,
+    pub saves: SaveLibrary,
+    pub slots: Vec<Option<SlotInfo>>,
+    pub slot_picker: Option<usize>,
+    pub bookmarks: Vec<Bookmark>,
+    pub menu: Option<usize>,
+    pub browser: Option<Browser>,      // the program picker, open over the left pane
+    pub explorer: Option<Explorer>,    // the machine state tree, same
+    pub power_on: PowerOnPattern,
+    pub alignment: u8,                 // the ppu's dot at power on, kept through power cycles
+    pub decay: Decay,                  // same, what of the ppu fades when left alone
+    pub read_glitch: bool,             // same, whether dmc fetches repeat controller reads
+    pub recording: Option<WavRecording>,
+    pub frame_recorder: Option<FrameRecorder>,
+    pub video: VideoOptions,
+    pub palette: Palette,
+    pub run_ahead: u32,
+    pub battery: Option<BatterySave>,
+    pub screenshot: Option<(u64, u32)>,
+    pub netplay: Option<Netplay>,
+    pub netplay_error: Option<String>,
+    pub state_hash: StateHash,
+    pub state_trace: Option<BufWriter<File>>,
     pub previous_machine_state: Vec<State<'a>>,
     pub inner_machine_state: Rc<RefCell<State<'a>>>,
 }
@@ -44,87 +207,115 @@ pub struct App<'a> {
 impl<'a> State<'a> {
     pub fn build_view<B: Backend>(f: &mut Frame<B>, app: &App)  {
         let size = Rect::new(0, 0, f.size().width, f.size().height);
+        app.panes.set(Panes { size, pages: Rect::default(), memory: Rect::default(), ..app.panes.get() });
         let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
         f.render_widget(block, size);
 
-        State::memory_viewer(f, app);
+        if app.browser.is_some() {
+            State::browser_viewer(f, app);
+        } else if app.explorer.is_some() {
+            State::explorer_viewer(f, app);
+        } else if app.menu.is_some() {
+            State::menu_viewer(f, app);
+        } else if app.slot_picker.is_some() {
+            State::savestate_viewer(f, app);
+        } else if app.show_comparison && app.comparison.is_some() {
+            State::comparison_viewer(f, app);
+        } else if app.show_history {
+            State::history_viewer(f, app);
+        } else if app.show_log {
+            State::log_viewer(f, app);
+        } else if app.show_terminal {
+            State::terminal_viewer(f, app);
+        } else if app.show_source && app.debug_info.is_some() {
+            State::source_viewer(f, app);
+        } else {
+            State::memory_viewer(f, app);
+        }
         State::program_viewer(f, app);
         State::processor_viewer(f, app);
     }
 
-    pub fn initiate_state() -> Rc<RefCell<State<'a>>> {
-        let ram = Rc::new(RefCell::new(Device::Ram(Ram::new())));
-        let bus = Rc::new(RefCell::new(Bus::new()));
-        let cpu = Rc::new(RefCell::new(Cpu::new()));
-        
-        bus.borrow_mut().add_device(ram.clone());
-
-        (*cpu).borrow_mut().bus = Some(bus.clone());
-
-
-        let state = Rc::new(RefCell::new(State {
-            bus : Rc::clone(&bus),
-            cpu : Rc::clone(&cpu),
-            dis : Disassembler::new()
-        }));
+    pub fn initiate_state(profile: &MachineProfile, pattern: PowerOnPattern, peripheral: Peripheral, alignment: u8, decay: Decay, read_glitch: bool) -> Rc<RefCell<State<'a>>> {
+        let settings = MachineSettings { power_on: pattern, peripheral, alignment, decay, read_glitch };
+        Rc::new(RefCell::new(State {
+            machine   : settings.build(profile),
+            dis       : Disassembler::new(),
+            reference : None,
+        }))
+    }
 
-        
+    // asked on the bottom line, out of raw mode so the answer is echoed and edited as usual,
+    // the mouse is let go while typing, its reports would land in the answer
+    pub fn prompt(message: &str) -> Result<String, Error> {
+        let (_, rows) = terminal::size().unwrap_or((80, 24));
+        let _ = terminal::disable_raw_mode();
+        PROMPTED.store(true, Ordering::Relaxed);
+        if execute!(io::stdout(), DisableMouseCapture, MoveTo(0, rows.saturating_sub(1)), Clear(ClearType::CurrentLine), Show, Print(message)).is_err() {
+            return Err(Error::other("Error"));
+        }
+        let mut input = String::new();
+        let read = io::stdin().read_line(&mut input);
+        let _ = terminal::enable_raw_mode();
+        let _ = execute!(io::stdout(), Hide, EnableMouseCapture);
+        read?;
+        Ok(input.trim().to_string())
+    }
 
-        (*bus).borrow_mut().write(0xFFFC, 0x00);
-        (*bus).borrow_mut().write(0xFFFC + 1, 0x80);
-        (*cpu).borrow_mut().reset();
+    pub fn load_program_from_file(possible_path : Option<String>, origin: u16) -> Result<Program, Error> {
+        let prompt = "Enter a file name: ";
 
+        if let Some(path) = possible_path {
+            return loader::load(&path, origin)
+        } 
 
-        state
+        let path = browser::pasted_path(&State::prompt(prompt)?);
+        loader::load(&path, origin)
     }
 
-    pub fn load_program_from_file(possible_path : Option<String>) -> Result<Vec<u8>, Error> {
-        let prompt = "Enter a file name: ";
-        
-        let parse_file = |path: String| {
-            if let Ok(metadata_file) = File::options()
-                                                    .read(true)
-                                                    .open(path.trim())
-            {
-                // readlines 
-                let reader = BufReader::new(metadata_file);
-                let mut lines = Vec::new();
-                let mut bytes = Vec::new();
-                for line in reader.lines() {
-                    // read line
-                    if let Ok(line) = line {
-                        lines.push(line);
-                    }
-                }
-                
-                for line in lines {
-                    let mut split = line.split_whitespace();
-                    while let Some(byte) = split.next() {
-                        if let Ok(byte) = u8::from_str_radix(byte, 16) {
-                            bytes.push(byte);
-                        }
-                    }
+    // `[pad1]` to `[pad4]` sections binding a button to a key, `a = 1`, pressing the key
+    // toggles the button like F1 - F8 do for the selected pad, `turbo_a` and `turbo_b` bind
+    // the turbo ones, true in the binding. command keys are left out with a warning
+    pub fn pad_bindings(config: &Config) -> HashMap<char, (usize, Button, bool)> {
+        let mut bindings = HashMap::new();
+        for pad in 0..4 {
+            let buttons = Button::ALL.iter().map(|button| (button.name().to_string(), *button, false))
+                .chain([Button::A, Button::B].iter().map(|button| (format!("turbo_{}", button.name()), *button, true)));
+            for (name, button, turbo) in buttons {
+                let key = config.get(&format!("pad{}.{}", pad + 1, name)).and_then(|key| key.chars().next());
+                match key {
+                    Some(key) if State::is_command_key(key) =>
+                        crate::log_event!(Loader, Warn, "pad{}.{} = {} is left out, {} is a command key", pad + 1, name, key, key),
+                    Some(key) => { bindings.insert(key, (pad, button, turbo)); },
+                    None => {},
                 }
-                return Ok(bytes);
             }
-            return Err(Error::new(ErrorKind::Other, "Error"))
-        };
+        }
+        bindings
+    }
 
-        if let Some(path) = possible_path {
-            return parse_file(path)
-        } 
+    pub fn load_options_from_prompt(current: LoadOptions) -> Result<LoadOptions, Error> {
+        let address = State::prompt(&format!("Enter a load address [{:04X}]: ", current.load_address))?;
+        let load_address = if address.is_empty() {
+            current.load_address
+        } else {
+            loader::parse_address(&address)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid address: {}", address)))?
+        };
 
-        if let Ok(_) = execute!(io::stdout(), Print(prompt)) {
-            let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-            return parse_file(input);
-        }
-        return Err(Error::new(ErrorKind::Other, "Error"))
+        let patch = State::prompt(&format!("Patch reset vector? (y/n) [{}]: ", if current.patch_reset_vector { "y" } else { "n" }))?;
+        let patch_reset_vector = match patch.to_ascii_lowercase().as_str() {
+            "" => current.patch_reset_vector,
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid answer: {}", patch))),
+        };
 
+        Ok(LoadOptions { load_address, patch_reset_vector })
     }
 
     pub fn memory_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
-        let size = Rect::new(0, 0, (f.size().width as f32 * 0.70) as u16, f.size().height);
+        let size = app.left_pane(f.size());
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .margin(5)
@@ -134,7 +325,14 @@ impl<'a> State<'a> {
         let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
         f.render_widget(block, size);
 
-        let build_page_table = |bus: &Rc<RefCell<Bus>>, page: u16| {
+        // what changed since the state stepping back would return to, only the pages
+        // written since then are compared
+        let previous = app.previous_machine_state.last().map(|state| state.machine.bus.clone());
+        let dirty = previous.as_ref()
+            .map(|previous| (*app.inner_machine_state).borrow().machine.bus.borrow().dirty_since(&previous.borrow().write_counts()))
+            .unwrap_or_default();
+
+        let build_page_table = |bus: &Rc<RefCell<Bus>>, page: u16, base: u16| {
             let header_cells = (0..=16)
                 .map(|i| format!("{:02X}", i))
                 .map(|s| Cell::from(s).style(Style::default().fg(Color::Red)));
@@ -146,12 +344,20 @@ impl<'a> State<'a> {
             let mut rows = vec![];
             for i in 0..=16 {
                 let mut row_data = vec![
-                    Cell::from(format!("{:02X}", i + 1)).style(Style::default().fg(Color::Black).bg(Color::Blue)),
+                    Cell::from(format!("{:02X}", i + 1 + app.memory_row as u16)).style(Style::default().fg(Color::Black).bg(Color::Blue)),
                 ];
                 for j in 0..16 {
-                    let address = (page << 8) + (i * 16 + j);
-                    let value = bus.borrow_mut().read(address);
-                    let cell = Cell::from(format!("{:04X}", value)).style(Style::default().fg(Color::Black));
+                    let address = base.wrapping_add(i * 16 + j);
+                    let value = bus.borrow().peek(address);
+                    let changed = dirty.contains((address >> 8) as u8) && previous.as_ref().is_some_and(|previous| previous.borrow().peek(address) != value);
+                    let style = Style::default().fg(if changed { Color::Red } else { Color::Black });
+                    let style = match regions::find(&app.regions, address) {
+                        Some(region) => style.bg(region.color()),
+                        None => style,
+                    };
+                    let style = if app.watchpoints.iter().any(|watchpoint| watchpoint.range.contains(&address)) { style.add_modifier(Modifier::UNDERLINED) } else { style };
+                    let style = if app.selected_address == Some(address) { style.add_modifier(Modifier::REVERSED) } else { style };
+                    let cell = Cell::from(format!("{:04X}", value)).style(style);
                     row_data.push(cell);
                 }
                 let row = Row::new(row_data).height(2);
@@ -159,7 +365,16 @@ impl<'a> State<'a> {
             }
 
 
-            let page_title = format!("Page {:02X}", page);
+            // the regions the rows shown overlap
+            let shown = base..=base.saturating_add(16 * 17 - 1);
+            let names = app.regions.iter()
+                .filter(|region| region.range.start() <= shown.end() && shown.start() <= region.range.end())
+                .map(|region| region.name.as_str())
+                .collect::<Vec<&str>>();
+            let page_title = format!("Page {:02X}{}{}{}", page,
+                if app.memory_row != 0 { format!(" from ${:04X}", base) } else { String::new() },
+                if dirty.contains(page as u8) { " (written)" } else { "" },
+                if names.is_empty() { String::new() } else { format!(" - {}", names.join(", ")) });
             let table = Table::new(rows)
                 .header(header_row)
                 .block(Block::default().borders(Borders::ALL).title(page_title))
@@ -195,7 +410,7 @@ impl<'a> State<'a> {
         let mut rows_vec = Vec::new();
         rows_vec.push(Row::new([Cell::from("[ Pages ]")]));
         (lower_bound..upper_bound)
-            .map(|i| format!("[{}{:02X}]", if i == app.memory_page_index { ">>" } else { "" } ,i))
+            .map(|i| format!("[{}{:02X}{}]", if i == app.memory_page_index { ">>" } else { "" }, i, if dirty.contains(i as u8) { "*" } else { "" }))
             .map(|s| Span::styled(s, Style::default().fg(Color::LightBlue)))
             .map(|s| Row::new([s]).height(2))
             .for_each(|r| rows_vec.push(r));
@@ -208,13 +423,21 @@ impl<'a> State<'a> {
         f.render_widget(page_selection_table, chunks[0]);
         
         let local_app_state_deref = (*app.inner_machine_state).borrow_mut();
-        let table = build_page_table(&local_app_state_deref.bus, app.memory_page_index as u16);
+        let base = ((app.memory_page_index as u16) << 8).wrapping_add(app.memory_row as u16 * 16);
+        let table = build_page_table(&local_app_state_deref.machine.bus, app.memory_page_index as u16, base);
         f.render_widget(table, chunks[1]);
+        app.panes.set(Panes {
+            pages       : chunks[0],
+            first_page  : lower_bound,
+            memory      : chunks[1],
+            memory_base : base,
+            ..app.panes.get()
+        });
 
     }
 
     pub fn processor_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
-        let size = Rect::new((f.size().width as f32 * 0.70) as u16, 0, (f.size().width as f32 * 0.30) as u16, (f.size().height as f32 * 0.60) as u16);
+        let size = app.processor_pane(f.size());
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(5)
@@ -225,36 +448,104 @@ impl<'a> State<'a> {
         f.render_widget(block, size);
 
         
+        let run_mode = match (app.running, app.turbo) {
+            (false, _) => "paused".to_string(),
+            (true, true) => "turbo".to_string(),
+            (true, false) => format!("running x{}", app.speed),
+        };
+        let describe_port = |port: usize| {
+            let marker = if port == app.input_port { ">" } else { "" };
+            match (port, app.peripheral) {
+                (1, Peripheral::Zapper) => {
+                    let aim = (*app.inner_machine_state).borrow().machine.zapper().and_then(|zapper| zapper.aim);
+                    let aim = aim.map(|(x, y)| format!("{},{}", x, y)).unwrap_or_else(|| "off".to_string());
+                    let trigger = if app.held_buttons[1] & Button::A as u8 != 0 { " fire" } else { "" };
+                    format!("{}2:zapper {}{}", marker, aim, trigger)
+                },
+                _ => format!("{}{}:{}", marker, port + 1, controller::describe_turbo(app.held_buttons[port], app.held_turbo[port])),
+            }
+        };
+        let input = (0..app.peripheral.pads()).map(describe_port).collect::<Vec<String>>().join(" ");
+        let run_mode = match &app.recording {
+            Some(recording) => format!("{} - rec {:.1}s", run_mode, recording.seconds()),
+            None => run_mode,
+        };
+        let run_mode = match (&app.macro_recording, &app.macro_playing) {
+            (Some(take), _) => format!("{} - macro p{} {:.1}s", run_mode, take.pad + 1, take.frames.seconds()),
+            (None, Some(take)) => format!("{} - playing p{}", run_mode, take.pad + 1),
+            _ => run_mode,
+        };
+        let run_mode = match app.gamepads.status() {
+            Some(status) => format!("{} - {}", run_mode, status),
+            None => run_mode,
+        };
+        let run_mode = match (&app.netplay, &app.netplay_error) {
+            (Some(netplay), _) => match netplay.desync {
+                Some(frame) => format!("{} - net p{} frame {} delay {} desync at {}", run_mode, netplay.port + 1, netplay.frame, netplay.delay, frame),
+                None => format!("{} - net p{} frame {} delay {}", run_mode, netplay.port + 1, netplay.frame, netplay.delay),
+            },
+            (None, Some(err)) => format!("{} - net lost: {}", run_mode, err),
+            _ => run_mode,
+        };
+        let run_mode = match app.video.filter {
+            Filter::None => run_mode,
+            filter => format!("{} - {}", run_mode, filter),
+        };
+        let run_mode = if app.video.blend { format!("{} - blend", run_mode) } else { run_mode };
+        let run_mode = match app.screenshot {
+            Some((frame, hash)) => format!("{} - shot {} {:08X}", run_mode, frame, hash),
+            None => run_mode,
+        };
         let build_registers_list = |cpu: &Rc<RefCell<Cpu>>| {
             let cpu_local = cpu.borrow_mut();
-            let list_elements = vec![
-                ListItem::new(Spans::from(vec![Span::raw(format!(" A: {:02X}", cpu_local.registers.a))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!(" X: {:02X}", cpu_local.registers.x))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!(" Y: {:02X}", cpu_local.registers.y))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("PC: {:04X}", cpu_local.registers.pc))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("SP: {:02X}", cpu_local.registers.sp))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!(" P: {:02X}", cpu_local.registers.status))])), 
-            ];
+            let position = cpu_local.raster();
+            let lines = {
+                let bus = cpu_local.bus.as_ref().unwrap().borrow();
+                let irq = match (bus.irq_held(), cpu_local.registers.get_flag(Flag::I)) {
+                    (false, _) => "-",
+                    (true, false) => "held",
+                    (true, true) => "held, masked",
+                };
+                let nmi = match (bus.nmi.is_pending(), bus.nmi.is_asserted()) {
+                    (true, _) => "pending",
+                    (false, true) => "held",
+                    (false, false) => "-",
+                };
+                format!("IRQ: {} NMI: {}", irq, nmi)
+            };
+            let mut list_elements = edits::REGISTERS.iter()
+                .map(|register| ListItem::new(Spans::from(vec![Span::raw(register.show(&cpu_local.registers))])))
+                .collect::<Vec<ListItem>>();
+            list_elements.extend(vec![
+                ListItem::new(Spans::from(vec![Span::raw(lines)])), 
+                ListItem::new(Spans::from(vec![Span::raw(format!("CY: {}", cpu_local.total_cycles))])), 
+                ListItem::new(Spans::from(vec![Span::raw(format!("FR: {}", position.frame))])), 
+                ListItem::new(Spans::from(vec![Span::raw(format!("SL: {:3} DOT: {:3} AL: {}", position.scanline, position.dot, cpu_local.alignment))])), 
+            ]);
+            if let PowerOnPattern::Random(seed) = app.power_on {
+                list_elements.push(ListItem::new(Spans::from(vec![Span::raw(format!("SEED: {}", seed))])));
+            }
+            // first, the pane is often too short for the whole list
+            if let Some(edit) = app.edits.done.last() {
+                list_elements.insert(0, ListItem::new(Spans::from(vec![Span::raw(format!("ED: {} ({}, {} undone)", edit.describe(), app.edits.done.len(), app.edits.undone.len()))])));
+            }
+            if let (Some(reason), false) = (&app.stop_reason, app.running) {
+                list_elements.insert(0, ListItem::new(Spans::from(vec![Span::styled(format!("STOP: {}", reason), Style::default().fg(Color::Red))])));
+            }
+            // the rows above the registers, for the clicks
+            let first = list_elements.len() - edits::REGISTERS.len() - 4 - matches!(app.power_on, PowerOnPattern::Random(_)) as usize;
             let list = List::new(list_elements)
-                .block(Block::default().borders(Borders::ALL).title("Registers"))
+                .block(Block::default().borders(Borders::ALL).title(format!("Registers - {} - {}", run_mode, input)))
                 .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                 .highlight_symbol(">> ");
-            list
+            (list, first)
         };
 
         let build_status_view = |cpu: &Rc<RefCell<Cpu>>| {
             let cpu_local = cpu.borrow_mut();
-            let list_elements = vec![
-                ListItem::new(Spans::from(vec![Span::raw(format!("N: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::N)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("V: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::O)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("B: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::B)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("D: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::D)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("I: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::I)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("Z: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::Z)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("C: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::C)))])), 
-                ListItem::new(Spans::from(vec![Span::raw(format!("U: {}", cpu_local.registers.get_flag(crate::hardware::registers::Flag::U)))])), 
-
-            ];
+            let list_elements = edits::FLAGS.iter()
+                .map(|(letter, flag)| ListItem::new(Spans::from(vec![Span::raw(format!("{}: {}", letter, cpu_local.registers.get_flag(*flag)))])))
+                .collect::<Vec<ListItem>>();
             let list = List::new(list_elements)
                 .block(Block::default().borders(Borders::ALL).title("Status"))
                 .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
@@ -263,16 +554,413 @@ impl<'a> State<'a> {
         };
 
         let local_app_state_deref = (*app.inner_machine_state).borrow_mut();
-        let cpu_local = local_app_state_deref.cpu.clone();
-        let registers_list = build_registers_list(&cpu_local);
-        f.render_widget(registers_list, chunks[1]);
+        let cpu_local = local_app_state_deref.machine.cpu.clone();
+        let (registers_list, first_register) = build_registers_list(&cpu_local);
+        let mut selected = ListState::default();
+        selected.select(app.selected_register.and_then(|register| edits::REGISTERS.iter().position(|other| *other == register)).map(|at| first_register + at));
+        f.render_stateful_widget(registers_list, chunks[1], &mut selected);
         let status_list = build_status_view(&cpu_local);
-        f.render_widget(status_list, chunks[0]);
+        let status = if app.running {
+            chunks[0]
+        } else {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+                .split(chunks[0])[0]
+        };
+        app.panes.set(Panes { status, registers: chunks[1], first_register, ..app.panes.get() });
+        f.render_widget(status_list, status);
+        if app.running {
+            return;
+        }
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(chunks[0]);
+
+        // stepping a copy of the machine, again only once something changed
+        let key = (cpu_local.borrow().total_cycles, app.previous_machine_state.len());
+        if app.preview.borrow().as_ref().map(|preview| preview.key) != Some(key) {
+            app.preview.replace(Some(Preview::of(&local_app_state_deref.machine, key)));
+        }
+        // a routine called since takes the pane until then
+        let (title, lines) = match app.call.as_ref().filter(|call| call.key == key) {
+            Some(call) => (format!("Call ${:04X}", call.target), call.lines()),
+            None => ("Next step".to_string(), app.preview.borrow().as_ref().map(|preview| preview.lines()).unwrap_or_default()),
+        };
+        let preview_list = List::new(lines.into_iter().map(|line| ListItem::new(Spans::from(vec![Span::raw(line)]))).collect::<Vec<ListItem>>())
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(preview_list, top[1]);
+
+    }
+
+    // what the serial ports or a test rom printed, the last lines that fit
+    pub fn terminal_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let state = (*app.inner_machine_state).borrow();
+        let (title, text) = match (state.machine.terminal_output(), test_rom::status(&state.machine)) {
+            (Some(output), _) => {
+                let bridges = app.profile.bridges();
+                let linked = if bridges.is_empty() { String::new() } else { format!(" linked to {}", bridges.join(", ")) };
+                (format!("Terminal ({}){}", app.profile.name, linked), String::from_utf8_lossy(&output).replace('\r', "\n"))
+            },
+            // what a test rom printed at $6004
+            (None, Some(status)) => (format!("Terminal (test rom, {})", test_rom::describe(status)), test_rom::text(&state.machine)),
+            (None, None) => (format!("Terminal ({}, no serial port)", app.profile.name), String::new()),
+        };
+        drop(state);
+        let visible = chunks[0].height.saturating_sub(2) as usize;
+        let lines = text.lines().collect::<Vec<&str>>();
+        let list_elements = lines.iter()
+            .skip(lines.len().saturating_sub(visible))
+            .map(|line| ListItem::new(Spans::from(vec![Span::raw(line.to_string())])))
+            .collect::<Vec<ListItem>>();
+
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[0]);
+    }
+
+    // the a/b comparison : both machines side by side, the reference where it stopped following
+    // once they differed
+    pub fn comparison_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let comparison = match &app.comparison {
+            Some(comparison) => comparison,
+            None => return,
+        };
+        let state = (*app.inner_machine_state).borrow();
+        let lines = match state.reference.as_ref() {
+            Some(reference) => {
+                comparison.catch_up(&state.machine, reference);
+                comparison::lines(&state.machine, reference)
+            },
+            None => Vec::new(),
+        };
+        let title = match &comparison.divergence {
+            Some(divergence) => format!("A/B with {} - differ at cycle {} : {}", comparison.name, divergence.cycles, divergence.describe()),
+            None => format!("A/B with {} - no difference", comparison.name),
+        };
+        let list_elements = lines.into_iter()
+            .map(|line| {
+                let style = if line.starts_with('>') { Style::default().fg(Color::Red) } else { Style::default() };
+                ListItem::new(Spans::from(vec![Span::styled(line, style)]))
+            })
+            .collect::<Vec<ListItem>>();
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[0]);
+    }
+
+    // the last events logged, at the levels --log asked for
+    pub fn log_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let levels = logging::SUBSYSTEMS.iter()
+            .map(|subsystem| format!("{} {}", subsystem.name(), logging::level(*subsystem).name()))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let visible = chunks[0].height.saturating_sub(2) as usize;
+        let list_elements = logging::recent(visible).into_iter()
+            .map(|line| ListItem::new(Spans::from(vec![Span::raw(line)])))
+            .collect::<Vec<ListItem>>();
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(format!("Log ({})", levels)));
+        f.render_widget(list, chunks[0]);
+    }
+
+    // how the cpu got where it is : the calls it is in, innermost first, with their labels
+    // when a listing or debug info has them, then the instructions that ran last, newest
+    // at the bottom, with the registers they saw every few of them
+    pub fn history_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let state = (*app.inner_machine_state).borrow();
+        let frames = state.machine.cpu.borrow().calls.frames.clone();
+
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Length(frames.len().clamp(1, 10) as u16 + 2), Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let labels = app.listing.as_ref().map(Listing::labels).unwrap_or_default();
+        let symbol = |address: u16| {
+            app.debug_info.as_ref().and_then(|debug_info| debug_info.symbol(address).map(str::to_string))
+                .or_else(|| labels.get(&address).cloned())
+                .map(|name| format!(" {}", name))
+                .unwrap_or_default()
+        };
+        let backtrace = frames.iter().rev().enumerate()
+            .map(|(number, frame)| {
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("#{:<3}", number), Style::default().fg(Color::DarkGray)),
+                    Span::raw(format!("{} ${:04X}{}", frame.kind.name(), frame.entry, symbol(frame.entry))),
+                    Span::styled(format!("   from ${:04X}{}, returns to ${:04X}", frame.caller, symbol(frame.caller), frame.return_address), Style::default().fg(Color::Blue)),
+                ]))
+            })
+            .collect::<Vec<ListItem>>();
+        let title = match app.return_break {
+            Some(depth) => format!("Backtrace (break on return to depth {})", depth),
+            None => "Backtrace".to_string(),
+        };
+        let list = List::new(backtrace)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[0]);
+
+        let visible = chunks[1].height.saturating_sub(2) as usize;
+        let entries = app.filtered_history();
+        let list_elements = entries.iter()
+            .skip(entries.len().saturating_sub(visible))
+            .map(|(executed, line, registers, region)| {
+                let mut spans = vec![
+                    Span::styled(format!("{:>12} ", executed.cycles), Style::default().fg(Color::DarkGray)),
+                    match executed.intervention {
+                        Some(_) => Span::styled(line.clone(), Style::default().fg(Color::Magenta)),
+                        None => Span::raw(line.clone()),
+                    },
+                ];
+                if let Some((label, color)) = region {
+                    spans.push(Span::styled(format!("  ; {}", label), Style::default().bg(*color)));
+                }
+                if let Some(registers) = registers {
+                    spans.push(Span::styled(format!("   {}", registers), Style::default().fg(Color::Blue)));
+                }
+                ListItem::new(Spans::from(spans))
+            })
+            .collect::<Vec<ListItem>>();
+
+        let recorded = app.history.entries.len();
+        let title = if app.trace_filter.is_empty() {
+            format!("History (last {} of {} instructions)", entries.len().min(visible), recorded)
+        } else {
+            format!("History ({} of {} instructions match {})", entries.len(), recorded, app.trace_filter.text)
+        };
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[1]);
+    }
+
+    pub fn source_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let debug_info = match &app.debug_info {
+            Some(debug_info) => debug_info,
+            None => return,
+        };
+        let program_counter = (*app.inner_machine_state).borrow().machine.cpu.borrow().registers.pc;
+        let location = debug_info.location(program_counter);
+
+        let (title, list_elements) = match location.and_then(|location| debug_info.files.get(&location.file).map(|file| (location, file))) {
+            Some((location, file)) => {
+                let visible = chunks[0].height.saturating_sub(2) as usize;
+                let start = location.line.saturating_sub(visible / 2).max(1);
+                let breakpoint_lines = app.breakpoints.iter()
+                    .filter_map(|address| debug_info.location(*address))
+                    .filter(|breakpoint| breakpoint.file == location.file)
+                    .map(|breakpoint| breakpoint.line)
+                    .collect::<HashSet<usize>>();
+                let list_elements = file.lines.iter()
+                    .enumerate()
+                    .map(|(i, text)| (i + 1, text))
+                    .skip(start - 1)
+                    .take(visible)
+                    .map(|(line, text)| {
+                        let style = if line == location.line { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                        ListItem::new(Spans::from(vec![Span::styled(format!("{}{}{:>5} {}",
+                            if line == location.line { ">" } else { " " },
+                            if breakpoint_lines.contains(&line) { "*" } else { " " },
+                            line, text), style)]))
+                    })
+                    .collect::<Vec<ListItem>>();
+                (format!("{}:{}", file.name, location.line), list_elements)
+            },
+            None => (format!("no source for {:04X}", program_counter), Vec::new()),
+        };
+
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[0]);
+    }
+
+    pub fn menu_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let selected = app.menu.unwrap_or(0);
+        let list_elements = MACHINE_MENU.iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let style = if i == selected { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                ListItem::new(Spans::from(vec![Span::styled(format!("{} {}", if i == selected { ">" } else { " " }, item), style)]))
+            })
+            .collect::<Vec<ListItem>>();
+        let cartridge = if (*app.inner_machine_state).borrow().machine.bus.borrow().has_cartridge() { app.program_path.as_str() } else { "no program" };
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(format!("Machine - {}", cartridge)));
+        f.render_widget(list, chunks[0]);
+    }
+
+    pub fn browser_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let browser = match &app.browser {
+            Some(browser) => browser,
+            None => return,
+        };
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        // the list scrolls to keep the selection in view
+        let height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        let first = browser.selected.saturating_sub(height - 1);
+        let list_elements = browser.items.iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(i, item)| {
+                let selected = i == browser.selected;
+                let style = if selected { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                ListItem::new(Spans::from(vec![Span::styled(format!("{} {}", if selected { ">" } else { " " }, Browser::label(item)), style)]))
+            })
+            .collect::<Vec<ListItem>>();
+        let title = match &browser.error {
+            Some(error) => format!("Open - {} - {}", browser.location.display(), error),
+            None => format!("Open - {}", browser.location.display()),
+        };
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, chunks[0]);
+    }
+
+    pub fn explorer_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let explorer = match &app.explorer {
+            Some(explorer) => explorer,
+            None => return,
+        };
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(5)
+            .constraints([Constraint::Min(0)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let tree = app.state_tree();
+        let rows = explorer.rows(&tree);
+        let height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        let first = explorer.selected.saturating_sub(height - 1);
+        let list_elements = rows.iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(i, row)| {
+                let style = if i == explorer.selected { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                ListItem::new(Spans::from(vec![Span::styled(explorer.label(row), style)]))
+            })
+            .collect::<Vec<ListItem>>();
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title("Machine state"));
+        f.render_widget(list, chunks[0]);
+    }
 
+    pub fn savestate_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
+        let size = app.left_pane(f.size());
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .margin(5)
+            .constraints([Constraint::Min(0), Constraint::Length(savestates::THUMBNAIL_WIDTH as u16 + 2)].as_ref())
+            .split(size);
+
+        let block = Block::default().style(Style::default().bg(Color::White).fg(Color::Black));
+        f.render_widget(block, size);
+
+        let selected = app.slot_picker.unwrap_or(0);
+        let list_elements = app.slots.iter()
+            .enumerate()
+            .map(|(slot, info)| {
+                let description = match info {
+                    Some(info) => format!("{}  frame {}", savestates::format_timestamp(info.saved_at), RasterPosition::from_cycles(info.cycles).frame),
+                    None => "empty".to_string(),
+                };
+                let style = if slot == selected { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                ListItem::new(Spans::from(vec![Span::styled(format!("{} {}  {}", if slot == selected { ">" } else { " " }, slot, description), style)]))
+            })
+            .collect::<Vec<ListItem>>();
+        let list = List::new(list_elements)
+            .block(Block::default().borders(Borders::ALL).title(format!("Savestates - {}", app.saves.directory.display())));
+        f.render_widget(list, chunks[0]);
+
+        // one character per thumbnail pixel, shaded by brightness
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let thumbnail_lines = match app.slots.get(selected) {
+            Some(Some(info)) => info.thumbnail
+                .chunks(savestates::THUMBNAIL_WIDTH)
+                .map(|row| row.iter().map(|pixel| {
+                    let brightness = ((pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF)) as usize / 3;
+                    SHADES[brightness * (SHADES.len() - 1) / 255] as char
+                }).collect::<String>())
+                .map(|line| Spans::from(vec![Span::raw(line)]))
+                .collect::<Vec<Spans>>(),
+            _ => Vec::new(),
+        };
+        let thumbnail = Paragraph::new(thumbnail_lines)
+            .block(Block::default().borders(Borders::ALL).title("Preview"));
+        f.render_widget(thumbnail, chunks[1]);
     }
 
     pub fn program_viewer<B: Backend>(f: &mut Frame<B>, app: &App)  {
-        let size = Rect::new((f.size().width as f32 * 0.70) as u16, (f.size().height as f32 * 0.31) as u16, (f.size().width as f32 * 0.30) as u16, (f.size().height as f32 * 0.69) as u16);
+        let size = app.program_pane(f.size());
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(5)
@@ -283,10 +971,23 @@ impl<'a> State<'a> {
         f.render_widget(block, size);
 
         
-        let build_program_list = |program_counter: i32, dis: Disassembler| {
+        let title = match &app.game {
+            Some(GameSettings { name: Some(name), .. }) => format!("Program - {}", name),
+            Some(game) => format!("Program - {:08X}", game.identity.crc32),
+            None => "Program".to_string(),
+        };
+        let title = if app.program_view.is_some() { format!("{} - scrolled", title) } else { title };
+        let title = match (*app.inner_machine_state).borrow().machine.disk() {
+            Some((Some(side), _)) => format!("{} - Disk side {}", title, (b'A' + side as u8) as char),
+            Some((None, _)) => format!("{} - Disk ejected", title),
+            None => title,
+        };
+        let mut first_line = 0;
+        // as many lines as the pane holds
+        let max_count = chunks[1].height.saturating_sub(2).max(1) as usize;
+        let build_program_list = |program_counter: i32, dis: Disassembler, breakpoints: HashSet<usize>, effective: &dyn Fn(usize) -> Option<u16>| {
             let (counter, start, end) = {
                 let program_len = dis.program.len();
-                let max_count = 15 as usize;
                 if let Some(program_counter) = dis.counters.get(&program_counter) {
     
                     let range_start = program_counter / max_count;
@@ -301,6 +1002,11 @@ impl<'a> State<'a> {
                     (0, 0, program_len.min(max_count))
                 }
             };
+            let (start, end) = match app.program_view {
+                None => (start, end),
+                Some(line) => (line.min(dis.program.len().saturating_sub(1)), max_count),
+            };
+            first_line = start;
 
             let list_elements = dis.program
                 .into_iter()
@@ -308,15 +1014,15 @@ impl<'a> State<'a> {
                 .skip(start as usize)
                 .take(end as usize)
                 .map(|s| ListItem::new(Spans::from(
-                    if s.0 == counter  {
-                        vec![Span::raw(format!("> {}", s.1))]
-                    } else {
-                        vec![Span::raw(format!("  {}", s.1))]
-                    }
+                    vec![Span::raw(format!("{}{}{}{}",
+                        if s.0 == counter { ">" } else { " " },
+                        if breakpoints.contains(&s.0) { "*" } else { " " },
+                        s.1,
+                        effective(s.0).map(|address| format!(" @${:04X}", address)).unwrap_or_default()))]
                 )))
                 .collect::<Vec<ListItem>>();
             let list = List::new(list_elements)
-                .block(Block::default().borders(Borders::ALL).title("Program"))
+                .block(Block::default().borders(Borders::ALL).title(title))
                 .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                 .highlight_symbol(">> ");
             list
@@ -324,11 +1030,94 @@ impl<'a> State<'a> {
 
         let local_app_state_deref = (*app.inner_machine_state).borrow_mut();
         let program = local_app_state_deref.dis.clone();
-        let list = build_program_list((local_app_state_deref.cpu.borrow().registers.pc) as i32, program);
+        let breakpoint_lines = app.breakpoints.iter()
+            .filter_map(|address| program.counters.get(&(*address as i32)).copied())
+            .collect::<HashSet<usize>>();
+        // when paused, where each line would access with the registers and memory as they are
+        let addresses = if app.running { HashMap::new() } else {
+            program.counters.iter().map(|(address, line)| (*line, *address as u16)).collect::<HashMap<usize, u16>>()
+        };
+        let cpu = local_app_state_deref.machine.cpu.borrow();
+        let bus = local_app_state_deref.machine.bus.borrow();
+        let effective = |line: usize| addresses.get(&line).and_then(|address| {
+            let mode = &cpu.instruction_set[bus.peek(*address) as usize].address_mode;
+            Disassembler::effective_address(mode, *address, &cpu.registers, |at| bus.peek(at))
+        });
+        let list = build_program_list(cpu.registers.pc as i32, program, breakpoint_lines, &effective);
         f.render_widget(list, chunks[1]);
+        app.panes.set(Panes { program: chunks[1], first_line, ..app.panes.get() });
+    }
+
+    // --run-frames / --screenshot : no interface, the program is loaded and run for that
+    // many frames, the last frame's hash is printed and the frame saved if asked. a frame
+    // nothing drew into has no hash printed, it would be the same for every program
+    pub fn headless(arguments: Arguments) -> Result<(), Error> {
+        let mut app = App::new(&arguments)?;
+        let program = app.load_current_program()?;
+        app.load_program(&program);
+        // a test rom of the $6000 protocol has its text streamed, and ends the run with its result
+        let mut watch = test_rom::Watch::default();
+        let mut outcome = None;
+        for _ in 0..arguments.run_frames.unwrap_or(1) {
+            app.run_frames(1);
+            let progress = watch.frame(&(*app.inner_machine_state).borrow().machine);
+            if !progress.text.is_empty() {
+                print!("{}", progress.text);
+                io::stdout().flush()?;
+            }
+            if progress.reset {
+                app.soft_reset();
+            }
+            outcome = progress.outcome;
+            if outcome.is_some() {
+                break;
+            }
+        }
+        if let Some(err) = &app.netplay_error {
+            return Err(Error::new(ErrorKind::ConnectionAborted, format!("netplay: {}", err)));
+        }
+        if let Some(frame) = app.netplay.as_ref().and_then(|netplay| netplay.desync) {
+            println!("netplay desync at frame {}", frame);
+        }
+
+        let (frame, hash) = app.screenshot(arguments.screenshot.as_deref())?;
+        if (*app.inner_machine_state).borrow().machine.video.borrow().drawn {
+            println!("frame {} {:08X}", frame, hash);
+        } else {
+            println!("frame {} blank, nothing drew into it", frame);
+            if let Some(path) = &arguments.screenshot {
+                eprintln!("warning: {} is blank, nothing drew into the frame", path.trim());
+            }
+        }
+        if let Some(outcome) = outcome {
+            println!("test rom {}", outcome);
+        }
+        if let Some(comparison) = &app.comparison {
+            match &comparison.divergence {
+                Some(divergence) => println!("a/b first difference from {} at cycle {} : {}", comparison.name, divergence.cycles, divergence.describe()),
+                None => println!("a/b no difference from {}", comparison.name),
+            }
+        }
+        if let Some(output) = (*app.inner_machine_state).borrow().machine.terminal_output() {
+            println!("{}", String::from_utf8_lossy(&output).replace('\r', "\n"));
+        }
+        app.flush_battery();
+        app.stop_recording();
+        if let Some(recorder) = app.frame_recorder.as_mut() {
+            recorder.finish()?;
+        }
+        Ok(())
     }
 
-    pub fn start(program_path: String) -> Result<(), Error> {
+    pub fn start(arguments: Arguments) -> Result<(), Error> {
+        if let Some(port) = arguments.remote {
+            return server::serve(arguments, port);
+        }
+        if arguments.run_frames.is_some() || arguments.screenshot.is_some() {
+            return State::headless(arguments);
+        }
+        // the terminal is put back however this ends, a panic included
+        let _guard = TerminalGuard::enter()?;
         let stdout = io::stdout();
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = match Terminal::new(backend) {
@@ -336,114 +1125,1507 @@ impl<'a> State<'a> {
             Err(err) => return Err(err),
         };
 
-
-        let mut app = App {
-            memory_page_index: 0,
-            inner_machine_state: State::initiate_state(),
-            previous_machine_state: Vec::new(),
-        };
+        let mut app = App::new(&arguments)?;
+        if app.config.get_or("session.restore", true) {
+            app.keep_session = true;
+            app.restore_session();
+        }
+        // without a program, or with an archive of several, the session starts in the browser
+        let several = archive::is_archive(&app.program_path) && archive::split(&app.program_path).1.is_none()
+            && fs::read(app.program_path.trim()).and_then(|content| archive::entries(&content))
+                .is_ok_and(|entries| archive::programs(&entries).len() > 1);
+        if app.program_path.trim().is_empty() {
+            app.open_browser();
+        } else if several {
+            app.browser = Some(Browser::open_archive(&app.program_path, session::recent_programs(&app.config)));
+        }
 
         terminal.clear()?;
         terminal.hide_cursor()?;
         
+        // a panic leaves a bookmark of the machine as it was behind, to look at after a restart
+        match panic::catch_unwind(AssertUnwindSafe(|| State::interface(&mut terminal, &mut app))) {
+            Ok(result) => result?,
+            Err(panic) => {
+                if app.config.get_or("crash.bookmark", true) {
+                    app.add_bookmark("crash");
+                }
+                app.flush_battery();
+                app.save_session();
+                panic::resume_unwind(panic);
+            },
+        }
+
+        app.flush_battery();
+        app.save_session();
+        app.stop_recording();
+        if let Some(recorder) = app.frame_recorder.as_mut() {
+            recorder.finish()?;
+        }
+        Ok(())
+    }
+
+    // draws and handles events until quit
+    // the letters interface dispatches commands on, kept in step with its match : pad bindings
+    // and macros are looked up first, so they cannot take these
+    pub const COMMAND_KEYS: &'static str = "mF[].nfkBKueoGMzgpt+-rildsjwLATECJSURWXxyYNhbcvaq";
+
+    pub fn is_command_key(key: char) -> bool {
+        State::COMMAND_KEYS.contains(key)
+    }
+
+    fn interface<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'a>) -> Result<(), Error> {
+        let mut last_draw = Instant::now();
+        let mut drawn = false;
         loop {
-            terminal.draw(|f| State::build_view(f, &app))?;
+            // the prompt wrote over the screen, everything is drawn again
+            if PROMPTED.swap(false, Ordering::Relaxed) {
+                terminal.clear()?;
+                drawn = false;
+            }
+            if !drawn || !app.running || !app.turbo || last_draw.elapsed() >= TURBO_REDRAW_INTERVAL {
+                terminal.draw(|f| State::build_view(f, app))?;
+                last_draw = Instant::now();
+                drawn = true;
+            }
 
-            if let Ok(Event::Key(key)) = read() {
-                match key.code {
-                    KeyCode::PageUp => {
-                        app.memory_page_index = (app.memory_page_index + 1) % 0xFF;
-                    },
-                    KeyCode::PageDown => {
-                        if app.memory_page_index == 0 {
-                            app.memory_page_index = 0xFF -1;
-                        } else {
-                            app.memory_page_index = (app.memory_page_index - 1) % 0xFF;
-                        }
-                    },
-                    KeyCode::Enter => {
-                        if let Ok(program) = State::load_program_from_file(Some(program_path.clone()))
-                        {
-                            for (i, byte) in program.iter().enumerate() {
-                                app.write((0x8000 + i as u16) as u16, *byte);
-                            }
+            // mapping a gamepad waits on it as well as on the keyboard
+            let event = if app.running || app.gamepads.mapping.is_some() {
+                let wait = if app.running { 0 } else { GAMEPAD_MAPPING_POLL_MS };
+                if poll(Duration::from_millis(wait)).unwrap_or(false) { read().ok() } else { None }
+            } else {
+                read().ok()
+            };
+            app.gamepads.poll();
 
-                            let disassembled_program = Disassembler::disassemble(&program);
+            if app.running {
+                app.run_slice();
+            }
 
-                            let mut app_state_local_val = (*app.inner_machine_state).borrow_mut();
-                            app_state_local_val.dis = disassembled_program;
-                        }
-                    },
-                    KeyCode::Right | KeyCode::Tab => {
-                        
-                        // add 3 to register A
-                        let app_state_local_val = (*app.inner_machine_state).borrow_mut();
-                        
-                        // deep copy the state
-                        let previous_state = app_state_local_val.clone();
-                        let mut cpu_local_val = (*app_state_local_val.cpu).borrow_mut();
-
-                        let proceed = {
-                            let program_counter = cpu_local_val.registers.pc;
-                            app_state_local_val.dis.counters.contains_key(&(program_counter as i32))
-                        };
-                        
-                        if proceed {
-                            app.previous_machine_state.push(previous_state);
-                            cpu_local_val.tick();
-                        }
+            // raw mode keeps ctrl-c from the shell
+            if let Some(Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers })) = event {
+                if modifiers.contains(KeyModifiers::CONTROL) {
+                    break;
+                }
+            }
+            if let Some(Event::Resize(width, height)) = event {
+                terminal.resize(Rect::new(0, 0, width, height))?;
+                app.dragging = None;
+                continue;
+            }
+            app.collect_audio();
+            app.update_battery();
+            app.capture_frame();
+            app.watch_test_rom();
+
+            if let (Some(Event::Key(key)), Some(browser)) = (event, app.browser.as_mut()) {
+                match key.code {
+                    KeyCode::Up => browser.select(-1),
+                    KeyCode::Down => browser.select(1),
+                    KeyCode::PageUp => browser.select(-BROWSER_PAGE),
+                    KeyCode::PageDown => browser.select(BROWSER_PAGE),
+                    KeyCode::Home => browser.select(isize::MIN / 2),
+                    KeyCode::End => browser.select(isize::MAX / 2),
+                    KeyCode::Left | KeyCode::Backspace => browser.leave(),
+                    KeyCode::Enter | KeyCode::Right => if let Some(path) = browser.activate() {
+                        app.browser = None;
+                        app.open_program(&path);
                     },
-                    KeyCode::Left | KeyCode::Backspace => {
-                        if let Some(previous_state) = &app.previous_machine_state.pop() {
-                            app.inner_machine_state = Rc::new(RefCell::new((*previous_state).clone()));
-                            let cpu_ref_local = (*app.inner_machine_state).borrow_mut().cpu.clone();
-                            let bus = (*app.inner_machine_state).borrow_mut().bus.clone();
-                            (*cpu_ref_local).borrow_mut().bus = Some(bus.clone());
+                    // a file dropped on the terminal is typed as its path, into the prompt
+                    KeyCode::Char('/') => if let Ok(answer) = State::prompt("Open (type a path, or drop a file on the terminal): ") {
+                        let path = browser::pasted_path(&answer);
+                        if Path::new(archive::split(&path).0).is_file() {
+                            app.browser = None;
+                            app.open_program(&path);
+                        } else if !path.is_empty() {
+                            browser.error = Some(format!("{} not found", path));
                         }
                     },
-                    KeyCode::Insert | KeyCode::Char('i') => {
-                        if let Ok(program) = State::load_program_from_file(None)
-                        {
-                            for (i, byte) in program.iter().enumerate() {
-                                app.write((0x8000 + i as u16) as u16, *byte);
-                            }
-
-                            let disassembled_program = Disassembler::disassemble(&program);
+                    KeyCode::Esc => app.browser = None,
+                    _ => {}
+                }
+                continue;
+            }
 
-                            let mut app_state_local_val = (*app.inner_machine_state).borrow_mut();
-                            app_state_local_val.dis = disassembled_program;
-                        }
+            if let (Some(Event::Key(key)), true) = (event, app.explorer.is_some()) {
+                let tree = app.state_tree();
+                // r and w check the boxes of the register selected
+                let access = match key.code {
+                    KeyCode::Char('r') => Some(Access::Read),
+                    KeyCode::Char('w') => Some(Access::Write),
+                    _ => None,
+                };
+                if let Some(access) = access {
+                    let register = app.explorer.as_ref()
+                        .and_then(|explorer| explorer.rows(&tree).get(explorer.selected).and_then(|row| register_breaks::at(&row.path)));
+                    if let Some(register) = register {
+                        register_breaks::toggle(&mut app.register_breaks, RegisterBreak { register, access });
                     }
-
-                    KeyCode::Esc | KeyCode::Char('q') => break,
+                    continue;
+                }
+                let explorer = match app.explorer.as_mut() {
+                    Some(explorer) => explorer,
+                    None => continue,
+                };
+                match key.code {
+                    KeyCode::Up => explorer.select(-1, &tree),
+                    KeyCode::Down => explorer.select(1, &tree),
+                    KeyCode::PageUp => explorer.select(-BROWSER_PAGE, &tree),
+                    KeyCode::PageDown => explorer.select(BROWSER_PAGE, &tree),
+                    KeyCode::Right | KeyCode::Enter => explorer.expand(&tree),
+                    KeyCode::Left => explorer.collapse(&tree),
+                    KeyCode::Esc | KeyCode::Char('T') => app.explorer = None,
                     _ => {}
                 }
+                continue;
             }
-        }
 
-        Ok(())
+            if let (Some(Event::Key(key)), true) = (event, app.gamepads.mapping.is_some()) {
+                match key.code {
+                    KeyCode::Char(' ') => app.gamepads.next_target(),
+                    KeyCode::Esc | KeyCode::Char('G') => app.gamepads.mapping = None,
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let (Some(Event::Key(key)), Some(selected)) = (event, app.slot_picker) {
+                match key.code {
+                    KeyCode::Up => app.slot_picker = Some((selected + savestates::SLOT_COUNT - 1) % savestates::SLOT_COUNT),
+                    KeyCode::Down => app.slot_picker = Some((selected + 1) % savestates::SLOT_COUNT),
+                    KeyCode::Char(digit @ '0'..='9') => app.slot_picker = digit.to_digit(10).map(|slot| slot as usize),
+                    KeyCode::Char('w') => app.save_slot(selected),
+                    KeyCode::Enter => {
+                        app.load_slot(selected);
+                        app.slot_picker = None;
+                    },
+                    KeyCode::Esc | KeyCode::Char('v') => app.slot_picker = None,
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let (Some(Event::Key(key)), Some(selected)) = (event, app.menu) {
+                match key.code {
+                    KeyCode::Up => app.menu = Some((selected + MACHINE_MENU.len() - 1) % MACHINE_MENU.len()),
+                    KeyCode::Down => app.menu = Some((selected + 1) % MACHINE_MENU.len()),
+                    KeyCode::Enter => {
+                        app.menu = None;
+                        match selected {
+                            0 => app.open_browser(),
+                            1 => app.eject_program(),
+                            2 => app.soft_reset(),
+                            3 => app.power_cycle(),
+                            _ => if let Ok(name) = State::prompt(&format!("Palette ({} or a .pal file, now {}): ", Palette::built_in().join(", "), app.palette.name)) {
+                                if let Ok(palette) = Palette::named(&name) {
+                                    app.palette = palette;
+                                    app.apply_output_settings();
+                                }
+                            },
+                        }
+                    },
+                    KeyCode::Esc | KeyCode::Char('m') => app.menu = None,
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(Event::Mouse(mouse)) = event {
+                app.on_mouse(mouse);
+                continue;
+            }
+
+            if let Some(Event::Key(key)) = event {
+                if let KeyCode::Char(c) = key.code {
+                    if let Some((pad, button, turbo)) = app.pad_bindings.get(&c) {
+                        let held = if *turbo { &mut app.held_turbo } else { &mut app.held_buttons };
+                        held[*pad] ^= *button as u8;
+                        continue;
+                    }
+                    if let Some(input_macro) = app.macros.get(&c) {
+                        app.macro_playing = Some(MacroTake::new(app.input_port, input_macro.clone()));
+                        continue;
+                    }
+                }
+                match key.code {
+                    KeyCode::PageUp => {
+                        app.memory_page_index = (app.memory_page_index + 1) % 0xFF;
+                    },
+                    KeyCode::PageDown => {
+                        if app.memory_page_index == 0 {
+                            app.memory_page_index = 0xFF -1;
+                        } else {
+                            app.memory_page_index = (app.memory_page_index - 1) % 0xFF;
+                        }
+                    },
+                    KeyCode::Enter => {
+                        if let Ok(program) = app.load_current_program()
+                        {
+                            app.load_program(&program);
+                        }
+                    },
+                    KeyCode::Char('m') => {
+                        app.menu = Some(0);
+                    },
+                    KeyCode::Up => app.scroll_program(-1),
+                    KeyCode::Down => app.scroll_program(1),
+                    KeyCode::Char('F') => app.follow_pc(),
+                    KeyCode::Char('[') => app.scroll_memory(-1),
+                    KeyCode::Char(']') => app.scroll_memory(1),
+                    KeyCode::Right | KeyCode::Tab => {
+                        app.step_instruction();
+                    },
+                    KeyCode::Char('.') => {
+                        app.run_until(|_| true);
+                    },
+                    KeyCode::Char('n') => {
+                        let dots = (*app.inner_machine_state).borrow().machine.cpu.borrow().dots();
+                        let next_scanline = dots / timing::DOTS_PER_SCANLINE + 1;
+                        app.run_until(|cpu| cpu.dots() / timing::DOTS_PER_SCANLINE >= next_scanline);
+                    },
+                    KeyCode::Char('f') => {
+                        // frame advance : pauses, then runs exactly one frame with the held buttons latched
+                        app.running = false;
+                        let state = (*app.inner_machine_state).borrow().clone();
+                        app.previous_machine_state.push(state);
+                        app.advance_frame();
+                    },
+                    KeyCode::F(9) => {
+                        app.soft_reset();
+                    },
+                    KeyCode::F(10) => {
+                        app.power_cycle();
+                    },
+                    KeyCode::F(n @ 1..=8) => {
+                        app.held_buttons[app.input_port] ^= Button::ALL[n as usize - 1] as u8;
+                    },
+                    KeyCode::Char('k') => {
+                        if let Ok(path) = State::prompt("Save the frame to (empty for screenshot.png): ") {
+                            let path = if path.trim().is_empty() { "screenshot.png".to_string() } else { path };
+                            app.screenshot = app.screenshot(Some(&path)).ok();
+                        }
+                    },
+                    KeyCode::Char('B') => {
+                        app.video.blend = !app.video.blend;
+                        if let Some(recorder) = app.frame_recorder.as_mut() {
+                            recorder.video.blend = app.video.blend;
+                        }
+                    },
+                    KeyCode::Char('K') => {
+                        app.video.filter = app.video.filter.next();
+                        if let Some(recorder) = app.frame_recorder.as_mut() {
+                            recorder.video.filter = app.video.filter;
+                        }
+                    },
+                    KeyCode::Char('u') => {
+                        if app.recording.is_some() {
+                            app.stop_recording();
+                        } else if let Ok(answer) = State::prompt("Record audio to file[,seconds] (empty seconds until stopped, + for stems): ") {
+                            let stems = answer.ends_with('+');
+                            let mut parts = answer.trim_end_matches('+').splitn(2, ',');
+                            let path = parts.next().unwrap_or("").trim();
+                            let seconds = parts.next().and_then(|seconds| seconds.trim().parse::<f64>().ok());
+                            if !path.is_empty() {
+                                app.start_recording(path, seconds, stems);
+                            }
+                        }
+                    },
+                    KeyCode::Char('e') => {
+                        (*app.inner_machine_state).borrow().machine.flip_disk();
+                    },
+                    KeyCode::Char('o') => {
+                        app.input_port = (app.input_port + 1) % app.peripheral.pads();
+                    },
+                    KeyCode::Char('G') => {
+                        app.gamepads.start_mapping(app.input_port);
+                    },
+                    KeyCode::Char('M') => {
+                        match app.macro_recording.take() {
+                            Some(take) => {
+                                let mut prompt = format!("Bind the {:.1}s macro to key (empty to drop it): ", take.frames.seconds());
+                                while let Some(key) = State::prompt(&prompt).ok().and_then(|answer| answer.chars().next()) {
+                                    if !State::is_command_key(key) {
+                                        app.macros.insert(key, take.frames);
+                                        break;
+                                    }
+                                    prompt = format!("{} is a command key, bind the macro to another (empty to drop it): ", key);
+                                }
+                            },
+                            None => app.macro_recording = Some(MacroTake::new(app.input_port, InputMacro::default())),
+                        }
+                    },
+                    KeyCode::Char('z') => {
+                        if let Ok(target) = State::prompt("Aim zapper at x,y (empty for off screen): ") {
+                            let mut parts = target.split(',').map(|part| part.trim().parse::<u16>());
+                            let aim = match (parts.next(), parts.next()) {
+                                (Some(Ok(x)), Some(Ok(y))) => Some((x, y)),
+                                _ => None,
+                            };
+                            (*app.inner_machine_state).borrow().machine.aim_zapper(aim);
+                        }
+                    },
+                    KeyCode::Char('g') => {
+                        if let Ok(target) = State::prompt("Run until scanline,dot: ") {
+                            let mut parts = target.split(',').map(|part| part.trim().parse::<u16>());
+                            if let (Some(Ok(scanline)), Some(Ok(dot))) = (parts.next(), parts.next()) {
+                                let dots = (*app.inner_machine_state).borrow().machine.cpu.borrow().dots();
+                                let target_dot = timing::next_dot_at(dots, scanline, dot);
+                                app.run_until(|cpu| cpu.dots() >= target_dot);
+                            }
+                        }
+                    },
+                    KeyCode::Left | KeyCode::Backspace => {
+                        if let Some(previous_state) = &app.previous_machine_state.pop() {
+                            app.restore(previous_state);
+                        }
+                    },
+                    KeyCode::Char('p') => {
+                        app.running = !app.running;
+                        if app.running {
+                            let state = (*app.inner_machine_state).borrow().clone();
+                            app.previous_machine_state.push(state);
+                        }
+                    },
+                    KeyCode::Char('t') => {
+                        app.turbo = !app.turbo;
+                    },
+                    KeyCode::Char('+') => {
+                        app.speed = (app.speed + 1).min(MAX_SPEED);
+                    },
+                    KeyCode::Char('-') => {
+                        app.speed = (app.speed - 1).max(1);
+                    },
+                    KeyCode::Char('r') => {
+                        app.rewind_to_checkpoint();
+                    },
+                    KeyCode::Insert | KeyCode::Char('i') => {
+                        app.open_browser();
+                    }
+                    KeyCode::Char('l') => {
+                        if let Ok(path) = State::prompt("Enter a listing file name: ") {
+                            if let Ok(listing) = Listing::load(&path, app.load_options.load_address) {
+                                app.listing = Some(listing);
+                                app.refresh_disassembly();
+                            }
+                        }
+                    },
+                    KeyCode::Char('d') => {
+                        if let Ok(path) = State::prompt("Enter a debug info file name: ") {
+                            if let Ok(debug_info) = DebugInfo::load(&path) {
+                                app.debug_info = Some(debug_info);
+                                app.show_source = true;
+                            }
+                        }
+                    },
+                    KeyCode::Char('s') => {
+                        app.show_source = !app.show_source;
+                    },
+                    KeyCode::Char('j') => {
+                        app.show_terminal = !app.show_terminal;
+                    },
+                    KeyCode::Char('w') => {
+                        app.show_history = !app.show_history;
+                    },
+                    KeyCode::Char('L') => {
+                        app.show_log = !app.show_log;
+                    },
+                    KeyCode::Char('A') => {
+                        app.show_comparison = !app.show_comparison;
+                    },
+                    KeyCode::Char('T') => {
+                        app.explorer = Some(Explorer::default());
+                    },
+                    KeyCode::Char('E') => {
+                        if let Ok(answer) = State::prompt("Set a register (a=05, x, y, sp, p, pc=8000) or toggle a flag (n, v, b, d, i, z, c, u): ") {
+                            if let Some(flag) = edits::flag_named(&answer) {
+                                app.toggle_flag(flag);
+                            } else if let Ok(value) = answer.parse::<RegisterValue>() {
+                                app.set_register(value);
+                            }
+                        }
+                    },
+                    KeyCode::Char('C') => {
+                        if let Ok(answer) = State::prompt("Call a routine (address, then registers to set : 8020 a=05 x=01): ") {
+                            if let Ok(request) = answer.parse::<CallRequest>() {
+                                app.call_routine(&request);
+                            }
+                        }
+                    },
+                    KeyCode::Char('J') => {
+                        if let Ok(target) = State::prompt("Jump to (address or file:line): ") {
+                            if let Some(address) = app.resolve_location(&target) {
+                                app.force_jump(address);
+                            }
+                        }
+                    },
+                    KeyCode::Char('S') => {
+                        app.skip_instruction();
+                    },
+                    KeyCode::Char('U') => {
+                        app.undo_edit();
+                    },
+                    KeyCode::Char('R') => {
+                        app.redo_edit();
+                    },
+                    KeyCode::Char('W') => {
+                        if let Ok(answer) = State::prompt("Filter the history (8000-80ff, load, a>10, /text, empty shows all): ") {
+                            if let Ok(filter) = answer.parse::<TraceFilter>() {
+                                app.trace_filter = filter;
+                                app.show_history = true;
+                            }
+                        }
+                    },
+                    KeyCode::Char('X') => {
+                        if let Ok(answer) = State::prompt("Export the history shown to (default history.txt): ") {
+                            let path = if answer.trim().is_empty() { "history.txt" } else { answer.trim() };
+                            let _ = app.export_history(path);
+                        }
+                    },
+                    KeyCode::Char('x') => {
+                        if let Ok(answer) = State::prompt("Break on return from frame (0 is the innermost): ") {
+                            let depth = (*app.inner_machine_state).borrow().machine.cpu.borrow().calls.depth();
+                            if let Ok(frame) = answer.trim().parse::<usize>() {
+                                if frame < depth {
+                                    app.return_break = Some(depth - 1 - frame);
+                                    app.run_until_breakpoint();
+                                }
+                            }
+                        }
+                    },
+                    KeyCode::Char('y') => {
+                        if let Ok(name) = State::prompt("Bookmark this moment as: ") {
+                            if !name.trim().is_empty() {
+                                app.add_bookmark(&name);
+                            }
+                        }
+                    },
+                    KeyCode::Char('Y') => {
+                        let names = app.bookmarks.iter()
+                            .map(|bookmark| format!("{} (frame {})", bookmark.name, bookmark.frame))
+                            .collect::<Vec<String>>();
+                        if let Ok(name) = State::prompt(&format!("Go to bookmark [{}]: ", names.join(", "))) {
+                            app.goto_bookmark(&name);
+                        }
+                    },
+                    KeyCode::Char('N') => {
+                        let colors = regions::COLORS.iter().map(|(name, _)| *name).collect::<Vec<&str>>().join(" ");
+                        let prompt = format!("Name a region (0200-02ff,color,name, colors {}, an address alone drops the region there): ", colors);
+                        if let Ok(answer) = State::prompt(&prompt) {
+                            match answer.parse::<Region>() {
+                                Ok(region) => regions::annotate(&mut app.regions, region),
+                                Err(_) => if let Some(address) = loader::parse_address(&answer) {
+                                    regions::remove(&mut app.regions, address);
+                                },
+                            }
+                        }
+                    },
+                    KeyCode::Char('h') => {
+                        if let Ok(text) = State::prompt("Type on the terminal: ") {
+                            (*app.inner_machine_state).borrow().machine.type_text(&format!("{}\r", text));
+                        }
+                    },
+                    KeyCode::Char('b') => {
+                        if let Ok(target) = State::prompt("Toggle breakpoint (address, file:line, w 00d0 =00 on data, w PPUDATA on a register, or nmi, irq, brk, rti, vectors, stack): ") {
+                            if app.set_break_class(&target, None).is_some() {
+                            } else if let Ok(register_break) = target.parse::<RegisterBreak>() {
+                                register_breaks::toggle(&mut app.register_breaks, register_break);
+                            } else if let Ok(watchpoint) = target.parse::<Watchpoint>() {
+                                watchpoints::toggle(&mut app.watchpoints, watchpoint);
+                            } else if let Some(address) = app.resolve_location(&target) {
+                                if !app.breakpoints.remove(&address) {
+                                    app.breakpoints.insert(address);
+                                }
+                            }
+                        }
+                    },
+                    KeyCode::Char('c') => {
+                        app.run_until_breakpoint();
+                    },
+                    KeyCode::Char('v') => {
+                        app.slots = app.saves.list();
+                        app.slot_picker = Some(0);
+                    },
+                    KeyCode::Char('a') => {
+                        if let Ok(load_options) = State::load_options_from_prompt(app.load_options) {
+                            app.load_options = load_options;
+                        }
+                    },
+
+                    KeyCode::Esc | KeyCode::Char('q') => break,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> App<'a> {
+    pub fn new(arguments: &Arguments) -> Result<App<'a>, Error> {
+        let program_path = arguments.program_path.clone();
+        // before anything is loaded, the loader logs too
+        if let Some(spec) = arguments.log.clone().or_else(|| std::env::var(logging::LOG_ENV).ok()) {
+            logging::configure(&spec)?;
+        }
+        if let Some(path) = &arguments.log_file {
+            logging::log_to_file(path)?;
+        }
+        let config = Config::load(&arguments.config_path)?;
+        let game = match archive::read(&program_path) {
+            Ok((name, content)) => Some(GameSettings::identify(&config, &name, &content)?),
+            Err(_) => None,
+        };
+        let config = game.as_ref().map_or(config.clone(), |game| game.overlaid(&config));
+        // a fuzz seed always means random power on memory, whatever the config says
+        let settings = MachineSettings::from_config(&config);
+        let peripheral = settings.peripheral;
+        let power_on = match arguments.fuzz_seed {
+            Some(seed) => PowerOnPattern::Random(seed),
+            None => settings.power_on,
+        };
+        let profile = MachineProfile::load(arguments.machine.as_deref()
+            .unwrap_or(&config.get_or("machine.profile", "nes".to_string())))?;
+        let base_options = LoadOptions {
+            load_address : profile.load_address.unwrap_or(LoadOptions::new().load_address),
+            ..LoadOptions::new()
+        };
+        let load_options = arguments.load_options(game.as_ref()
+            .map(|game| game.load_options(base_options))
+            .unwrap_or(base_options));
+        let alignment = arguments.alignment.unwrap_or(settings.alignment);
+        let (decay, read_glitch) = (settings.decay, settings.read_glitch);
+        let inner_machine_state = State::initiate_state(&profile, power_on, peripheral, alignment, decay, read_glitch);
+        let comparison = arguments.compare.as_deref().map(|path| Comparison::new(&config, path)).transpose()?;
+
+        let mut app = App {
+            memory_page_index: 0,
+            memory_row: 0,
+            selected_address: None,
+            program_view: None,
+            split: 0.70,
+            program_top: 0.31,
+            dragging: None,
+            panes: std::cell::Cell::new(Panes::default()),
+            program_path: program_path.clone(),
+            patches: arguments.patches.clone(),
+            load_options,
+            game,
+            program: Program::new(),
+            listing: None,
+            debug_info: None,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            register_breaks: BTreeSet::new(),
+            show_source: false,
+            show_terminal: profile.has_terminal(),
+            show_log: false,
+            show_history: false,
+            show_comparison: comparison.is_some(),
+            checkpoints: Checkpoints::new(CheckpointPolicy::from_config(&config)),
+            history: History::new(HistoryPolicy::from_config(&config)),
+            trace_filter: TraceFilter::default(),
+            return_break: None,
+            break_events: 0,
+            stop_reason: None,
+            stack_floor: config.get_or("stack.floor", 0),
+            sanitizer: SanitizerReport::new(SanitizerPolicy::from_config(&config, arguments.sanitize)),
+            comparison,
+            preview: RefCell::new(None),
+            keep_session: false,
+            edits: EditLog::default(),
+            selected_register: None,
+            call: None,
+            test_rom_seen: false,
+            running: false,
+            turbo: false,
+            speed: config.get_or("speed.multiplier", 1u32).clamp(1, MAX_SPEED),
+            held_buttons: [0; 4],
+            held_turbo: [0; 4],
+            auto_fire: Turbo::new(config.get_or("input.turbo_rate", Turbo::default().rate)),
+            input_port: 0,
+            pad_bindings: State::pad_bindings(&config),
+            macros: HashMap::new(),
+            regions: Vec::new(),
+            macro_recording: None,
+            macro_playing: None,
+            gamepads: Gamepads::new(&config),
+            peripheral,
+            profile,
+            saves: SaveLibrary::for_program(&config, &program_path),
+            video: VideoOptions::from_config(&config),
+            run_ahead: config.get_or("input.run_ahead", 0),
+            battery: None,
+            palette: Palette::named(&config.get_or("video.palette", "2c02".to_string()))?,
+            slots: Vec::new(),
+            slot_picker: None,
+            bookmarks: Vec::new(),
+            menu: None,
+            browser: None,
+            explorer: None,
+            config,
+            power_on,
+            alignment,
+            decay,
+            read_glitch,
+            recording: None,
+            frame_recorder: None,
+            screenshot: None,
+            netplay: None,
+            netplay_error: None,
+            state_hash: StateHash::new(),
+            state_trace: None,
+            inner_machine_state,
+            previous_machine_state: Vec::new(),
+        };
+
+        app.watch_accesses();
+        app.apply_output_settings();
+        app.attach_reference();
+        app.bookmarks = app.saves.bookmarks().unwrap_or_default();
+
+        if let Some(path) = &arguments.trace_states {
+            app.state_trace = Some(BufWriter::new(File::create(path)?));
+        }
+
+        if let Some(target) = &arguments.record_frames {
+            app.frame_recorder = Some(FrameRecorder::new(target, app.video)?);
+        }
+
+        if let Some(path) = &arguments.record_audio {
+            app.start_recording(path, arguments.record_seconds, arguments.stems);
+        }
+
+        if let Some(listing_path) = &arguments.listing_path {
+            app.listing = Some(Listing::load(listing_path, app.load_options.load_address)?);
+        }
+
+        if let Some(debug_info_path) = &arguments.debug_info_path {
+            app.debug_info = Some(DebugInfo::load(debug_info_path)?);
+            app.show_source = true;
+        }
+
+        // both players need the same program file, it is checked before anything runs
+        if let Some(role) = &arguments.netplay {
+            let program_crc = loader::read_patched(&program_path, &app.patches).map(|(_, content)| crc32(&content)).unwrap_or(0);
+            app.netplay = Some(match role {
+                Role::Host(port) => {
+                    let delay = arguments.input_delay.unwrap_or_else(|| app.config.get_or("netplay.input_delay", DEFAULT_INPUT_DELAY));
+                    println!("waiting for the other player on port {}", port);
+                    Netplay::host(*port, delay, program_crc)?
+                },
+                Role::Connect(address) => Netplay::connect(address, program_crc)?,
+            });
+        }
+        Ok(app)
+    }
+
+    pub fn load_current_program(&self) -> Result<Program, Error> {
+        let mut program = loader::load_patched(&self.program_path, self.load_options.load_address, &self.patches)?;
+        if let Some(game) = self.game.as_ref() {
+            game.correct(&mut program)?;
+        }
+        Ok(program)
+    }
+
+    pub fn load_program(&mut self, program: &Program) {
+        let bios = if program.disk.is_some() { self.disk_system_bios() } else { None };
+        self.program = {
+            let state = (*self.inner_machine_state).borrow();
+            if let Some(reference) = state.reference.as_ref() {
+                emulator::insert_program(reference, program, bios.clone(), self.load_options);
+            }
+            emulator::insert_program(&state.machine, program, bios, self.load_options)
+        };
+        if let Some(comparison) = self.comparison.as_mut() {
+            comparison.divergence = None;
+        }
+        self.attach_battery();
+        self.refresh_disassembly();
+    }
+
+    // a fresh reference machine for the a/b comparison, at power on as the machine is
+    fn attach_reference(&mut self) {
+        if let Some(comparison) = self.comparison.as_mut() {
+            comparison.divergence = None;
+            (*self.inner_machine_state).borrow_mut().reference = Some(comparison.settings.build(&self.profile));
+        }
+    }
+
+    // a board with a battery gets its save back, see BatterySave
+    fn attach_battery(&mut self) {
+        self.battery = None;
+        if !self.program.image.as_ref().is_some_and(|image| image.battery) {
+            return;
+        }
+        let interval = Duration::from_secs_f64(self.config.get_or("battery.flush_seconds", DEFAULT_BATTERY_FLUSH).max(0.0));
+        let state = (*self.inner_machine_state).borrow();
+        self.battery = BatterySave::attach(self.saves.battery_path(), interval, &state.machine).unwrap_or_else(|err| {
+            crate::log_event!(Loader, Warn, "{}, the battery save is not used", err);
+            None
+        });
+    }
+
+    // every frame or so, the save is written once a change is old enough
+    pub fn update_battery(&mut self) {
+        if let Some(battery) = self.battery.as_mut() {
+            if let Err(err) = battery.update(&(*self.inner_machine_state).borrow().machine) {
+                crate::log_event!(Loader, Error, "{}: {}", battery.path.display(), err);
+            }
+        }
+    }
+
+    // before the board goes away or the program ends
+    pub fn flush_battery(&mut self) {
+        if let Some(battery) = self.battery.as_mut() {
+            if let Err(err) = battery.flush(&(*self.inner_machine_state).borrow().machine) {
+                crate::log_event!(Loader, Error, "{}: {}", battery.path.display(), err);
+            }
+        }
+    }
+
+    // `[fds] bios`, the 8k disk system rom, disksys.rom in the working directory by default
+    fn disk_system_bios(&self) -> Option<Vec<u8>> {
+        let path = self.config.get_or("fds.bios", "disksys.rom".to_string());
+        fs::read(path).ok().filter(|bios| bios.len() == fds::BIOS_SIZE)
+    }
+
+    // swaps in another program without restarting : it is identified again, so its own
+    // settings and savestate slots apply, and the current program is ejected by the insert
+    pub fn open_program(&mut self, path: &str) {
+        let game = archive::read(path).ok()
+            .and_then(|(name, content)| GameSettings::identify(&self.config, &name, &content).ok());
+        let load_options = match &game {
+            Some(game) => game.load_options(self.load_options),
+            None => self.load_options,
+        };
+        // the patches given on the command line only go with the program they were given for
+        let patches = if path.trim() == self.program_path.trim() { self.patches.clone() } else { Vec::new() };
+        let program = loader::load_patched(path, load_options.load_address, &patches)
+            .and_then(|mut program| game.as_ref().map_or(Ok(()), |game| game.correct(&mut program)).map(|_| program));
+        if let Ok(program) = program {
+            self.flush_battery();
+            let state = (*self.inner_machine_state).borrow().clone();
+            self.previous_machine_state.push(state);
+            self.save_session();
+            self.load_options = load_options;
+            self.program_path = path.trim().to_string();
+            self.patches = patches;
+            self.saves = SaveLibrary::for_program(&self.config, &self.program_path);
+            self.bookmarks = self.saves.bookmarks().unwrap_or_default();
+            self.game = game;
+            self.test_rom_seen = false;
+            self.load_program(&program);
+            self.restore_session();
+        }
+    }
+
+    // a rom of the $6000 protocol gets the terminal pane the first time it shows up, for its text
+    pub fn watch_test_rom(&mut self) {
+        if self.test_rom_seen || test_rom::status(&(*self.inner_machine_state).borrow().machine).is_none() {
+            return;
+        }
+        self.test_rom_seen = true;
+        self.show_terminal = true;
+        self.show_history = false;
+        self.show_log = false;
+    }
+
+    // edits from the debugger are kept apart from the states stepping pushes, Left does not
+    // revert them, U and R do
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.call = None;
+        let state = (*self.inner_machine_state).borrow();
+        let edit = Edit::memory(&state.machine, address, value);
+        self.edits.make(edit, &state.machine);
+        if let Some(reference) = state.reference.as_ref() {
+            edit.apply(reference);
+        }
+        *self.preview.borrow_mut() = None;
+    }
+
+    pub fn set_register(&mut self, RegisterValue(register, value): RegisterValue) {
+        self.call = None;
+        let state = (*self.inner_machine_state).borrow();
+        let edit = Edit::register(&state.machine, register, value);
+        self.edits.make(edit, &state.machine);
+        if let Some(reference) = state.reference.as_ref() {
+            edit.apply(reference);
+        }
+        *self.preview.borrow_mut() = None;
+    }
+
+    // the pc moved without running anything, shown in the history and undone as an edit
+    pub fn force_jump(&mut self, to: u16) {
+        self.intervene(Intervention::Jump(to), to);
+    }
+
+    pub fn skip_instruction(&mut self) {
+        let next = {
+            let state = (*self.inner_machine_state).borrow();
+            let cpu = state.machine.cpu.borrow();
+            let mode = cpu.instruction_set[cpu.peek(cpu.registers.pc) as usize].address_mode;
+            cpu.registers.pc.wrapping_add(1 + Disassembler::operand_length(&mode) as u16)
+        };
+        self.intervene(Intervention::Skip(next), next);
+    }
+
+    fn intervene(&mut self, intervention: Intervention, pc: u16) {
+        self.history.intervene(&(*self.inner_machine_state).borrow().machine.cpu.borrow(), intervention);
+        self.set_register(RegisterValue(Register::Pc, pc));
+    }
+
+    // `[call] cycle_limit` bounds a routine that never returns
+    pub fn call_routine(&mut self, request: &CallRequest) {
+        let state = (*self.inner_machine_state).borrow();
+        let key = (state.machine.cpu.borrow().total_cycles, self.previous_machine_state.len());
+        let limit = self.config.get_or("call.cycle_limit", RUN_CYCLE_LIMIT as u64);
+        self.call = Some(CallReport::run(&state.machine, key, request, limit));
+    }
+
+    pub fn toggle_flag(&mut self, flag: Flag) {
+        let flags = (*self.inner_machine_state).borrow().machine.cpu.borrow().registers.flags;
+        self.set_register(RegisterValue(Register::P, (flags ^ flag as u8) as u16));
+    }
+
+    pub fn undo_edit(&mut self) -> Option<Edit> {
+        self.call = None;
+        *self.preview.borrow_mut() = None;
+        let state = (*self.inner_machine_state).borrow();
+        let edit = self.edits.undo(&state.machine)?;
+        if let Some(reference) = state.reference.as_ref() {
+            edit.inverse().apply(reference);
+        }
+        Some(edit)
+    }
+
+    pub fn redo_edit(&mut self) -> Option<Edit> {
+        self.call = None;
+        *self.preview.borrow_mut() = None;
+        let state = (*self.inner_machine_state).borrow();
+        let edit = self.edits.redo(&state.machine)?;
+        if let Some(reference) = state.reference.as_ref() {
+            edit.apply(reference);
+        }
+        Some(edit)
+    }
+
+    // the program's session as it was left, when there is one, and it is the one run last now
+    pub fn restore_session(&mut self) {
+        if !self.keep_session {
+            return;
+        }
+        if let Some(session) = Session::load(&self.saves.directory) {
+            session.apply(self);
+        }
+        let _ = session::save_last_program(&self.config, &self.program_path);
+    }
+
+    pub fn open_browser(&mut self) {
+        self.browser = Some(Browser::open(&self.program_path, session::recent_programs(&self.config)));
+    }
+
+    pub fn save_session(&self) {
+        if self.keep_session && !self.program_path.trim().is_empty() {
+            let _ = Session::of(self).save(&self.saves.directory);
+        }
+    }
+
+    pub fn eject_program(&mut self) {
+        self.flush_battery();
+        self.battery = None;
+        let state = (*self.inner_machine_state).borrow().clone();
+        self.previous_machine_state.push(state);
+        {
+            let state = (*self.inner_machine_state).borrow();
+            state.machine.bus.borrow_mut().eject_cartridge();
+            if let Some(reference) = state.reference.as_ref() {
+                reference.bus.borrow_mut().eject_cartridge();
+            }
+        }
+        self.program = Program::new();
+        self.refresh_disassembly();
+    }
+
+    pub fn soft_reset(&mut self) {
+        let state = (*self.inner_machine_state).borrow().clone();
+        self.previous_machine_state.push(state);
+        let state = (*self.inner_machine_state).borrow();
+        state.machine.soft_reset();
+        if let Some(reference) = state.reference.as_ref() {
+            reference.soft_reset();
+        }
+    }
+
+    // a fresh machine, ram filled with the power on pattern, with the current
+    // program inserted again from its original image
+    pub fn power_cycle(&mut self) {
+        self.flush_battery();
+        let state = (*self.inner_machine_state).borrow().clone();
+        self.previous_machine_state.push(state);
+        let program = self.program.clone();
+        self.history.rewind(0);
+        self.inner_machine_state = State::initiate_state(&self.profile, self.power_on, self.peripheral, self.alignment, self.decay, self.read_glitch);
+        self.watch_accesses();
+        self.apply_output_settings();
+        self.attach_reference();
+        if !program.is_empty() {
+            self.load_program(&program);
+        }
+    }
+
+    // the palette and the audio latency, again on every fresh machine. frames drawn from now
+    // on use the palette, the ones already drawn keep their colors
+    pub fn apply_output_settings(&self) {
+        let state = (*self.inner_machine_state).borrow();
+        state.machine.video.borrow_mut().palette = self.palette.clone();
+        state.machine.audio.borrow_mut().set_latency(self.config.get_or("audio.latency_ms", DEFAULT_AUDIO_LATENCY));
+    }
+
+    // a fresh machine's bus starts checking accesses when the sanitizer policy needs it to,
+    // nothing counts as written before that
+    fn watch_accesses(&self) {
+        if self.sanitizer.policy.watches_bus() {
+            (*self.inner_machine_state).borrow().machine.bus.borrow_mut().sanitizer = Some(Sanitizer::new());
+        }
+    }
+
+    // the bus watches what the watchpoints and the register breaks need from now on, the
+    // accesses it saw before dropped
+    fn watch_data(&self) {
+        let mut watched = self.watchpoints.iter().map(|watchpoint| (watchpoint.range.clone(), watchpoint.access)).collect::<Vec<_>>();
+        watched.extend(register_breaks::watched(&self.register_breaks));
+        let watches = if watched.is_empty() { None } else { Some(Watches::new(watched)) };
+        (*self.inner_machine_state).borrow().machine.bus.borrow_mut().watches = watches;
+    }
+
+    // the watchpoints and register breaks the accesses of the instruction at pc hit, taken
+    // from the bus
+    fn data_hits(&self, machine: &Machine, pc: u16) -> Vec<String> {
+        let accesses = machine.bus.borrow().watches.as_ref().map(|watches| watches.accesses.take()).unwrap_or_default();
+        let mut hits = watchpoints::hits(&self.watchpoints, &accesses, &self.regions, pc);
+        hits.extend(register_breaks::hits(&self.register_breaks, &accesses, pc));
+        hits
+    }
+
+    // the machine as the state explorer shows it, with the boxes of the register breaks in
+    // the groups of the devices they belong to
+    pub fn state_tree(&self) -> Vec<StateNode> {
+        let mut tree = inspect::machine_tree(&(*self.inner_machine_state).borrow().machine);
+        for node in tree.iter_mut() {
+            node.children.extend(register_breaks::group(&self.register_breaks, &node.name));
+        }
+        tree
+    }
+
+    pub fn resolve_location(&self, target: &str) -> Option<u16> {
+        loader::parse_address(target).or_else(|| {
+            self.debug_info.as_ref().and_then(|debug_info| debug_info.resolve(target))
+        })
+    }
+
+    pub fn step_instruction(&mut self) {
+        self.watch_data();
+        let app_state_local_val = (*self.inner_machine_state).borrow();
+
+        let proceed = {
+            let program_counter = app_state_local_val.machine.cpu.borrow().registers.pc;
+            app_state_local_val.dis.counters.contains_key(&(program_counter as i32))
+        };
+
+        // machine.step, with the instruction it starts recorded
+        if proceed {
+            self.previous_machine_state.push(app_state_local_val.clone());
+            let machine = &app_state_local_val.machine;
+            while machine.cpu.borrow().cycle > 0 {
+                machine.tick();
+            }
+            self.history.record(&machine.cpu.borrow());
+            let pc = machine.cpu.borrow().registers.pc;
+            machine.tick();
+            let mut findings = self.sanitizer.check(&app_state_local_val);
+            findings.extend(self.data_hits(machine, pc));
+            findings.extend(self.comparison.as_mut().and_then(|comparison| comparison.check(machine, app_state_local_val.reference.as_ref(), false)));
+            self.stop_reason = if findings.is_empty() { None } else { Some(findings.join(", ")) };
+        }
+    }
+
+    // ticks the machine cycle by cycle until the predicate holds, stopping early on instruction
+    // boundaries that hit a breakpoint or leave the program, the run is reverted as a single step
+    pub fn run_until<P: Fn(&Cpu) -> bool>(&mut self, predicate: P) {
+        let state = (*self.inner_machine_state).borrow().clone();
+        self.previous_machine_state.push(state);
+        self.advance_until(predicate);
+    }
+
+    // same as run_until without recording history, returns true when stopped early
+    pub fn advance_until<P: Fn(&Cpu) -> bool>(&mut self, predicate: P) -> bool {
+        let state = self.inner_machine_state.clone();
+        self.stop_reason = None;
+        self.watch_data();
+        let mut started = (0, 0); // pc and sp the current instruction started with
+        let mut frame_ended = false; // the a/b comparison looks at the ram on the next boundary
+
+        for _ in 0..RUN_CYCLE_LIMIT {
+            let (reached, interrupted, cycles) = {
+                let app_state_local_val = (*state).borrow();
+                if app_state_local_val.machine.cpu.borrow().cycle == 0 {
+                    let cpu = app_state_local_val.machine.cpu.borrow();
+                    self.history.record(&cpu);
+                    started = (cpu.registers.pc, cpu.registers.sp);
+                }
+                if app_state_local_val.machine.tick() {
+                    frame_ended = true;
+                    if let Some(recorder) = self.frame_recorder.as_mut() {
+                        let _ = recorder.capture(&app_state_local_val.machine.video.borrow());
+                    }
+                }
+
+                let cpu_local_val = app_state_local_val.machine.cpu.borrow();
+                let reached = predicate(&cpu_local_val);
+                let mut interrupted = false;
+                let findings = if cpu_local_val.cycle == 0 {
+                    self.sanitizer.check(&app_state_local_val)
+                } else {
+                    Vec::new()
+                };
+                if !reached && cpu_local_val.cycle == 0 {
+                    let program_counter = cpu_local_val.registers.pc;
+                    interrupted = self.breakpoints.contains(&program_counter) || !app_state_local_val.dis.counters.contains_key(&(program_counter as i32));
+                    if self.return_break.is_some_and(|depth| cpu_local_val.calls.depth() <= depth) {
+                        self.return_break = None;
+                        interrupted = true;
+                    }
+                    let events = cpu_local_val.events.get() & self.break_events;
+                    let mut reasons = BREAK_CLASSES.iter()
+                        .filter(|(_, event, _)| events & event != 0)
+                        .map(|(_, _, reason)| reason.to_string())
+                        .collect::<Vec<String>>();
+                    let (pc, sp) = started;
+                    let stack = cpu_local_val.registers.sp;
+                    let below_floor = self.break_events & EVENT_STACK_WRAP != 0 && stack < self.stack_floor && stack < sp;
+                    if below_floor {
+                        reasons.push(format!("push below ${:04X}", 0x0100 + self.stack_floor as u16));
+                    }
+                    if events & EVENT_STACK_WRAP != 0 || below_floor {
+                        reasons.push(format!("sp {:02X}>{:02X} at ${:04X}", sp, stack, pc));
+                    }
+                    reasons.extend(findings);
+                    reasons.extend(self.data_hits(&app_state_local_val.machine, pc));
+                    let compared = self.comparison.as_mut().and_then(|comparison| comparison.check(&app_state_local_val.machine, app_state_local_val.reference.as_ref(), frame_ended));
+                    frame_ended = false;
+                    // both sides are shown once they differ
+                    self.show_comparison |= compared.is_some();
+                    reasons.extend(compared);
+                    if !reasons.is_empty() {
+                        self.stop_reason = Some(reasons.join(", "));
+                        interrupted = true;
+                    }
+                }
+                (reached, interrupted, cpu_local_val.total_cycles)
+            };
+
+            let frame = RasterPosition::from_cycles(cycles).frame;
+            if self.checkpoints.is_due(frame) {
+                let snapshot = (*state).borrow().clone();
+                self.checkpoints.record(frame, cycles, snapshot);
+            }
+
+            if reached {
+                return false;
+            }
+            if interrupted {
+                return true;
+            }
+        }
+        true
+    }
+
+    // the held buttons are latched into the controller at the frame boundary only,
+    // so toggling them mid frame never leaks into the frame being run
+    pub fn advance_frame(&mut self) -> bool {
+        if !self.latch_buttons() {
+            return true;
+        }
+        let cycles = (*self.inner_machine_state).borrow().machine.cpu.borrow().total_cycles;
+        if !self.profile.nes {
+            let next_frame = self.next_frame_cycles(cycles);
+            return self.advance_until(|cpu| cpu.total_cycles >= next_frame);
+        }
+        let next_frame = (*self.inner_machine_state).borrow().machine.cpu.borrow().raster().frame + 1;
+        let interrupted = self.advance_until(|cpu| cpu.raster().frame >= next_frame);
+        if !interrupted {
+            self.run_ahead();
+        }
+        interrupted
+    }
+
+    // the frame buffer shows [input] run_ahead frames further than the machine is
+    fn run_ahead(&self) {
+        if self.run_ahead > 0 && self.profile.nes {
+            (*self.inner_machine_state).borrow().machine.run_ahead(self.run_ahead);
+        }
+    }
+
+    // boards without the console's video count their frames in slices of their own clock
+    fn next_frame_cycles(&self, cycles: u64) -> u64 {
+        let per_frame = self.profile.cycles_per_frame();
+        (cycles / per_frame + 1) * per_frame
+    }
+
+    // whatever the mixer sampled goes to the recording, which is written once it has
+    // its duration, with no recording the mixer keeps only the last audio.latency_ms
+    pub fn start_recording(&mut self, path: &str, seconds: Option<f64>, stems: bool) {
+        {
+            let state = (*self.inner_machine_state).borrow();
+            let mut audio = state.machine.audio.borrow_mut();
+            audio.drain();
+            audio.split_stems(stems);
+        }
+        self.recording = Some(WavRecording::new(path, SAMPLE_RATE, seconds, stems));
+    }
+
+    pub fn collect_audio(&mut self) {
+        let recording = match self.recording.as_mut() {
+            Some(recording) => recording,
+            None => return,
+        };
+        {
+            let state = (*self.inner_machine_state).borrow();
+            let mut audio = state.machine.audio.borrow_mut();
+            if recording.split != audio.has_stems() {
+                audio.split_stems(recording.split);
+            }
+            recording.append(audio.drain(), audio.drain_stems());
+        }
+        if recording.is_complete() {
+            self.stop_recording();
+        }
+    }
+
+    // with netplay the local pad's buttons travel and the peer's replace whatever is held
+    // for its pad, false when the peer is gone. frames start here, so does their state hash
+    fn latch_buttons(&mut self) -> bool {
+        let frame = self.state_hash.frame;
+        let state_hash = self.state_hash.fold(&(*self.inner_machine_state).borrow().machine);
+        if let Some(trace) = self.state_trace.as_mut() {
+            let _ = writeln!(trace, "frame {} {:08X}", frame, state_hash);
+        }
+
+        let mut buttons = [0; 4];
+        for (port, buttons) in buttons.iter_mut().enumerate() {
+            let (pressed, turbo) = self.gamepads.buttons(port);
+            *buttons = self.auto_fire.apply(self.held_buttons[port] | pressed, self.held_turbo[port] | turbo, frame);
+        }
+        // a macro plays over what the pad holds, and is recorded as it comes out
+        if let Some(take) = self.macro_playing.as_mut() {
+            match take.play() {
+                Some(played) => buttons[take.pad] = played,
+                None => self.macro_playing = None,
+            }
+        }
+        if let Some(take) = self.macro_recording.as_mut() {
+            take.record(buttons[take.pad]);
+        }
+        if let Some(netplay) = self.netplay.as_mut() {
+            match netplay.exchange(buttons[netplay.port], state_hash) {
+                Ok((local, remote)) => {
+                    buttons[netplay.port] = local;
+                    buttons[netplay.remote_port()] = remote;
+                },
+                Err(err) => {
+                    self.netplay_error = Some(err.to_string());
+                    self.netplay = None;
+                    return false;
+                },
+            }
+        }
+        // the reference gets them at the same cycle
+        let state = (*self.inner_machine_state).borrow();
+        if let (Some(comparison), Some(reference)) = (self.comparison.as_ref(), state.reference.as_ref()) {
+            comparison.catch_up(&state.machine, reference);
+        }
+        for (port, buttons) in buttons.iter().enumerate() {
+            state.machine.set_buttons(port as u16, *buttons);
+            if let Some(reference) = state.reference.as_ref() {
+                reference.set_buttons(port as u16, *buttons);
+            }
+        }
+        true
+    }
+
+    // whole frames with the held buttons, breakpoints and the program bounds are ignored
+    // the sanitizer only logs here, nothing stops these runs
+    pub fn run_frames(&mut self, frames: u64) {
+        let sanitizing = self.sanitizer.policy.enabled();
+        for _ in 0..frames {
+            if !self.latch_buttons() {
+                return;
+            }
+            let state = (*self.inner_machine_state).borrow();
+            let next_frame = self.next_frame_cycles(state.machine.cpu.borrow().total_cycles);
+            loop {
+                let frame_done = state.machine.tick();
+                if sanitizing && state.machine.cpu.borrow().cycle == 0 {
+                    self.sanitizer.check(&state);
+                }
+                if let Some(comparison) = self.comparison.as_mut().filter(|_| frame_done || state.machine.cpu.borrow().cycle == 0) {
+                    comparison.check(&state.machine, state.reference.as_ref(), frame_done);
+                }
+                let done = if self.profile.nes { frame_done } else { state.machine.cpu.borrow().total_cycles >= next_frame };
+                if done {
+                    break;
+                }
+            }
+            drop(state);
+            self.run_ahead();
+            self.collect_audio();
+            self.update_battery();
+            self.capture_frame();
+        }
+    }
+
+    // the last completed frame, saved as png when a path is given (cropped and scaled as the
+    // [video] settings say), with its number and the hash of the whole frame
+    pub fn screenshot(&self, path: Option<&str>) -> Result<(u64, u32), Error> {
+        let state = (*self.inner_machine_state).borrow();
+        let video = state.machine.video.borrow();
+        if let Some(path) = path {
+            let (width, height, pixels) = self.video.present(&video);
+            fs::write(path.trim(), png::encode(width, height, &pixels))?;
+        }
+        Ok((video.frame, frames::frame_hash(&video)))
+    }
+
+    // frames completed by single steps, running frames are captured as they complete
+    pub fn capture_frame(&mut self) {
+        if let Some(recorder) = self.frame_recorder.as_mut() {
+            let state = (*self.inner_machine_state).borrow();
+            let _ = recorder.capture(&state.machine.video.borrow());
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            (*self.inner_machine_state).borrow().machine.audio.borrow_mut().split_stems(false);
+            let _ = recording.finish();
+        }
+    }
+
+    // free running : `speed` frames per 60th of a second, or in turbo as many as fit
+    // between two redraws, a breakpoint or leaving the program pauses
+    pub fn run_slice(&mut self) {
+        let started = Instant::now();
+        let mut frames = 0;
+        while self.running {
+            if self.turbo && started.elapsed() >= TURBO_REDRAW_INTERVAL {
+                break;
+            }
+            if !self.turbo && frames >= self.speed {
+                break;
+            }
+            if self.advance_frame() {
+                self.running = false;
+            }
+            // drained every frame, the mixer only holds the audio latency
+            self.collect_audio();
+            frames += 1;
+        }
+        if !self.turbo {
+            thread::sleep(FRAME_TIME.saturating_sub(started.elapsed()));
+        }
+    }
+
+    pub fn restore(&mut self, state: &State<'a>) {
+        self.history.rewind(state.machine.cpu.borrow().total_cycles);
+        self.inner_machine_state = Rc::new(RefCell::new(state.clone()));
+        if let Some(comparison) = self.comparison.as_mut() {
+            comparison.divergence = None;
+        }
+    }
+
+    pub fn rewind_to_checkpoint(&mut self) {
+        let current = (*self.inner_machine_state).borrow().clone();
+        let cycles = current.machine.cpu.borrow().total_cycles;
+        let checkpoint_state = match self.checkpoints.rewind(cycles) {
+            Some(checkpoint) => checkpoint.state.clone(),
+            None => return,
+        };
+        self.previous_machine_state.push(current);
+        self.restore(&checkpoint_state);
+    }
+
+    pub fn save_slot(&mut self, slot: usize) {
+        let saved = {
+            let app_state_local_val = (*self.inner_machine_state).borrow();
+            self.saves.save(slot, &app_state_local_val.machine)
+        };
+        if saved.is_ok() {
+            self.slots = self.saves.list();
+        }
+    }
+
+    // the slot is restored into a copy first, so a stale or corrupt file leaves the machine untouched
+    pub fn load_slot(&mut self, slot: usize) {
+        let current = (*self.inner_machine_state).borrow().clone();
+        let mut loaded = current.clone();
+        if let Some(reference) = loaded.reference.as_mut() {
+            let _ = self.saves.load(slot, reference);
+        }
+        if self.saves.load(slot, &mut loaded.machine).is_ok() {
+            self.previous_machine_state.push(current);
+            self.restore(&loaded);
+        }
+    }
+
+    // a bookmark of the same name is moved here
+    pub fn add_bookmark(&mut self, name: &str) {
+        let bookmark = {
+            let state = (*self.inner_machine_state).borrow();
+            let frame = RasterPosition::from_cycles(state.machine.cpu.borrow().total_cycles).frame;
+            Bookmark::new(name, frame, &state.machine)
+        };
+        self.bookmarks.retain(|other| other.name != bookmark.name);
+        self.bookmarks.push(bookmark);
+        self.bookmarks.sort_by_key(|bookmark| bookmark.cycles);
+        let _ = self.saves.save_bookmarks(&self.bookmarks);
+    }
+
+    // back or forward, the machine is restored as it was when the bookmark was dropped
+    pub fn goto_bookmark(&mut self, name: &str) -> bool {
+        let bookmark = match self.bookmarks.iter().find(|bookmark| bookmark.name == name.trim()) {
+            Some(bookmark) => bookmark,
+            None => return false,
+        };
+        let current = (*self.inner_machine_state).borrow().clone();
+        let mut loaded = current.clone();
+        if loaded.machine.restore(&mut &bookmark.state[..]).is_err() {
+            return false;
+        }
+        if let Some(reference) = loaded.reference.as_mut() {
+            let _ = reference.restore(&mut &bookmark.state[..]);
+        }
+        self.previous_machine_state.push(current);
+        self.restore(&loaded);
+        true
+    }
+
+    // sets, clears (Some(on)) or toggles (None) a breakpoint class, None when the name is not
+    // one, else whether the class is set now
+    pub fn set_break_class(&mut self, name: &str, on: Option<bool>) -> Option<bool> {
+        let (_, event, _) = BREAK_CLASSES.iter().find(|(class, _, _)| class.eq_ignore_ascii_case(name.trim()))?;
+        let on = on.unwrap_or(self.break_events & event == 0);
+        if on {
+            self.break_events |= event;
+        } else {
+            self.break_events &= !event;
+        }
+        Some(on)
+    }
+
+    pub fn break_classes(&self) -> Vec<&'static str> {
+        BREAK_CLASSES.iter()
+            .filter(|(_, event, _)| self.break_events & event != 0)
+            .map(|(class, _, _)| *class)
+            .collect()
+    }
+
+    // the history entries the filter lets through, with the line they show as, the
+    // registers they kept and the region their operand names
+    pub fn filtered_history(&self) -> Vec<HistoryLine> {
+        let state = (*self.inner_machine_state).borrow();
+        let cpu = state.machine.cpu.borrow();
+        self.history.entries.iter()
+            .map(|executed| {
+                let text = match executed.intervention {
+                    Some(intervention) => intervention.describe(),
+                    None => state.dis.counters.get(&(executed.pc as i32))
+                        .and_then(|line| state.dis.program.get(*line))
+                        .cloned()
+                        .unwrap_or_default(),
+                };
+                let registers = executed.registers.map(|registers| format!("A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X}",
+                    registers.a, registers.x, registers.y, registers.sp, registers.flags));
+                let mode = &cpu.instruction_set[executed.opcode as usize].address_mode;
+                let operand = regions::operand(mode, cpu.peek(executed.pc.wrapping_add(1)), cpu.peek(executed.pc.wrapping_add(2)));
+                let region = operand.filter(|_| executed.intervention.is_none())
+                    .and_then(|address| regions::find(&self.regions, address).map(|region| (region.label(address), region.color())));
+                (*executed, format!("{:04X}  {}", executed.pc, text), registers, region)
+            })
+            .filter(|(executed, line, registers, region)| {
+                let label = region.as_ref().map_or("", |(label, _)| label.as_str());
+                let shown = format!("{} {} {}", line, registers.as_deref().unwrap_or_default(), label);
+                self.trace_filter.matches(executed, &shown, &cpu.instruction_set)
+            })
+            .collect()
+    }
+
+    // as shown, or json lines / csv by the extension with the `[trace] fields`
+    pub fn export_history(&self, path: &str) -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let writer = TraceWriter::from_config(&self.config, path);
+        if let Some(header) = writer.header() {
+            writeln!(out, "{}", header)?;
+        }
+        for (executed, line, registers, region) in self.filtered_history() {
+            // the disassembly without the address and the cycle count
+            let instruction = match executed.intervention {
+                Some(intervention) => intervention.describe(),
+                None => line.get(6..).map_or("", |text| text.rsplit_once(' ').map_or(text, |(text, _)| text).trim_end()).to_string(),
+            };
+            let record = TraceRecord { cycles: executed.cycles, pc: executed.pc, opcode: executed.opcode, instruction: &instruction, registers: executed.registers };
+            let line = match region {
+                Some((label, _)) => format!("{}  ; {}", line, label),
+                None => line,
+            };
+            match (writer.line(&record), registers) {
+                (Some(structured), _) => writeln!(out, "{}", structured)?,
+                (None, Some(registers)) => writeln!(out, "{:>12} {}   {}", executed.cycles, line, registers)?,
+                (None, None) => writeln!(out, "{:>12} {}", executed.cycles, line)?,
+            }
+        }
+        out.flush()
+    }
+
+    pub fn run_until_breakpoint(&mut self) {
+        self.run_until(|_| false);
+    }
+
+    pub fn refresh_disassembly(&mut self) {
+        let mut disassembled_program = Disassembler::disassemble_program(&self.program);
+        if let Some(listing) = &self.listing {
+            disassembled_program = disassembled_program.annotate(listing);
+        }
+
+        let mut app_state_local_val = (*self.inner_machine_state).borrow_mut();
+        app_state_local_val.dis = disassembled_program;
     }
 }
 
 impl DeviceOps for App<'_> {
     fn read(&self, address: u16) -> u8 {
         let local_app_state_deref = (*self.inner_machine_state).borrow_mut();
-        let x = local_app_state_deref.bus.borrow_mut().read(address); x
+        let x = local_app_state_deref.machine.bus.borrow_mut().read(address); x
     }
 
     fn write(&mut self, address: u16, data: u8) {
         let local_app_state_deref = (*self.inner_machine_state).borrow_mut();
-        local_app_state_deref.bus.borrow_mut().write(address, data);
+        local_app_state_deref.machine.bus.borrow_mut().write(address, data);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        let local_app_state_deref = (*self.inner_machine_state).borrow();
+        let value = local_app_state_deref.machine.bus.borrow().peek(address);
+        value
     }
 }
 
 impl<'a> Clone for State<'a> {
     fn clone(&self) -> Self {
-        let cpu_ref = (*self.cpu).borrow();
         State {
             dis: self.dis.clone(),
-            cpu: Rc::new(RefCell::new((*cpu_ref).clone())),
-            bus: self.bus.deref().borrow().clone_state(),
+            machine: self.machine.clone(),
+            reference: self.reference.clone(),
         }
     }
-} 
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,192 @@
+use std::cell::Ref;
+use std::fs;
+use std::io::Error;
+
+use crate::cli::{Arguments, DeterminismArguments};
+use crate::hardware::machine::Machine;
+use crate::hardware::profile::IoDevice;
+use crate::hardware::ram::PowerOnPattern;
+use crate::hardware::snapshot::Snapshot;
+use crate::hardware::state_hash::StateHash;
+use super::comparison::Divergence;
+use super::debugger::App;
+use super::macros::InputMacro;
+
+// the power on ram of the probe run when the audited runs are not seeded themselves
+pub const PROBE_SEED: u64 = 0x5EED;
+
+// what makes two runs of the same program with the same input come apart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Rerun,     // the same settings twice : the host's time, iteration order, anything outside the machine
+    PowerOn,   // other power on ram : the program reads memory it never wrote
+    Restore,   // a savestate restored into a fresh machine : state the savestate leaves out
+    HostClock, // ram seeded from the clock, power.ram = random
+    HostLink,  // an acia linked to a pty or a socket
+}
+
+impl Source {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Source::Rerun => "the same settings run twice",
+            Source::PowerOn => "other power on ram (memory read before it was written)",
+            Source::Restore => "a savestate restored into a fresh machine",
+            Source::HostClock => "power on ram seeded from the host clock (power.ram = random, --fuzz-seed fixes it)",
+            Source::HostLink => "an acia linked to the host, what comes in depends on it",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub source : Source,
+    pub frame  : Option<u64>, // the first frame the runs disagree on, none for what the settings show
+    pub detail : String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audit {
+    pub frames   : u64,
+    pub findings : Vec<Finding>,
+}
+
+impl Audit {
+    pub fn is_deterministic(&self) -> bool {
+        self.findings.iter().all(|finding| finding.source == Source::PowerOn)
+    }
+
+    pub fn report(&self) -> String {
+        if self.findings.is_empty() {
+            return format!("deterministic : no difference in {} frames", self.frames);
+        }
+        let mut lines = vec![match self.is_deterministic() {
+            true => format!("deterministic in {} frames, as long as the power on ram is the same", self.frames),
+            false => format!("not deterministic in {} frames", self.frames),
+        }];
+        for finding in self.findings.iter() {
+            let frame = finding.frame.map_or(String::new(), |frame| format!("frame {}, ", frame));
+            lines.push(format!(" * {}{} : {}", frame, finding.source.describe(), finding.detail));
+        }
+        lines.join("\n")
+    }
+}
+
+// a headless run of the program with a state hash folded each frame, as netplay and
+// --trace-states do
+struct Run<'a> {
+    app  : App<'a>,
+    hash : StateHash,
+}
+
+impl<'a> Run<'a> {
+    fn new(arguments: &Arguments) -> Result<Run<'a>, Error> {
+        let mut app = App::new(arguments)?;
+        let program = app.load_current_program()?;
+        app.load_program(&program);
+        Ok(Run { app, hash: StateHash::new() })
+    }
+
+    // a fresh run put in the state that was saved
+    fn restored(arguments: &Arguments, mut state: &[u8]) -> Result<Run<'a>, Error> {
+        let run = Run::new(arguments)?;
+        (*run.app.inner_machine_state).borrow_mut().machine.restore(&mut state)?;
+        Ok(run)
+    }
+
+    fn machine(&self) -> Ref<'_, Machine<'a>> {
+        Ref::map((*self.app.inner_machine_state).borrow(), |state| &state.machine)
+    }
+
+    fn frame(&mut self, buttons: u8) -> u32 {
+        let state = (*self.app.inner_machine_state).borrow();
+        state.machine.set_buttons(0, buttons);
+        while !state.machine.tick() {}
+        self.hash.fold(&state.machine)
+    }
+
+    fn state(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.machine().save(&mut out);
+        out
+    }
+}
+
+// what the two machines disagree on that a program or a player would see : the registers,
+// the cpu's ram when asked and the last picture. devices catch up with the cpu lazily so
+// their own state can differ for a while without anything coming of it
+fn disagreement(this: &Machine, other: &Machine, ram: bool) -> Option<String> {
+    let picture = this.video.borrow().front() != other.video.borrow().front();
+    match (Divergence::find(this, other, ram), picture) {
+        (Some(divergence), picture) => Some(format!("{}{} differ at cycle {}", divergence.describe(), if picture { ", the picture" } else { "" }, divergence.cycles)),
+        (None, true) => Some("the picture differs".to_string()),
+        (None, false) => None,
+    }
+}
+
+// what the settings alone tell : a clock seed differs between two runs made with them
+fn settings_findings(runs: (&App, &App)) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if let (PowerOnPattern::Random(first), PowerOnPattern::Random(second)) = (runs.0.power_on, runs.1.power_on) {
+        if first != second {
+            findings.push(Finding { source: Source::HostClock, frame: None, detail: format!("seeds {} and {}", first, second) });
+        }
+    }
+    let links = runs.0.profile.devices.iter()
+        .filter_map(|device| match device {
+            IoDevice::Acia(address, Some(_)) => Some(format!("${:04X}", address)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    if !links.is_empty() {
+        findings.push(Finding { source: Source::HostLink, frame: None, detail: links.join(", ") });
+    }
+    findings
+}
+
+// the program run twice with the same arguments, once with other power on ram (`probe`)
+// and once from a savestate of the first run taken halfway, frame by frame with the same
+// buttons on the first pad. the second run's state hash is compared with the first's each
+// frame, the other two only on what can be seen since their memory differs from the start
+// or their hash starts over
+pub fn audit(arguments: &Arguments, probe: &Arguments, frames: u64, inputs: &InputMacro) -> Result<Audit, Error> {
+    let mut runs = [Run::new(arguments)?, Run::new(arguments)?, Run::new(probe)?];
+    let mut findings = settings_findings((&runs[0].app, &runs[1].app));
+    let mut restored: Option<Run> = None;
+    let found = |findings: &Vec<Finding>, source: Source| findings.iter().any(|finding| finding.source == source);
+
+    for frame in 1..=frames {
+        let buttons = inputs.frames.get(frame as usize - 1).copied().unwrap_or(0);
+        let hashes = runs.iter_mut().map(|run| run.frame(buttons)).collect::<Vec<u32>>();
+        if let Some(run) = restored.as_mut() {
+            run.frame(buttons);
+        }
+        if hashes[0] != hashes[1] && !found(&findings, Source::Rerun) {
+            let detail = disagreement(&runs[0].machine(), &runs[1].machine(), true)
+                .unwrap_or_else(|| "memory outside the cpu's ram differs".to_string());
+            findings.push(Finding { source: Source::Rerun, frame: Some(frame), detail });
+        }
+        let seen = [(Source::PowerOn, Some(&runs[2]), false), (Source::Restore, restored.as_ref(), true)];
+        for (source, run, ram) in seen {
+            let detail = run.and_then(|run| disagreement(&runs[0].machine(), &run.machine(), ram));
+            if let (Some(detail), false) = (detail, found(&findings, source)) {
+                findings.push(Finding { source, frame: Some(frame), detail });
+            }
+        }
+        if frame == frames / 2 {
+            restored = Some(Run::restored(arguments, &runs[0].state())?);
+        }
+    }
+    Ok(Audit { frames, findings })
+}
+
+pub fn start(arguments: DeterminismArguments) -> Result<(), Error> {
+    let this = arguments.side(&[])?;
+    let seed = this.fuzz_seed.map_or(PROBE_SEED, |seed| seed.wrapping_add(1));
+    let probe = arguments.side(&["--fuzz-seed".to_string(), seed.to_string()])?;
+    let inputs = match &arguments.inputs {
+        Some(path) => fs::read_to_string(path)?.trim().parse::<InputMacro>()?,
+        None => InputMacro::default(),
+    };
+    println!("{}", audit(&this, &probe, arguments.frames, &inputs)?.report());
+    Ok(())
+}
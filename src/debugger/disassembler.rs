@@ -1,5 +1,8 @@
 use crate::hardware::address_mode::AddressMode;
-use crate::hardware::cpu::*;
+use crate::hardware::opcode_table::OPCODES;
+use crate::hardware::registers::Registers;
+use crate::loader::program::Program;
+use super::listing::Listing;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -16,79 +19,129 @@ impl Disassembler {
         }
     }
 
-    pub fn disassemble(program: &Vec<u8>) -> Disassembler {
-        let memory_region_start : i32 = 0x8000;
+    pub fn disassemble(program: &[u8]) -> Disassembler {
+        Disassembler::disassemble_at(program, 0x8000)
+    }
+
+    pub fn disassemble_program(program: &Program) -> Disassembler {
+        let mut disassembler = Disassembler::new();
+        for segment in program.segments.iter() {
+            let part = Disassembler::disassemble_at(&segment.bytes, segment.address);
+            let offset = disassembler.program.len();
+            disassembler.program.extend(part.program);
+            disassembler.counters.extend(part.counters.into_iter().map(|(address, line)| (address, line + offset)));
+        }
+        disassembler
+    }
+
+    pub fn disassemble_at(program: &[u8], origin: u16) -> Disassembler {
+        let memory_region_start : i32 = origin as i32;
         let mut string_builder = Vec::new();
         let mut wires_builder = HashMap::new();
-        let instruction_set = Cpu::setup_instruction_map();
 
         let mut i = 0;
         while i < program.len() {
-            let byte = program[i];
-            if let Some(instruction) = instruction_set.get(&byte) {
-                wires_builder.insert(memory_region_start + (i as i32), string_builder.len());
-                let mut instruction_string = String::new();
-                instruction_string.push_str(format!("{}", instruction.mnemonic).as_str());
-                instruction_string.push_str(" ");
+            let instruction = &OPCODES[program[i] as usize];
+            wires_builder.insert(memory_region_start + (i as i32), string_builder.len());
+            // operands cut by the end of the segment read as 0
+            let operand = |n: usize| program.get(i + n).copied().unwrap_or(0);
+            let word = ((operand(2) as u16) << 8) | operand(1) as u16;
+            let address = origin.wrapping_add(i as u16);
+            // an illegal opcode shows as the byte it is, it runs as a nop
+            let text = if instruction.legal {
+                format!("{} {}", instruction.mnemonic, Disassembler::format_operand(&instruction.address_mode, address, operand(1), word))
+            } else {
+                format!(".byte ${:02X}", program[i])
+            };
+            string_builder.push(format!("{:<13} {}c", text.trim_end(), instruction.cycles));
+            i += 1 + Disassembler::operand_length(&instruction.address_mode);
+        }
+
+        Disassembler {
+            program: string_builder,
+            counters: wires_builder
+        }
+    }
+
+    pub fn operand_length(mode: &AddressMode) -> usize {
+        match mode {
+            AddressMode::Imp => 0,
+            AddressMode::Abs | AddressMode::Abx | AddressMode::Aby | AddressMode::Ind => 2,
+            _ => 1,
+        }
+    }
 
-                match instruction.address_mode {
-                    AddressMode::Abs => {
-                        instruction_string.push_str(format!("${:02X}{:02X}", program[1], program[2]).as_str());
-                        i += 2;
-                    },
-                    AddressMode::Abx => {
-                        instruction_string.push_str(format!("${:02X}{:02X}, X", program[1], program[2]).as_str());
-                        i += 2;
-                    },
-                    AddressMode::Aby => {
-                        instruction_string.push_str(format!("${:02X}{:02X}, Y", program[1], program[2]).as_str());
-                        i += 2;
-                    },
-                    AddressMode::Imm => {
-                        instruction_string.push_str(format!("#${:02X}", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Imp => {
-                        instruction_string.push_str("");
-                    },
-                    AddressMode::Ind => {
-                        instruction_string.push_str(format!("(${:02X}{:02X})", program[1], program[2]).as_str());
-                        i += 2;
-                    },
-                    AddressMode::Izx => {
-                        instruction_string.push_str(format!("(${:02X}, X)", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Izy => {
-                        instruction_string.push_str(format!("(${:02X}), Y", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Rel => {
-                        instruction_string.push_str(format!("${:02X}", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Zp0 => {
-                        instruction_string.push_str(format!("${:02X}", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Zpx => {
-                        instruction_string.push_str(format!("${:02X}, X", program[1]).as_str());
-                        i += 1;
-                    },
-                    AddressMode::Zpy => {
-                        instruction_string.push_str(format!("${:02X}, Y", program[1]).as_str());
-                        i += 1;
-                    },
-                }
-                string_builder.push(instruction_string);
-                i += 1;
+    // branches show where they go
+    fn format_operand(mode: &AddressMode, address: u16, byte: u8, word: u16) -> String {
+        match mode {
+            AddressMode::Imp => String::new(),
+            AddressMode::Imm => format!("#${:02X}", byte),
+            AddressMode::Zp0 => format!("${:02X}", byte),
+            AddressMode::Zpx => format!("${:02X}, X", byte),
+            AddressMode::Zpy => format!("${:02X}, Y", byte),
+            AddressMode::Abs => format!("${:04X}", word),
+            AddressMode::Abx => format!("${:04X}, X", word),
+            AddressMode::Aby => format!("${:04X}, Y", word),
+            AddressMode::Ind => format!("(${:04X})", word),
+            AddressMode::Izx => format!("(${:02X}, X)", byte),
+            AddressMode::Izy => format!("(${:02X}), Y", byte),
+            AddressMode::Rel => format!("${:04X}", address.wrapping_add(2).wrapping_add(byte as i8 as u16)),
+        }
+    }
+
+    // what the instruction at pc would access with the registers and memory as they are now,
+    // as the cpu resolves it (zero page wrapping, the indirect jump staying in its page),
+    // None for the modes with no memory operand
+    pub fn effective_address<P: Fn(u16) -> u8>(mode: &AddressMode, pc: u16, registers: &Registers, peek: P) -> Option<u16> {
+        let byte = peek(pc.wrapping_add(1));
+        let word = ((peek(pc.wrapping_add(2)) as u16) << 8) | byte as u16;
+        let pointer = |at: u8| ((peek(at.wrapping_add(1) as u16) as u16) << 8) | peek(at as u16) as u16;
+        match mode {
+            AddressMode::Imp | AddressMode::Imm | AddressMode::Rel => None,
+            AddressMode::Zp0 => Some(byte as u16),
+            AddressMode::Zpx => Some(byte.wrapping_add(registers.x) as u16),
+            AddressMode::Zpy => Some(byte.wrapping_add(registers.y) as u16),
+            AddressMode::Abs => Some(word),
+            AddressMode::Abx => Some(word.wrapping_add(registers.x as u16)),
+            AddressMode::Aby => Some(word.wrapping_add(registers.y as u16)),
+            AddressMode::Ind => {
+                let high = (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF);
+                Some(((peek(high) as u16) << 8) | peek(word) as u16)
+            },
+            AddressMode::Izx => Some(pointer(byte.wrapping_add(registers.x))),
+            AddressMode::Izy => Some(pointer(byte).wrapping_add(registers.y as u16)),
+        }
+    }
+
+    // interleaves labels and comments from an assembler listing with the disassembly,
+    // and appends the original source to each instruction line
+    pub fn annotate(&self, listing: &Listing) -> Disassembler {
+        let annotations = listing.annotations();
+        let addresses = self.counters.iter()
+            .map(|(address, line)| (*line, *address))
+            .collect::<HashMap<usize, i32>>();
+
+        let mut string_builder = Vec::new();
+        let mut wires_builder = HashMap::new();
+        for (line, text) in self.program.iter().enumerate() {
+            let address = addresses.get(&line);
+            let annotation = address.and_then(|address| annotations.get(&(*address as u16)));
+
+            if let Some(annotation) = annotation {
+                string_builder.extend(annotation.preceding.iter().cloned());
+            }
+            if let Some(address) = address {
+                wires_builder.insert(*address, string_builder.len());
+            }
+            match annotation.and_then(|annotation| annotation.source.as_ref()) {
+                Some(source) => string_builder.push(format!("{:<14}; {}", text, source)),
+                None => string_builder.push(text.clone()),
             }
         }
-        
+
         Disassembler {
             program: string_builder,
             counters: wires_builder
         }
     }
 }
-
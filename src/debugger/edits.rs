@@ -0,0 +1,160 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use crate::hardware::registers::{Flag, Registers};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    Sp,
+    P,
+    Pc,
+}
+
+// in the order the processor pane lists them
+pub const REGISTERS: [Register; 6] = [Register::A, Register::X, Register::Y, Register::Pc, Register::Sp, Register::P];
+// the status pane's rows, with the letter they show as
+pub const FLAGS: [(char, Flag); 8] = [('N', Flag::N), ('V', Flag::O), ('B', Flag::B), ('D', Flag::D), ('I', Flag::I), ('Z', Flag::Z), ('C', Flag::C), ('U', Flag::U)];
+
+pub fn flag_named(name: &str) -> Option<Flag> {
+    FLAGS.iter().find(|(letter, _)| name.trim().eq_ignore_ascii_case(&letter.to_string())).map(|(_, flag)| *flag)
+}
+
+impl Register {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Register::A => "a",
+            Register::X => "x",
+            Register::Y => "y",
+            Register::Sp => "sp",
+            Register::P => "p",
+            Register::Pc => "pc",
+        }
+    }
+
+    // as the processor pane shows it
+    pub fn show(&self, registers: &Registers) -> String {
+        match self {
+            Register::Pc => format!("PC: {:04X}", registers.pc),
+            _ => format!("{:>2}: {:02X}", self.name().to_uppercase(), self.get(registers)),
+        }
+    }
+
+    pub fn get(&self, registers: &Registers) -> u16 {
+        match self {
+            Register::A => registers.a as u16,
+            Register::X => registers.x as u16,
+            Register::Y => registers.y as u16,
+            Register::Sp => registers.sp as u16,
+            Register::P => registers.flags as u16,
+            Register::Pc => registers.pc,
+        }
+    }
+
+    pub fn set(&self, registers: &mut Registers, value: u16) {
+        match self {
+            Register::A => registers.a = value as u8,
+            Register::X => registers.x = value as u8,
+            Register::Y => registers.y = value as u8,
+            Register::Sp => registers.sp = value as u8,
+            Register::P => registers.flags = value as u8,
+            Register::Pc => registers.pc = value,
+        }
+    }
+}
+
+// a change made from the debugger rather than by the program, with what it replaced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Memory { address: u16, before: u8, after: u8 },
+    Register { register: Register, before: u16, after: u16 },
+}
+
+impl Edit {
+    pub fn memory(machine: &Machine, address: u16, value: u8) -> Edit {
+        Edit::Memory { address, before: machine.bus.borrow().peek(address), after: value }
+    }
+
+    pub fn register(machine: &Machine, register: Register, value: u16) -> Edit {
+        Edit::Register { register, before: register.get(&machine.cpu.borrow().registers), after: value }
+    }
+
+    pub fn inverse(&self) -> Edit {
+        match *self {
+            Edit::Memory { address, before, after } => Edit::Memory { address, before: after, after: before },
+            Edit::Register { register, before, after } => Edit::Register { register, before: after, after: before },
+        }
+    }
+
+    // memory goes through the bus, as a write of the program would
+    pub fn apply(&self, machine: &Machine) {
+        match *self {
+            Edit::Memory { address, after, .. } => machine.bus.borrow_mut().write(address, after),
+            Edit::Register { register, after, .. } => register.set(&mut machine.cpu.borrow_mut().registers, after),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Edit::Memory { address, before, after } => format!("${:04X}: {:02X} > {:02X}", address, before, after),
+            Edit::Register { register: Register::Pc, before, after } => format!("PC: {:04X} > {:04X}", before, after),
+            Edit::Register { register, before, after } => format!("{}: {:02X} > {:02X}", register.name().to_uppercase(), before, after),
+        }
+    }
+}
+
+// `a=05`, `sp=fd`, `pc=8000`, values in hex
+pub struct RegisterValue(pub Register, pub u16);
+
+impl FromStr for RegisterValue {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<RegisterValue, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid register value: {}", text));
+        let (name, value) = text.split_once('=').ok_or_else(invalid)?;
+        let register = REGISTERS.iter().copied()
+            .find(|register| register.name() == name.trim().to_ascii_lowercase())
+            .ok_or_else(invalid)?;
+        let value = u16::from_str_radix(value.trim().trim_start_matches('$'), 16).map_err(|_| invalid())?;
+        if register != Register::Pc && value > 0xFF {
+            return Err(invalid());
+        }
+        Ok(RegisterValue(register, value))
+    }
+}
+
+// the memory and register edits made from the debugger, apart from the states stepping and
+// running keep : undoing one puts back what it replaced on the machine as it is now, whatever
+// ran since, and leaves the emulation history alone
+#[derive(Debug, Clone, Default)]
+pub struct EditLog {
+    pub done   : Vec<Edit>,
+    pub undone : Vec<Edit>,
+}
+
+impl EditLog {
+    // a new edit drops the ones undone before it
+    pub fn make(&mut self, edit: Edit, machine: &Machine) {
+        edit.apply(machine);
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    pub fn undo(&mut self, machine: &Machine) -> Option<Edit> {
+        let edit = self.done.pop()?;
+        edit.inverse().apply(machine);
+        self.undone.push(edit);
+        Some(edit)
+    }
+
+    pub fn redo(&mut self, machine: &Machine) -> Option<Edit> {
+        let edit = self.undone.pop()?;
+        edit.apply(machine);
+        self.done.push(edit);
+        Some(edit)
+    }
+}
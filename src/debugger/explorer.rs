@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+use crate::hardware::inspect::StateNode;
+
+// where the state explorer is : the groups opened, by their path from the root, and the
+// row selected. the tree is taken from the machine each time it is drawn, so what is open
+// stays open as the machine runs and devices come and go
+#[derive(Debug, Clone)]
+pub struct Explorer {
+    pub expanded : HashSet<String>,
+    pub selected : usize,
+}
+
+// a line of the tree as shown
+pub struct Row<'a> {
+    pub depth : usize,
+    pub path  : String,
+    pub node  : &'a StateNode,
+}
+
+impl Default for Explorer {
+    fn default() -> Explorer {
+        Explorer {
+            expanded : ["cpu", "cpu/registers"].iter().map(|path| path.to_string()).collect(),
+            selected : 0,
+        }
+    }
+}
+
+impl Explorer {
+    pub fn rows<'a>(&self, tree: &'a [StateNode]) -> Vec<Row<'a>> {
+        let mut rows = Vec::new();
+        self.walk(tree, 0, "", &mut rows);
+        rows
+    }
+
+    fn walk<'a>(&self, nodes: &'a [StateNode], depth: usize, parent: &str, rows: &mut Vec<Row<'a>>) {
+        // devices of a kind (two controllers, several ram regions) are told apart by position
+        for (index, node) in nodes.iter().enumerate() {
+            let twins = nodes.iter().filter(|other| other.name == node.name).count();
+            let name = if twins > 1 { format!("{}#{}", node.name, index) } else { node.name.clone() };
+            let path = if parent.is_empty() { name } else { format!("{}/{}", parent, name) };
+            let open = self.expanded.contains(&path);
+            rows.push(Row { depth, path : path.clone(), node });
+            if open {
+                self.walk(&node.children, depth + 1, &path, rows);
+            }
+        }
+    }
+
+    pub fn label(&self, row: &Row) -> String {
+        let marker = match (row.node.children.is_empty(), self.expanded.contains(&row.path)) {
+            (true, _) => " ",
+            (false, true) => "-",
+            (false, false) => "+",
+        };
+        let value = if row.node.value.is_empty() { String::new() } else { format!(" : {}", row.node.value) };
+        format!("{}{} {}{}", "  ".repeat(row.depth), marker, row.node.name, value)
+    }
+
+    pub fn select(&mut self, offset: isize, tree: &[StateNode]) {
+        let last = self.rows(tree).len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + offset).clamp(0, last.max(0)) as usize;
+    }
+
+    // Right : opens the group selected, or moves into it when it is open already
+    pub fn expand(&mut self, tree: &[StateNode]) {
+        let rows = self.rows(tree);
+        if let Some(row) = rows.get(self.selected).filter(|row| !row.node.children.is_empty()) {
+            if !self.expanded.insert(row.path.clone()) {
+                self.selected += 1;
+            }
+        }
+    }
+
+    // Left : closes the group selected, or goes up to the group holding the row
+    pub fn collapse(&mut self, tree: &[StateNode]) {
+        let rows = self.rows(tree);
+        let row = match rows.get(self.selected) {
+            Some(row) => row,
+            None => return,
+        };
+        if self.expanded.remove(&row.path) {
+            return;
+        }
+        if let Some((parent, _)) = row.path.rsplit_once('/') {
+            if let Some(index) = rows.iter().position(|other| other.path == parent) {
+                self.selected = index;
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::hardware::controller::Button;
+
+// physical gamepads through the linux joystick interface, /dev/input/js<n>, read without
+// blocking : each event is the time in ms, a value, its type and the button or axis number
+const EVENT_SIZE     : usize = 8;
+const EVENT_BUTTON   : u8 = 0x01;
+const EVENT_AXIS     : u8 = 0x02;
+const EVENT_INIT     : u8 = 0x80; // the state the device was in when opened
+const AXIS_THRESHOLD : i16 = 16384; // half way, analog sticks and d-pads reported as axes alike
+const DEVICES        : usize = 8;  // js0 - js7 are looked for
+const SCAN_INTERVAL  : Duration = Duration::from_secs(1);
+pub const PLAYERS    : usize = 4;
+const MAPPINGS_FILE  : &str = "gamepads.toml";
+
+// a button, or an axis pushed past half way in one direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Button(u8),
+    Axis(u8, bool), // the axis, true for the positive direction
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Button(number) => write!(f, "button{}", number),
+            Input::Axis(number, positive) => write!(f, "axis{}{}", number, if *positive { '+' } else { '-' }),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Input, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid gamepad input: {}", text));
+        let text = text.trim();
+        if let Some(number) = text.strip_prefix("button") {
+            return number.parse().map(Input::Button).map_err(|_| invalid());
+        }
+        let axis = text.strip_prefix("axis").ok_or_else(invalid)?;
+        let (number, positive) = match axis.chars().last() {
+            Some('+') => (&axis[..axis.len() - 1], true),
+            Some('-') => (&axis[..axis.len() - 1], false),
+            _ => return Err(invalid()),
+        };
+        number.parse().map(|number| Input::Axis(number, positive)).map_err(|_| invalid())
+    }
+}
+
+// what a player's gamepad can press, in the order the mapping asks for them, the last two
+// are held as turbo buttons
+pub const TARGETS : [(&str, Button, bool); 10] = [
+    ("a", Button::A, false), ("b", Button::B, false), ("select", Button::Select, false), ("start", Button::Start, false),
+    ("up", Button::Up, false), ("down", Button::Down, false), ("left", Button::Left, false), ("right", Button::Right, false),
+    ("turbo_a", Button::A, true), ("turbo_b", Button::B, true),
+];
+
+// the inputs pressing each target, `[gamepad1]` to `[gamepad4]` sections list them space
+// separated, `up = "axis1- axis7-"`. the default suits xbox style pads : A on the east
+// button and B on the south one as on the console's pad, the stick and the d-pad axes
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMapping {
+    pub inputs : Vec<Vec<Input>>, // by target
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        let inputs = ["button1", "button0", "button6", "button7", "axis1- axis7-", "axis1+ axis7+", "axis0- axis6-", "axis0+ axis6+", "button3", "button2"];
+        GamepadMapping { inputs : inputs.iter().map(|inputs| parse_inputs(inputs).unwrap_or_default()).collect() }
+    }
+}
+
+fn parse_inputs(text: &str) -> Result<Vec<Input>, Error> {
+    text.split_whitespace().map(str::parse).collect()
+}
+
+impl GamepadMapping {
+    // the player's section over the default, targets that do not read are left as they are
+    pub fn from_config(config: &Config, player: usize) -> GamepadMapping {
+        let mut mapping = GamepadMapping::default();
+        for (target, (name, _, _)) in TARGETS.iter().enumerate() {
+            if let Some(Ok(inputs)) = config.get(&format!("gamepad{}.{}", player + 1, name)).map(parse_inputs) {
+                mapping.inputs[target] = inputs;
+            }
+        }
+        mapping
+    }
+
+    fn section(&self, player: usize) -> String {
+        let mut lines = vec![format!("[gamepad{}]", player + 1)];
+        for ((name, _, _), inputs) in TARGETS.iter().zip(self.inputs.iter()) {
+            let inputs = inputs.iter().map(Input::to_string).collect::<Vec<String>>();
+            lines.push(format!("{} = \"{}\"", name, inputs.join(" ")));
+        }
+        lines.join("\n")
+    }
+
+    // the buttons and the turbo buttons the gamepad presses
+    pub fn buttons(&self, gamepad: &Gamepad) -> (u8, u8) {
+        let mut buttons = (0, 0);
+        for ((_, button, turbo), inputs) in TARGETS.iter().zip(self.inputs.iter()) {
+            if inputs.iter().any(|input| gamepad.active(*input)) {
+                let held = if *turbo { &mut buttons.1 } else { &mut buttons.0 };
+                *held |= *button as u8;
+            }
+        }
+        buttons
+    }
+}
+
+#[derive(Debug)]
+pub struct Gamepad {
+    pub path : PathBuf,
+    file     : File,
+    buttons  : HashMap<u8, bool>,
+    axes     : HashMap<u8, i16>,
+    partial  : Vec<u8>, // an event read in part
+}
+
+impl Gamepad {
+    #[cfg(unix)]
+    pub fn open(path: &Path) -> Option<Gamepad> {
+        use std::os::unix::fs::OpenOptionsExt;
+        const O_NONBLOCK : i32 = 0o4000;
+        let file = fs::OpenOptions::new().read(true).custom_flags(O_NONBLOCK).open(path).ok()?;
+        Some(Gamepad { path : path.to_path_buf(), file, buttons : HashMap::new(), axes : HashMap::new(), partial : Vec::new() })
+    }
+
+    // the joystick interface is linux only, elsewhere no gamepad shows up
+    #[cfg(not(unix))]
+    pub fn open(_: &Path) -> Option<Gamepad> {
+        None
+    }
+
+    pub fn active(&self, input: Input) -> bool {
+        match input {
+            Input::Button(number) => self.buttons.get(&number).copied().unwrap_or(false),
+            Input::Axis(number, positive) => {
+                let value = self.axes.get(&number).copied().unwrap_or(0);
+                if positive { value >= AXIS_THRESHOLD } else { value <= -AXIS_THRESHOLD }
+            },
+        }
+    }
+
+    // takes the events queued, the inputs that went active are added to `pressed`. false
+    // once the gamepad was unplugged, reads then fail
+    pub fn poll(&mut self, pressed: &mut Vec<Input>) -> bool {
+        let mut chunk = [0u8; EVENT_SIZE * 16];
+        loop {
+            match self.file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(count) => self.partial.extend_from_slice(&chunk[..count]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        let complete = self.partial.len() - self.partial.len() % EVENT_SIZE;
+        let events = self.partial.drain(..complete).collect::<Vec<u8>>();
+        for event in events.chunks(EVENT_SIZE) {
+            let value = i16::from_le_bytes([event[4], event[5]]);
+            let (kind, number) = (event[6], event[7]);
+            let initial = kind & EVENT_INIT != 0;
+            match kind & !EVENT_INIT {
+                EVENT_BUTTON => {
+                    if value != 0 && !initial && !self.active(Input::Button(number)) {
+                        pressed.push(Input::Button(number));
+                    }
+                    self.buttons.insert(number, value != 0);
+                },
+                EVENT_AXIS => {
+                    let before = [true, false].map(|positive| self.active(Input::Axis(number, positive)));
+                    self.axes.insert(number, value);
+                    for (positive, was_active) in [true, false].iter().zip(before.iter()) {
+                        if !initial && !was_active && self.active(Input::Axis(number, *positive)) {
+                            pressed.push(Input::Axis(number, *positive));
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        true
+    }
+}
+
+// walking a player through the targets, the next input pressed on their gamepad maps the
+// one asked for
+#[derive(Debug, Clone)]
+pub struct MappingSession {
+    pub player  : usize,
+    pub target  : usize,
+    pub mapping : GamepadMapping,
+}
+
+// gamepads take the first player free when plugged in and keep it until unplugged, looked
+// for again every second. mappings made in the interface go to gamepads.toml at the
+// savestates root, over the config's sections
+pub struct Gamepads {
+    pub players  : [Option<Gamepad>; PLAYERS],
+    pub mappings : Vec<GamepadMapping>,
+    pub mapping  : Option<MappingSession>,
+    pub notice   : Option<String>, // the last plug or unplug, for the status line
+    file         : PathBuf,
+    scanned      : Option<Instant>,
+}
+
+impl Gamepads {
+    pub fn new(config: &Config) -> Gamepads {
+        let file = Path::new(&config.get_or("savestates.directory", "savestates".to_string())).join(MAPPINGS_FILE);
+        let mut values = config.clone();
+        if let Some(saved) = file.to_str().and_then(|path| Config::load(path).ok()) {
+            values.overlay(&saved);
+        }
+        Gamepads {
+            players  : Default::default(),
+            mappings : (0..PLAYERS).map(|player| GamepadMapping::from_config(&values, player)).collect(),
+            mapping  : None,
+            notice   : None,
+            file,
+            scanned  : None,
+        }
+    }
+
+    pub fn poll(&mut self) {
+        if self.scanned.is_none_or(|scanned| scanned.elapsed() >= SCAN_INTERVAL) {
+            self.scan();
+        }
+        let mut pressed = Vec::new();
+        for player in 0..PLAYERS {
+            let mut inputs = Vec::new();
+            let present = match self.players[player].as_mut() {
+                Some(gamepad) => gamepad.poll(&mut inputs),
+                None => continue,
+            };
+            if !present {
+                if let Some(gamepad) = self.players[player].take() {
+                    self.notice = Some(format!("gamepad {} unplugged from p{}", gamepad.path.display(), player + 1));
+                }
+            }
+            pressed.extend(inputs.into_iter().map(|input| (player, input)));
+        }
+        for (player, input) in pressed {
+            self.map(player, input);
+        }
+    }
+
+    fn scan(&mut self) {
+        self.scanned = Some(Instant::now());
+        for index in 0..DEVICES {
+            let path = PathBuf::from(format!("/dev/input/js{}", index));
+            let taken = self.players.iter().flatten().any(|gamepad| gamepad.path == path);
+            let free = self.players.iter().position(Option::is_none);
+            if let (false, Some(player)) = (taken, free) {
+                if let Some(gamepad) = Gamepad::open(&path) {
+                    self.notice = Some(format!("gamepad {} on p{}", path.display(), player + 1));
+                    self.players[player] = Some(gamepad);
+                }
+            }
+        }
+    }
+
+    // the buttons and turbo buttons player's gamepad holds, none without one
+    pub fn buttons(&self, player: usize) -> (u8, u8) {
+        match self.players.get(player).and_then(Option::as_ref) {
+            Some(gamepad) => self.mappings[player].buttons(gamepad),
+            None => (0, 0),
+        }
+    }
+
+    pub fn start_mapping(&mut self, player: usize) {
+        let mapping = self.mappings[player].clone();
+        self.mapping = Some(MappingSession { player, target : 0, mapping });
+    }
+
+    // the input maps the target asked for alone, the last one ends the session
+    fn map(&mut self, player: usize, input: Input) {
+        match self.mapping.as_mut() {
+            Some(session) if session.player == player => session.mapping.inputs[session.target] = vec![input],
+            _ => return,
+        }
+        self.next_target();
+    }
+
+    // leaves the target asked for as it was
+    pub fn next_target(&mut self) {
+        let session = match self.mapping.as_mut() {
+            Some(session) => session,
+            None => return,
+        };
+        session.target += 1;
+        if session.target < TARGETS.len() {
+            return;
+        }
+        self.mappings[session.player] = session.mapping.clone();
+        self.mapping = None;
+        let _ = self.save();
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(directory) = self.file.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let sections = self.mappings.iter().enumerate()
+            .map(|(player, mapping)| mapping.section(player))
+            .collect::<Vec<String>>();
+        fs::write(&self.file, sections.join("\n") + "\n")
+    }
+
+    // what the status line shows : the target asked for while mapping, else the players
+    // with a gamepad and the last plug or unplug
+    pub fn status(&self) -> Option<String> {
+        if let Some(session) = self.mapping.as_ref() {
+            let present = if self.players[session.player].is_some() { "" } else { ", no gamepad yet" };
+            return Some(format!("map p{} : press {} (space keeps it, esc stops{})", session.player + 1, TARGETS[session.target].0, present));
+        }
+        let players = self.players.iter().enumerate()
+            .filter(|(_, gamepad)| gamepad.is_some())
+            .map(|(player, _)| format!("p{}", player + 1))
+            .collect::<Vec<String>>();
+        match (players.is_empty(), self.notice.as_ref()) {
+            (true, None) => None,
+            (true, Some(notice)) => Some(notice.clone()),
+            (false, _) => Some(format!("gamepads {}", players.join(" "))),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use crate::config::Config;
+use crate::hardware::cpu::Cpu;
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::registers::Registers;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryPolicy {
+    pub length          : usize, // 0 disables the history
+    pub registers_every : usize, // 0 keeps no registers
+}
+
+impl HistoryPolicy {
+    pub fn from_config(config: &Config) -> HistoryPolicy {
+        HistoryPolicy {
+            length          : config.get_or("history.length", 256),
+            registers_every : config.get_or("history.registers_every", 16),
+        }
+    }
+}
+
+// what the debugger did to the cpu in place of the instruction at the pc
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intervention {
+    Jump(u16), // the pc set to the address
+    Skip(u16), // the instruction passed over, the pc set to the next one
+}
+
+impl Intervention {
+    pub fn describe(&self) -> String {
+        match self {
+            Intervention::Jump(to) => format!("debugger: jump to ${:04X}", to),
+            Intervention::Skip(to) => format!("debugger: skipped to ${:04X}", to),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Executed {
+    pub pc           : u16,
+    pub opcode       : u8,
+    pub cycles       : u64,
+    pub registers    : Option<Registers>, // as they were before the instruction ran
+    pub intervention : Option<Intervention>,
+}
+
+// the instructions the cpu last started, oldest first, with the registers kept every
+// few of them : enough to see how a breakpoint was reached without a full trace
+pub struct History {
+    pub policy  : HistoryPolicy,
+    pub entries : VecDeque<Executed>,
+    recorded    : u64,
+}
+
+impl History {
+    pub fn new(policy: HistoryPolicy) -> History {
+        History {
+            policy,
+            entries  : VecDeque::with_capacity(policy.length),
+            recorded : 0,
+        }
+    }
+
+    // called on the tick an instruction starts on
+    pub fn record(&mut self, cpu: &Cpu) {
+        if self.policy.length == 0 {
+            return;
+        }
+        let keep_registers = self.policy.registers_every != 0 && self.recorded.is_multiple_of(self.policy.registers_every as u64);
+        if self.entries.len() == self.policy.length {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Executed {
+            pc           : cpu.registers.pc,
+            opcode       : cpu.peek(cpu.registers.pc),
+            cycles       : cpu.total_cycles,
+            registers    : if keep_registers { Some(cpu.registers) } else { None },
+            intervention : None,
+        });
+        self.recorded += 1;
+    }
+
+    // with the registers as they were before, always
+    pub fn intervene(&mut self, cpu: &Cpu, intervention: Intervention) {
+        if self.policy.length == 0 {
+            return;
+        }
+        if self.entries.len() == self.policy.length {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Executed {
+            pc           : cpu.registers.pc,
+            opcode       : cpu.peek(cpu.registers.pc),
+            cycles       : cpu.total_cycles,
+            registers    : Some(cpu.registers),
+            intervention : Some(intervention),
+        });
+    }
+
+    // going back in time forgets what ran after
+    pub fn rewind(&mut self, cycles: u64) {
+        while self.entries.back().is_some_and(|executed| executed.cycles >= cycles) {
+            self.entries.pop_back();
+        }
+    }
+}
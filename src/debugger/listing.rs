@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    pub address : u16,
+    pub size    : usize, // bytes emitted by the line, 0 for labels, comments and directives
+    pub source  : String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub preceding : Vec<String>,     // labels and comment lines sitting at the address
+    pub source    : Option<String>,  // the line that emitted the instruction
+}
+
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub lines: Vec<ListingLine>,
+}
+
+impl Listing {
+    pub fn load(path: &str, origin: u16) -> Result<Listing, Error> {
+        let content = fs::read(path.trim())?;
+        Ok(Listing::parse(&String::from_utf8_lossy(&content), origin))
+    }
+
+    // understands ca65 (`000000r 1  A9 00     lda #0`) and asm6 (`0C000 A9 00     lda #0`) listings,
+    // relocatable ca65 addresses are taken relative to origin
+    pub fn parse(content: &str, origin: u16) -> Listing {
+        let lines = content.lines()
+            .filter_map(|line| Listing::parse_ca65_line(line, origin).or_else(|| Listing::parse_asm6_line(line)))
+            .filter(|line| !line.source.is_empty())
+            .collect();
+        Listing { lines }
+    }
+
+    fn hex_prefix(line: &str, digits: usize) -> Option<u32> {
+        let prefix = line.get(..digits)?;
+        if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(prefix, 16).ok()
+    }
+
+    fn is_byte_token(token: &str) -> bool {
+        token.len() == 2 && (token.chars().all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c)) || token == "rr" || token == "xx")
+    }
+
+    // splits the emitted bytes from the source text, returns (byte count, source)
+    fn split_bytes(rest: &str) -> (usize, String) {
+        let mut size = 0;
+        let mut remainder = rest.trim_start();
+        while let Some(token) = remainder.split_whitespace().next() {
+            if !Listing::is_byte_token(token) {
+                break;
+            }
+            size += 1;
+            remainder = remainder[token.len()..].trim_start();
+        }
+        (size, remainder.trim_end().to_string())
+    }
+
+    fn parse_ca65_line(line: &str, origin: u16) -> Option<ListingLine> {
+        let address = Listing::hex_prefix(line, 6)?;
+        let relocatable = match line.get(6..7)? {
+            "r" => true,
+            " " => false,
+            _ => return None,
+        };
+        let rest = line.get(7..)?.trim_start();
+        let level_end = rest.find(|c: char| !c.is_ascii_digit())?;
+        if level_end == 0 {
+            return None;
+        }
+
+        // bytes occupy a fixed 12 column field, source starts at column 24
+        let (size, _) = Listing::split_bytes(line.get(11..24).unwrap_or(""));
+        let source = line.get(24..).unwrap_or("").trim_end().to_string();
+        let address = if relocatable { origin.wrapping_add(address as u16) } else { address as u16 };
+        Some(ListingLine { address, size, source })
+    }
+
+    fn parse_asm6_line(line: &str) -> Option<ListingLine> {
+        let address = Listing::hex_prefix(line, 5)?;
+        if line.get(5..6)? != " " {
+            return None;
+        }
+        let (size, source) = Listing::split_bytes(line.get(6..)?);
+        Some(ListingLine { address: address as u16, size, source })
+    }
+
+    // the labels defined at each address, `name:` lines
+    pub fn labels(&self) -> HashMap<u16, String> {
+        let mut labels = HashMap::new();
+        for line in self.lines.iter().filter(|line| line.size == 0) {
+            if let Some(label) = line.source.trim().split(';').next().and_then(|text| text.trim().strip_suffix(':')) {
+                if !label.is_empty() && !label.contains(char::is_whitespace) {
+                    labels.entry(line.address).or_insert_with(|| label.to_string());
+                }
+            }
+        }
+        labels
+    }
+
+    pub fn annotations(&self) -> HashMap<u16, Annotation> {
+        let mut annotations: HashMap<u16, Annotation> = HashMap::new();
+        for line in self.lines.iter() {
+            let annotation = annotations.entry(line.address).or_insert(Annotation { preceding: Vec::new(), source: None });
+            if line.size == 0 {
+                annotation.preceding.push(line.source.trim().to_string());
+            } else if annotation.source.is_none() {
+                annotation.source = Some(line.source.trim().to_string());
+            }
+        }
+        annotations
+    }
+}
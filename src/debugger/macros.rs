@@ -0,0 +1,91 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::hardware::controller::{self, Button};
+
+// an input macro : the buttons a pad held on each frame while it was recorded, played back
+// on the selected pad from the next frame, one frame of it per frame latched, so a combo
+// comes out with the timing it went in with. written as runs of the pad summary, "A.......x3
+// ........ .B......x2"
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputMacro {
+    pub frames : Vec<u8>,
+}
+
+impl InputMacro {
+    pub fn seconds(&self) -> f32 {
+        self.frames.len() as f32 / 60.0
+    }
+}
+
+impl fmt::Display for InputMacro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut runs = Vec::new();
+        for buttons in self.frames.iter() {
+            match runs.last_mut() {
+                Some((last, count)) if last == buttons => *count += 1,
+                _ => runs.push((*buttons, 1)),
+            }
+        }
+        let runs = runs.iter()
+            .map(|(buttons, count)| if *count > 1 { format!("{}x{}", controller::describe(*buttons), count) } else { controller::describe(*buttons) })
+            .collect::<Vec<String>>();
+        write!(f, "{}", runs.join(" "))
+    }
+}
+
+impl FromStr for InputMacro {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<InputMacro, Error> {
+        let invalid = |run: &str| Error::new(ErrorKind::InvalidData, format!("Invalid macro frames: {}", run));
+        let mut frames = Vec::new();
+        for run in text.split_whitespace() {
+            let (pad, count) = match run.split_once('x') {
+                Some((pad, count)) => (pad, count.parse::<usize>().map_err(|_| invalid(run))?),
+                None => (run, 1),
+            };
+            if pad.chars().count() != Button::ALL.len() {
+                return Err(invalid(run));
+            }
+            let buttons = pad.chars().zip(Button::ALL.iter())
+                .filter(|(symbol, _)| *symbol != '.')
+                .fold(0u8, |buttons, (_, button)| buttons | *button as u8);
+            frames.extend(std::iter::repeat_n(buttons, count));
+        }
+        Ok(InputMacro { frames })
+    }
+}
+
+// a macro being recorded from a pad or played back on one
+#[derive(Debug, Clone)]
+pub struct MacroTake {
+    pub pad    : usize,
+    pub frames : InputMacro,
+    pub next   : usize, // the frame played next
+}
+
+impl MacroTake {
+    pub fn new(pad: usize, frames: InputMacro) -> MacroTake {
+        MacroTake { pad, frames, next : 0 }
+    }
+
+    // the buttons of the next frame, None once it is over
+    pub fn play(&mut self) -> Option<u8> {
+        let buttons = self.frames.frames.get(self.next).copied()?;
+        self.next += 1;
+        Some(buttons)
+    }
+
+    pub fn record(&mut self, buttons: u8) {
+        self.frames.frames.push(buttons);
+    }
+}
+
+// "h A.......x3" : the key and the macro, as the session keeps them
+pub fn parse_binding(text: &str) -> Option<(char, InputMacro)> {
+    let mut chars = text.chars();
+    let key = chars.next()?;
+    Some((key, chars.as_str().parse().ok()?))
+}
@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"BSN2";
+// the buttons, then the state hash of the frame they were pressed on
+const MESSAGE_SIZE: usize = 5;
+pub const DEFAULT_INPUT_DELAY: u8 = 2;
+// a peer silent for this long is taken as gone
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Role {
+    Host(u16),       // listening port, plays port 1
+    Connect(String), // host:port, plays port 2
+}
+
+// lockstep over tcp : the core is deterministic, so two machines fed the same buttons
+// on the same frames stay identical and only the inputs travel. every frame each side
+// sends the buttons it holds now, which are used `delay` frames later on both ends,
+// the first frames run with nothing pressed. the state hashes travel along, so the
+// first frame the two machines differ on is known as soon as the peer's hash arrives
+pub struct Netplay {
+    stream     : TcpStream,
+    pub port   : usize, // the local player's pad
+    pub delay  : u8,
+    pub frame  : u64,
+    pub desync : Option<u64>,
+    local      : VecDeque<(u8, u32)>,
+}
+
+impl Netplay {
+    // the host decides the delay, both sides check they run the same program
+    pub fn host(port: u16, delay: u8, program_crc: u32) -> Result<Netplay, Error> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let (mut stream, _) = listener.accept()?;
+        let mut hello = MAGIC.to_vec();
+        hello.push(delay);
+        hello.extend_from_slice(&program_crc.to_le_bytes());
+        stream.write_all(&hello)?;
+        Netplay::start(stream, 0, delay)
+    }
+
+    pub fn connect(address: &str, program_crc: u32) -> Result<Netplay, Error> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        let mut hello = [0u8; 9];
+        stream.read_exact(&mut hello)?;
+        if &hello[..4] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a netplay host"));
+        }
+        let host_crc = u32::from_le_bytes([hello[5], hello[6], hello[7], hello[8]]);
+        if host_crc != program_crc {
+            return Err(Error::new(ErrorKind::InvalidData, format!("the host runs another program ({:08X}, here {:08X})", host_crc, program_crc)));
+        }
+        Netplay::start(stream, 1, hello[4])
+    }
+
+    fn start(mut stream: TcpStream, port: usize, delay: u8) -> Result<Netplay, Error> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        // the delay frames are sent up front, so the message read for a frame is always
+        // the one the peer used for it
+        stream.write_all(&vec![0; delay as usize * MESSAGE_SIZE])?;
+        Ok(Netplay {
+            stream,
+            port,
+            delay,
+            frame  : 0,
+            desync : None,
+            local  : vec![(0, 0); delay as usize].into(),
+        })
+    }
+
+    // the buttons held locally and the hash of the state they apply to go out, the buttons
+    // of both players for this frame come back as (local, remote), blocking until the
+    // peer's arrive
+    pub fn exchange(&mut self, held: u8, state_hash: u32) -> Result<(u8, u8), Error> {
+        let mut message = vec![held];
+        message.extend_from_slice(&state_hash.to_le_bytes());
+        self.stream.write_all(&message)?;
+        self.local.push_back((held, state_hash));
+
+        let mut remote = [0u8; MESSAGE_SIZE];
+        self.stream.read_exact(&mut remote)?;
+        let (local, local_hash) = self.local.pop_front().unwrap_or((0, 0));
+        let remote_hash = u32::from_le_bytes([remote[1], remote[2], remote[3], remote[4]]);
+        if local_hash != remote_hash && self.desync.is_none() {
+            self.desync = Some(self.frame.saturating_sub(self.delay as u64));
+        }
+        self.frame += 1;
+        Ok((local, remote[0]))
+    }
+
+    pub fn remote_port(&self) -> usize {
+        1 - self.port
+    }
+}
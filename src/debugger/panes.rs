@@ -0,0 +1,205 @@
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tui::layout::Rect;
+
+use super::debugger::{App, State};
+use super::edits::{self, RegisterValue};
+
+// every pane keeps this much space around it, dividers are grabbed anywhere in the gap
+const MARGIN: u16 = 5;
+const SPLIT_RANGE: (f32, f32) = (0.30, 0.85);
+const PROGRAM_TOP_RANGE: (f32, f32) = (0.15, 0.80);
+const PROGRAM_SCROLL: i32 = 3;
+// lines of 16 bytes in the pages the memory pane can show
+const MEMORY_ROWS: i32 = 0xFF * 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Divider {
+    Columns, // between the left pane and the processor / program column
+    Program, // above the program pane
+}
+
+// where the last draw put what the mouse can click on
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Panes {
+    pub size          : Rect,
+    pub pages         : Rect,
+    pub first_page    : i32,
+    pub memory        : Rect,
+    pub memory_base   : u16, // the address of the first byte shown
+    pub status        : Rect,
+    pub registers     : Rect,
+    pub first_register: usize, // the registers list's row A is on
+    pub program       : Rect,
+    pub first_line    : usize, // the disassembly line shown at the top of the program pane
+}
+
+fn inner(area: Rect) -> Rect {
+    Rect::new(area.x + 1, area.y + 1, area.width.saturating_sub(2), area.height.saturating_sub(2))
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+impl<'a> App<'a> {
+    pub fn left_pane(&self, size: Rect) -> Rect {
+        Rect::new(0, 0, (size.width as f32 * self.split) as u16, size.height)
+    }
+
+    pub fn processor_pane(&self, size: Rect) -> Rect {
+        let height = self.program_top.max(0.60);
+        Rect::new((size.width as f32 * self.split) as u16, 0, (size.width as f32 * (1.0 - self.split)) as u16, (size.height as f32 * height) as u16)
+    }
+
+    pub fn program_pane(&self, size: Rect) -> Rect {
+        Rect::new((size.width as f32 * self.split) as u16, (size.height as f32 * self.program_top) as u16,
+                  (size.width as f32 * (1.0 - self.split)) as u16, (size.height as f32 * (1.0 - self.program_top)) as u16)
+    }
+
+    // wheel scrolls the memory pages or the disassembly, clicks pick a page, edit a memory
+    // cell or a register, toggle a flag or a breakpoint on a disassembly line, dragging the
+    // gaps moves the dividers
+    pub fn on_mouse(&mut self, event: MouseEvent) {
+        let panes = self.panes.get();
+        let (column, row) = (event.column, event.row);
+        let split = (panes.size.width as f32 * self.split) as u16;
+        let program_top = (panes.size.height as f32 * self.program_top) as u16;
+        match event.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = event.kind == MouseEventKind::ScrollUp;
+                if column >= split {
+                    self.scroll_program(if up { -PROGRAM_SCROLL } else { PROGRAM_SCROLL });
+                } else if contains(panes.pages, column, row) {
+                    self.memory_page_index = (self.memory_page_index + if up { 0xFE } else { 1 }) % 0xFF;
+                } else if contains(panes.memory, column, row) {
+                    self.scroll_memory(if up { -1 } else { 1 });
+                }
+            },
+            MouseEventKind::Down(MouseButton::Left) => {
+                if column + MARGIN >= split && column < split + MARGIN {
+                    self.dragging = Some(Divider::Columns);
+                } else if contains(inner(panes.status), column, row) {
+                    // before the divider, whose gap these panes cover, it is still grabbed on their borders
+                    self.click_status(panes, row);
+                } else if contains(inner(panes.registers), column, row) {
+                    self.click_registers(panes, row);
+                } else if column >= split && row + MARGIN >= program_top && row < program_top + MARGIN {
+                    self.dragging = Some(Divider::Program);
+                } else if contains(panes.pages, column, row) {
+                    self.click_page(panes, row);
+                } else if contains(panes.memory, column, row) {
+                    self.click_memory(panes, column, row);
+                } else if contains(panes.program, column, row) {
+                    self.click_program(panes, row);
+                }
+            },
+            MouseEventKind::Drag(MouseButton::Left) => match self.dragging {
+                Some(Divider::Columns) if panes.size.width > 0 => {
+                    self.split = (column as f32 / panes.size.width as f32).clamp(SPLIT_RANGE.0, SPLIT_RANGE.1);
+                },
+                Some(Divider::Program) if panes.size.height > 0 => {
+                    self.program_top = (row as f32 / panes.size.height as f32).clamp(PROGRAM_TOP_RANGE.0, PROGRAM_TOP_RANGE.1);
+                },
+                _ => {},
+            },
+            MouseEventKind::Up(_) => self.dragging = None,
+            _ => {},
+        }
+    }
+
+    // scrolling stops following the pc, the view stays where it was put until F
+    pub fn scroll_program(&mut self, lines: i32) {
+        let first = self.program_view.unwrap_or(self.panes.get().first_line) as i32;
+        let length = (*self.inner_machine_state).borrow().dis.program.len() as i32;
+        self.program_view = Some((first + lines).clamp(0, (length - 1).max(0)) as usize);
+    }
+
+    pub fn follow_pc(&mut self) {
+        self.program_view = match self.program_view {
+            Some(_) => None,
+            None => Some(self.panes.get().first_line),
+        };
+    }
+
+    // by lines of 16 bytes, across pages
+    pub fn scroll_memory(&mut self, rows: i32) {
+        let row = (self.memory_page_index * 16 + self.memory_row + rows).rem_euclid(MEMORY_ROWS);
+        self.memory_page_index = row / 16;
+        self.memory_row = row % 16;
+    }
+
+    // a row for the title, then two rows per page
+    fn click_page(&mut self, panes: Panes, row: u16) {
+        let inner = inner(panes.pages);
+        if row > inner.y {
+            let page = panes.first_page + ((row - inner.y - 1) / 2) as i32;
+            if page < 0xFF {
+                self.memory_page_index = page;
+                self.memory_row = 0;
+            }
+        }
+    }
+
+    // the header and its margin, then two rows per line of 16 bytes after the line number,
+    // the 20 columns share the width as the table lays them out
+    fn click_memory(&mut self, panes: Panes, column: u16, row: u16) {
+        let inner = inner(panes.memory);
+        let stride = ((inner.width.saturating_sub(19) as f32 * 0.05).round() as u16 + 1).max(2);
+        if row < inner.y + 2 || column < inner.x + stride {
+            return;
+        }
+        let (line, cell) = ((row - inner.y - 2) / 2, (column - inner.x) / stride - 1);
+        if line > 16 || cell > 15 {
+            return;
+        }
+        let address = panes.memory_base.wrapping_add(line * 16 + cell);
+        self.selected_address = Some(address);
+        if let Ok(answer) = State::prompt(&format!("Set ${:04X} to (hex, empty keeps it): ", address)) {
+            let digits = answer.trim().trim_start_matches('$');
+            if let Ok(value) = u8::from_str_radix(digits, 16) {
+                self.poke(address, value);
+            }
+        }
+    }
+
+    // a flag row toggles the flag
+    fn click_status(&mut self, panes: Panes, row: u16) {
+        let inner = inner(panes.status);
+        if let Some((_, flag)) = row.checked_sub(inner.y).and_then(|line| edits::FLAGS.get(line as usize)) {
+            self.toggle_flag(*flag);
+        }
+    }
+
+    // a register row selects it and asks for its new value
+    fn click_registers(&mut self, panes: Panes, row: u16) {
+        let inner = inner(panes.registers);
+        let register = row.checked_sub(inner.y)
+            .and_then(|line| (line as usize).checked_sub(panes.first_register))
+            .and_then(|line| edits::REGISTERS.get(line))
+            .copied();
+        if let Some(register) = register {
+            self.selected_register = Some(register);
+            if let Ok(answer) = State::prompt(&format!("Set {} to (hex, empty keeps it): ", register.name().to_uppercase())) {
+                if let Ok(value) = format!("{}={}", register.name(), answer.trim()).parse::<RegisterValue>() {
+                    self.set_register(value);
+                }
+            }
+        }
+    }
+
+    fn click_program(&mut self, panes: Panes, row: u16) {
+        let inner = inner(panes.program);
+        if row < inner.y {
+            return;
+        }
+        let line = panes.first_line + (row - inner.y) as usize;
+        let address = (*self.inner_machine_state).borrow().dis.counters.iter()
+            .find(|(_, at)| **at == line)
+            .map(|(address, _)| *address as u16);
+        if let Some(address) = address {
+            if !self.breakpoints.remove(&address) {
+                self.breakpoints.insert(address);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::hardware::machine::Machine;
+use crate::hardware::registers::Registers;
+
+// what stepping would do, found by stepping a copy of the machine : the registers that
+// change and every write with the value it replaces, an interrupt taken instead shows too
+#[derive(Debug, Clone, Default)]
+pub struct Preview {
+    pub key       : (u64, usize), // the cycle and undo depth it was computed at
+    pub cycles    : u64,
+    pub registers : Vec<String>,
+    pub writes    : Vec<(u16, u8, u8)>,
+}
+
+impl Preview {
+    pub fn of(machine: &Machine, key: (u64, usize)) -> Preview {
+        let copy = machine.fork();
+        let before = copy.cpu.borrow().registers;
+        copy.bus.borrow_mut().recorded_writes = Some(Vec::new());
+        while copy.cpu.borrow().cycle > 0 {
+            copy.tick();
+        }
+        let started = copy.cpu.borrow().total_cycles;
+        copy.tick();
+        while copy.cpu.borrow().cycle > 0 {
+            copy.tick();
+        }
+        let (after, cycles) = {
+            let cpu = copy.cpu.borrow();
+            (cpu.registers, cpu.total_cycles - started)
+        };
+        let writes = copy.bus.borrow_mut().recorded_writes.take().unwrap_or_default();
+        Preview {
+            key,
+            cycles,
+            registers : Preview::changed(&before, &after),
+            writes,
+        }
+    }
+
+    pub fn changed(before: &Registers, after: &Registers) -> Vec<String> {
+        let bytes = [(" A", before.a, after.a), (" X", before.x, after.x), (" Y", before.y, after.y), ("SP", before.sp, after.sp), (" P", before.flags, after.flags)];
+        let mut lines = bytes.iter()
+            .filter(|(_, before, after)| before != after)
+            .map(|(name, before, after)| format!("{}: {:02X} > {:02X}", name, before, after))
+            .collect::<Vec<String>>();
+        lines.push(format!("PC: {:04X} > {:04X}", before.pc, after.pc));
+        lines
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("CY: +{}", self.cycles)];
+        lines.extend(self.registers.iter().cloned());
+        lines.extend(self.writes.iter().map(|(address, before, value)| format!("${:04X}: {:02X} > {:02X}", address, before, value)));
+        lines
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use tui::style::Color;
+
+use crate::hardware::address_mode::AddressMode;
+use crate::loader::loader::parse_address;
+
+// the names a region can be colored with, the first when none is given
+pub const COLORS: [(&str, Color); 7] = [
+    ("cyan", Color::LightCyan), ("red", Color::LightRed), ("green", Color::LightGreen), ("yellow", Color::LightYellow),
+    ("blue", Color::LightBlue), ("magenta", Color::LightMagenta), ("gray", Color::Gray),
+];
+
+// addresses given a name, "OAM shadow", "level data", "music driver vars" : shown over the
+// memory viewer and next to the instructions of the history using them, kept with the
+// program's session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub range : RangeInclusive<u16>,
+    pub name  : String,
+    pub color : &'static str, // one of COLORS
+}
+
+impl Region {
+    pub fn color(&self) -> Color {
+        COLORS.iter().find(|(name, _)| *name == self.color).map_or(COLORS[0].1, |(_, color)| *color)
+    }
+
+    // "OAM shadow" at its start, "OAM shadow+$12" further in
+    pub fn label(&self, address: u16) -> String {
+        match address - self.range.start() {
+            0 => self.name.clone(),
+            offset => format!("{}+${:X}", self.name, offset),
+        }
+    }
+}
+
+// "0200-02FF,red,OAM shadow", without the color it is the first one, a single address
+// names one byte
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Region, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid region, expected 0200-02ff,color,name : {}", text));
+        let (range, rest) = text.trim().split_once(',').ok_or_else(invalid)?;
+        let range = match range.split_once('-') {
+            Some((start, end)) => parse_address(start).zip(parse_address(end)).filter(|(start, end)| start <= end),
+            None => parse_address(range).map(|address| (address, address)),
+        }.ok_or_else(invalid)?;
+        let (color, name) = rest.split_once(',')
+            .and_then(|(color, name)| COLORS.iter().find(|(known, _)| known.eq_ignore_ascii_case(color.trim())).map(|(known, _)| (*known, name)))
+            .unwrap_or((COLORS[0].0, rest));
+        // quotes would not read back from the session
+        let name = name.trim().replace('"', "");
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Region { range: range.0..=range.1, name, color })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.range.start() == self.range.end() {
+            true => write!(f, "{:04X},{},{}", self.range.start(), self.color, self.name),
+            false => write!(f, "{:04X}-{:04X},{},{}", self.range.start(), self.range.end(), self.color, self.name),
+        }
+    }
+}
+
+// the innermost region the address is in, the smallest holding it
+pub fn find(regions: &[Region], address: u16) -> Option<&Region> {
+    regions.iter()
+        .filter(|region| region.range.contains(&address))
+        .min_by_key(|region| region.range.end() - region.range.start())
+}
+
+// a region over the same addresses is replaced
+pub fn annotate(regions: &mut Vec<Region>, region: Region) {
+    regions.retain(|other| other.range != region.range);
+    regions.push(region);
+    regions.sort_by_key(|region| (*region.range.start(), *region.range.end()));
+}
+
+// the innermost region at the address, taken out
+pub fn remove(regions: &mut Vec<Region>, address: u16) -> Option<Region> {
+    let range = find(regions, address)?.range.clone();
+    let at = regions.iter().position(|region| region.range == range)?;
+    Some(regions.remove(at))
+}
+
+// the address an instruction names in its operand, before any indexing, none for the modes
+// without one
+pub fn operand(mode: &AddressMode, low: u8, high: u8) -> Option<u16> {
+    match mode {
+        AddressMode::Zp0 | AddressMode::Zpx | AddressMode::Zpy | AddressMode::Izx | AddressMode::Izy => Some(low as u16),
+        AddressMode::Abs | AddressMode::Abx | AddressMode::Aby | AddressMode::Ind => Some(u16::from_le_bytes([low, high])),
+        AddressMode::Imp | AddressMode::Imm | AddressMode::Rel => None,
+    }
+}
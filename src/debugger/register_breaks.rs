@@ -0,0 +1,125 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::hardware::inspect::StateNode;
+use crate::hardware::watches::Access;
+use super::watchpoints;
+
+// the registers breaks can be set on by name, with the device whose explorer group shows
+// their boxes. the ppu's eight repeat every 8 bytes up to $3FFF, a break on one stops on
+// all of its mirrors
+pub const REGISTERS: [(&str, &str, u16); 29] = [
+    ("ppu", "PPUCTRL", 0x2000), ("ppu", "PPUMASK", 0x2001), ("ppu", "PPUSTATUS", 0x2002), ("ppu", "OAMADDR", 0x2003),
+    ("ppu", "OAMDATA", 0x2004), ("ppu", "PPUSCROLL", 0x2005), ("ppu", "PPUADDR", 0x2006), ("ppu", "PPUDATA", 0x2007),
+    ("apu", "SQ1_VOL", 0x4000), ("apu", "SQ1_SWEEP", 0x4001), ("apu", "SQ1_LO", 0x4002), ("apu", "SQ1_HI", 0x4003),
+    ("apu", "SQ2_VOL", 0x4004), ("apu", "SQ2_SWEEP", 0x4005), ("apu", "SQ2_LO", 0x4006), ("apu", "SQ2_HI", 0x4007),
+    ("apu", "TRI_LINEAR", 0x4008), ("apu", "TRI_LO", 0x400A), ("apu", "TRI_HI", 0x400B),
+    ("apu", "NOISE_VOL", 0x400C), ("apu", "NOISE_LO", 0x400E), ("apu", "NOISE_HI", 0x400F),
+    ("apu", "DMC_FREQ", 0x4010), ("apu", "DMC_RAW", 0x4011), ("apu", "DMC_START", 0x4012), ("apu", "DMC_LEN", 0x4013),
+    ("apu", "OAMDMA", 0x4014), ("apu", "SND_CHN", 0x4015), ("apu", "FRAME_COUNTER", 0x4017),
+];
+
+// the explorer group holding the boxes under the device's own
+pub const GROUP: &str = "break on";
+
+const PPU_MIRRORS: RangeInclusive<u16> = 0x2000..=0x3FFF;
+
+// the register an access lands on, a ppu mirror folded back
+pub fn register(address: u16) -> Option<u16> {
+    let address = if PPU_MIRRORS.contains(&address) { 0x2000 | address & 7 } else { address };
+    REGISTERS.iter().find(|(_, _, register)| *register == address).map(|(_, _, register)| *register)
+}
+
+pub fn name(register: u16) -> &'static str {
+    REGISTERS.iter().find(|(_, _, other)| *other == register).map_or("?", |(_, name, _)| name)
+}
+
+// a break on the reads or the writes of a register, "w PPUDATA" or "read ppustatus"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RegisterBreak {
+    pub register : u16,
+    pub access   : Access,
+}
+
+impl FromStr for RegisterBreak {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<RegisterBreak, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid register break, expected r or w and a register (w PPUDATA) : {}", text));
+        let (access, name) = text.trim().split_once(char::is_whitespace).ok_or_else(invalid)?;
+        let access = match access.to_ascii_lowercase().as_str() {
+            "r" | "read" => Access::Read,
+            "w" | "write" => Access::Write,
+            _ => return Err(invalid()),
+        };
+        let (_, _, register) = REGISTERS.iter().find(|(_, other, _)| other.eq_ignore_ascii_case(name.trim())).ok_or_else(invalid)?;
+        Ok(RegisterBreak { register: *register, access })
+    }
+}
+
+impl fmt::Display for RegisterBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", &self.access.describe()[..1], name(self.register))
+    }
+}
+
+// a break set is taken out, else it is set
+pub fn toggle(breaks: &mut BTreeSet<RegisterBreak>, register_break: RegisterBreak) {
+    if !breaks.remove(&register_break) {
+        breaks.insert(register_break);
+    }
+}
+
+// what the bus has to watch for them, every mirror of a ppu register
+pub fn watched(breaks: &BTreeSet<RegisterBreak>) -> Vec<(RangeInclusive<u16>, Option<Access>)> {
+    breaks.iter()
+        .map(|register_break| match PPU_MIRRORS.contains(&register_break.register) {
+            true => (PPU_MIRRORS, Some(register_break.access)),
+            false => (register_break.register..=register_break.register, Some(register_break.access)),
+        })
+        .collect()
+}
+
+// the accesses of the instruction at pc that hit a break : "write 3F to PPUDATA at $8012",
+// a mirror with its address "write 3F to PPUDATA ($3FFF) at $8012"
+pub fn hits(breaks: &BTreeSet<RegisterBreak>, accesses: &[(Access, u16, u8)], pc: u16) -> Vec<String> {
+    watchpoints::collapse(accesses.iter().filter_map(|(access, address, value)| {
+        let hit = RegisterBreak { register: register(*address)?, access: *access };
+        if !breaks.contains(&hit) {
+            return None;
+        }
+        let what = match hit.register == *address {
+            true => name(hit.register).to_string(),
+            false => format!("{} (${:04X})", name(hit.register), address),
+        };
+        Some((hit, watchpoints::describe(*access, *value, &what, pc)))
+    }))
+}
+
+// the group of boxes the explorer shows under the device, none for the devices without
+// registers here
+pub fn group(breaks: &BTreeSet<RegisterBreak>, device: &str) -> Option<StateNode> {
+    let checked = |register: u16, access: Access| if breaks.contains(&RegisterBreak { register, access }) { "[x]" } else { "[ ]" };
+    let boxes = REGISTERS.iter()
+        .filter(|(owner, _, _)| *owner == device)
+        .map(|(_, name, register)| StateNode::value(&format!("{} ${:04X}", name, register),
+            format!("{} read {} write", checked(*register, Access::Read), checked(*register, Access::Write))))
+        .collect::<Vec<StateNode>>();
+    match boxes.is_empty() {
+        true => None,
+        false => Some(StateNode::group(GROUP, boxes)),
+    }
+}
+
+// the register of an explorer row in one of the groups, by its path
+pub fn at(path: &str) -> Option<u16> {
+    let (parent, row) = path.rsplit_once('/')?;
+    if !parent.ends_with(GROUP) {
+        return None;
+    }
+    let (name, _) = row.split_once(' ')?;
+    REGISTERS.iter().find(|(_, other, _)| *other == name).map(|(_, _, register)| *register)
+}
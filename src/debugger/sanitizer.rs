@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::hardware::sanitizer::Check;
+use super::debugger::State;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Off,
+    Log,
+    Break, // logged too
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Action, Error> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Action::Off),
+            "log" => Ok(Action::Log),
+            "break" => Ok(Action::Break),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid sanitizer action: {}", text))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizerPolicy {
+    pub uninitialized_reads : Action,
+    pub rom_writes          : Action,
+    pub non_code            : Action,
+    pub log                 : String,
+}
+
+impl SanitizerPolicy {
+    // the action given on the command line applies to every check
+    pub fn from_config(config: &Config, every: Option<Action>) -> SanitizerPolicy {
+        let action = |key: &str| every.unwrap_or_else(|| config.get_or(key, Action::Off));
+        SanitizerPolicy {
+            uninitialized_reads : action("sanitizer.uninitialized_reads"),
+            rom_writes          : action("sanitizer.rom_writes"),
+            non_code            : action("sanitizer.non_code"),
+            log                 : config.get_or("sanitizer.log", "sanitizer.log".to_string()),
+        }
+    }
+
+    pub fn action(&self, check: Check) -> Action {
+        match check {
+            Check::UninitializedRead => self.uninitialized_reads,
+            Check::RomWrite => self.rom_writes,
+            Check::NonCode => self.non_code,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.watches_bus() || self.non_code != Action::Off
+    }
+
+    // whether the bus has to watch the accesses
+    pub fn watches_bus(&self) -> bool {
+        self.uninitialized_reads != Action::Off || self.rom_writes != Action::Off
+    }
+}
+
+// what the sanitizer found, each check reported once per address so a loop reading the
+// same uninitialized byte logs it (and stops) the first time only
+pub struct SanitizerReport {
+    pub policy : SanitizerPolicy,
+    reported   : HashSet<(Check, u16)>,
+    log        : Option<BufWriter<File>>,
+    started    : u16, // the pc of the instruction the bus findings belong to
+}
+
+impl SanitizerReport {
+    pub fn new(policy: SanitizerPolicy) -> SanitizerReport {
+        SanitizerReport { policy, reported: HashSet::new(), log: None, started: 0 }
+    }
+
+    // on every instruction boundary : what the bus found during the instruction that just
+    // ran, and whether the next one starts outside the disassembled program
+    pub fn check(&mut self, state: &State) -> Vec<String> {
+        let machine = &state.machine;
+        let mut findings = machine.bus.borrow().sanitizer.as_ref()
+            .map(|sanitizer| sanitizer.findings.take())
+            .unwrap_or_default();
+        let (next, cycles) = {
+            let cpu = machine.cpu.borrow();
+            (cpu.registers.pc, cpu.total_cycles)
+        };
+        if self.policy.non_code != Action::Off && !state.dis.counters.is_empty() && !state.dis.counters.contains_key(&(next as i32)) {
+            findings.push((Check::NonCode, next));
+        }
+        let pc = self.started;
+        self.started = next;
+        self.report(&findings, pc, cycles)
+    }
+
+    // logs the findings of the instruction at pc, the ones asking to break come back described
+    pub fn report(&mut self, findings: &[(Check, u16)], pc: u16, cycles: u64) -> Vec<String> {
+        let mut breaks = Vec::new();
+        for (check, address) in findings {
+            let action = self.policy.action(*check);
+            if action == Action::Off || !self.reported.insert((*check, *address)) {
+                continue;
+            }
+            let finding = format!("{} ${:04X}", check.describe(), address);
+            if self.log.is_none() {
+                self.log = File::create(&self.policy.log).ok().map(BufWriter::new);
+            }
+            if let Some(log) = self.log.as_mut() {
+                let _ = writeln!(log, "cycle {} pc ${:04X} {}", cycles, pc, finding);
+                let _ = log.flush();
+            }
+            if action == Action::Break {
+                breaks.push(finding);
+            }
+        }
+        breaks
+    }
+}
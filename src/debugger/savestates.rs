@@ -0,0 +1,196 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::hardware::framebuffer::{self, FrameBuffer};
+use crate::hardware::machine::Machine;
+use crate::hardware::snapshot::{Snapshot, put_u16, put_u32, put_u64, take_bytes, take_u16, take_u32, take_u64};
+
+pub const SLOT_COUNT       : usize = 10;
+pub const THUMBNAIL_SCALE  : usize = 8;
+pub const THUMBNAIL_WIDTH  : usize = framebuffer::WIDTH / THUMBNAIL_SCALE;
+pub const THUMBNAIL_HEIGHT : usize = framebuffer::HEIGHT / THUMBNAIL_SCALE;
+
+const MAGIC   : &[u8; 4] = b"BSST";
+const VERSION : u16 = 4;
+const BOOKMARKS_MAGIC : &[u8; 4] = b"BSBM";
+
+#[derive(Debug, Clone)]
+pub struct SlotInfo {
+    pub slot      : usize,
+    pub saved_at  : u64, // seconds since the unix epoch
+    pub cycles    : u64,
+    pub thumbnail : Vec<u32>, // THUMBNAIL_WIDTH x THUMBNAIL_HEIGHT, row major
+}
+
+// a named moment of the session, with the machine as it was then
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub name   : String,
+    pub frame  : u64,
+    pub cycles : u64,
+    pub state  : Vec<u8>, // as a savestate body
+}
+
+impl Bookmark {
+    pub fn new(name: &str, frame: u64, machine: &Machine) -> Bookmark {
+        let mut state = Vec::new();
+        machine.save(&mut state);
+        Bookmark { name: name.trim().to_string(), frame, cycles: machine.cpu.borrow().total_cycles, state }
+    }
+}
+
+// numbered savestates for one program, kept as `<directory>/<program name>/slot<n>.state`
+pub struct SaveLibrary {
+    pub directory: PathBuf,
+}
+
+impl SaveLibrary {
+    pub fn for_program(config: &Config, program_path: &str) -> SaveLibrary {
+        let root = config.get_or("savestates.directory", "savestates".to_string());
+        let name = Path::new(program_path.trim())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("untitled");
+        SaveLibrary { directory: Path::new(&root).join(name) }
+    }
+
+    pub fn slot_path(&self, slot: usize) -> PathBuf {
+        self.directory.join(format!("slot{}.state", slot))
+    }
+
+    pub fn save(&self, slot: usize, machine: &Machine) -> Result<(), Error> {
+        let saved_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        put_u16(&mut out, VERSION);
+        put_u64(&mut out, saved_at);
+        put_u64(&mut out, machine.cpu.borrow().total_cycles);
+        for pixel in thumbnail(&machine.video.borrow()) {
+            put_u32(&mut out, pixel);
+        }
+        machine.save(&mut out);
+
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.slot_path(slot), out)
+    }
+
+    // restores into the given machine, which must have the device layout the slot was saved from
+    pub fn load(&self, slot: usize, machine: &mut Machine) -> Result<(), Error> {
+        let content = fs::read(self.slot_path(slot))?;
+        let mut input = &content[..];
+        SaveLibrary::read_header(slot, &mut input)?;
+        machine.restore(&mut input)
+    }
+
+    // the board's battery backed ram, see battery.rs
+    pub fn battery_path(&self) -> PathBuf {
+        self.directory.join("battery.sav")
+    }
+
+    pub fn bookmarks_path(&self) -> PathBuf {
+        self.directory.join("bookmarks.state")
+    }
+
+    // all of them in one file, rewritten whenever one is added
+    pub fn save_bookmarks(&self, bookmarks: &[Bookmark]) -> Result<(), Error> {
+        let mut out = Vec::new();
+        out.extend_from_slice(BOOKMARKS_MAGIC);
+        put_u16(&mut out, VERSION);
+        put_u16(&mut out, bookmarks.len() as u16);
+        for bookmark in bookmarks {
+            put_u16(&mut out, bookmark.name.len() as u16);
+            out.extend_from_slice(bookmark.name.as_bytes());
+            put_u64(&mut out, bookmark.frame);
+            put_u64(&mut out, bookmark.cycles);
+            put_u32(&mut out, bookmark.state.len() as u32);
+            out.extend_from_slice(&bookmark.state);
+        }
+
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.bookmarks_path(), out)
+    }
+
+    // empty when the program has no bookmarks file yet
+    pub fn bookmarks(&self) -> Result<Vec<Bookmark>, Error> {
+        let content = match fs::read(self.bookmarks_path()) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut input = &content[..];
+        if take_bytes(&mut input, BOOKMARKS_MAGIC.len())? != BOOKMARKS_MAGIC || take_u16(&mut input)? != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a bookmarks file"));
+        }
+        (0..take_u16(&mut input)?)
+            .map(|_| {
+                let length = take_u16(&mut input)? as usize;
+                let name = String::from_utf8_lossy(take_bytes(&mut input, length)?).to_string();
+                let frame = take_u64(&mut input)?;
+                let cycles = take_u64(&mut input)?;
+                let length = take_u32(&mut input)? as usize;
+                let state = take_bytes(&mut input, length)?.to_vec();
+                Ok(Bookmark { name, frame, cycles, state })
+            })
+            .collect()
+    }
+
+    pub fn info(&self, slot: usize) -> Option<SlotInfo> {
+        let content = fs::read(self.slot_path(slot)).ok()?;
+        SaveLibrary::read_header(slot, &mut &content[..]).ok()
+    }
+
+    pub fn list(&self) -> Vec<Option<SlotInfo>> {
+        (0..SLOT_COUNT).map(|slot| self.info(slot)).collect()
+    }
+
+    fn read_header(slot: usize, input: &mut &[u8]) -> Result<SlotInfo, Error> {
+        if take_bytes(input, MAGIC.len())? != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a savestate"));
+        }
+        let version = take_u16(input)?;
+        if version != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Unsupported savestate version {}", version)));
+        }
+        let saved_at = take_u64(input)?;
+        let cycles = take_u64(input)?;
+        let thumbnail = (0..THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT)
+            .map(|_| take_u32(input))
+            .collect::<Result<Vec<u32>, Error>>()?;
+        Ok(SlotInfo { slot, saved_at, cycles, thumbnail })
+    }
+}
+
+// nearest neighbour downscale of the displayed frame
+pub fn thumbnail(video: &FrameBuffer) -> Vec<u32> {
+    let front = video.front();
+    let mut pixels = Vec::with_capacity(THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT);
+    for y in 0..THUMBNAIL_HEIGHT {
+        for x in 0..THUMBNAIL_WIDTH {
+            pixels.push(front[y * THUMBNAIL_SCALE * video.width + x * THUMBNAIL_SCALE]);
+        }
+    }
+    pixels
+}
+
+// `YYYY-MM-DD HH:MM:SS` in utc
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // days to civil date, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, time / 3_600, time % 3_600 / 60, time % 60)
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::loader::archive;
+use super::debugger::{App, State};
+use super::macros::{self, InputMacro};
+use super::regions::Region;
+use super::watchpoints::Watchpoint;
+use super::register_breaks::RegisterBreak;
+
+// the file at the savestates root naming the program run last
+const LAST_PROGRAM_FILE : &str = "last_session.toml";
+const SESSION_FILE      : &str = "session.toml";
+const RECENT_PROGRAMS   : usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    Memory,
+    History,
+    Log,
+    Terminal,
+    Source,
+}
+
+impl Pane {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pane::Memory => "memory",
+            Pane::History => "history",
+            Pane::Log => "log",
+            Pane::Terminal => "terminal",
+            Pane::Source => "source",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Pane> {
+        [Pane::Memory, Pane::History, Pane::Log, Pane::Terminal, Pane::Source].iter().copied()
+            .find(|pane| pane.name() == name)
+    }
+}
+
+// what a debugging session of one program had set up, written next to its savestates on
+// quit and when another program is opened, read back when the program is opened again.
+// bookmarks keep their own file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub breakpoints   : Vec<u16>,
+    pub break_classes : Vec<String>,
+    pub watchpoints   : Vec<Watchpoint>,
+    pub registers     : Vec<RegisterBreak>,
+    pub filter        : String,
+    pub pane          : Pane,
+    pub split         : f32,
+    pub program_top   : f32,
+    pub memory_page   : i32,
+    pub memory_row    : i32,
+    pub macros        : Vec<(char, InputMacro)>,
+    pub regions       : Vec<Region>,
+}
+
+// nothing set up, the layout the debugger starts with
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            breakpoints   : Vec::new(),
+            break_classes : Vec::new(),
+            watchpoints   : Vec::new(),
+            registers     : Vec::new(),
+            filter        : String::new(),
+            pane          : Pane::Memory,
+            split         : 0.70,
+            program_top   : 0.31,
+            memory_page   : 0,
+            memory_row    : 0,
+            macros        : Vec::new(),
+            regions       : Vec::new(),
+        }
+    }
+}
+
+impl Session {
+    pub fn of(app: &App) -> Session {
+        let pane = if app.show_history {
+            Pane::History
+        } else if app.show_log {
+            Pane::Log
+        } else if app.show_terminal {
+            Pane::Terminal
+        } else if app.show_source {
+            Pane::Source
+        } else {
+            Pane::Memory
+        };
+        Session {
+            breakpoints   : app.breakpoints.iter().copied().collect(),
+            break_classes : app.break_classes().iter().map(|class| class.to_string()).collect(),
+            watchpoints   : app.watchpoints.clone(),
+            registers     : app.register_breaks.iter().copied().collect(),
+            filter        : app.trace_filter.text.clone(),
+            pane,
+            split         : app.split,
+            program_top   : app.program_top,
+            memory_page   : app.memory_page_index,
+            memory_row    : app.memory_row,
+            macros        : {
+                let mut macros = app.macros.iter().map(|(key, input_macro)| (*key, input_macro.clone())).collect::<Vec<(char, InputMacro)>>();
+                macros.sort_by_key(|(key, _)| *key);
+                macros
+            },
+            regions       : app.regions.clone(),
+        }
+    }
+
+    pub fn path(directory: &Path) -> PathBuf {
+        directory.join(SESSION_FILE)
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<(), Error> {
+        let hex = |addresses: &[u16]| addresses.iter().map(|address| format!("{:04X}", address)).collect::<Vec<String>>().join(",");
+        let mut content = vec![
+            "[breakpoints]".to_string(),
+            format!("addresses = \"{}\"", hex(&self.breakpoints)),
+            format!("classes = \"{}\"", self.break_classes.join(",")),
+            format!("watchpoints = \"{}\"", self.watchpoints.iter().map(|watchpoint| watchpoint.to_string()).collect::<Vec<String>>().join(",")),
+            format!("registers = \"{}\"", self.registers.iter().map(|register_break| register_break.to_string()).collect::<Vec<String>>().join(",")),
+            "[layout]".to_string(),
+            format!("pane = \"{}\"", self.pane.name()),
+            format!("split = {}", self.split),
+            format!("program_top = {}", self.program_top),
+            "[memory]".to_string(),
+            format!("page = {}", self.memory_page),
+            format!("row = {}", self.memory_row),
+            "[history]".to_string(),
+            format!("filter = \"{}\"", self.filter.replace('"', "")),
+            "[macros]".to_string(),
+        ];
+        // a quote or backslash key would not read back
+        let macros = self.macros.iter()
+            .filter(|(key, _)| *key != '"' && *key != '\\')
+            .enumerate()
+            .map(|(index, (key, input_macro))| format!("macro{} = \"{}{}\"", index + 1, key, input_macro));
+        content.extend(macros);
+        content.push("[regions]".to_string());
+        content.extend(self.regions.iter().enumerate().map(|(index, region)| format!("region{} = \"{}\"", index + 1, region)));
+        fs::create_dir_all(directory)?;
+        fs::write(Session::path(directory), content.join("\n") + "\n")
+    }
+
+    // None when the program has no session yet, values that do not read are left as they are
+    pub fn load(directory: &Path) -> Option<Session> {
+        let path = Session::path(directory);
+        if !path.exists() {
+            return None;
+        }
+        let values = Config::load(path.to_str()?).ok()?;
+        let list = |key: &str| values.get_or(key, String::new()).split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<String>>();
+        let default = Session::default();
+        Some(Session {
+            breakpoints   : list("breakpoints.addresses").iter().filter_map(|address| u16::from_str_radix(address, 16).ok()).collect(),
+            break_classes : list("breakpoints.classes"),
+            watchpoints   : list("breakpoints.watchpoints").iter().filter_map(|watchpoint| watchpoint.parse().ok()).collect(),
+            registers     : list("breakpoints.registers").iter().filter_map(|register_break| register_break.parse().ok()).collect(),
+            filter        : values.get_or("history.filter", String::new()),
+            pane          : Pane::from_name(&values.get_or("layout.pane", String::new())).unwrap_or(default.pane),
+            split         : values.get_or("layout.split", default.split).clamp(0.1, 0.9),
+            program_top   : values.get_or("layout.program_top", default.program_top).clamp(0.1, 0.9),
+            memory_page   : values.get_or("memory.page", 0i32).clamp(0, 0xFE),
+            memory_row    : values.get_or("memory.row", 0i32).clamp(0, 15),
+            macros        : (1..).map_while(|index| values.get(&format!("macros.macro{}", index)))
+                .filter_map(macros::parse_binding)
+                .collect(),
+            regions       : (1..).map_while(|index| values.get(&format!("regions.region{}", index)))
+                .filter_map(|region| region.parse().ok())
+                .collect(),
+        })
+    }
+
+    pub fn apply(&self, app: &mut App) {
+        app.breakpoints = self.breakpoints.iter().copied().collect();
+        app.watchpoints = self.watchpoints.clone();
+        app.register_breaks = self.registers.iter().copied().collect();
+        app.break_events = 0;
+        for class in self.break_classes.iter() {
+            app.set_break_class(class, Some(true));
+        }
+        app.trace_filter = self.filter.parse().unwrap_or_default();
+        app.show_history = self.pane == Pane::History;
+        app.show_log = self.pane == Pane::Log;
+        app.show_terminal = self.pane == Pane::Terminal;
+        // debug info only comes from the command line, without it the memory is shown
+        app.show_source = self.pane == Pane::Source;
+        app.split = self.split;
+        app.program_top = self.program_top;
+        app.memory_page_index = self.memory_page;
+        app.memory_row = self.memory_row;
+        // a macro on a command key would hide the command, sessions from before keys were checked
+        app.macros = self.macros.iter().filter(|(key, _)| !State::is_command_key(*key)).cloned().collect();
+        app.regions = self.regions.clone();
+    }
+}
+
+fn last_program_path(config: &Config) -> PathBuf {
+    Path::new(&config.get_or("savestates.directory", "savestates".to_string())).join(LAST_PROGRAM_FILE)
+}
+
+// the paths made absolute, an archive's entry kept after it
+fn absolute(program_path: &str) -> String {
+    let (path, entry) = archive::split(program_path);
+    let path = Path::new(path).canonicalize().unwrap_or_else(|_| PathBuf::from(path));
+    match entry {
+        Some(entry) => format!("{}{}{}", path.display(), archive::SEPARATOR, entry),
+        None => path.display().to_string(),
+    }
+}
+
+// the program is also put first in the recent programs, listed by the program browser
+pub fn save_last_program(config: &Config, program_path: &str) -> Result<(), Error> {
+    if program_path.trim().is_empty() {
+        return Ok(());
+    }
+    let path = last_program_path(config);
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let program = absolute(program_path);
+    let mut recent = recent_programs(config);
+    recent.retain(|other| *other != program);
+    recent.insert(0, program.clone());
+    recent.truncate(RECENT_PROGRAMS);
+    let mut content = format!("program = \"{}\"\n", program);
+    for (index, path) in recent.iter().enumerate() {
+        content.push_str(&format!("recent{} = \"{}\"\n", index + 1, path));
+    }
+    fs::write(path, content)
+}
+
+fn exists(program_path: &str) -> bool {
+    Path::new(archive::split(program_path).0).exists()
+}
+
+// the program the last session ran, for a start without one
+pub fn last_program(config: &Config) -> Option<String> {
+    let values = Config::load(last_program_path(config).to_str()?).ok()?;
+    values.get("program").map(str::to_string).filter(|program| exists(program))
+}
+
+// most recent first, the ones gone since left out
+pub fn recent_programs(config: &Config) -> Vec<String> {
+    let values = last_program_path(config).to_str()
+        .and_then(|path| Config::load(path).ok())
+        .unwrap_or_default();
+    (1..=RECENT_PROGRAMS)
+        .filter_map(|index| values.get(&format!("recent{}", index)).map(str::to_string))
+        .filter(|program| exists(program))
+        .collect()
+}
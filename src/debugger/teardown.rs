@@ -0,0 +1,47 @@
+use std::io::{self, Error};
+use std::panic;
+use std::sync::Once;
+
+use crossterm::cursor::Show;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+
+static HOOK: Once = Once::new();
+
+// whatever the interface changed, undone : safe to run more than once and on a terminal
+// that was never switched
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen, Show);
+}
+
+// held while the interface runs : the alternate screen in raw mode with the mouse reported,
+// dropping it, on return, on an error or while a panic unwinds, gives the shell its screen
+// back. the panic hook restores it before the message is printed, so the message stays
+// readable
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter() -> Result<TerminalGuard, Error> {
+        HOOK.call_once(|| {
+            let default = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                default(info);
+            }));
+        });
+        // dropped on failure, whatever got switched is switched back
+        let guard = TerminalGuard;
+        enable_raw_mode()
+            .and_then(|_| execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture))
+            .map_err(|error| Error::other(error.to_string()))?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
@@ -0,0 +1,211 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+
+use crate::cli::{Arguments, TraceDiffArguments};
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::registers::Registers;
+use super::debugger::App;
+use super::trace_format::{TraceFormat, TraceRecord, TraceWriter};
+
+// the break and unused bits of P are left out, loggers disagree on them
+const COMPARED_FLAGS: u8 = 0xCF;
+
+// one instruction as it started : where, and the registers it saw
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceLine {
+    pub pc          : u16,
+    pub a           : u8,
+    pub x           : u8,
+    pub y           : u8,
+    pub sp          : u8,
+    pub p           : u8,
+    pub text        : String, // the line as written or read
+    pub cycles      : u64,    // 0 when a reference does not give them
+    pub opcode      : u8,     // the same
+    pub instruction : String, // empty for references
+}
+
+impl TraceLine {
+    // `C000  4C F5 C5  JMP $C5F5  A:00 X:00 Y:00 P:24 SP:FD CYC:7` (nestest and what --write
+    // produces) or Mesen's `8000 $A9 $00  LDA #$00  A:00 X:00 Y:00 S:FD P:nvUbdIzc ...`,
+    // the pc first then the registers by name, anything else is ignored
+    pub fn parse(line: &str) -> Option<TraceLine> {
+        let pc = u16::from_str_radix(line.get(..4)?, 16).ok()?;
+        let register = |names: &[&str]| line.split_whitespace()
+            .filter_map(|token| token.split_once(':'))
+            .find(|(name, _)| names.contains(name))
+            .map(|(_, value)| value);
+        let byte = |names: &[&str]| register(names).and_then(|value| u8::from_str_radix(value, 16).ok());
+        let p = register(&["P"]).and_then(|value| match u8::from_str_radix(value, 16) {
+            Ok(p) => Some(p),
+            // nvubdizc, capitals set
+            Err(_) if value.len() == 8 => Some(value.chars().fold(0, |p, flag| p << 1 | flag.is_ascii_uppercase() as u8)),
+            Err(_) => None,
+        })?;
+        Some(TraceLine {
+            pc,
+            a           : byte(&["A"])?,
+            x           : byte(&["X"])?,
+            y           : byte(&["Y"])?,
+            sp          : byte(&["SP", "S"])?,
+            p,
+            text        : line.trim_end().to_string(),
+            cycles      : register(&["CYC", "Cycle"]).and_then(|value| value.parse().ok()).unwrap_or(0),
+            opcode      : 0,
+            instruction : String::new(),
+        })
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers { a: self.a, x: self.x, y: self.y, sp: self.sp, flags: self.p, pc: self.pc, ..Registers::new() }
+    }
+
+    // the registers that differ between two lines
+    pub fn differences(&self, other: &TraceLine) -> Vec<&'static str> {
+        [("PC", self.pc != other.pc), ("A", self.a != other.a), ("X", self.x != other.x), ("Y", self.y != other.y),
+         ("SP", self.sp != other.sp), ("P", (self.p ^ other.p) & COMPARED_FLAGS != 0)].iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+// a program run headless one instruction at a time, each of its own machine, so the two
+// sides of a comparison can run side by side in the same process
+pub struct Tracer<'a> {
+    app : App<'a>,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(arguments: &Arguments) -> Result<Tracer<'a>, Error> {
+        let mut app = App::new(arguments)?;
+        let program = app.load_current_program()?;
+        app.load_program(&program);
+        Ok(Tracer { app })
+    }
+
+    // the next instruction, in nestest's layout, once it ran
+    pub fn next_line(&self) -> TraceLine {
+        let state = (*self.app.inner_machine_state).borrow();
+        let machine = &state.machine;
+        while machine.cpu.borrow().cycle > 0 {
+            machine.tick();
+        }
+        let line = {
+            let cpu = machine.cpu.borrow();
+            let registers = cpu.registers;
+            let text = state.dis.counters.get(&(registers.pc as i32))
+                .and_then(|line| state.dis.program.get(*line))
+                .map(|text| text.rsplit_once(' ').map_or(text.as_str(), |(text, _)| text).trim_end())
+                .unwrap_or("???");
+            TraceLine {
+                pc          : registers.pc,
+                a           : registers.a,
+                x           : registers.x,
+                y           : registers.y,
+                sp          : registers.sp,
+                p           : registers.flags,
+                text        : format!("{:04X}  {:<14} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                    registers.pc, text, registers.a, registers.x, registers.y, registers.flags, registers.sp, cpu.total_cycles),
+                cycles      : cpu.total_cycles,
+                opcode      : cpu.peek(registers.pc),
+                instruction : text.to_string(),
+            }
+        };
+        machine.tick();
+        line
+    }
+}
+
+// runs the program headless for that many instructions
+pub fn record(arguments: &Arguments, instructions: u64) -> Result<Vec<TraceLine>, Error> {
+    let tracer = Tracer::new(arguments)?;
+    Ok((0..instructions).map(|_| tracer.next_line()).collect())
+}
+
+// both sides in lockstep, up to `context` instructions past the first they disagree on
+pub fn record_pair(this: &Arguments, other: &Arguments, instructions: u64, context: usize) -> Result<(Vec<TraceLine>, Vec<TraceLine>), Error> {
+    let tracers = (Tracer::new(this)?, Tracer::new(other)?);
+    let (mut these, mut others) = (Vec::new(), Vec::new());
+    let mut left = instructions;
+    while left > 0 {
+        let (line, other) = (tracers.0.next_line(), tracers.1.next_line());
+        if !line.differences(&other).is_empty() && left > context as u64 {
+            left = context as u64 + 1;
+        }
+        these.push(line);
+        others.push(other);
+        left -= 1;
+    }
+    Ok((these, others))
+}
+
+pub fn read(path: &str) -> Result<Vec<TraceLine>, Error> {
+    let text = fs::read_to_string(path)?;
+    let lines = text.lines().filter_map(TraceLine::parse).collect::<Vec<TraceLine>>();
+    if lines.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, format!("No trace lines in {}", path)));
+    }
+    Ok(lines)
+}
+
+// the first instruction the two traces disagree on, with the lines around it, or how far
+// they agreed
+pub fn compare(this: &[TraceLine], other: &[TraceLine], names: (&str, &str), context: usize) -> String {
+    let compared = this.len().min(other.len());
+    let first = (0..compared).find(|at| !this[*at].differences(&other[*at]).is_empty());
+    let at = match first {
+        Some(at) => at,
+        None if this.len() == other.len() => return format!("no difference in {} instructions", compared),
+        None => return format!("no difference in {} instructions, then the {} trace ends", compared,
+            if this.len() < other.len() { names.0 } else { names.1 }),
+    };
+
+    let width = names.0.len().max(names.1.len());
+    let mut report = vec![format!("first difference at instruction {} : {}", at + 1, this[at].differences(&other[at]).join(", "))];
+    // the traces agree before, one side is enough there
+    for (number, line) in this.iter().enumerate().take(at).skip(at.saturating_sub(context)) {
+        report.push(format!("  {:>8}  {:width$}  {}", number + 1, names.0, line.text, width = width));
+    }
+    for number in at..compared.min(at + context + 1) {
+        let marker = if number == at { ">" } else { " " };
+        report.push(format!("{} {:>8}  {:width$}  {}", marker, number + 1, names.0, this[number].text, width = width));
+        report.push(format!("{} {:>8}  {:width$}  {}", marker, number + 1, names.1, other[number].text, width = width));
+    }
+    report.join("\n")
+}
+
+pub fn start(arguments: TraceDiffArguments) -> Result<(), Error> {
+    let this_side = arguments.side(&arguments.options)?;
+    let reference = match (&arguments.reference, &arguments.write) {
+        (Some(path), None) => Some(read(path)?),
+        (None, None) => {
+            let against = arguments.side(&[arguments.options.clone(), arguments.against.clone()].concat())?;
+            let (this, other) = record_pair(&this_side, &against, arguments.instructions, arguments.context)?;
+            println!("{}", compare(&this, &other, ("this", "against"), arguments.context));
+            return Ok(());
+        },
+        _ => None,
+    };
+
+    let this = record(&this_side, arguments.instructions)?;
+    if let Some(path) = &arguments.write {
+        let mut out = BufWriter::new(File::create(path)?);
+        let writer = TraceWriter::new(TraceFormat::from_path(path), arguments.fields.clone());
+        if let Some(header) = writer.header() {
+            writeln!(out, "{}", header)?;
+        }
+        for line in this.iter() {
+            let record = TraceRecord { cycles: line.cycles, pc: line.pc, opcode: line.opcode, instruction: &line.instruction, registers: Some(line.registers()) };
+            writeln!(out, "{}", writer.line(&record).unwrap_or_else(|| line.text.clone()))?;
+        }
+        out.flush()?;
+        println!("{} instructions written to {}", this.len(), path);
+        return Ok(());
+    }
+
+    if let Some(other) = reference {
+        println!("{}", compare(&this, &other, ("this", "reference"), arguments.context));
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::hardware::instructions::Instructions;
+use crate::hardware::opcodes::Opcode;
+use crate::hardware::registers::Registers;
+use super::history::Executed;
+
+pub const CLASSES: [&str; 12] = ["load", "store", "transfer", "stack", "arithmetic", "logic", "shift", "compare", "branch", "jump", "flag", "nop"];
+
+pub fn class_of(opcode: Opcode) -> &'static str {
+    use Opcode::*;
+    match opcode {
+        LDA | LDX | LDY => "load",
+        STA | STX | STY => "store",
+        TAX | TAY | TXA | TYA | TSX | TXS => "transfer",
+        PHA | PHP | PLA | PLP => "stack",
+        ADC | SBC | INC | INX | INY | DEC | DEX | DEY => "arithmetic",
+        AND | ORA | EOR | BIT => "logic",
+        ASL | LSR | ROL | ROR => "shift",
+        CMP | CPX | CPY => "compare",
+        BCC | BCS | BEQ | BMI | BNE | BPL | BVC | BVS => "branch",
+        JMP | JSR | RTS | RTI | BRK => "jump",
+        CLC | CLD | CLI | CLV | SEC | SED | SEI => "flag",
+        NOP => "nop",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitsSet, // every bit of the value set, for the flags
+}
+
+// two character operators first, == as =
+const OPERATORS: [(&str, Comparison); 8] = [("!=", Comparison::NotEqual), ("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual), ("==", Comparison::Equal),
+                                            ("=", Comparison::Equal), ("<", Comparison::Less), (">", Comparison::Greater), ("&", Comparison::BitsSet)];
+
+impl Comparison {
+    pub fn holds(&self, value: u8, against: u8) -> bool {
+        match self {
+            Comparison::Equal => value == against,
+            Comparison::NotEqual => value != against,
+            Comparison::Less => value < against,
+            Comparison::LessOrEqual => value <= against,
+            Comparison::Greater => value > against,
+            Comparison::GreaterOrEqual => value >= against,
+            Comparison::BitsSet => value & against == against,
+        }
+    }
+
+    pub fn operator(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::BitsSet => "&",
+        }
+    }
+
+    // an operator and a hex byte, "=05", "!= $80", "&01"
+    pub fn parse(text: &str) -> Option<(Comparison, u8)> {
+        let text = text.trim();
+        let (operator, comparison) = OPERATORS.iter().find(|(operator, _)| text.starts_with(operator))?;
+        let value = u8::from_str_radix(text[operator.len()..].trim().trim_start_matches('$'), 16).ok()?;
+        Some((*comparison, value))
+    }
+}
+
+// a register against a value, e.g. a=05, sp<f0 or p&01
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub register   : &'static str,
+    pub comparison : Comparison,
+    pub value      : u8,
+}
+
+impl Condition {
+    pub fn holds(&self, registers: &Registers) -> bool {
+        let register = match self.register {
+            "a" => registers.a,
+            "x" => registers.x,
+            "y" => registers.y,
+            "sp" => registers.sp,
+            _ => registers.flags,
+        };
+        self.comparison.holds(register, self.value)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Condition, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid register condition: {}", text));
+        let register = ["sp", "a", "x", "y", "p"].iter()
+            .find(|register| text.starts_with(*register))
+            .ok_or_else(invalid)?;
+        let (comparison, value) = Comparison::parse(&text[register.len()..]).ok_or_else(invalid)?;
+        Ok(Condition { register, comparison, value })
+    }
+}
+
+// which history entries to show : every part given has to hold, the ranges and the classes
+// each match any of theirs, register conditions only hold on the entries that kept registers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceFilter {
+    pub text       : String, // as typed, for the title
+    pub ranges     : Vec<RangeInclusive<u16>>,
+    pub classes    : Vec<&'static str>,
+    pub conditions : Vec<Condition>,
+    pub search     : Option<String>, // lowercase, matched against the line as shown
+}
+
+impl TraceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.classes.is_empty() && self.conditions.is_empty() && self.search.is_none()
+    }
+
+    pub fn matches(&self, executed: &Executed, line: &str, instructions: &[Instructions; 256]) -> bool {
+        (self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&executed.pc)))
+            && (self.classes.is_empty() || self.classes.contains(&class_of(instructions[executed.opcode as usize].mnemonic)))
+            && self.conditions.iter().all(|condition| executed.registers.as_ref().is_some_and(|registers| condition.holds(registers)))
+            && self.search.as_ref().is_none_or(|search| line.to_lowercase().contains(search))
+    }
+}
+
+fn address(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim_start_matches('$'), 16).ok()
+}
+
+// space separated : 8000-80ff or $8010, a class name, a register condition, and /text
+// searching the rest of the line
+impl FromStr for TraceFilter {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<TraceFilter, Error> {
+        let mut filter = TraceFilter { text: text.trim().to_string(), ..TraceFilter::default() };
+        let (text, search) = match text.find('/') {
+            Some(at) => (&text[..at], Some(text[at + 1..].trim().to_lowercase())),
+            None => (text, None),
+        };
+        filter.search = search.filter(|search| !search.is_empty());
+        for token in text.split_whitespace().map(str::to_lowercase) {
+            if let Some(class) = CLASSES.iter().find(|class| **class == token) {
+                filter.classes.push(class);
+            } else if let Some((start, end)) = token.split_once('-') {
+                match (address(start), address(end)) {
+                    (Some(start), Some(end)) if start <= end => filter.ranges.push(start..=end),
+                    _ => return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid address range: {}", token))),
+                }
+            } else if let Some(at) = address(&token) {
+                filter.ranges.push(at..=at);
+            } else {
+                filter.conditions.push(token.parse()?);
+            }
+        }
+        Ok(filter)
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::hardware::registers::Registers;
+use crate::remote::json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    JsonLines, // an object per instruction
+    Csv,       // a header naming the fields first
+}
+
+impl TraceFormat {
+    // .jsonl / .json and .csv, anything else stays text
+    pub fn from_path(path: &str) -> TraceFormat {
+        let lower = path.to_ascii_lowercase();
+        if lower.ends_with(".jsonl") || lower.ends_with(".json") {
+            TraceFormat::JsonLines
+        } else if lower.ends_with(".csv") {
+            TraceFormat::Csv
+        } else {
+            TraceFormat::Text
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Cycles,
+    Pc,
+    Opcode,
+    Instruction,
+    A,
+    X,
+    Y,
+    Sp,
+    P,
+}
+
+pub const FIELDS: [Field; 9] = [Field::Cycles, Field::Pc, Field::Opcode, Field::Instruction, Field::A, Field::X, Field::Y, Field::Sp, Field::P];
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Cycles => "cycles",
+            Field::Pc => "pc",
+            Field::Opcode => "opcode",
+            Field::Instruction => "instruction",
+            Field::A => "a",
+            Field::X => "x",
+            Field::Y => "y",
+            Field::Sp => "sp",
+            Field::P => "p",
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Field, Error> {
+        let text = text.trim().to_ascii_lowercase();
+        FIELDS.iter().copied()
+            .find(|field| field.name() == text)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unknown trace field: {}", text)))
+    }
+}
+
+// comma separated field names
+pub fn parse_fields(text: &str) -> Result<Vec<Field>, Error> {
+    text.split(',').filter(|name| !name.trim().is_empty()).map(str::parse).collect()
+}
+
+// one instruction as it started, the registers when they were kept
+pub struct TraceRecord<'t> {
+    pub cycles      : u64,
+    pub pc          : u16,
+    pub opcode      : u8,
+    pub instruction : &'t str,
+    pub registers   : Option<Registers>,
+}
+
+impl TraceRecord<'_> {
+    fn value(&self, field: Field) -> Json {
+        let register = |value: fn(&Registers) -> u8| self.registers.as_ref().map_or(Json::Null, |registers| Json::Number(value(registers) as f64));
+        match field {
+            Field::Cycles => Json::Number(self.cycles as f64),
+            Field::Pc => Json::Number(self.pc as f64),
+            Field::Opcode => Json::Number(self.opcode as f64),
+            Field::Instruction => Json::String(self.instruction.to_string()),
+            Field::A => register(|registers| registers.a),
+            Field::X => register(|registers| registers.x),
+            Field::Y => register(|registers| registers.y),
+            Field::Sp => register(|registers| registers.sp),
+            Field::P => register(|registers| registers.flags),
+        }
+    }
+}
+
+// json lines and csv for scripts and spreadsheets : the fields asked for in that order,
+// numbers in decimal, registers an entry did not keep are null / empty
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceWriter {
+    pub format : TraceFormat,
+    pub fields : Vec<Field>,
+}
+
+impl TraceWriter {
+    pub fn new(format: TraceFormat, fields: Vec<Field>) -> TraceWriter {
+        TraceWriter { format, fields: if fields.is_empty() { FIELDS.to_vec() } else { fields } }
+    }
+
+    // `[trace] fields`, every field by default
+    pub fn from_config(config: &Config, path: &str) -> TraceWriter {
+        let fields = parse_fields(&config.get_or("trace.fields", String::new())).unwrap_or_default();
+        TraceWriter::new(TraceFormat::from_path(path), fields)
+    }
+
+    pub fn header(&self) -> Option<String> {
+        match self.format {
+            TraceFormat::Csv => Some(self.fields.iter().map(Field::name).collect::<Vec<&str>>().join(",")),
+            _ => None,
+        }
+    }
+
+    // None for text, the caller keeps its own layout
+    pub fn line(&self, record: &TraceRecord) -> Option<String> {
+        match self.format {
+            TraceFormat::Text => None,
+            TraceFormat::JsonLines => Some(Json::object(self.fields.iter().map(|field| (field.name(), record.value(*field))).collect()).to_string()),
+            TraceFormat::Csv => Some(self.fields.iter()
+                .map(|field| match record.value(*field) {
+                    Json::Null => String::new(),
+                    Json::String(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(",")),
+        }
+    }
+}
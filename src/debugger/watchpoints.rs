@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::hardware::watches::Access;
+use crate::loader::loader::parse_address;
+use super::regions::{self, Region};
+use super::trace_filter::Comparison;
+
+// a data breakpoint : reads, writes or both of an address or a range, stopping only when
+// the value read or written holds the condition when there is one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watchpoint {
+    pub range     : RangeInclusive<u16>,
+    pub access    : Option<Access>, // none for both
+    pub condition : Option<(Comparison, u8)>,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: Access, address: u16, value: u8) -> bool {
+        self.range.contains(&address)
+            && self.access.is_none_or(|only| only == access)
+            && self.condition.is_none_or(|(comparison, against)| comparison.holds(value, against))
+    }
+}
+
+// "w 00d0 =00", "rw 0200-02ff", "r $2002 &80", spelled out too : "write to $00D0 where value == 0"
+impl FromStr for Watchpoint {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Watchpoint, Error> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid watchpoint, expected r, w or rw, an address or range and a condition (w 00d0 =00) : {}", text));
+        let mut words = text.split_whitespace()
+            .filter(|word| !["to", "from", "at", "where", "value"].contains(&word.to_ascii_lowercase().as_str()));
+        let access = match words.next().map(str::to_ascii_lowercase).as_deref() {
+            Some("r") | Some("read") => Some(Access::Read),
+            Some("w") | Some("write") => Some(Access::Write),
+            Some("rw") | Some("access") => None,
+            _ => return Err(invalid()),
+        };
+        let range = words.next().ok_or_else(invalid)?;
+        let range = match range.split_once('-') {
+            Some((start, end)) => parse_address(start).zip(parse_address(end)).filter(|(start, end)| start <= end),
+            None => parse_address(range).map(|address| (address, address)),
+        }.ok_or_else(invalid)?;
+        let condition = words.collect::<String>();
+        let condition = match condition.trim_start_matches("value").is_empty() {
+            true => None,
+            false => Some(Comparison::parse(condition.trim_start_matches("value")).ok_or_else(invalid)?),
+        };
+        Ok(Watchpoint { range: range.0..=range.1, access, condition })
+    }
+}
+
+// without commas, the session keeps them in a list
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.access.map_or("rw", |access| &access.describe()[..1]))?;
+        match self.range.start() == self.range.end() {
+            true => write!(f, " {:04X}", self.range.start())?,
+            false => write!(f, " {:04X}-{:04X}", self.range.start(), self.range.end())?,
+        }
+        match self.condition {
+            Some((comparison, value)) => write!(f, " {}{:02X}", comparison.operator(), value),
+            None => Ok(()),
+        }
+    }
+}
+
+// the accesses of the instruction at pc that hit a watchpoint, described with the region
+// they fall in : "write 00 to $00D0 (enemy hp) at $8012"
+pub fn hits(watchpoints: &[Watchpoint], accesses: &[(Access, u16, u8)], named: &[Region], pc: u16) -> Vec<String> {
+    collapse(accesses.iter().filter_map(|(access, address, value)| {
+        let hit = watchpoints.iter().position(|watchpoint| watchpoint.matches(*access, *address, *value))?;
+        let region = regions::find(named, *address).map_or(String::new(), |region| format!(" ({})", region.label(*address)));
+        Some(((*access, hit), describe(*access, *value, &format!("${:04X}{}", address, region), pc)))
+    }))
+}
+
+pub fn describe(access: Access, value: u8, what: &str, pc: u16) -> String {
+    let direction = if access == Access::Read { "from" } else { "to" };
+    format!("{} {:02X} {} {} at ${:04X}", access.describe(), value, direction, what, pc)
+}
+
+// the first hit of each key described, the others only counted : a sprite dma reads a whole
+// page and writes $2004 256 times in one instruction
+pub fn collapse<K: PartialEq>(hits: impl Iterator<Item = (K, String)>) -> Vec<String> {
+    let mut seen: Vec<(K, String, usize)> = Vec::new();
+    for (key, hit) in hits {
+        match seen.iter_mut().find(|(other, _, _)| *other == key) {
+            Some((_, _, count)) => *count += 1,
+            None => seen.push((key, hit, 1)),
+        }
+    }
+    seen.into_iter()
+        .map(|(_, hit, count)| if count > 1 { format!("{} ({} times)", hit, count) } else { hit })
+        .collect()
+}
+
+// a watchpoint the same as one set takes it out, else it is set
+pub fn toggle(watchpoints: &mut Vec<Watchpoint>, watchpoint: Watchpoint) {
+    match watchpoints.iter().position(|other| *other == watchpoint) {
+        Some(at) => { watchpoints.remove(at); },
+        None => watchpoints.push(watchpoint),
+    }
+}
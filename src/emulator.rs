@@ -0,0 +1,467 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::task::{Context, Poll};
+
+use crate::hardware::cartridge::Cartridge;
+use crate::events::{Event, EventKind, Hooks};
+use crate::hardware::controller::{Peripheral, Turbo};
+use crate::hardware::cpu::EVENT_NMI;
+use crate::hardware::device::Device;
+use crate::hardware::fds::{self, Fds};
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use crate::hardware::mapper::{self, Bank, BoardRam};
+use crate::hardware::nametables;
+use crate::hardware::plugin::Plugin;
+use crate::hardware::ram::PowerOnPattern;
+use crate::hardware::snapshot::Snapshot;
+use crate::hardware::timing;
+use crate::loader::ines::Mirroring;
+use crate::loader::archive;
+use crate::loader::loader::{self, Format, LoadOptions};
+use crate::loader::program::{Program, Segment};
+
+// puts a program in the machine the way the console would get it : dumps go through
+// their mapper, a board that is not emulated falls back to the flat power on layout of its
+// segments, disks boot through the disk system bios, which is added to the returned program
+// so it can be disassembled and stepped through. the cpu then runs its reset sequence
+pub fn insert_program(machine: &Machine, program: &Program, bios: Option<Vec<u8>>, options: LoadOptions) -> Program {
+    let mut program = program.clone();
+    let mut disk_system = None;
+    if let (Some(disk), Some(bios)) = (&program.disk, bios) {
+        program.entry = Some(u16::from_le_bytes([bios[0x1FFC], bios[0x1FFD]]));
+        program.segments.push(Segment { address: 0xE000, bytes: bios.clone() });
+        disk_system = Some(Device::Fds(Fds::new(bios, &disk.sides)));
+    }
+    let flat = || Device::Cartridge(Cartridge::new(program.segments.iter().map(|segment| (segment.address, segment.bytes.clone())).collect()));
+    if program.disk.is_some() && disk_system.is_none() {
+        crate::log_event!(Loader, Warn, "disk image without the disk system bios, the sides are not inserted");
+    }
+    let cartridge = match (&program.image, disk_system) {
+        (Some(image), _) => mapper::create(image.mapper, image.prg.clone(), image.chr.clone(), BoardRam::of(image)).unwrap_or_else(|err| {
+            crate::log_event!(Loader, Warn, "{}, the image is mapped flat", err);
+            flat()
+        }),
+        (None, Some(disk_system)) => disk_system,
+        (None, None) => flat(),
+    };
+    crate::log_event!(Loader, Info, "{} inserted, {} segments", cartridge.name(), program.segments.len());
+    machine.bus.borrow_mut().insert_cartridge(cartridge);
+
+    if options.patch_reset_vector {
+        let entry = program.entry
+            .or_else(|| program.segments.first().map(|segment| segment.address))
+            .unwrap_or(options.load_address);
+        let mut bus = machine.bus.borrow_mut();
+        bus.write(0xFFFC, entry as u8);
+        bus.write(0xFFFC + 1, (entry >> 8) as u8);
+    }
+    machine.cpu.borrow_mut().reset();
+    program
+}
+
+// cycles run_until runs before giving the executor back, a quarter of a frame
+const SLICE_CYCLES: u64 = timing::DOTS_PER_FRAME / timing::DOTS_PER_CPU_CYCLE / 4;
+
+// a plugin and the regions it was attached on, as given, before it ran
+pub type Attached = (Box<dyn Plugin>, Vec<RangeInclusive<u16>>);
+
+// the console behind a small api without any interface, history or debugger state,
+// for scripts, tests and frontends embedding the core
+pub struct Emulator<'a> {
+    pub machine    : Machine<'a>,
+    pub program    : Program,
+    pub options    : LoadOptions,
+    pub bios       : Option<Vec<u8>>, // the disk system rom, needed for disk images
+    pub power_on   : PowerOnPattern,
+    pub peripheral : Peripheral,
+    pub alignment  : u8, // the ppu's dot at power on (timing::ALIGNMENTS), taken on the next power cycle
+    pub run_ahead  : u32, // frames the frame buffer shows ahead of the machine, see Machine::run_ahead
+    pub plugins    : Vec<Attached>,
+    pub turbo       : Turbo,
+    pub breakpoints : BTreeSet<u16>, // run_frame stops before the instruction at any of these
+    held            : Cell<[u8; 4]>,
+    held_turbo      : Cell<[u8; 4]>,
+    hooks           : RefCell<Hooks>,
+    banks           : RefCell<Option<Vec<Bank>>>, // the board's layout at the last boundary, to see it switch
+    printed         : Cell<usize>, // the terminal output already sent
+}
+
+impl<'a> Default for Emulator<'a> {
+    fn default() -> Self {
+        Emulator::new(PowerOnPattern::Fill(0), Peripheral::Controller)
+    }
+}
+
+impl<'a> Emulator<'a> {
+    pub fn new(power_on: PowerOnPattern, peripheral: Peripheral) -> Emulator<'a> {
+        let machine = Machine::power_on(power_on);
+        machine.connect(peripheral);
+        Emulator {
+            machine,
+            program : Program::new(),
+            options : LoadOptions::new(),
+            bios    : None,
+            power_on,
+            peripheral,
+            alignment : 0,
+            run_ahead : 0,
+            plugins   : Vec::new(),
+            turbo       : Turbo::default(),
+            breakpoints : BTreeSet::new(),
+            held        : Cell::new([0; 4]),
+            held_turbo  : Cell::new([0; 4]),
+            hooks       : RefCell::new(Hooks::default()),
+            banks       : RefCell::new(None),
+            printed     : Cell::new(0),
+        }
+    }
+
+    // an emulator of its own in the state this one is in, machine, program and settings,
+    // to run side by side with it : to replay frames and check a rollback came back to the
+    // same state, or to try other input from here. its subscribers are its own, it starts
+    // with none
+    pub fn fork(&self) -> Emulator<'a> {
+        Emulator {
+            machine    : self.machine.fork(),
+            program    : self.program.clone(),
+            options    : self.options,
+            bios       : self.bios.clone(),
+            power_on   : self.power_on,
+            peripheral : self.peripheral,
+            alignment  : self.alignment,
+            run_ahead  : self.run_ahead,
+            plugins    : self.plugins.iter().map(|(plugin, regions)| (plugin.clone(), regions.clone())).collect(),
+            turbo       : self.turbo,
+            breakpoints : self.breakpoints.clone(),
+            held        : self.held.clone(),
+            held_turbo  : self.held_turbo.clone(),
+            hooks       : RefCell::new(Hooks::default()),
+            banks       : RefCell::new(None),
+            printed     : self.printed.clone(),
+        }
+    }
+
+    pub fn set_disk_system_bios(&mut self, bios: Vec<u8>) -> Result<(), Error> {
+        if bios.len() != fds::BIOS_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, format!("a disk system bios is {} bytes", fds::BIOS_SIZE)));
+        }
+        self.bios = Some(bios);
+        Ok(())
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<(), Error> {
+        let (name, content) = archive::read(path)?;
+        self.load_bytes(&name, &content)
+    }
+
+    // the name only helps detecting the format, as a path would
+    pub fn load_bytes(&mut self, name: &str, content: &[u8]) -> Result<(), Error> {
+        let format = Format::detect(Path::new(name.trim()), content);
+        let program = loader::parse(format, content, self.options.load_address)?;
+        if program.disk.is_some() && self.bios.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "disk images need the disk system bios"));
+        }
+        self.load_program(&program);
+        Ok(())
+    }
+
+    pub fn load_program(&mut self, program: &Program) {
+        self.program = insert_program(&self.machine, program, self.bios.clone(), self.options);
+        self.banks.replace(None);
+    }
+
+    pub fn eject(&mut self) {
+        self.machine.bus.borrow_mut().eject_cartridge();
+        self.program = Program::new();
+        self.banks.replace(None);
+    }
+
+    pub fn reset(&self) {
+        self.machine.soft_reset();
+    }
+
+    // a device of the embedding program's own, see hardware::plugin. it is attached again,
+    // as it was given, on a power cycle
+    pub fn attach(&mut self, plugin: Box<dyn Plugin>, regions: Vec<RangeInclusive<u16>>) {
+        self.plugins.push((plugin.clone(), regions.clone()));
+        self.machine.attach(plugin, regions);
+    }
+
+    // a fresh machine with the program inserted again
+    pub fn power_cycle(&mut self) {
+        self.machine = Machine::power_on(self.power_on);
+        self.banks.replace(None);
+        self.printed.set(0);
+        self.machine.connect(self.peripheral);
+        self.machine.align(self.alignment);
+        for (plugin, regions) in self.plugins.iter() {
+            self.machine.attach(plugin.clone(), regions.clone());
+        }
+        let program = self.program.clone();
+        if !program.is_empty() {
+            self.load_program(&program);
+        }
+    }
+
+    // one instruction, returns the cycles it took
+    pub fn step(&self) -> u64 {
+        let before = self.cycles();
+        while self.machine.cpu.borrow().cycle > 0 {
+            self.tick();
+        }
+        self.tick();
+        self.cycles() - before
+    }
+
+    // until the next frame is complete, with the turbo buttons as they are for it. it stops
+    // early before an instruction at a breakpoint, and goes on past it when called again
+    pub fn run_frame(&self) {
+        self.press_turbo();
+        loop {
+            let (frame_done, stopped) = self.tick();
+            if stopped {
+                return;
+            }
+            if frame_done {
+                break;
+            }
+        }
+        self.machine.run_ahead(self.run_ahead);
+    }
+
+    // runs until the predicate holds after an instruction, a breakpoint is reached or the
+    // token is cancelled, a slice of cycles per poll so the executor driving it is never
+    // held for long. frames completed on the way are as run_frame makes them
+    pub fn run_until<P: FnMut(&Emulator<'a>) -> bool>(&self, predicate: P, token: CancelToken) -> RunUntil<'_, 'a, P> {
+        self.press_turbo();
+        RunUntil { emulator: self, predicate, token }
+    }
+
+    // the turbo buttons as they are for the frame being run
+    fn press_turbo(&self) {
+        let frame = self.frame();
+        for (port, (held, turbo)) in self.held.get().iter().zip(self.held_turbo.get().iter()).enumerate() {
+            self.machine.set_buttons(port as u16, self.turbo.apply(*held, *turbo, frame));
+        }
+    }
+
+    // the callback is called with each event of the kinds given, from within run_frame or
+    // step, in the order they came in
+    pub fn subscribe(&self, kinds: &[EventKind], callback: Box<dyn FnMut(&Event)>) {
+        self.hooks.borrow_mut().subscribe(kinds, callback);
+    }
+
+    // the events of the kinds given, sent as they come in. dropping the receiver ends it
+    pub fn events(&self, kinds: &[EventKind]) -> Receiver<Event> {
+        self.hooks.borrow_mut().channel(kinds)
+    }
+
+    // one cycle with the events it brought, returns whether the frame is complete and
+    // whether a breakpoint was reached
+    fn tick(&self) -> (bool, bool) {
+        let starting = self.machine.cpu.borrow().cycle == 0;
+        let frame_done = self.machine.tick();
+        let (boundary, pc) = {
+            let cpu = self.machine.cpu.borrow();
+            (cpu.cycle == 0, cpu.registers.pc)
+        };
+        let stopped = boundary && self.breakpoints.contains(&pc);
+        if self.hooks.borrow().is_empty() {
+            return (frame_done, stopped);
+        }
+        let mut events = Vec::new();
+        let hooks = self.hooks.borrow();
+        if starting && hooks.wants(EventKind::NmiRaised) {
+            let cpu = self.machine.cpu.borrow();
+            if cpu.events.get() & EVENT_NMI != 0 {
+                events.push(Event::NmiRaised { cycles: cpu.total_cycles - 1 });
+            }
+        }
+        if boundary && hooks.wants(EventKind::MapperBankSwitched) {
+            let banks = self.machine.banks();
+            if let Some(last) = self.banks.borrow().as_ref() {
+                events.extend(banks.iter()
+                    .filter(|bank| !last.contains(bank))
+                    .map(|bank| Event::MapperBankSwitched { memory: bank.memory, address: bank.address, bank: bank.bank }));
+            }
+            self.banks.replace(Some(banks));
+        }
+        if boundary && hooks.wants(EventKind::SerialOutput) {
+            // drained by whoever reads the terminal, it starts over from what is left
+            let printed = self.machine.terminal_length();
+            let sent = self.printed.get().min(printed);
+            if printed > sent {
+                let output = self.machine.terminal_output().unwrap_or_default();
+                events.push(Event::SerialOutput { bytes: output[sent..].to_vec() });
+            }
+            self.printed.set(printed);
+        }
+        if stopped {
+            events.push(Event::BreakpointHit { pc });
+        }
+        if frame_done {
+            events.push(Event::FrameCompleted { frame: self.frame() });
+        }
+        drop(hooks);
+        let mut hooks = self.hooks.borrow_mut();
+        events.into_iter().for_each(|event| hooks.emit(event));
+        (frame_done, stopped)
+    }
+
+    // the nametable layout in effect, for dumps only : the header's unless the board switches it
+    pub fn mirroring(&self) -> Option<Mirroring> {
+        let header = self.program.image.as_ref()?.mirroring;
+        let bus = self.machine.bus.borrow();
+        let board = bus.devices.iter()
+            .map(|mapping| mapping.device.borrow())
+            .find(|device| device.is_cartridge())
+            .and_then(|device| device.mirroring());
+        Some(nametables::effective(board, header))
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.machine.cpu.borrow().total_cycles
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.machine.video.borrow().frame
+    }
+
+    // pads 0 - 3, bits A B Select Start Up Down Left Right from bit 0
+    pub fn set_buttons(&self, port: u16, buttons: u8) {
+        let mut held = self.held.get();
+        if let Some(pad) = held.get_mut(port as usize) {
+            *pad = buttons;
+        }
+        self.held.set(held);
+        self.machine.set_buttons(port, buttons);
+    }
+
+    // the buttons held as turbo ones, same bits, pressed and released in turn by run_frame
+    pub fn set_turbo_buttons(&self, port: u16, buttons: u8) {
+        let mut held = self.held_turbo.get();
+        if let Some(turbo) = held.get_mut(port as usize) {
+            *turbo = buttons;
+        }
+        self.held_turbo.set(held);
+    }
+
+    // the last completed frame, 0x00RRGGBB pixels row by row
+    pub fn framebuffer(&self) -> (usize, usize, Vec<u32>) {
+        let video = self.machine.video.borrow();
+        (video.width, video.height, video.front().to_vec())
+    }
+
+    // mono samples at audio::SAMPLE_RATE since the last call
+    pub fn audio(&self) -> Vec<f32> {
+        self.machine.audio.borrow_mut().drain()
+    }
+
+    // how many of the `size` samples the frontend's output queue holds are still queued,
+    // before each audio call, to keep the queue from running dry or filling up
+    pub fn steer_audio(&self, queued: usize, size: usize) {
+        self.machine.audio.borrow_mut().steer(queued, size);
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.machine.save(&mut out);
+        out
+    }
+
+    // restored into a copy first, so a state that does not fit leaves the machine as it was
+    pub fn load_state(&mut self, mut state: &[u8]) -> Result<(), Error> {
+        let mut machine = self.machine.clone();
+        machine.restore(&mut state)?;
+        self.machine = machine;
+        self.banks.replace(None);
+        Ok(())
+    }
+}
+
+// stops a run_until from another task or thread, the run ends at its next slice
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// why a run_until ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stopped {
+    Reached,
+    Breakpoint(u16),
+    Cancelled,
+}
+
+// the future of Emulator::run_until, it wakes itself after each slice it ran
+pub struct RunUntil<'e, 'a, P> {
+    emulator  : &'e Emulator<'a>,
+    predicate : P,
+    token     : CancelToken,
+}
+
+impl<'a, P: FnMut(&Emulator<'a>) -> bool + Unpin> Future for RunUntil<'_, 'a, P> {
+    type Output = Stopped;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Stopped> {
+        let run = self.get_mut();
+        let emulator = run.emulator;
+        if run.token.is_cancelled() {
+            return Poll::Ready(Stopped::Cancelled);
+        }
+        let end = emulator.cycles() + SLICE_CYCLES;
+        while emulator.cycles() < end {
+            let (frame_done, stopped) = emulator.tick();
+            if frame_done {
+                emulator.machine.run_ahead(emulator.run_ahead);
+                emulator.press_turbo();
+            }
+            if emulator.machine.cpu.borrow().cycle > 0 {
+                continue;
+            }
+            if (run.predicate)(emulator) {
+                return Poll::Ready(Stopped::Reached);
+            }
+            if stopped {
+                return Poll::Ready(Stopped::Breakpoint(emulator.machine.cpu.borrow().registers.pc));
+            }
+        }
+        context.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl DeviceOps for Emulator<'_> {
+    fn read(&self, address: u16) -> u8 {
+        self.machine.bus.borrow().read(address)
+    }
+
+    fn write(&mut self, address: u16, data: u8) {
+        self.machine.bus.borrow_mut().write(address, data);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.machine.bus.borrow().peek(address)
+    }
+}
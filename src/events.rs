@@ -0,0 +1,86 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::hardware::mapper::BankMemory;
+
+// what an embedding program can hear about from an Emulator without polling it, see
+// Emulator::subscribe and Emulator::events
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    FrameCompleted { frame: u64 },
+    NmiRaised { cycles: u64 }, // the cpu cycle the interrupt sequence started at
+    MapperBankSwitched { memory: BankMemory, address: u16, bank: u16 },
+    BreakpointHit { pc: u16 },
+    SerialOutput { bytes: Vec<u8> }, // what the serial ports and displays printed since the last one
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    FrameCompleted,
+    NmiRaised,
+    MapperBankSwitched,
+    BreakpointHit,
+    SerialOutput,
+}
+
+pub const ALL: [EventKind; 5] = [EventKind::FrameCompleted, EventKind::NmiRaised, EventKind::MapperBankSwitched, EventKind::BreakpointHit, EventKind::SerialOutput];
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::FrameCompleted { .. } => EventKind::FrameCompleted,
+            Event::NmiRaised { .. } => EventKind::NmiRaised,
+            Event::MapperBankSwitched { .. } => EventKind::MapperBankSwitched,
+            Event::BreakpointHit { .. } => EventKind::BreakpointHit,
+            Event::SerialOutput { .. } => EventKind::SerialOutput,
+        }
+    }
+}
+
+enum Subscriber {
+    Callback(Box<dyn FnMut(&Event)>),
+    Channel(Sender<Event>),
+}
+
+// the subscribers and the kinds each asked for. a channel whose receiver was dropped is
+// dropped with the next event it would get
+#[derive(Default)]
+pub struct Hooks {
+    subscribers : Vec<(Vec<EventKind>, Subscriber)>,
+}
+
+impl Hooks {
+    pub fn subscribe(&mut self, kinds: &[EventKind], callback: Box<dyn FnMut(&Event)>) {
+        self.subscribers.push((kinds.to_vec(), Subscriber::Callback(callback)));
+    }
+
+    pub fn channel(&mut self, kinds: &[EventKind]) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push((kinds.to_vec(), Subscriber::Channel(sender)));
+        receiver
+    }
+
+    // whether anyone listens, so the checks behind an event are only made when needed
+    pub fn wants(&self, kind: EventKind) -> bool {
+        self.subscribers.iter().any(|(kinds, _)| kinds.contains(&kind))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        let kind = event.kind();
+        self.subscribers.retain_mut(|(kinds, subscriber)| {
+            if !kinds.contains(&kind) {
+                return true;
+            }
+            match subscriber {
+                Subscriber::Callback(callback) => {
+                    callback(&event);
+                    true
+                },
+                Subscriber::Channel(sender) => sender.send(event.clone()).is_ok(),
+            }
+        });
+    }
+}
@@ -0,0 +1,264 @@
+// every handle comes from bs_create, the pointer contracts are in include/brick_station.h
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::emulator::Emulator;
+use crate::hardware::audio::SAMPLE_RATE;
+use crate::hardware::controller::Turbo;
+use crate::hardware::interfaces::DeviceOps;
+
+// the c abi, see include/brick_station.h, bumped on any incompatible change
+pub const API_VERSION: u32 = 1;
+
+// what a handle points to : the emulator, and the buffers handed out last so their
+// pointers stay valid until the next call asking for them
+pub struct BsMachine {
+    emulator  : Emulator<'static>,
+    video     : Vec<u32>,
+    audio     : Vec<f32>,
+}
+
+unsafe fn machine<'m>(handle: *mut BsMachine) -> Option<&'m mut BsMachine> {
+    handle.as_mut()
+}
+
+// a panic cannot unwind into the host, the call fails instead : -1, null, 0 or nothing
+fn guarded<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(failed)
+}
+
+#[no_mangle]
+pub extern "C" fn bs_api_version() -> u32 {
+    API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn bs_audio_sample_rate() -> u32 {
+    SAMPLE_RATE
+}
+
+#[no_mangle]
+pub extern "C" fn bs_create() -> *mut BsMachine {
+    guarded(ptr::null_mut(), || {
+        Box::into_raw(Box::new(BsMachine {
+            emulator : Emulator::default(),
+            video    : Vec::new(),
+            audio    : Vec::new(),
+        }))
+    })
+}
+
+// a handle of its own, in the state `handle` is in, destroyed on its own
+#[no_mangle]
+pub unsafe extern "C" fn bs_fork(handle: *mut BsMachine) -> *mut BsMachine {
+    guarded(ptr::null_mut(), || {
+        match machine(handle) {
+            Some(machine) => Box::into_raw(Box::new(BsMachine {
+                emulator : machine.emulator.fork(),
+                video    : Vec::new(),
+                audio    : Vec::new(),
+            })),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+// `handle` comes from bs_create and is not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn bs_destroy(handle: *mut BsMachine) {
+    guarded((), || {
+        if !handle.is_null() {
+            drop(Box::from_raw(handle));
+        }
+    })
+}
+
+// `data` points to `size` readable bytes, `name` is null or a nul terminated string,
+// only used to detect the format from its extension
+#[no_mangle]
+pub unsafe extern "C" fn bs_load_rom(handle: *mut BsMachine, data: *const u8, size: usize, name: *const c_char) -> c_int {
+    guarded(-1, || {
+        let machine = match machine(handle) {
+            Some(machine) if !data.is_null() => machine,
+            _ => return -1,
+        };
+        let name = if name.is_null() { String::new() } else { CStr::from_ptr(name).to_string_lossy().to_string() };
+        match machine.emulator.load_bytes(&name, slice::from_raw_parts(data, size)) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
+    })
+}
+
+// `data` points to `size` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn bs_set_disk_system_bios(handle: *mut BsMachine, data: *const u8, size: usize) -> c_int {
+    guarded(-1, || {
+        match machine(handle) {
+            Some(machine) if !data.is_null() => match machine.emulator.set_disk_system_bios(slice::from_raw_parts(data, size).to_vec()) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            },
+            _ => -1,
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_reset(handle: *mut BsMachine) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.reset();
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_power_cycle(handle: *mut BsMachine) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.power_cycle();
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_run_frame(handle: *mut BsMachine) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.run_frame();
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_set_run_ahead(handle: *mut BsMachine, frames: u32) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.run_ahead = frames;
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_set_input(handle: *mut BsMachine, port: u32, buttons: u8) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.set_buttons(port as u16, buttons);
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_set_turbo_input(handle: *mut BsMachine, port: u32, buttons: u8) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.set_turbo_buttons(port as u16, buttons);
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_set_turbo_rate(handle: *mut BsMachine, rate: u8) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.turbo = Turbo::new(rate);
+        }
+    })
+}
+
+// `width` and `height` are null or writable, the pixels stay valid until the next
+// bs_framebuffer call or bs_destroy
+#[no_mangle]
+pub unsafe extern "C" fn bs_framebuffer(handle: *mut BsMachine, width: *mut usize, height: *mut usize) -> *const u32 {
+    guarded(ptr::null(), || {
+        let machine = match machine(handle) {
+            Some(machine) => machine,
+            None => return ptr::null(),
+        };
+        let (frame_width, frame_height, pixels) = machine.emulator.framebuffer();
+        machine.video = pixels;
+        if !width.is_null() {
+            *width = frame_width;
+        }
+        if !height.is_null() {
+            *height = frame_height;
+        }
+        machine.video.as_ptr()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_audio_queued(handle: *mut BsMachine, queued: usize, size: usize) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.steer_audio(queued, size);
+        }
+    })
+}
+
+// `count` is writable, the samples stay valid until the next bs_audio call or bs_destroy
+#[no_mangle]
+pub unsafe extern "C" fn bs_audio(handle: *mut BsMachine, count: *mut usize) -> *const f32 {
+    guarded(ptr::null(), || {
+        let machine = match machine(handle) {
+            Some(machine) if !count.is_null() => machine,
+            _ => return ptr::null(),
+        };
+        machine.audio = machine.emulator.audio();
+        *count = machine.audio.len();
+        machine.audio.as_ptr()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_peek(handle: *mut BsMachine, address: u16) -> u8 {
+    guarded(0, || {
+        machine(handle).map(|machine| machine.emulator.peek(address)).unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bs_write(handle: *mut BsMachine, address: u16, value: u8) {
+    guarded((), || {
+        if let Some(machine) = machine(handle) {
+            machine.emulator.write(address, value);
+        }
+    })
+}
+
+// returns the state's size, written to `out` only when it fits in `capacity`, so a
+// first call with a null buffer asks for the size, `out` is null or points to `capacity` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn bs_save_state(handle: *mut BsMachine, out: *mut u8, capacity: usize) -> usize {
+    guarded(0, || {
+        let machine = match machine(handle) {
+            Some(machine) => machine,
+            None => return 0,
+        };
+        let state = machine.emulator.save_state();
+        if !out.is_null() && state.len() <= capacity {
+            ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+        }
+        state.len()
+    })
+}
+
+// `data` points to `size` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn bs_load_state(handle: *mut BsMachine, data: *const u8, size: usize) -> c_int {
+    guarded(-1, || {
+        match machine(handle) {
+            Some(machine) if !data.is_null() => match machine.emulator.load_state(slice::from_raw_parts(data, size)) {
+                Ok(()) => 0,
+                Err(_) => -1,
+            },
+            _ => -1,
+        }
+    })
+}
@@ -3,7 +3,44 @@ pub mod cpu;
 pub mod instructions;
 pub mod interfaces;
 pub mod ram;
+pub mod dirty;
+pub mod state_hash;
+pub mod sanitizer;
+pub mod watches;
 pub mod registers;
 pub mod bus;
+pub mod interrupts;
+pub mod call_stack;
 pub mod device;
-pub mod opcodes;
\ No newline at end of file
+pub mod opcodes;
+pub mod opcode_table;
+pub mod timing;
+pub mod framebuffer;
+pub mod machine;
+pub mod controller;
+pub mod snapshot;
+pub mod cartridge;
+pub mod rng;
+pub mod zapper;
+pub mod four_score;
+pub mod audio;
+pub mod resampler;
+pub mod vrc6;
+pub mod mapper;
+pub mod fds;
+pub mod apu;
+pub mod dma;
+pub mod ppu;
+pub mod nsf;
+pub mod rom;
+pub mod serial;
+pub mod pia;
+pub mod via;
+pub mod acia;
+pub mod plugin;
+pub mod bridge;
+pub mod profile;
+pub mod fuzzing;
+pub mod nametables;
+pub mod palette;
+pub mod inspect;
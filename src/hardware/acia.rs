@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Error;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use super::bridge::Bridge;
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u8, put_u32, take_bytes, take_u8, take_u32};
+use super::inspect::{DebugState, StateNode};
+
+// what is printed is kept up to this, older output is dropped
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+pub const STATUS_RECEIVE_FULL   : u8 = 1 << 3;
+pub const STATUS_TRANSMIT_EMPTY : u8 = 1 << 4;
+pub const STATUS_IRQ            : u8 = 1 << 7;
+
+const COMMAND_DTR          : u8 = 1 << 0;
+const COMMAND_RX_IRQ_OFF   : u8 = 1 << 1;
+const COMMAND_RESET_MASK   : u8 = 0xE0; // what a programmed reset leaves of the command
+
+// a 6551 asynchronous communications interface adapter : data, status (a write is a
+// programmed reset), command and control from base. characters go through at once
+// whatever the baud rate, the transmitter is always empty, the modem lines always up,
+// a received character holds irq while the receiver interrupt is enabled. the lines
+// can be bridged to the host
+#[derive(Debug, Clone, Default)]
+pub struct Acia {
+    pub base    : u16,
+    pub command : u8,
+    pub control : u8,
+    pub input   : RefCell<VecDeque<u8>>,
+    pub output  : Vec<u8>,
+    pub bridge  : Option<Rc<Bridge>>,
+}
+
+impl Acia {
+    pub fn new(base: u16, bridge: Option<Rc<Bridge>>) -> Acia {
+        Acia { base, bridge, ..Default::default() }
+    }
+
+    pub fn region(&self) -> RangeInclusive<u16> {
+        self.base..=self.base.wrapping_add(3)
+    }
+
+    pub fn type_text(&self, text: &str) {
+        self.input.borrow_mut().extend(text.bytes());
+    }
+
+    // sampled between instructions, so what the host sent is picked up here as well
+    pub fn irq(&self) -> bool {
+        self.poll();
+        self.status() & STATUS_IRQ != 0
+    }
+
+    fn status(&self) -> u8 {
+        let full = !self.input.borrow().is_empty();
+        let irq = full && self.command & (COMMAND_DTR | COMMAND_RX_IRQ_OFF) == COMMAND_DTR;
+        STATUS_TRANSMIT_EMPTY | if full { STATUS_RECEIVE_FULL } else { 0 } | if irq { STATUS_IRQ } else { 0 }
+    }
+
+    // what arrived from the host waits with the typed characters
+    fn poll(&self) {
+        if let Some(bridge) = &self.bridge {
+            self.input.borrow_mut().extend(bridge.receive());
+        }
+    }
+}
+
+impl DeviceOps for Acia {
+    fn within_range(&self, addr: u16) -> bool {
+        self.region().contains(&addr)
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.poll();
+        match addr.wrapping_sub(self.base) {
+            0 => self.input.borrow_mut().pop_front().unwrap_or(0),
+            _ => self.peek(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr.wrapping_sub(self.base) {
+            0 => {
+                if let Some(bridge) = &self.bridge {
+                    bridge.send(value);
+                }
+                self.output.push(value);
+                if self.output.len() > OUTPUT_LIMIT {
+                    self.output.drain(..OUTPUT_LIMIT / 2);
+                }
+            },
+            1 => self.command &= COMMAND_RESET_MASK,
+            2 => self.command = value,
+            _ => self.control = value,
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr.wrapping_sub(self.base) {
+            0 => self.input.borrow().front().copied().unwrap_or(0),
+            1 => self.status(),
+            2 => self.command,
+            _ => self.control,
+        }
+    }
+}
+
+// the bridge is the host's, it stays as it is
+impl Snapshot for Acia {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.command);
+        put_u8(out, self.control);
+        let input = self.input.borrow();
+        put_u32(out, input.len() as u32);
+        out.extend(input.iter());
+        put_u32(out, self.output.len() as u32);
+        out.extend_from_slice(&self.output);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.command = take_u8(input)?;
+        self.control = take_u8(input)?;
+        let length = take_u32(input)? as usize;
+        *self.input.borrow_mut() = take_bytes(input, length)?.iter().copied().collect();
+        let length = take_u32(input)? as usize;
+        self.output = take_bytes(input, length)?.to_vec();
+        Ok(())
+    }
+}
+
+impl DebugState for Acia {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::word("base", self.base),
+            StateNode::byte("command", self.command),
+            StateNode::byte("control", self.control),
+            StateNode::value("input queued", self.input.borrow().len()),
+            StateNode::bytes("output", &self.output),
+            StateNode::value("bridged", self.bridge.is_some()),
+        ]
+    }
+}
@@ -59,45 +59,45 @@ impl AddressMode {
             }, 
             AddressMode::Imm => {
                 cpu_ref.address_mode.address_abs = cpu_ref.registers.pc as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 false
             },
             AddressMode::Zp0 => {
                 cpu_ref.address_mode.address_abs = cpu_ref.read(cpu_ref.registers.pc as u16).into();
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 cpu_ref.address_mode.address_abs &= 0x00FF;
                 false
             },
             AddressMode::Zpx => {
                 cpu_ref.address_mode.address_abs = cpu_ref.read(cpu_ref.registers.pc as u16).into();
                 cpu_ref.address_mode.address_abs+= cpu_ref.registers.x as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 cpu_ref.address_mode.address_abs &= 0x00FF;
                 false
             },
             AddressMode::Zpy => {
                 cpu_ref.address_mode.address_abs = cpu_ref.read(cpu_ref.registers.pc as u16).into();
                 cpu_ref.address_mode.address_abs+= cpu_ref.registers.y as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 cpu_ref.address_mode.address_abs &= 0x00FF;
                 false
             },
             AddressMode::Abs => {
                 let lo = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 let hi = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 cpu_ref.address_mode.address_abs = (hi << 8) | lo;
                 false
             },
             AddressMode::Abx => {
                 let lo = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 let hi = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
 
                 cpu_ref.address_mode.address_abs  = (hi << 8) | lo;
-                cpu_ref.address_mode.address_abs += cpu_ref.registers.x as u16;
+                cpu_ref.address_mode.address_abs = cpu_ref.address_mode.address_abs.wrapping_add(cpu_ref.registers.x as u16);
                 
                 if cpu_ref.address_mode.address_abs & 0xFF00 != (hi << 8).into() {
                     true
@@ -108,12 +108,12 @@ impl AddressMode {
             },
             AddressMode::Aby => {
                 let lo = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 let hi = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
 
                 cpu_ref.address_mode.address_abs  = (hi << 8) | lo;
-                cpu_ref.address_mode.address_abs += cpu_ref.registers.y as u16;
+                cpu_ref.address_mode.address_abs = cpu_ref.address_mode.address_abs.wrapping_add(cpu_ref.registers.y as u16);
                 
                 if cpu_ref.address_mode.address_abs & 0xFF00 != (hi << 8).into() {
                     true
@@ -124,9 +124,9 @@ impl AddressMode {
             },
             AddressMode::Ind => {
                 let lo = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 let hi = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
 
                 let ptr = (hi << 8) | lo;
                 let hi_ptr = if lo == 0x00ff { ptr & 0xff00 } else { ptr + 1 };
@@ -135,7 +135,7 @@ impl AddressMode {
             },
             AddressMode::Izx => {
                 let t = cpu_ref.read(cpu_ref.registers.pc as u16) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 
                 let lo = cpu_ref.read((t + cpu_ref.registers.x as u16) & 0x00FF) as u16;
                 let hi = cpu_ref.read((t + 1 + cpu_ref.registers.x as u16) & 0x00FF) as u16;
@@ -144,11 +144,11 @@ impl AddressMode {
             },
             AddressMode::Izy => {
                 let t = cpu_ref.read(cpu_ref.registers.pc) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 
                 let lo = cpu_ref.read(t & 0x00FF) as u16;
                 let hi = cpu_ref.read((t + 1) & 0x00FF) as u16;
-                cpu_ref.address_mode.address_abs = cpu_ref.registers.y  as u16 + ((hi << 8) | lo);
+                cpu_ref.address_mode.address_abs = ((hi << 8) | lo).wrapping_add(cpu_ref.registers.y as u16);
                 if cpu_ref.address_mode.address_abs & 0xFF00 != hi << 8 {
                     true
                 }
@@ -158,7 +158,7 @@ impl AddressMode {
             },
             AddressMode::Rel => {
                 cpu_ref.address_mode.address_rel = cpu_ref.read(cpu_ref.registers.pc) as u16;
-                cpu_ref.registers.pc += 1;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
                 
                 if cpu_ref.address_mode.address_rel & 0x0080 != 0 {
                     cpu_ref.address_mode.address_rel |= 0xFF00;
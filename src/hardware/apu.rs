@@ -0,0 +1,822 @@
+use std::cell::Cell;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::audio::AudioSource;
+use super::interfaces::{Clocked, DeviceOps};
+use super::snapshot::{Snapshot, put_u8, put_u16, put_u32, put_u64, take_u8, take_u16, take_u32, take_u64};
+use super::inspect::{DebugState, StateNode};
+
+const LENGTHS: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTIES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const TRIANGLE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+// ntsc, in cpu cycles
+const NOISE_PERIODS: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+
+// ntsc, in cpu cycles between two bits of a sample
+const DMC_PERIODS: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+// frame sequencer steps in cpu cycles, the last one ends the sequence
+const FOUR_STEP : [u32; 4] = [7457, 14913, 22371, 29829];
+const FIVE_STEP : [u32; 5] = [7457, 14913, 22371, 29829, 37281];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    pub looping  : bool, // doubles as the length counter halt
+    pub constant : bool,
+    pub volume   : u8,
+    start        : bool,
+    divider      : u8,
+    decay        : u8,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.constant { self.volume } else { self.decay }
+    }
+
+    fn save(&self, out: &mut Vec<u8>) {
+        for value in [self.looping as u8, self.constant as u8, self.volume, self.start as u8, self.divider, self.decay].iter() {
+            put_u8(out, *value);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.looping = take_u8(input)? != 0;
+        self.constant = take_u8(input)? != 0;
+        self.volume = take_u8(input)?;
+        self.start = take_u8(input)? != 0;
+        self.divider = take_u8(input)?;
+        self.decay = take_u8(input)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pulse {
+    pub duty     : u8,
+    pub period   : u16,
+    pub length   : u8,
+    pub envelope : Envelope,
+    pub sweep    : u8, // $4001 as written
+    second       : bool, // the second pulse negates in two's complement
+    timer        : u16,
+    step         : u8,
+    sweep_reload : bool,
+    sweep_timer  : u8,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8, enabled: bool) {
+        match register & 3 {
+            0 => {
+                self.duty = value >> 6;
+                self.envelope.write(value);
+            },
+            1 => {
+                self.sweep = value;
+                self.sweep_reload = true;
+            },
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 7) as u16) << 8;
+                if enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.step = 0;
+                self.envelope.start = true;
+            },
+        }
+    }
+
+    fn target(&self) -> u16 {
+        let change = self.period >> (self.sweep & 7);
+        if self.sweep & 0x08 == 0 {
+            self.period + change
+        } else if self.second {
+            self.period.saturating_sub(change)
+        } else {
+            self.period.saturating_sub(change + 1)
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.period < 8 || self.target() > 0x7FF
+    }
+
+    fn clock(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) & 7;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_timer == 0 && self.sweep & 0x80 != 0 && self.sweep & 7 != 0 && !self.muted() {
+            self.period = self.target();
+        }
+        if self.sweep_timer == 0 || self.sweep_reload {
+            self.sweep_timer = (self.sweep >> 4) & 7;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_timer -= 1;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.envelope.looping && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length == 0 || self.muted() || DUTIES[self.duty as usize][self.step as usize] == 0 { 0 } else { self.envelope.output() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Triangle {
+    pub control       : bool, // length counter halt and linear counter control
+    pub linear_reload : u8,
+    pub period        : u16,
+    pub length        : u8,
+    linear            : u8,
+    reload            : bool,
+    timer             : u16,
+    step              : u8,
+}
+
+impl Triangle {
+    fn write(&mut self, register: u16, value: u8, enabled: bool) {
+        match register & 3 {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.linear_reload = value & 0x7F;
+            },
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            3 => {
+                self.period = (self.period & 0x00FF) | ((value & 7) as u16) << 8;
+                if enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.reload = true;
+            },
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.length > 0 && self.linear > 0 {
+                self.step = (self.step + 1) & 31;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_linear(&mut self) {
+        if self.reload {
+            self.linear = self.linear_reload;
+        } else if self.linear > 0 {
+            self.linear -= 1;
+        }
+        if !self.control {
+            self.reload = false;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.control && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        TRIANGLE[self.step as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    pub short    : bool, // 93 step mode
+    pub period   : u16,
+    pub length   : u8,
+    pub envelope : Envelope,
+    timer        : u16,
+    shift        : u16,
+}
+
+impl Noise {
+    fn new() -> Noise {
+        Noise {
+            short    : false,
+            period   : NOISE_PERIODS[0],
+            length   : 0,
+            envelope : Envelope::default(),
+            timer    : 0,
+            shift    : 1,
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8, enabled: bool) {
+        match register & 3 {
+            0 => self.envelope.write(value),
+            2 => {
+                self.short = value & 0x80 != 0;
+                self.period = NOISE_PERIODS[(value & 0x0F) as usize];
+            },
+            3 => {
+                if enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.envelope.start = true;
+            },
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period - 1;
+            let tap = if self.short { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+            self.shift = (self.shift >> 1) | feedback << 14;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.envelope.looping && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length == 0 || self.shift & 1 != 0 { 0 } else { self.envelope.output() }
+    }
+}
+
+// the delta modulation channel : samples are read a byte at a time from $C000 - $FFFF by
+// dma as its buffer empties, each bit moving the output level up or down by 2. the apu only
+// asks for the byte, see dma.rs for the cpu cycles the fetch takes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dmc {
+    pub irq_enable : bool,
+    pub looping    : bool,
+    pub period     : u16,
+    pub level      : u8,  // $4011, the output
+    pub start      : u16, // $4012 as an address
+    pub length     : u16, // $4013 as a byte count
+    address        : u16,
+    remaining      : u16,
+    buffer         : Option<u8>,
+    shift          : u8,
+    bits           : u8,
+    silence        : bool,
+    timer          : u16,
+    irq            : bool,
+    requested_at   : Option<u64>, // the cycle the buffer went empty with bytes left to fetch
+}
+
+impl Dmc {
+    fn new() -> Dmc {
+        Dmc { period : DMC_PERIODS[0], start : 0xC000, length : 1, bits : 8, silence : true, ..Dmc::default() }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register & 3 {
+            0 => {
+                self.irq_enable = value & 0x80 != 0;
+                self.looping = value & 0x40 != 0;
+                self.period = DMC_PERIODS[(value & 0x0F) as usize];
+                if !self.irq_enable {
+                    self.irq = false;
+                }
+            },
+            1 => self.level = value & 0x7F,
+            2 => self.start = 0xC000 + value as u16 * 64,
+            _ => self.length = value as u16 * 16 + 1,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.address = self.start;
+        self.remaining = self.length;
+    }
+
+    // the byte the channel waits for, if any
+    pub fn request(&self) -> Option<u16> {
+        if self.buffer.is_none() && self.remaining > 0 { Some(self.address) } else { None }
+    }
+
+    pub fn requested_at(&self) -> Option<u64> {
+        self.requested_at
+    }
+
+    fn check_request(&mut self, cycle: u64) {
+        if self.request().is_some() && self.requested_at.is_none() {
+            self.requested_at = Some(cycle);
+        }
+    }
+
+    // the fetched byte, the address wraps to $8000 past $FFFF
+    pub fn fill(&mut self, value: u8) {
+        self.buffer = Some(value);
+        self.requested_at = None;
+        self.address = if self.address == 0xFFFF { 0x8000 } else { self.address + 1 };
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enable {
+                self.irq = true;
+            }
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period - 1;
+        if !self.silence {
+            if self.shift & 1 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift >>= 1;
+        self.bits -= 1;
+        if self.bits == 0 {
+            self.bits = 8;
+            match self.buffer.take() {
+                Some(value) => {
+                    self.shift = value;
+                    self.silence = false;
+                },
+                None => self.silence = true,
+            }
+        }
+    }
+}
+
+// the 2A03 sound channels at $4000 - $4017 : two pulses, the triangle, noise and the
+// frame sequencer clocking their envelopes and counters, and the dmc. the frame and dmc irqs
+// are flagged in $4015 and hold the irq line until acknowledged. $4014 starts the sprite dma,
+// which like the dmc's fetches the cpu carries out between instructions
+#[derive(Debug, Clone)]
+pub struct Apu {
+    pub pulses      : [Pulse; 2],
+    pub triangle    : Triangle,
+    pub noise       : Noise,
+    pub dmc         : Dmc,
+    pub enabled     : u8,      // $4015
+    pub oam_dma     : Option<u8>, // the page written to $4014, until the cpu copies it
+    pub read_glitch : bool,       // a dmc fetch repeats a read of $4016 / $4017 it lands on
+    pub five_step   : bool,
+    pub irq_inhibit : bool,
+    frame_irq       : Cell<bool>,
+    frame_cycle     : u32,
+    clocked         : u64,
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        Apu {
+            pulses      : [Pulse::default(), Pulse { second: true, ..Pulse::default() }],
+            triangle    : Triangle::default(),
+            noise       : Noise::new(),
+            dmc         : Dmc::new(),
+            enabled     : 0,
+            oam_dma     : None,
+            read_glitch : true,
+            five_step   : false,
+            irq_inhibit : false,
+            frame_irq   : Cell::new(false),
+            frame_cycle : 0,
+            clocked     : 0,
+        }
+    }
+
+    // $4016 belongs to the controller ports, $4017 is shared : writes set the frame
+    // sequencer, reads come from the second port, which is mapped before the apu
+    pub fn regions() -> Vec<RangeInclusive<u16>> {
+        vec![0x4000..=0x4015, 0x4017..=0x4017]
+    }
+
+    pub fn irq(&self) -> bool {
+        self.frame_irq.get() || self.dmc.irq
+    }
+
+    fn channel_enabled(&self, channel: u8) -> bool {
+        self.enabled & (1 << channel) != 0
+    }
+
+    fn quarter_frame(&mut self) {
+        self.pulses.iter_mut().for_each(|pulse| pulse.envelope.clock());
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    fn half_frame(&mut self) {
+        for pulse in self.pulses.iter_mut() {
+            pulse.clock_length();
+            pulse.clock_sweep();
+        }
+        self.triangle.clock_length();
+        self.noise.clock_length();
+    }
+
+    fn clock_sequencer(&mut self) {
+        self.frame_cycle += 1;
+        let steps: &[u32] = if self.five_step { &FIVE_STEP } else { &FOUR_STEP };
+        match steps.iter().position(|step| *step == self.frame_cycle) {
+            Some(0) | Some(2) => self.quarter_frame(),
+            Some(1) => {
+                self.quarter_frame();
+                self.half_frame();
+            },
+            Some(3) if self.five_step => {},
+            Some(_) => {
+                self.quarter_frame();
+                self.half_frame();
+                if !self.five_step && !self.irq_inhibit {
+                    self.frame_irq.set(true);
+                }
+                self.frame_cycle = 0;
+            },
+            None => {},
+        }
+    }
+
+    fn status(&self) -> u8 {
+        (self.pulses[0].length > 0) as u8
+            | ((self.pulses[1].length > 0) as u8) << 1
+            | ((self.triangle.length > 0) as u8) << 2
+            | ((self.noise.length > 0) as u8) << 3
+            | ((self.dmc.remaining > 0) as u8) << 4
+            | (self.frame_irq.get() as u8) << 6
+            | (self.dmc.irq as u8) << 7
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Apu::new()
+    }
+}
+
+impl DeviceOps for Apu {
+    fn within_range(&self, addr: u16) -> bool {
+        Apu::regions().iter().any(|region| region.contains(&addr))
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        if addr == 0x4015 {
+            self.frame_irq.set(false);
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4000..=0x400F => {
+                let channel = ((addr - 0x4000) / 4) as u8;
+                let enabled = self.channel_enabled(channel);
+                match channel {
+                    0 | 1 => self.pulses[channel as usize].write(addr, value, enabled),
+                    2 => self.triangle.write(addr, value, enabled),
+                    _ => self.noise.write(addr, value, enabled),
+                }
+            },
+            0x4010..=0x4013 => self.dmc.write(addr, value),
+            0x4014 => self.oam_dma = Some(value),
+            0x4015 => {
+                self.enabled = value & 0x1F;
+                self.dmc.irq = false;
+                if value & 0x10 == 0 {
+                    self.dmc.remaining = 0;
+                    self.dmc.requested_at = None;
+                } else if self.dmc.remaining == 0 {
+                    self.dmc.restart();
+                    self.dmc.check_request(self.clocked);
+                }
+                for (channel, pulse) in self.pulses.iter_mut().enumerate() {
+                    if value & (1 << channel) == 0 {
+                        pulse.length = 0;
+                    }
+                }
+                if value & 4 == 0 {
+                    self.triangle.length = 0;
+                }
+                if value & 8 == 0 {
+                    self.noise.length = 0;
+                }
+            },
+            0x4017 => {
+                self.five_step = value & 0x80 != 0;
+                self.irq_inhibit = value & 0x40 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq.set(false);
+                }
+                self.frame_cycle = 0;
+                if self.five_step {
+                    self.quarter_frame();
+                    self.half_frame();
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x4015 => self.status(),
+            _ => 0,
+        }
+    }
+}
+
+impl Clocked for Apu {
+    // pulse and noise timers run at half the cpu clock, the triangle at the full clock
+    fn catch_up(&mut self, cycles: u64) {
+        while self.clocked < cycles {
+            self.triangle.clock();
+            if self.clocked.is_multiple_of(2) {
+                self.pulses.iter_mut().for_each(Pulse::clock);
+            }
+            self.noise.clock();
+            self.dmc.clock();
+            self.clock_sequencer();
+            self.clocked += 1;
+            self.dmc.check_request(self.clocked);
+        }
+    }
+}
+
+impl AudioSource for Apu {
+    // the 2A03's non linear dac, approximated with the usual formulas
+    fn level(&self) -> f32 {
+        let pulses = (self.pulses[0].output() + self.pulses[1].output()) as f32;
+        let pulse_out = if pulses == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulses + 100.0) };
+        let tnd = self.triangle.output() as f32 / 8227.0 + self.noise.output() as f32 / 12241.0 + self.dmc.level as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+        pulse_out + tnd_out
+    }
+
+    fn channels(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("pulse1", self.pulses[0].output() as f32 / 15.0),
+            ("pulse2", self.pulses[1].output() as f32 / 15.0),
+            ("triangle", self.triangle.output() as f32 / 15.0),
+            ("noise", self.noise.output() as f32 / 15.0),
+            ("dmc", self.dmc.level as f32 / 127.0),
+        ]
+    }
+}
+
+impl Snapshot for Dmc {
+    fn save(&self, out: &mut Vec<u8>) {
+        for value in [self.irq_enable as u8, self.looping as u8, self.level, self.shift, self.bits, self.silence as u8, self.irq as u8].iter() {
+            put_u8(out, *value);
+        }
+        for value in [self.period, self.start, self.length, self.address, self.remaining, self.timer, self.buffer.map_or(0xFFFF, |value| value as u16)].iter() {
+            put_u16(out, *value);
+        }
+        put_u64(out, self.requested_at.unwrap_or(u64::MAX));
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.irq_enable = take_u8(input)? != 0;
+        self.looping = take_u8(input)? != 0;
+        self.level = take_u8(input)?;
+        self.shift = take_u8(input)?;
+        self.bits = take_u8(input)?;
+        self.silence = take_u8(input)? != 0;
+        self.irq = take_u8(input)? != 0;
+        self.period = take_u16(input)?;
+        self.start = take_u16(input)?;
+        self.length = take_u16(input)?;
+        self.address = take_u16(input)?;
+        self.remaining = take_u16(input)?;
+        self.timer = take_u16(input)?;
+        self.buffer = Some(take_u16(input)?).filter(|value| *value <= 0xFF).map(|value| value as u8);
+        self.requested_at = Some(take_u64(input)?).filter(|cycle| *cycle != u64::MAX);
+        Ok(())
+    }
+}
+
+impl Snapshot for Apu {
+    fn save(&self, out: &mut Vec<u8>) {
+        for pulse in self.pulses.iter() {
+            for value in [pulse.duty, pulse.length, pulse.sweep, pulse.step, pulse.sweep_reload as u8, pulse.sweep_timer].iter() {
+                put_u8(out, *value);
+            }
+            put_u16(out, pulse.period);
+            put_u16(out, pulse.timer);
+            pulse.envelope.save(out);
+        }
+        let triangle = &self.triangle;
+        for value in [triangle.control as u8, triangle.linear_reload, triangle.length, triangle.linear, triangle.reload as u8, triangle.step].iter() {
+            put_u8(out, *value);
+        }
+        put_u16(out, triangle.period);
+        put_u16(out, triangle.timer);
+        put_u8(out, self.noise.short as u8);
+        put_u8(out, self.noise.length);
+        put_u16(out, self.noise.period);
+        put_u16(out, self.noise.timer);
+        put_u16(out, self.noise.shift);
+        self.noise.envelope.save(out);
+        self.dmc.save(out);
+        put_u16(out, self.oam_dma.map_or(0xFFFF, |page| page as u16));
+        for value in [self.enabled, self.five_step as u8, self.irq_inhibit as u8, self.frame_irq.get() as u8].iter() {
+            put_u8(out, *value);
+        }
+        put_u32(out, self.frame_cycle);
+        put_u64(out, self.clocked);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        for pulse in self.pulses.iter_mut() {
+            pulse.duty = take_u8(input)?;
+            pulse.length = take_u8(input)?;
+            pulse.sweep = take_u8(input)?;
+            pulse.step = take_u8(input)?;
+            pulse.sweep_reload = take_u8(input)? != 0;
+            pulse.sweep_timer = take_u8(input)?;
+            pulse.period = take_u16(input)?;
+            pulse.timer = take_u16(input)?;
+            pulse.envelope.restore(input)?;
+        }
+        let triangle = &mut self.triangle;
+        triangle.control = take_u8(input)? != 0;
+        triangle.linear_reload = take_u8(input)?;
+        triangle.length = take_u8(input)?;
+        triangle.linear = take_u8(input)?;
+        triangle.reload = take_u8(input)? != 0;
+        triangle.step = take_u8(input)?;
+        triangle.period = take_u16(input)?;
+        triangle.timer = take_u16(input)?;
+        self.noise.short = take_u8(input)? != 0;
+        self.noise.length = take_u8(input)?;
+        self.noise.period = take_u16(input)?;
+        self.noise.timer = take_u16(input)?;
+        self.noise.shift = take_u16(input)?;
+        self.noise.envelope.restore(input)?;
+        self.dmc.restore(input)?;
+        self.oam_dma = Some(take_u16(input)?).filter(|page| *page <= 0xFF).map(|page| page as u8);
+        self.enabled = take_u8(input)?;
+        self.five_step = take_u8(input)? != 0;
+        self.irq_inhibit = take_u8(input)? != 0;
+        self.frame_irq.set(take_u8(input)? != 0);
+        self.frame_cycle = take_u32(input)?;
+        self.clocked = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Envelope {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("loop", self.looping),
+            StateNode::value("constant", self.constant),
+            StateNode::value("volume", self.volume),
+            StateNode::value("start", self.start),
+            StateNode::value("divider", self.divider),
+            StateNode::value("decay", self.decay),
+        ]
+    }
+}
+
+impl DebugState for Pulse {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("duty", self.duty),
+            StateNode::word("period", self.period),
+            StateNode::value("length", self.length),
+            StateNode::group("envelope", self.envelope.debug_state()),
+            StateNode::byte("sweep", self.sweep),
+            StateNode::value("sweep reload", self.sweep_reload),
+            StateNode::value("sweep timer", self.sweep_timer),
+            StateNode::word("timer", self.timer),
+            StateNode::value("step", self.step),
+        ]
+    }
+}
+
+impl DebugState for Triangle {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("control", self.control),
+            StateNode::value("linear reload", self.linear_reload),
+            StateNode::value("linear", self.linear),
+            StateNode::value("reload", self.reload),
+            StateNode::word("period", self.period),
+            StateNode::value("length", self.length),
+            StateNode::word("timer", self.timer),
+            StateNode::value("step", self.step),
+        ]
+    }
+}
+
+impl DebugState for Noise {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("short", self.short),
+            StateNode::word("period", self.period),
+            StateNode::value("length", self.length),
+            StateNode::group("envelope", self.envelope.debug_state()),
+            StateNode::word("timer", self.timer),
+            StateNode::word("shift", self.shift),
+        ]
+    }
+}
+
+impl DebugState for Dmc {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("irq enable", self.irq_enable),
+            StateNode::value("loop", self.looping),
+            StateNode::word("period", self.period),
+            StateNode::value("level", self.level),
+            StateNode::word("start", self.start),
+            StateNode::word("length", self.length),
+            StateNode::word("address", self.address),
+            StateNode::word("remaining", self.remaining),
+            StateNode::value("buffer", self.buffer.map_or("-".to_string(), |value| format!("${:02X}", value))),
+            StateNode::byte("shift", self.shift),
+            StateNode::value("bits", self.bits),
+            StateNode::value("silence", self.silence),
+            StateNode::word("timer", self.timer),
+            StateNode::value("irq", self.irq),
+            StateNode::value("requested at", self.requested_at.map_or("-".to_string(), |cycle| cycle.to_string())),
+        ]
+    }
+}
+
+impl DebugState for Apu {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::group("pulse 1", self.pulses[0].debug_state()),
+            StateNode::group("pulse 2", self.pulses[1].debug_state()),
+            StateNode::group("triangle", self.triangle.debug_state()),
+            StateNode::group("noise", self.noise.debug_state()),
+            StateNode::group("dmc", self.dmc.debug_state()),
+            StateNode::value("sprite dma", self.oam_dma.map_or("-".to_string(), |page| format!("${:02X}00", page))),
+            StateNode::byte("enabled", self.enabled),
+            StateNode::value("five step", self.five_step),
+            StateNode::value("irq inhibit", self.irq_inhibit),
+            StateNode::value("frame irq", self.frame_irq.get()),
+            StateNode::value("frame cycle", self.frame_cycle),
+            StateNode::value("clocked to", self.clocked),
+        ]
+    }
+}
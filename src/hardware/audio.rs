@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use super::interfaces::Clocked;
+use super::resampler::Resampler;
+
+pub const CPU_CLOCK   : f64 = 1_789_773.0;
+pub const SAMPLE_RATE : u32 = 44_100;
+// the sources are sampled every this many cycles, about 224 kHz
+const POINT_CYCLES    : u64 = 8;
+// the most the output rate is bent to keep a frontend's queue half full, inaudible as pitch
+pub const MAX_RATE_DEVIATION : f64 = 0.005;
+
+// anything producing sound, caught up to the current cycle before its level is sampled
+pub trait AudioSource: Clocked {
+    fn level(&self) -> f32; // 0.0 to 1.0
+
+    // each channel on its own, 0.0 to 1.0, for recording stems
+    fn channels(&self) -> Vec<(&'static str, f32)> {
+        Vec::new()
+    }
+}
+
+// the summed sources at the output rate : they are sampled every few cycles, and the
+// resampler turns their changes into band limited steps. the output lags by resampler::DELAY
+// samples, under a millisecond
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    pub sample_rate : u32,
+    capacity        : usize, // samples kept for whoever drains them (a second unless set), the latency at most
+    next_point      : u64,   // cpu cycle the sources are sampled at next
+    time            : f64,   // of the next point, in output samples since the start
+    rate            : f64,   // output samples per cycle, the sample rate as steered
+    resampler       : Resampler,
+    samples         : VecDeque<f32>,
+    stems           : Option<Vec<Stem>>, // per channel samples, when asked for
+}
+
+#[derive(Debug, Clone)]
+struct Stem {
+    name      : &'static str,
+    resampler : Resampler,
+    samples   : VecDeque<f32>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            capacity   : sample_rate as usize,
+            next_point : 0,
+            time       : 0.0,
+            rate       : sample_rate as f64 / CPU_CLOCK,
+            resampler  : Resampler::new(),
+            samples    : VecDeque::new(),
+            stems      : None,
+        }
+    }
+
+    // how much output is kept when nobody drains it, the oldest samples go first
+    pub fn set_latency(&mut self, milliseconds: u32) {
+        self.capacity = (self.sample_rate as usize * milliseconds as usize / 1000).max(1);
+    }
+
+    // a machine restored to an earlier point restarts the sample clock there
+    pub fn is_due(&mut self, cycles: u64) -> bool {
+        if self.next_point > cycles + CPU_CLOCK as u64 {
+            self.next_point = cycles;
+            self.time = 0.0;
+            self.resampler.restart();
+            for stem in self.stems.iter_mut().flatten() {
+                stem.resampler.restart();
+            }
+        }
+        cycles >= self.next_point
+    }
+
+    pub fn push(&mut self, level: f32) {
+        let (samples, capacity) = (&mut self.samples, self.capacity);
+        self.resampler.input(self.time, level, &mut |sample| keep(samples, capacity, sample));
+        self.next_point += POINT_CYCLES;
+        self.time += POINT_CYCLES as f64 * self.rate;
+    }
+
+    // dynamic rate control : a frontend playing the samples tells how many of the `size` its
+    // output queue holds are still queued, and the output rate is bent by up to
+    // MAX_RATE_DEVIATION towards keeping it half full. the emulation runs at the video's pace,
+    // the host's sound card at its own, and without this the queue slowly runs dry (crackles)
+    // or fills up (latency)
+    pub fn steer(&mut self, queued: usize, size: usize) {
+        let fill = if size == 0 { 0.5 } else { (queued as f64 / size as f64).min(1.0) };
+        let deviation = MAX_RATE_DEVIATION * (1.0 - 2.0 * fill);
+        self.rate = self.sample_rate as f64 * (1.0 + deviation) / CPU_CLOCK;
+    }
+
+    pub fn drain(&mut self) -> Vec<f32> {
+        self.samples.drain(..).collect()
+    }
+
+    pub fn split_stems(&mut self, enabled: bool) {
+        self.stems = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn has_stems(&self) -> bool {
+        self.stems.is_some()
+    }
+
+    // the channels of one sample point, before push, a channel showing up late is padded
+    // with silence
+    pub fn push_channels(&mut self, channels: &[(&'static str, f32)]) {
+        let time = self.time;
+        let (position, capacity) = (self.resampler.position(), self.capacity);
+        let stems = match self.stems.as_mut() {
+            Some(stems) => stems,
+            None => return,
+        };
+        let length = stems.iter().map(|stem| stem.samples.len()).max().unwrap_or(0);
+        for (name, level) in channels {
+            if !stems.iter().any(|stem| stem.name == *name) {
+                // written out from where the mix is
+                stems.push(Stem { name, resampler : Resampler::starting_at(position), samples : std::iter::repeat_n(0.0, length).collect() });
+            }
+            let stem = stems.iter_mut().find(|stem| stem.name == *name).unwrap();
+            let samples = &mut stem.samples;
+            stem.resampler.input(time, *level, &mut |sample| keep(samples, capacity, sample));
+        }
+    }
+
+    pub fn drain_stems(&mut self) -> Vec<(&'static str, Vec<f32>)> {
+        self.stems.iter_mut()
+            .flat_map(|stems| stems.iter_mut())
+            .map(|stem| (stem.name, stem.samples.drain(..).collect()))
+            .collect()
+    }
+}
+
+fn keep(samples: &mut VecDeque<f32>, capacity: usize, sample: f32) {
+    if samples.len() >= capacity {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::io::{Error, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// while nothing is attached to the other end, reads are retried this often
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+// where an emulated serial port's lines go on the host
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Link {
+    Pty,       // a pseudo terminal, for screen, minicom, ...
+    Tcp(u16),  // one client at a time on 127.0.0.1
+}
+
+impl Link {
+    // pty, tcp:<port>
+    pub fn parse(text: &str) -> Option<Link> {
+        match text.trim() {
+            "pty" => Some(Link::Pty),
+            text => text.strip_prefix("tcp:").and_then(|port| port.trim().parse::<u16>().ok()).map(Link::Tcp),
+        }
+    }
+}
+
+type Writer = Arc<Mutex<Option<Box<dyn Write + Send>>>>;
+
+// the host side of a link : a thread moves whatever arrives into a channel the port polls,
+// bytes sent while nothing is attached are dropped
+pub struct Bridge {
+    pub name : String, // the pty path or the address to connect to
+    received : Receiver<u8>,
+    writer   : Writer,
+}
+
+impl fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bridge({})", self.name)
+    }
+}
+
+fn forward<R: Read>(mut reader: R, sender: &Sender<u8>) -> bool {
+    let mut buffer = [0u8; 256];
+    match reader.read(&mut buffer) {
+        Ok(0) | Err(_) => false,
+        Ok(count) => buffer[..count].iter().all(|byte| sender.send(*byte).is_ok()),
+    }
+}
+
+impl Bridge {
+    pub fn open(link: Link) -> Result<Bridge, Error> {
+        let (sender, received) = mpsc::channel();
+        let writer: Writer = Arc::new(Mutex::new(None));
+        let name = match link {
+            Link::Pty => {
+                let (master, name) = pty::open()?;
+                *writer.lock().unwrap() = Some(Box::new(master.try_clone()?));
+                // the master reports an error while no terminal has the other side open
+                thread::spawn(move || loop {
+                    if !forward(&master, &sender) {
+                        thread::sleep(RETRY_INTERVAL);
+                    }
+                });
+                name
+            },
+            Link::Tcp(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", port))?;
+                let shared = writer.clone();
+                thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        match stream.try_clone() {
+                            Ok(clone) => *shared.lock().unwrap() = Some(Box::new(clone)),
+                            Err(_) => continue,
+                        }
+                        while forward(&stream, &sender) {}
+                        *shared.lock().unwrap() = None;
+                    }
+                });
+                format!("127.0.0.1:{}", port)
+            },
+        };
+        Ok(Bridge { name, received, writer })
+    }
+
+    pub fn receive(&self) -> Vec<u8> {
+        self.received.try_iter().collect()
+    }
+
+    pub fn send(&self, byte: u8) {
+        if let Some(writer) = self.writer.lock().unwrap().as_mut() {
+            let _ = writer.write_all(&[byte]);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::Error;
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::io::FromRawFd;
+
+    const O_RDWR: c_int = 2;
+    const TCSANOW: c_int = 0;
+    // comfortably larger than any platform's struct termios, only handed back to libc
+    const TERMIOS_SIZE: usize = 256;
+
+    extern "C" {
+        fn posix_openpt(flags: c_int) -> c_int;
+        fn grantpt(fd: c_int) -> c_int;
+        fn unlockpt(fd: c_int) -> c_int;
+        fn ptsname(fd: c_int) -> *mut c_char;
+        fn tcgetattr(fd: c_int, termios: *mut u8) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const u8) -> c_int;
+        fn cfmakeraw(termios: *mut u8);
+    }
+
+    // the master side, and the path a terminal program opens. the line is raw from the
+    // start, an echoing line would feed the port its own output until a terminal attaches
+    pub fn open() -> Result<(File, String), Error> {
+        unsafe {
+            let fd = posix_openpt(O_RDWR);
+            if fd < 0 {
+                return Err(Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if grantpt(fd) != 0 || unlockpt(fd) != 0 {
+                return Err(Error::last_os_error());
+            }
+            let mut termios = [0u8; TERMIOS_SIZE];
+            if tcgetattr(fd, termios.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            cfmakeraw(termios.as_mut_ptr());
+            if tcsetattr(fd, TCSANOW, termios.as_ptr()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            let name = ptsname(fd);
+            if name.is_null() {
+                return Err(Error::last_os_error());
+            }
+            Ok((master, CStr::from_ptr(name).to_string_lossy().to_string()))
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod pty {
+    use std::fs::File;
+    use std::io::{Error, ErrorKind};
+
+    pub fn open() -> Result<(File, String), Error> {
+        Err(Error::new(ErrorKind::Unsupported, "pseudo terminals need a unix host, link the port over tcp instead"))
+    }
+}
@@ -1,58 +1,254 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, io::Error, ops::RangeInclusive, rc::Rc};
 
-use super::{interfaces::{DeviceOps}, device::Device};
+use super::{interfaces::{DeviceOps}, device::Device, dirty::DirtyPages, sanitizer::Sanitizer, watches::{Access, Watches}, interrupts::{InterruptLine, Trigger}, snapshot::{Snapshot, mismatch, put_u16, take_u16}};
+
+#[derive(Clone)]
+pub struct Mapping<'a> {
+    pub device   : Rc<RefCell<Device<'a>>>,
+    pub regions  : Vec<RangeInclusive<u16>>, // empty : defer to the device's own within_range
+    pub priority : i32,
+}
+
+impl<'a> Mapping<'a> {
+    pub fn claims(&self, addr: u16) -> bool {
+        if self.regions.is_empty() {
+            self.device.borrow().within_range(addr)
+        } else {
+            self.regions.iter().any(|region| region.contains(&addr))
+        }
+    }
+}
+
+// above ram, level with the other memory mapped devices
+pub const CARTRIDGE_PRIORITY: i32 = 1;
 
 pub struct Bus<'a> {
-    pub devices : Vec<Rc<RefCell<Device<'a>>>>
+    pub devices : Vec<Mapping<'a>>,
+    pub irq     : InterruptLine,
+    pub nmi     : InterruptLine,
+    pub clock   : u64, // the cpu cycle of the access in progress, set by the cpu
+    pub sanitizer : Option<Sanitizer>, // checks the accesses when the debugger asks for it
+    pub watches   : Option<Watches>,   // the accesses the debugger's data breakpoints look at
+    pub recorded_writes : Option<Vec<(u16, u8, u8)>>, // address, value before and written, for the step preview
 }
 
 impl<'a> Bus<'a> {
     pub fn new() -> Bus<'a> {
         Bus {
-            devices: Vec::new()
+            devices: Vec::new(),
+            irq: InterruptLine::new(Trigger::Level),
+            nmi: InterruptLine::new(Trigger::Edge),
+            clock: 0,
+            sanitizer: None,
+            watches: None,
+            recorded_writes: None,
         }
     }
 
+    // each mapped device drives the irq line as its own source, clocked ones as of now
+    pub fn poll_interrupts(&mut self) {
+        for (source, mapping) in self.devices.iter().enumerate() {
+            let mut device = mapping.device.borrow_mut();
+            device.tick(self.clock);
+            let asserted = device.irq();
+            if asserted != self.irq.holds(source) {
+                crate::log_event!(Irq, Trace, "{} {} irq at cycle {}", device.name(), if asserted { "asserts" } else { "releases" }, self.clock);
+            }
+            self.irq.set(source, asserted);
+        }
+    }
+
+    // the ppu drives nmi, sampled as of now like irq
+    pub fn poll_nmi(&mut self) {
+        for (source, mapping) in self.devices.iter().enumerate() {
+            let mut device = mapping.device.borrow_mut();
+            if let Device::Ppu(_) = &*device {
+                device.tick(self.clock);
+                self.nmi.set(source, device.nmi());
+            }
+        }
+    }
+
+    // whether some device holds irq right now, whatever the cpu sampled last
+    pub fn irq_held(&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.borrow().irq())
+    }
+
     pub fn load_program(&mut self, _: Vec<u8>) {
-        
+
     }
 
     pub fn add_device(&mut self, device: Rc<RefCell<Device<'a>>>) -> usize {
-        self.devices.push(device);
+        self.map_device(device, Vec::new(), 0)
+    }
+
+    // registers a device on one or more disjoint address ranges, the highest
+    // priority mapping claiming an address wins, so overlays can shadow
+    // whatever sits below them without touching the rest of the device list
+    pub fn map_device(&mut self, device: Rc<RefCell<Device<'a>>>, regions: Vec<RangeInclusive<u16>>, priority: i32) -> usize {
+        crate::log_event!(Bus, Debug, "{} mapped at {}, priority {}", device.borrow().name(), describe_regions(&regions), priority);
+        self.devices.push(Mapping {
+            device,
+            regions,
+            priority,
+        });
         self.devices.len()
     }
 
+    pub fn map_region(&mut self, at: usize, region: RangeInclusive<u16>) {
+        if let Some(mapping) = self.devices.get_mut(at) {
+            mapping.regions.push(region);
+        }
+    }
+
     pub fn remove_device(&mut self, at: usize) -> () {
         if at >= self.devices.len() {
             return;
         }
-        self.devices.remove(at);
+        let mapping = self.devices.remove(at);
+        crate::log_event!(Bus, Debug, "{} removed", mapping.device.borrow().name());
+    }
+
+    // a cartridge is a first class part of the bus : inserting one replaces
+    // the previous cartridge, and ejecting hands it back to the caller
+    pub fn insert_cartridge(&mut self, cartridge: Device<'a>) -> Option<Device<'a>> {
+        let ejected = self.eject_cartridge();
+        let regions = cartridge.cartridge_regions();
+        self.map_device(Rc::new(RefCell::new(cartridge)), regions, CARTRIDGE_PRIORITY);
+        ejected
+    }
+
+    pub fn eject_cartridge(&mut self) -> Option<Device<'a>> {
+        let at = self.devices.iter().position(|mapping| mapping.device.borrow().is_cartridge())?;
+        let mapping = self.devices.remove(at);
+        let device = mapping.device.borrow().clone();
+        crate::log_event!(Bus, Info, "{} ejected", device.name());
+        Some(device)
+    }
+
+    pub fn has_cartridge(&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.borrow().is_cartridge())
+    }
+
+    // every mapping claiming the address at the highest priority present,
+    // in registration order
+    pub fn claimants(&self, addr: u16) -> impl Iterator<Item = &Mapping<'a>> {
+        let top_priority = self.devices.iter()
+            .filter(|mapping| mapping.claims(addr))
+            .map(|mapping| mapping.priority)
+            .max();
+        self.devices.iter()
+            .filter(move |mapping| Some(mapping.priority) == top_priority && mapping.claims(addr))
+    }
+
+    // how many writes each mapping's device has seen, 0 for the ones not tracking them
+    pub fn write_counts(&self) -> Vec<u64> {
+        self.devices.iter()
+            .map(|mapping| mapping.device.borrow().write_tracker().map_or(0, |tracker| tracker.writes))
+            .collect()
+    }
+
+    // the pages written since the bus had these write counts
+    pub fn dirty_since(&self, counts: &[u64]) -> DirtyPages {
+        let mut dirty = DirtyPages::default();
+        for (at, mapping) in self.devices.iter().enumerate() {
+            if let Some(tracker) = mapping.device.borrow().write_tracker() {
+                dirty.union(&tracker.dirty_since(counts.get(at).copied().unwrap_or(0)));
+            }
+        }
+        dirty
     }
 
     pub fn clone_state(&self) -> Rc<RefCell<Bus<'a>>> {
         let bus = Rc::new(RefCell::new(Bus::new()));
 
         for plugin in self.devices.iter() {
-            let device = plugin.borrow_mut().to_owned();
+            let device = plugin.device.borrow_mut().to_owned();
             let device_clone = Rc::new(RefCell::new(device.clone()));
-            bus.borrow_mut().add_device(device_clone);
+            bus.borrow_mut().map_device(device_clone, plugin.regions.clone(), plugin.priority);
         }
+        bus.borrow_mut().irq = self.irq.clone();
+        bus.borrow_mut().nmi = self.nmi.clone();
+        bus.borrow_mut().clock = self.clock;
+        bus.borrow_mut().sanitizer = self.sanitizer.clone();
+        bus.borrow_mut().watches = self.watches.clone();
 
         bus
     }
 }
 
+fn describe_regions(regions: &[RangeInclusive<u16>]) -> String {
+    if regions.is_empty() {
+        return "its own range".to_string();
+    }
+    regions.iter().map(|region| format!("${:04X}-${:04X}", region.start(), region.end())).collect::<Vec<String>>().join(", ")
+}
+
+// boards described with holes in their memory map read 0 there, clocked devices are
+// caught up before their registers are touched, peeks see them as they are
 impl DeviceOps for Bus<'_> {
     fn read(&self, addr: u16) -> u8 {
-        self.devices.iter()
-            .filter(|device| device.borrow().within_range(addr))
-            .map(|device| device.borrow().read(addr))
-            .nth(0).unwrap()
+        let value = self.claimants(addr)
+            .map(|mapping| {
+                let mut device = mapping.device.borrow_mut();
+                device.tick(self.clock);
+                if let Some(sanitizer) = self.sanitizer.as_ref() {
+                    sanitizer.read(addr, &device);
+                }
+                device.read(addr)
+            })
+            .nth(0).unwrap_or(0);
+        if let Some(watches) = self.watches.as_ref() {
+            watches.access(Access::Read, addr, value);
+        }
+        value
     }
 
     fn write(&mut self, addr: u16, value: u8) -> () {
-        self.devices.iter_mut()
-            .filter(|device| device.borrow().within_range(addr))
-            .for_each(|device| device.borrow_mut().write(addr, value));
+        let clock = self.clock;
+        let before = self.recorded_writes.as_ref().map(|_| self.peek(addr));
+        if let (Some(writes), Some(before)) = (self.recorded_writes.as_mut(), before) {
+            writes.push((addr, before, value));
+        }
+        self.claimants(addr)
+            .for_each(|mapping| {
+                let mut device = mapping.device.borrow_mut();
+                device.tick(clock);
+                if let Some(sanitizer) = self.sanitizer.as_ref() {
+                    sanitizer.write(addr, &device);
+                }
+                device.write(addr, value);
+            });
+        if let Some(watches) = self.watches.as_ref() {
+            watches.access(Access::Write, addr, value);
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.claimants(addr)
+            .map(|mapping| mapping.device.borrow().peek(addr))
+            .nth(0).unwrap_or(0)
+    }
+}
+
+impl Snapshot for Bus<'_> {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u16(out, self.devices.len() as u16);
+        for mapping in self.devices.iter() {
+            mapping.device.borrow().save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        if take_u16(input)? as usize != self.devices.len() {
+            return Err(mismatch("device count"));
+        }
+        for mapping in self.devices.iter() {
+            mapping.device.borrow_mut().restore(input)?;
+        }
+        if let Some(sanitizer) = self.sanitizer.as_ref() {
+            sanitizer.written_all();
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,64 @@
+// frames deeper than this are dropped from the bottom, programs that never return
+// (a jsr used as a jump) would grow the stack forever otherwise
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+    Call,
+    Brk,
+    Irq,
+    Nmi,
+}
+
+impl FrameKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrameKind::Call => "jsr",
+            FrameKind::Brk => "brk",
+            FrameKind::Irq => "irq",
+            FrameKind::Nmi => "nmi",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub kind           : FrameKind,
+    pub entry          : u16, // where the routine or handler starts
+    pub caller         : u16, // the jsr / brk, or the instruction an interrupt came before
+    pub return_address : u16,
+    pub sp             : u8,  // the stack pointer before the frame pushed anything, back there on return
+}
+
+// the logical calls the cpu is in, innermost last : jsr, brk and interrupts open a frame,
+// rts and rti close every frame whose pushed bytes they pulled, so routines leaving
+// through pla / jmp or tail calls do not leave frames behind for long
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    pub frames : Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn enter(&mut self, frame: Frame) {
+        self.unwind(frame.sp);
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+
+    // the stack pointer is back up at sp, the frames that started at or below it are gone
+    pub fn unwind(&mut self, sp: u8) {
+        while self.frames.last().is_some_and(|frame| frame.sp <= sp) {
+            self.frames.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::dirty::WriteTracker;
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, mismatch, put_u16, put_u32, take_bytes, take_u16, take_u32};
+use super::inspect::{DebugState, StateNode};
+
+// the loaded program as a removable device : each bank is a contiguous run of bytes at a
+// fixed address, mapped above ram so ejecting it uncovers whatever ram holds underneath
+#[derive(Debug, Clone, Default)]
+pub struct Cartridge {
+    pub banks   : Vec<(u16, Vec<u8>)>,
+    pub tracker : WriteTracker,
+}
+
+impl Cartridge {
+    pub fn new(banks: Vec<(u16, Vec<u8>)>) -> Cartridge {
+        let banks = banks.into_iter()
+            .filter(|(_, bytes)| !bytes.is_empty())
+            .map(|(address, mut bytes)| {
+                bytes.truncate(0x10000 - address as usize);
+                (address, bytes)
+            })
+            .collect();
+        Cartridge { banks, tracker: WriteTracker::new() }
+    }
+
+    pub fn regions(&self) -> Vec<RangeInclusive<u16>> {
+        self.banks.iter()
+            .map(|(address, bytes)| *address..=address + (bytes.len() - 1) as u16)
+            .collect()
+    }
+
+    fn locate(&self, addr: u16) -> Option<(usize, usize)> {
+        self.banks.iter()
+            .position(|(address, bytes)| addr >= *address && ((addr - address) as usize) < bytes.len())
+            .map(|bank| (bank, (addr - self.banks[bank].0) as usize))
+    }
+}
+
+impl DeviceOps for Cartridge {
+    fn within_range(&self, addr: u16) -> bool {
+        self.locate(addr).is_some()
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.locate(addr).map(|(bank, offset)| self.banks[bank].1[offset]).unwrap_or(0)
+    }
+
+    // writable, programs loaded as plain images were free to patch themselves before
+    fn write(&mut self, addr: u16, value: u8) {
+        if let Some((bank, offset)) = self.locate(addr) {
+            self.banks[bank].1[offset] = value;
+            self.tracker.record(addr);
+        }
+    }
+
+    fn write_tracker(&self) -> Option<&WriteTracker> {
+        Some(&self.tracker)
+    }
+}
+
+impl Snapshot for Cartridge {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u16(out, self.banks.len() as u16);
+        for (address, bytes) in self.banks.iter() {
+            put_u16(out, *address);
+            put_u32(out, bytes.len() as u32);
+            out.extend_from_slice(bytes);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        if take_u16(input)? as usize != self.banks.len() {
+            return Err(mismatch("cartridge layout"));
+        }
+        for (address, bytes) in self.banks.iter_mut() {
+            if take_u16(input)? != *address || take_u32(input)? as usize != bytes.len() {
+                return Err(mismatch("cartridge layout"));
+            }
+            let size = bytes.len();
+            bytes.copy_from_slice(take_bytes(input, size)?);
+        }
+        self.tracker.record_all();
+        Ok(())
+    }
+}
+
+impl DebugState for Cartridge {
+    fn debug_state(&self) -> Vec<StateNode> {
+        let mut state = self.banks.iter()
+            .map(|(address, bytes)| StateNode::bytes(&format!("${:04X}", address), bytes))
+            .collect::<Vec<StateNode>>();
+        state.push(StateNode::value("writes", self.tracker.writes));
+        state
+    }
+}
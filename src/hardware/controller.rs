@@ -0,0 +1,231 @@
+use std::cell::Cell;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u8, take_u8};
+use super::inspect::{DebugState, StateNode};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Button {
+    A      = 1 << 0,
+    B      = 1 << 1,
+    Select = 1 << 2,
+    Start  = 1 << 3,
+    Up     = 1 << 4,
+    Down   = 1 << 5,
+    Left   = 1 << 6,
+    Right  = 1 << 7,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::A, Button::B, Button::Select, Button::Start,
+        Button::Up, Button::Down, Button::Left, Button::Right,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::A      => "a",
+            Button::B      => "b",
+            Button::Select => "select",
+            Button::Start  => "start",
+            Button::Up     => "up",
+            Button::Down   => "down",
+            Button::Left   => "left",
+            Button::Right  => "right",
+        }
+    }
+
+    pub fn symbol(&self) -> char {
+        match self {
+            Button::A      => 'A',
+            Button::B      => 'B',
+            Button::Select => 's',
+            Button::Start  => 'S',
+            Button::Up     => 'U',
+            Button::Down   => 'D',
+            Button::Left   => 'L',
+            Button::Right  => 'R',
+        }
+    }
+}
+
+// "AB.S...R" style summary of a button mask
+pub fn describe(buttons: u8) -> String {
+    Button::ALL.iter()
+        .map(|button| if buttons & (*button as u8) != 0 { button.symbol() } else { '.' })
+        .collect()
+}
+
+// the same with the buttons only held as turbo ones in lower case, "aB......"
+pub fn describe_turbo(buttons: u8, turbo: u8) -> String {
+    Button::ALL.iter()
+        .map(|button| match (buttons & (*button as u8) != 0, turbo & (*button as u8) != 0) {
+            (true, _) => button.symbol(),
+            (false, true) => button.symbol().to_ascii_lowercase(),
+            (false, false) => '.',
+        })
+        .collect()
+}
+
+// auto fire : turbo buttons held read pressed and released in turn, `rate` presses a second
+// at 60 frames a second, from 1 to 30, rounded to whole frames. the frontends apply it to
+// the buttons they latch for each frame, so netplay peers get the pressed or released state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Turbo {
+    pub rate : u8,
+}
+
+impl Turbo {
+    pub const MAX_RATE: u8 = 30;
+
+    pub fn new(rate: u8) -> Turbo {
+        Turbo { rate : rate.clamp(1, Turbo::MAX_RATE) }
+    }
+
+    // whether turbo buttons read pressed on the frame, from the first frame they are held
+    pub fn pressed(&self, frame: u64) -> bool {
+        let half_period = (Turbo::MAX_RATE / self.rate).max(1) as u64;
+        (frame / half_period).is_multiple_of(2)
+    }
+
+    // the buttons a pad reads on the frame, the held ones and its turbo ones while pressed
+    pub fn apply(&self, held: u8, turbo: u8, frame: u64) -> u8 {
+        if self.pressed(frame) { held | turbo } else { held }
+    }
+}
+
+impl Default for Turbo {
+    fn default() -> Self {
+        Turbo::new(15)
+    }
+}
+
+// what is plugged into the second port, the first one holds a controller
+// unless a four score takes both
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Peripheral {
+    Controller,
+    Zapper,
+    FourScore,
+}
+
+impl Peripheral {
+    pub fn pads(&self) -> usize {
+        match self {
+            Peripheral::FourScore => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl FromStr for Peripheral {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Peripheral, Error> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "controller" => Ok(Peripheral::Controller),
+            "zapper"     => Ok(Peripheral::Zapper),
+            "four_score" | "fourscore" => Ok(Peripheral::FourScore),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Unknown peripheral: {}", text))),
+        }
+    }
+}
+
+// standard controller : writing 1 then 0 to the strobe bit of $4016 latches the buttons
+// of both ports, each read of the port's register then shifts one button out, A first,
+// ones once all eight are out
+#[derive(Debug, Clone, Default)]
+pub struct Controller {
+    pub port    : u16, // 0 reads at $4016, 1 at $4017
+    pub buttons : u8,
+    pub strobe  : bool,
+    shift       : Cell<u8>,
+    reads       : Cell<u8>,
+}
+
+impl Controller {
+    pub fn new() -> Controller {
+        Controller::on_port(0)
+    }
+
+    pub fn on_port(port: u16) -> Controller {
+        Controller {
+            port,
+            buttons : 0,
+            strobe  : false,
+            shift   : Cell::new(0),
+            reads   : Cell::new(0),
+        }
+    }
+
+    fn current_bit(&self) -> u8 {
+        if self.strobe {
+            self.buttons & 1
+        } else if self.reads.get() >= 8 {
+            1
+        } else {
+            self.shift.get() & 1
+        }
+    }
+}
+
+impl DeviceOps for Controller {
+    fn within_range(&self, addr: u16) -> bool {
+        addr == 0x4016 || addr == 0x4016 + self.port
+    }
+
+    fn read(&self, _: u16) -> u8 {
+        let bit = self.current_bit();
+        if !self.strobe && self.reads.get() < 8 {
+            self.shift.set(self.shift.get() >> 1);
+            self.reads.set(self.reads.get() + 1);
+        }
+        0x40 | bit
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr != 0x4016 {
+            return;
+        }
+        self.strobe = value & 1 != 0;
+        if self.strobe {
+            self.shift.set(self.buttons);
+            self.reads.set(0);
+        }
+    }
+
+    fn peek(&self, _: u16) -> u8 {
+        0x40 | self.current_bit()
+    }
+}
+
+impl Snapshot for Controller {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.buttons);
+        put_u8(out, self.strobe as u8);
+        put_u8(out, self.shift.get());
+        put_u8(out, self.reads.get());
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.buttons = take_u8(input)?;
+        self.strobe = take_u8(input)? != 0;
+        self.shift.set(take_u8(input)?);
+        self.reads.set(take_u8(input)?);
+        Ok(())
+    }
+}
+
+impl DebugState for Controller {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("port", format!("${:04X}", 0x4016 + self.port)),
+            StateNode::byte("buttons", self.buttons),
+            StateNode::value("strobe", self.strobe),
+            StateNode::byte("shift", self.shift.get()),
+            StateNode::value("reads", self.reads.get()),
+        ]
+    }
+}
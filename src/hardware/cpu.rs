@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs::File, io::{BufReader, BufRead}, cell::RefCell, rc::Rc};
+use std::{io::Error, cell::{Cell, RefCell}, rc::Rc};
 
 use super::{
     registers::{Registers, Flag}, 
@@ -8,17 +8,35 @@ use super::{
         AddressingData,
     }, 
     interfaces::{DeviceOps},
-    bus::Bus, opcodes::Opcode
+    bus::Bus, opcode_table::OPCODES,
+    call_stack::{CallStack, Frame, FrameKind},
+    dma,
+    timing::{self, RasterPosition},
+    snapshot::{Snapshot, put_u8, put_u16, put_u32, put_u64, take_u8, take_u16, take_u32, take_u64},
 };
+use super::inspect::{DebugState, StateNode};
+
+// what the last instruction (or interrupt sequence) did, for the debugger to stop on
+pub const EVENT_NMI         : u8 = 1 << 0;
+pub const EVENT_IRQ         : u8 = 1 << 1;
+pub const EVENT_BRK         : u8 = 1 << 2;
+pub const EVENT_RTI         : u8 = 1 << 3;
+pub const EVENT_VECTOR_READ : u8 = 1 << 4; // $FFFA - $FFFF
+pub const EVENT_STACK_WRAP  : u8 = 1 << 5; // a push past $0100 or a pull past $01FF
 
 #[derive(Clone)]
 pub struct Cpu<'a> {
     pub registers : Registers,
     pub bus       : Option<Rc<RefCell<Bus<'a>>>>,
     pub cycle     : i32,
+    pub total_cycles : u64,
     pub opcode    : u8,
     pub address_mode : AddressingData,
-    pub instruction_set : HashMap<u8, Instructions>
+    pub instruction_set : [Instructions; 256],
+    pub calls     : CallStack, // for the debugger, not part of savestates
+    pub events    : Cell<u8>,  // same, cleared when the next instruction starts
+    pub alignment : u8,        // the ppu's dot at power on (timing::ALIGNMENTS), a machine keeps its own through savestates
+    pub port_read : Cell<Option<(u16, u64)>>, // a read of $4016 / $4017 by the last instruction, for the dmc fetches
 } 
 
 impl<'a> Cpu<'a> {
@@ -27,16 +45,31 @@ impl<'a> Cpu<'a> {
             registers : Registers::new(),
             bus       : None,
             cycle     : 0,
+            total_cycles : 0,
             opcode    : 0,
             address_mode : AddressingData::new(),
-            instruction_set : Cpu::setup_instruction_map(),
+            instruction_set : OPCODES,
+            calls     : CallStack::default(),
+            events    : Cell::new(0),
+            alignment : 0,
+            port_read : Cell::new(None),
         };
         new_cpu
     }
 
+    // where the beam is, the ppu running alongside from the alignment it powered up in
+    pub fn dots(&self) -> u64 {
+        timing::dots(self.total_cycles) + self.alignment as u64
+    }
+
+    pub fn raster(&self) -> RasterPosition {
+        RasterPosition::from_dots(self.dots())
+    }
+
     pub fn reset(&mut self) -> () {
         self.registers = Registers::new();
         self.address_mode = AddressingData::new();
+        self.calls.clear();
         self.cycle = 8;
         self.opcode = 0;
         self.registers.sp = 0xFD;
@@ -47,35 +80,100 @@ impl<'a> Cpu<'a> {
         self.registers.pc = (hi << 8) + lo;
     }
 
+    // the reset line pulled while running : a, x and y survive, the stack pointer moves down
+    // by three as if an interrupt was pushed without the writes, and interrupts get disabled
+    pub fn soft_reset(&mut self) {
+        self.address_mode = AddressingData::new();
+        self.calls.clear();
+        self.cycle = 7;
+        self.opcode = 0;
+        self.registers.sp = self.registers.sp.wrapping_sub(3);
+        self.registers.set_flag(Flag::I, true);
+        self.address_mode.address_abs = 0xFFFC;
+        let hi = self.read(self.address_mode.address_abs + 1) as u16;
+        let lo = self.read(self.address_mode.address_abs) as u16;
+        self.registers.pc = (hi << 8) + lo;
+    }
+
+    // the interrupt sequence, in place of the next instruction : pc and status (B clear) are
+    // pushed, interrupts get disabled and the vector is loaded, 7 cycles
     pub fn interrupt(&mut self, is_non_maskable: bool) -> () {
-        if !self.registers.get_flag(Flag::I) || is_non_maskable {
-            self.write(0x0100 + self.registers.sp as u16 + 0 , (self.registers.pc >> 8) as u8);
-            self.write(0x0100 + self.registers.sp as u16 - 1 , self.registers.pc  as u8);
-            self.registers.sp -= 2;
-            
-            self.registers.set_flag(Flag::B, false);
-            self.registers.set_flag(Flag::U, true);
-            self.registers.set_flag(Flag::I, true);
-            
-            self.write(0x0100 + self.registers.sp as u16 , self.registers.status  as u8);
-            self.registers.sp -= 1;
+        let (caller, sp) = (self.registers.pc, self.registers.sp);
+        self.push((self.registers.pc >> 8) as u8);
+        self.push(self.registers.pc as u8);
+        self.registers.set_flag(Flag::B, false);
+        self.registers.set_flag(Flag::U, true);
+        self.push(self.registers.flags);
+        self.registers.set_flag(Flag::I, true);
 
-            self.address_mode.address_abs = if is_non_maskable { 0xFFFA } else { 0xFFFE };
-            let lo = self.read(self.address_mode.address_abs + 0) as u16;
-            let hi = self.read(self.address_mode.address_abs + 1) as u16;
-            self.registers.pc = (hi << 8) + lo;
+        self.address_mode.address_abs = if is_non_maskable { 0xFFFA } else { 0xFFFE };
+        let lo = self.read(self.address_mode.address_abs) as u16;
+        let hi = self.read(self.address_mode.address_abs + 1) as u16;
+        self.registers.pc = (hi << 8) + lo;
+        self.cycle = 7;
+
+        crate::log_event!(Irq, Debug, "{} taken at ${:04X}, handler ${:04X}, cycle {}", if is_non_maskable { "nmi" } else { "irq" }, caller, self.registers.pc, self.total_cycles);
+        self.signal(if is_non_maskable { EVENT_NMI } else { EVENT_IRQ });
+        let kind = if is_non_maskable { FrameKind::Nmi } else { FrameKind::Irq };
+        self.calls.enter(Frame { kind, entry: self.registers.pc, caller, return_address: caller, sp });
+    }
 
-            self.cycle = if is_non_maskable { 8 } else { 7 } ;
+    pub fn signal(&self, event: u8) {
+        self.events.set(self.events.get() | event);
+    }
+
+    pub fn push(&mut self, value: u8) {
+        if self.registers.sp == 0x00 {
+            self.signal(EVENT_STACK_WRAP);
         }
+        self.write(0x0100 + self.registers.sp as u16, value);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
     }
 
-    pub fn fetch(&mut self) -> u8 {
-        let opcode_metadata = self.instruction_set.get(&self.opcode);
-        if let Some(metadata) = opcode_metadata {
-            if metadata.address_mode != AddressMode::Imp {
-                self.registers.fetched = self.read(self.address_mode.address_abs);
+    pub fn pull(&mut self) -> u8 {
+        if self.registers.sp == 0xFF {
+            self.signal(EVENT_STACK_WRAP);
+        }
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        self.read(0x0100 + self.registers.sp as u16)
+    }
+
+    // the lines are sampled between instructions : a latched nmi wins, irq only gets
+    // through while the I flag is clear, Some(true) for nmi
+    fn sample_interrupts(&mut self) -> Option<bool> {
+        let bus = self.bus.clone()?;
+        let mut bus = bus.borrow_mut();
+        bus.clock = self.total_cycles;
+        bus.poll_nmi();
+        if bus.nmi.take() {
+            return Some(true);
+        }
+        if !self.registers.get_flag(Flag::I) {
+            bus.poll_interrupts();
+            if bus.irq.take() {
+                return Some(false);
             }
         }
+        None
+    }
+
+    // the dma asked for during the last instruction, the cycles it halts the cpu for
+    fn service_dma(&mut self) -> u32 {
+        let port_read = self.port_read.take();
+        match self.bus.clone() {
+            Some(bus) => {
+                let mut bus = bus.borrow_mut();
+                bus.clock = self.total_cycles;
+                dma::service(&mut bus, port_read)
+            },
+            None => 0,
+        }
+    }
+
+    pub fn fetch(&mut self) -> u8 {
+        if self.instruction_set[self.opcode as usize].address_mode != AddressMode::Imp {
+            self.registers.fetched = self.read(self.address_mode.address_abs);
+        }
         self.registers.fetched
     }
     
@@ -83,45 +181,27 @@ impl<'a> Cpu<'a> {
         self.bus = Some(bus);
     }
 
-    pub fn setup_instruction_map() -> HashMap<u8, Instructions> {
-        let metadata_file: File = File::options()
-            .read(true)
-            .open("instructions.txt").unwrap();
-    
-        let reader = BufReader::new(metadata_file);
-    
-        let mut instructions_set = HashMap::new();
-        reader.split(b'\n').for_each(|line| {
-            let line = line.unwrap();
-            let tokens = line.split(|&c| c == b',')
-                .map(std::str::from_utf8)
-                .map(|s| s.unwrap().trim())
-                .collect::<Vec<&str>>();
-            let instruction = Instructions::new(
-                Opcode::from_str(tokens[1]),
-                tokens[0].parse::<u8>().unwrap(),
-                tokens[3].parse::<u8>().unwrap(),
-                AddressMode::from_str(tokens[2])
-            );
-            instructions_set.insert(instruction.opcode, instruction);
-        });
-
-        // foreach index in 0..255 not in instruction_set add a default instruction
-        for i in 0..255 {
-            if !instructions_set.contains_key(&i) {
-                instructions_set.insert(i, Instructions::new(Opcode::NOP, i, 1, AddressMode::Imp));
-            }
-        }
-
-        instructions_set
-    }
-
+    // indexed by opcode byte, undocumented opcodes decode as single byte NOPs
     pub fn tick(&mut self) -> () {
         if self.cycle == 0 {
+            self.events.set(0);
+            // like an interrupt sequence, dma stands in for the next instruction
+            let halted = self.service_dma();
+            if halted > 0 {
+                self.cycle = halted as i32 - 1;
+                self.total_cycles += 1;
+                return;
+            }
+            if let Some(is_non_maskable) = self.sample_interrupts() {
+                self.interrupt(is_non_maskable);
+                self.cycle -= 1;
+                self.total_cycles += 1;
+                return;
+            }
             self.opcode = self.read(self.registers.pc as u16);
-            self.registers.pc += 1;
+            self.registers.pc = self.registers.pc.wrapping_add(1);
 
-            let instruction_data = self.instruction_set.get(&self.opcode).unwrap().to_owned();
+            let instruction_data = self.instruction_set[self.opcode as usize];
 
             self.cycle = instruction_data.cycles as i32;
             
@@ -131,17 +211,86 @@ impl<'a> Cpu<'a> {
             self.cycle += (additional_cycles1 && additional_cycles2) as i32;
         }
         self.cycle -= 1;
+        self.total_cycles += 1;
+    }
+
+    // finishes the cycles left of the current instruction then executes the next one
+    pub fn step(&mut self) {
+        while self.cycle > 0 {
+            self.tick();
+        }
+        self.tick();
     }
 }
 
+// accesses carry the cycle they happen on, for the devices catching up on their own clock
 impl DeviceOps for Cpu<'_> {
     fn read(&self, addr : u16 ) -> u8 {
-        self.bus.as_ref().unwrap().borrow_mut()
-            .read(addr)
+        if addr >= 0xFFFA {
+            self.signal(EVENT_VECTOR_READ);
+        }
+        if addr == 0x4016 || addr == 0x4017 {
+            self.port_read.set(Some((addr, self.total_cycles)));
+        }
+        let mut bus = self.bus.as_ref().unwrap().borrow_mut();
+        bus.clock = self.total_cycles;
+        bus.read(addr)
     }
     
     fn write(&mut self, addr : u16, data: u8) -> () {
-        self.bus.as_ref().unwrap().borrow_mut()
-            .write(addr, data)
+        let mut bus = self.bus.as_ref().unwrap().borrow_mut();
+        bus.clock = self.total_cycles;
+        bus.write(addr, data)
+    }
+
+    fn peek(&self, addr : u16) -> u8 {
+        self.bus.as_ref().unwrap().borrow()
+            .peek(addr)
+    }
+}
+
+impl Snapshot for Cpu<'_> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.registers.save(out);
+        put_u32(out, self.cycle as u32);
+        put_u64(out, self.total_cycles);
+        put_u8(out, self.opcode);
+        put_u16(out, self.address_mode.address_abs);
+        put_u16(out, self.address_mode.address_rel);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.registers.restore(input)?;
+        self.calls.clear();
+        self.cycle = take_u32(input)? as i32;
+        self.total_cycles = take_u64(input)?;
+        self.opcode = take_u8(input)?;
+        self.address_mode.address_abs = take_u16(input)?;
+        self.address_mode.address_rel = take_u16(input)?;
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl DebugState for Cpu<'_> {
+    fn debug_state(&self) -> Vec<StateNode> {
+        let registers = &self.registers;
+        // set flags in capitals, nvUbdIzc
+        let flags = "nvubdizc".chars().enumerate()
+            .map(|(bit, letter)| if registers.flags & (0x80 >> bit) != 0 { letter.to_ascii_uppercase() } else { letter })
+            .collect::<String>();
+        vec![
+            StateNode::group("registers", vec![
+                StateNode::byte("a", registers.a),
+                StateNode::byte("x", registers.x),
+                StateNode::byte("y", registers.y),
+                StateNode::word("pc", registers.pc),
+                StateNode::byte("sp", registers.sp),
+                StateNode::value("p", format!("${:02X} {}", registers.flags, flags)),
+            ]),
+            StateNode::byte("opcode", self.opcode),
+            StateNode::value("cycles left", self.cycle),
+            StateNode::value("total cycles", self.total_cycles),
+            StateNode::value("call depth", self.calls.depth()),
+        ]
+    }
+}
@@ -1,30 +1,358 @@
-use super::{interfaces::{DeviceOps}, ram::Ram, cpu::Cpu};
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::{interfaces::DeviceOps, dirty::WriteTracker, ram::Ram, cpu::Cpu, controller::Controller, cartridge::Cartridge, zapper::Zapper, four_score::FourScore, vrc6::Vrc6, fds::Fds, apu::Apu, nsf::Nsf, rom::Rom, serial::Serial, pia::Pia, via::Via, acia::Acia, ppu::Ppu, plugin::Plugin, audio::AudioSource, interfaces::Clocked, inspect::{DebugState, StateNode}, snapshot::{Snapshot, mismatch, put_u8, put_u16, take_u8, take_u16, take_bytes}};
+use super::mapper::{Bank, BankMemory};
+use crate::loader::ines::Mirroring;
 
 #[derive(Clone)]
 pub enum Device<'a> {
     Ram(Ram),
-    Cpu(Cpu<'a>)
+    Cpu(Cpu<'a>),
+    Controller(Controller),
+    Cartridge(Cartridge),
+    Zapper(Zapper),
+    FourScore(FourScore),
+    Vrc6(Vrc6),
+    Fds(Fds),
+    Apu(Apu),
+    Nsf(Nsf),
+    Rom(Rom),
+    Serial(Serial),
+    Pia(Pia),
+    Via(Via),
+    Acia(Acia),
+    Plugin(Box<dyn Plugin>),
+    Ppu(Ppu)
 }
 
 impl<'a> DeviceOps for Device<'a> {
     fn within_range(&self, addr: u16) -> bool {
         match self {
             Device::Ram(ram) => ram.within_range(addr),
-            Device::Cpu(cpu) => cpu.within_range(addr)
+            Device::Cpu(cpu) => cpu.within_range(addr),
+            Device::Controller(controller) => controller.within_range(addr),
+            Device::Cartridge(cartridge) => cartridge.within_range(addr),
+            Device::Zapper(zapper) => zapper.within_range(addr),
+            Device::FourScore(four_score) => four_score.within_range(addr),
+            Device::Vrc6(vrc6) => vrc6.within_range(addr),
+            Device::Fds(fds) => fds.within_range(addr),
+            Device::Apu(apu) => apu.within_range(addr),
+            Device::Nsf(nsf) => nsf.within_range(addr),
+            Device::Rom(rom) => rom.within_range(addr),
+            Device::Serial(serial) => serial.within_range(addr),
+            Device::Pia(pia) => pia.within_range(addr),
+            Device::Via(via) => via.within_range(addr),
+            Device::Acia(acia) => acia.within_range(addr),
+            Device::Plugin(plugin) => plugin.within_range(addr),
+            Device::Ppu(ppu) => ppu.within_range(addr)
         }
     }
 
     fn read(&self, addr: u16) -> u8 {
         match self {
             Device::Ram(ram) => ram.read(addr),
-            Device::Cpu(cpu) => cpu.read(addr)
+            Device::Cpu(cpu) => cpu.read(addr),
+            Device::Controller(controller) => controller.read(addr),
+            Device::Cartridge(cartridge) => cartridge.read(addr),
+            Device::Zapper(zapper) => zapper.read(addr),
+            Device::FourScore(four_score) => four_score.read(addr),
+            Device::Vrc6(vrc6) => vrc6.read(addr),
+            Device::Fds(fds) => fds.read(addr),
+            Device::Apu(apu) => apu.read(addr),
+            Device::Nsf(nsf) => nsf.read(addr),
+            Device::Rom(rom) => rom.read(addr),
+            Device::Serial(serial) => serial.read(addr),
+            Device::Pia(pia) => pia.read(addr),
+            Device::Via(via) => via.read(addr),
+            Device::Acia(acia) => acia.read(addr),
+            Device::Plugin(plugin) => plugin.read(addr),
+            Device::Ppu(ppu) => ppu.read(addr)
         }
     }
 
     fn write(&mut self, addr: u16, value: u8) -> () {
         match self {
             Device::Ram(ram) => ram.write(addr, value),
-            Device::Cpu(cpu) => cpu.write(addr, value)
+            Device::Cpu(cpu) => cpu.write(addr, value),
+            Device::Controller(controller) => controller.write(addr, value),
+            Device::Cartridge(cartridge) => cartridge.write(addr, value),
+            Device::Zapper(zapper) => zapper.write(addr, value),
+            Device::FourScore(four_score) => four_score.write(addr, value),
+            Device::Vrc6(vrc6) => vrc6.write(addr, value),
+            Device::Fds(fds) => fds.write(addr, value),
+            Device::Apu(apu) => apu.write(addr, value),
+            Device::Nsf(nsf) => nsf.write(addr, value),
+            Device::Rom(rom) => rom.write(addr, value),
+            Device::Serial(serial) => serial.write(addr, value),
+            Device::Pia(pia) => pia.write(addr, value),
+            Device::Via(via) => via.write(addr, value),
+            Device::Acia(acia) => acia.write(addr, value),
+            Device::Plugin(plugin) => plugin.write(addr, value),
+            Device::Ppu(ppu) => ppu.write(addr, value)
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match self {
+            Device::Ram(ram) => ram.peek(addr),
+            Device::Cpu(cpu) => cpu.peek(addr),
+            Device::Controller(controller) => controller.peek(addr),
+            Device::Cartridge(cartridge) => cartridge.peek(addr),
+            Device::Zapper(zapper) => zapper.peek(addr),
+            Device::FourScore(four_score) => four_score.peek(addr),
+            Device::Vrc6(vrc6) => vrc6.peek(addr),
+            Device::Fds(fds) => fds.peek(addr),
+            Device::Apu(apu) => apu.peek(addr),
+            Device::Nsf(nsf) => nsf.peek(addr),
+            Device::Rom(rom) => rom.peek(addr),
+            Device::Serial(serial) => serial.peek(addr),
+            Device::Pia(pia) => pia.peek(addr),
+            Device::Via(via) => via.peek(addr),
+            Device::Acia(acia) => acia.peek(addr),
+            Device::Plugin(plugin) => plugin.peek(addr),
+            Device::Ppu(ppu) => ppu.peek(addr)
+        }
+    }
+
+    fn write_tracker(&self) -> Option<&WriteTracker> {
+        match self {
+            Device::Ram(ram) => ram.write_tracker(),
+            Device::Cartridge(cartridge) => cartridge.write_tracker(),
+            Device::Plugin(plugin) => plugin.write_tracker(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Device<'a> {
+    pub fn tag(&self) -> u8 {
+        match self {
+            Device::Ram(_) => 0,
+            Device::Cpu(_) => 1,
+            Device::Controller(_) => 2,
+            Device::Cartridge(_) => 3,
+            Device::Zapper(_) => 4,
+            Device::FourScore(_) => 5,
+            Device::Vrc6(_) => 6,
+            Device::Fds(_) => 7,
+            Device::Apu(_) => 8,
+            Device::Nsf(_) => 9,
+            Device::Rom(_) => 10,
+            Device::Serial(_) => 11,
+            Device::Pia(_) => 12,
+            Device::Via(_) => 13,
+            Device::Acia(_) => 14,
+            Device::Plugin(_) => 15,
+            Device::Ppu(_) => 16,
+        }
+    }
+
+    // for logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Device::Ram(_) => "ram",
+            Device::Cpu(_) => "cpu",
+            Device::Controller(_) => "controller",
+            Device::Cartridge(_) => "cartridge",
+            Device::Zapper(_) => "zapper",
+            Device::FourScore(_) => "four score",
+            Device::Vrc6(_) => "vrc6",
+            Device::Fds(_) => "disk system",
+            Device::Apu(_) => "apu",
+            Device::Nsf(_) => "nsf",
+            Device::Rom(_) => "rom",
+            Device::Serial(_) => "serial",
+            Device::Pia(_) => "pia",
+            Device::Via(_) => "via",
+            Device::Acia(_) => "acia",
+            Device::Plugin(plugin) => plugin.name(),
+            Device::Ppu(_) => "ppu",
+        }
+    }
+
+    pub fn debug_state(&self) -> Vec<StateNode> {
+        match self {
+            Device::Ram(ram) => ram.debug_state(),
+            Device::Cpu(cpu) => cpu.debug_state(),
+            Device::Controller(controller) => controller.debug_state(),
+            Device::Cartridge(cartridge) => cartridge.debug_state(),
+            Device::Zapper(zapper) => zapper.debug_state(),
+            Device::FourScore(four_score) => four_score.debug_state(),
+            Device::Vrc6(vrc6) => vrc6.debug_state(),
+            Device::Fds(fds) => fds.debug_state(),
+            Device::Apu(apu) => apu.debug_state(),
+            Device::Nsf(nsf) => nsf.debug_state(),
+            Device::Rom(rom) => rom.debug_state(),
+            Device::Serial(serial) => serial.debug_state(),
+            Device::Pia(pia) => pia.debug_state(),
+            Device::Via(via) => via.debug_state(),
+            Device::Acia(acia) => acia.debug_state(),
+            Device::Plugin(plugin) => plugin.debug_state(),
+            Device::Ppu(ppu) => ppu.debug_state(),
+        }
+    }
+
+    // the device holding the irq line
+    pub fn irq(&self) -> bool {
+        match self {
+            Device::Fds(fds) => fds.irq(),
+            Device::Apu(apu) => apu.irq(),
+            Device::Via(via) => via.irq(),
+            Device::Acia(acia) => acia.irq(),
+            Device::Plugin(plugin) => plugin.irq(),
+            _ => false,
+        }
+    }
+
+    // the device driving the nmi line
+    pub fn nmi(&self) -> bool {
+        match self {
+            Device::Ppu(ppu) => ppu.nmi(),
+            _ => false,
+        }
+    }
+
+    // the nametable layout a board switches itself, None when it is wired as the header says
+    pub fn mirroring(&self) -> Option<Mirroring> {
+        match self {
+            Device::Vrc6(vrc6) => Some(vrc6.mirroring()),
+            _ => None,
+        }
+    }
+
+    // the banks a board switches, as its registers were last written, empty for boards
+    // without any
+    pub fn banks(&self) -> Vec<Bank> {
+        match self {
+            Device::Vrc6(vrc6) => vrc6.banks(),
+            Device::Nsf(nsf) if nsf.bankswitched => nsf.banks.iter().enumerate()
+                .map(|(slot, bank)| Bank { memory: BankMemory::Prg, address: 0x8000 + slot as u16 * 0x1000, bank: *bank as u16 })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // the board's work ram at $6000, what a battery keeps through power offs
+    pub fn prg_ram(&self) -> Option<&[u8]> {
+        match self {
+            Device::Cartridge(cartridge) => cartridge.banks.iter().find(|(address, _)| *address == 0x6000).map(|(_, bytes)| &bytes[..]),
+            Device::Vrc6(vrc6) if !vrc6.prg_ram.is_empty() => Some(&vrc6.prg_ram),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match self {
+            Device::Cartridge(cartridge) => cartridge.banks.iter_mut().find(|(address, _)| *address == 0x6000).map(|(_, bytes)| &mut bytes[..]),
+            Device::Vrc6(vrc6) if !vrc6.prg_ram.is_empty() => Some(&mut vrc6.prg_ram),
+            _ => None,
+        }
+    }
+
+    // boards that plug into the cartridge slot
+    pub fn is_cartridge(&self) -> bool {
+        matches!(self, Device::Cartridge(_) | Device::Vrc6(_) | Device::Fds(_) | Device::Nsf(_))
+    }
+
+    pub fn cartridge_regions(&self) -> Vec<RangeInclusive<u16>> {
+        match self {
+            Device::Cartridge(cartridge) => cartridge.regions(),
+            Device::Vrc6(_) => vec![0x6000..=0xFFFF],
+            Device::Fds(_) => Fds::regions(),
+            Device::Nsf(_) => Nsf::regions(),
+            _ => Vec::new(),
+        }
+    }
+
+    // runs a clocked device up to the cpu cycle, nothing for the others
+    pub fn tick(&mut self, cycles: u64) {
+        if let Some(clocked) = self.clocked() {
+            clocked.catch_up(cycles);
+        }
+    }
+
+    pub fn clocked(&mut self) -> Option<&mut dyn Clocked> {
+        match self {
+            Device::Vrc6(vrc6) => Some(vrc6),
+            Device::Fds(fds) => Some(fds),
+            Device::Apu(apu) => Some(apu),
+            Device::Nsf(nsf) => nsf.vrc6.as_mut().map(|vrc6| vrc6 as &mut dyn Clocked),
+            Device::Via(via) => Some(via),
+            Device::Ppu(ppu) => Some(ppu),
+            Device::Plugin(plugin) => plugin.clocked(),
+            _ => None,
+        }
+    }
+
+    pub fn audio_source(&mut self) -> Option<&mut dyn AudioSource> {
+        match self {
+            Device::Vrc6(vrc6) => Some(vrc6),
+            Device::Apu(apu) => Some(apu),
+            Device::Nsf(nsf) => nsf.vrc6.as_mut().map(|vrc6| vrc6 as &mut dyn AudioSource),
+            Device::Plugin(plugin) => plugin.audio_source(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Snapshot for Device<'a> {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.tag());
+        match self {
+            Device::Ram(ram) => ram.save(out),
+            Device::Cpu(cpu) => cpu.save(out),
+            Device::Controller(controller) => controller.save(out),
+            Device::Cartridge(cartridge) => cartridge.save(out),
+            Device::Zapper(zapper) => zapper.save(out),
+            Device::FourScore(four_score) => four_score.save(out),
+            Device::Vrc6(vrc6) => vrc6.save(out),
+            Device::Fds(fds) => fds.save(out),
+            Device::Apu(apu) => apu.save(out),
+            Device::Nsf(nsf) => nsf.save(out),
+            Device::Rom(rom) => rom.save(out),
+            Device::Serial(serial) => serial.save(out),
+            Device::Pia(pia) => pia.save(out),
+            Device::Via(via) => via.save(out),
+            Device::Acia(acia) => acia.save(out),
+            // named so a savestate only goes back into the same kind of plugin
+            Device::Plugin(plugin) => {
+                put_u16(out, plugin.name().len() as u16);
+                out.extend_from_slice(plugin.name().as_bytes());
+                plugin.save(out);
+            },
+            Device::Ppu(ppu) => ppu.save(out),
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        if take_u8(input)? != self.tag() {
+            return Err(mismatch("device kind"));
+        }
+        match self {
+            Device::Ram(ram) => ram.restore(input),
+            Device::Cpu(cpu) => cpu.restore(input),
+            Device::Controller(controller) => controller.restore(input),
+            Device::Cartridge(cartridge) => cartridge.restore(input),
+            Device::Zapper(zapper) => zapper.restore(input),
+            Device::FourScore(four_score) => four_score.restore(input),
+            Device::Vrc6(vrc6) => vrc6.restore(input),
+            Device::Fds(fds) => fds.restore(input),
+            Device::Apu(apu) => apu.restore(input),
+            Device::Nsf(nsf) => nsf.restore(input),
+            Device::Rom(rom) => rom.restore(input),
+            Device::Serial(serial) => serial.restore(input),
+            Device::Pia(pia) => pia.restore(input),
+            Device::Via(via) => via.restore(input),
+            Device::Acia(acia) => acia.restore(input),
+            Device::Plugin(plugin) => {
+                let length = take_u16(input)? as usize;
+                if take_bytes(input, length)? != plugin.name().as_bytes() {
+                    return Err(mismatch("plugin"));
+                }
+                plugin.restore(input)
+            },
+            Device::Ppu(ppu) => ppu.restore(input),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+pub const PAGE_SIZE : usize = 0x100;
+pub const PAGES     : usize = 0x100;
+
+// one bit per 256 byte page of the address space
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirtyPages {
+    bits : [u64; PAGES / 64],
+}
+
+impl DirtyPages {
+    pub fn all() -> DirtyPages {
+        DirtyPages { bits: [u64::MAX; PAGES / 64] }
+    }
+
+    pub fn mark(&mut self, page: u8) {
+        self.bits[page as usize / 64] |= 1 << (page % 64);
+    }
+
+    pub fn contains(&self, page: u8) -> bool {
+        self.bits[page as usize / 64] & 1 << (page % 64) != 0
+    }
+
+    pub fn union(&mut self, other: &DirtyPages) {
+        for (bits, other) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *bits |= other;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|bits| *bits == 0)
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..PAGES).map(|page| page as u8).filter(move |page| self.contains(*page))
+    }
+}
+
+// what a stateful device keeps to answer which pages were written since some earlier
+// moment : its count of writes, and for each page the count when it was last written.
+// a copy of the device keeps its counts, so comparing against an older copy needs no
+// bookkeeping, a copy that is not older (a rewound machine) sees every page as written
+#[derive(Debug, Clone)]
+pub struct WriteTracker {
+    pub writes : u64,
+    stamps     : [u64; PAGES],
+}
+
+impl WriteTracker {
+    pub fn new() -> WriteTracker {
+        WriteTracker { writes: 0, stamps: [0; PAGES] }
+    }
+
+    pub fn record(&mut self, addr: u16) {
+        self.writes += 1;
+        self.stamps[addr as usize / PAGE_SIZE] = self.writes;
+    }
+
+    // restored contents come from elsewhere, every page counts as written
+    pub fn record_all(&mut self) {
+        self.writes += 1;
+        self.stamps.fill(self.writes);
+    }
+
+    pub fn dirty_since(&self, writes: u64) -> DirtyPages {
+        if writes > self.writes {
+            return DirtyPages::all();
+        }
+        let mut dirty = DirtyPages::default();
+        for (page, stamp) in self.stamps.iter().enumerate() {
+            if *stamp > writes {
+                dirty.mark(page as u8);
+            }
+        }
+        dirty
+    }
+}
+
+impl Default for WriteTracker {
+    fn default() -> WriteTracker {
+        WriteTracker::new()
+    }
+}
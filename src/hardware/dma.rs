@@ -0,0 +1,78 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{apu::Apu, bus::Bus, device::Device, interfaces::DeviceOps};
+
+// the 2A03's two dma units take the bus from the cpu, done here between instructions as
+// the cpu does the rest : the sprite dma copies the page written to $4014 into $2004, 256
+// reads and 256 writes after a halt cycle, one more to line up with a read cycle when it
+// starts on an odd one
+pub const OAM_DMA_CYCLES : u32 = 513;
+// halt, dummy, alignment and the read of a dmc sample byte
+pub const DMC_DMA_CYCLES : u32 = 4;
+
+fn apu<'a>(bus: &Bus<'a>) -> Option<Rc<RefCell<Device<'a>>>> {
+    bus.devices.iter()
+        .map(|mapping| mapping.device.clone())
+        .find(|device| matches!(&*device.borrow(), Device::Apu(_)))
+}
+
+// the apu caught up to `cycle`
+fn with_apu<T>(device: &RefCell<Device>, cycle: u64, action: impl FnOnce(&mut Apu) -> T) -> Option<T> {
+    let mut device = device.borrow_mut();
+    device.tick(cycle);
+    match &mut *device {
+        Device::Apu(apu) => Some(action(apu)),
+        _ => None,
+    }
+}
+
+// the dma the apu asked for during the last instruction, the cycles the cpu is halted for.
+// a dmc fetch asked for while a sprite dma runs slips into the cycles it leaves free : 2
+// more, 1 on its second to last cycle, 3 on its last. `port_read` is a read of $4016 /
+// $4017 the last instruction made with its cycle, a dmc fetch halting the cpu on it has the
+// cpu read the port again, clocking the controller's shift register once more and losing a
+// button bit, unless the apu's read_glitch is off
+pub fn service(bus: &mut Bus, port_read: Option<(u16, u64)>) -> u32 {
+    let device = match apu(bus) {
+        Some(device) => device,
+        None => return 0,
+    };
+    let start = bus.clock;
+    let mut halted = 0;
+    if let Some(page) = with_apu(&device, start, |apu| apu.oam_dma.take()).flatten() {
+        for offset in 0..=0xFF {
+            let value = bus.read((page as u16) << 8 | offset);
+            bus.write(0x2004, value);
+        }
+        halted = OAM_DMA_CYCLES + (start % 2) as u32;
+        crate::log_event!(Bus, Trace, "sprite dma from ${:02X}00 at cycle {}, {} cycles", page, start, halted);
+    }
+
+    let end = start + halted as u64;
+    let (request, glitch) = match with_apu(&device, end, |apu| (apu.dmc.request().zip(apu.dmc.requested_at()), apu.read_glitch)) {
+        Some(state) => state,
+        None => return halted,
+    };
+    let (address, at) = match request {
+        // asked for as the sprite dma ended, left for the next instruction boundary
+        Some((_, at)) if halted > 0 && at >= end => return halted,
+        Some(request) => request,
+        None => return halted,
+    };
+    if halted == 0 {
+        halted = DMC_DMA_CYCLES;
+        if let Some((port, cycle)) = port_read.filter(|(_, cycle)| glitch && at >= *cycle) {
+            crate::log_event!(Bus, Trace, "dmc fetch repeats the read of ${:04X} from cycle {}", port, cycle);
+            bus.read(port);
+        }
+    } else {
+        halted += match end - at {
+            2 => 1,
+            1 => 3,
+            _ => 2,
+        };
+    }
+    let value = bus.read(address);
+    with_apu(&device, end, |apu| apu.dmc.fill(value));
+    halted
+}
@@ -0,0 +1,337 @@
+use std::cell::Cell;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::{Clocked, DeviceOps};
+use super::snapshot::{Snapshot, mismatch, put_u8, put_u16, put_u32, put_u64, take_bytes, take_u8, take_u16, take_u32, take_u64};
+use super::inspect::{DebugState, StateNode};
+
+pub const BIOS_SIZE  : usize = 0x2000;
+const RAM_SIZE       : usize = 0x8000;
+// the drive moves one byte under the head about every 149 cpu cycles
+const BYTE_CYCLES    : u64 = 149;
+const LEADING_GAP    : usize = 28_300 / 8;
+const BLOCK_GAP      : usize = 976 / 8;
+const GAP_MARK       : u8 = 0x80;
+
+const TIMER_IRQ      : u8 = 1 << 0;
+const BYTE_TRANSFER  : u8 = 1 << 1;
+
+// $4025
+const MOTOR_ON       : u8 = 1 << 0;
+const TRANSFER_RESET : u8 = 1 << 1;
+const READ_MODE      : u8 = 1 << 2;
+const TRANSFER_ON    : u8 = 1 << 6;
+const DISK_IRQ       : u8 = 1 << 7;
+
+// a .fds side laid out the way the drive sees it : a long leading gap, then each block
+// behind a gap mark and followed by its crc (left zero, the drive never reports a crc
+// error) and a short gap
+pub fn gapped(side: &[u8]) -> Vec<u8> {
+    let mut disk = vec![0; LEADING_GAP];
+    let mut position = 0;
+    let mut file_size = 0;
+    while let Some(code) = side.get(position) {
+        let length = match code {
+            1 => 56,
+            2 => 2,
+            3 => 16,
+            4 => 1 + file_size,
+            _ => break,
+        };
+        if position + length > side.len() {
+            break;
+        }
+        if *code == 3 {
+            file_size = u16::from_le_bytes([side[position + 13], side[position + 14]]) as usize;
+        }
+        disk.push(GAP_MARK);
+        disk.extend_from_slice(&side[position..position + length]);
+        disk.extend_from_slice(&[0, 0]);
+        disk.extend(std::iter::repeat_n(0, BLOCK_GAP));
+        position += length;
+    }
+    // the unused rest of the side still has to pass under the head
+    disk.resize(disk.len().max(LEADING_GAP + side.len()), 0);
+    disk
+}
+
+// Famicom Disk System ram adapter : 32k of ram at $6000, the bios at $E000, a
+// countdown timer and the drive interface at $4020 - $4033, interrupts are flagged
+// in the status register and hold the irq line until acknowledged
+#[derive(Debug, Clone)]
+pub struct Fds {
+    pub bios          : Vec<u8>,
+    pub ram           : Vec<u8>,
+    pub disks         : Vec<Vec<u8>>, // gapped sides
+    pub side          : Option<usize>,
+    pub timer_reload  : u16,
+    pub timer_counter : u16,
+    pub timer_control : u8,
+    pub io_enable     : u8,
+    pub control       : u8,
+    pub write_data    : u8,
+    pub read_data     : u8,
+    status            : Cell<u8>,
+    head              : usize,
+    byte_timer        : u64,
+    gap_ended         : bool,
+    ready             : bool,
+    clocked           : u64,
+}
+
+impl Fds {
+    pub fn new(bios: Vec<u8>, sides: &[Vec<u8>]) -> Fds {
+        Fds {
+            bios,
+            ram           : vec![0; RAM_SIZE],
+            disks         : sides.iter().map(|side| gapped(side)).collect(),
+            side          : if sides.is_empty() { None } else { Some(0) },
+            timer_reload  : 0,
+            timer_counter : 0,
+            timer_control : 0,
+            io_enable     : 0,
+            control       : 0,
+            write_data    : 0,
+            read_data     : 0,
+            status        : Cell::new(0),
+            head          : 0,
+            byte_timer    : 0,
+            gap_ended     : false,
+            ready         : false,
+            clocked       : 0,
+        }
+    }
+
+    pub fn regions() -> Vec<RangeInclusive<u16>> {
+        vec![0x4020..=0x4033, 0x6000..=0xFFFF]
+    }
+
+    // None ejects the disk
+    pub fn insert(&mut self, side: Option<usize>) {
+        self.side = side.filter(|side| *side < self.disks.len());
+        self.head = 0;
+        self.gap_ended = false;
+        self.ready = false;
+    }
+
+    pub fn irq(&self) -> bool {
+        let status = self.status.get();
+        status & TIMER_IRQ != 0 || (status & BYTE_TRANSFER != 0 && self.control & DISK_IRQ != 0)
+    }
+
+    fn drive_status(&self) -> u8 {
+        let empty = self.side.is_none() as u8;
+        0x40 | empty | (((empty != 0 || !self.ready) as u8) << 1) | (empty << 2)
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer_control & 2 == 0 || self.io_enable & 1 == 0 {
+            return;
+        }
+        if self.timer_counter == 0 {
+            self.status.set(self.status.get() | TIMER_IRQ);
+            self.timer_counter = self.timer_reload;
+            if self.timer_control & 1 == 0 {
+                self.timer_control &= !2;
+            }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn clock_drive(&mut self) {
+        let side = match self.side {
+            Some(side) if self.control & MOTOR_ON != 0 => side,
+            _ => {
+                self.ready = false;
+                return;
+            },
+        };
+        if self.control & TRANSFER_RESET != 0 {
+            self.head = 0;
+            self.gap_ended = false;
+            return;
+        }
+
+        self.byte_timer += 1;
+        if self.byte_timer < BYTE_CYCLES {
+            return;
+        }
+        self.byte_timer = 0;
+
+        // past the end the head goes back to the start, not ready until it is there
+        if self.head >= self.disks[side].len() {
+            self.head = 0;
+            self.gap_ended = false;
+            self.ready = false;
+            return;
+        }
+        self.ready = true;
+
+        let transfer = self.control & TRANSFER_ON != 0;
+        if self.control & READ_MODE != 0 {
+            let byte = self.disks[side][self.head];
+            if !transfer {
+                self.gap_ended = false;
+            } else if !self.gap_ended {
+                self.gap_ended = byte == GAP_MARK;
+            } else {
+                self.read_data = byte;
+                self.status.set(self.status.get() | BYTE_TRANSFER);
+            }
+        } else if transfer {
+            self.disks[side][self.head] = self.write_data;
+            self.status.set(self.status.get() | BYTE_TRANSFER);
+        }
+        self.head += 1;
+    }
+}
+
+impl DeviceOps for Fds {
+    fn within_range(&self, addr: u16) -> bool {
+        (0x4020..=0x4033).contains(&addr) || addr >= 0x6000
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x4030 => {
+                let status = self.peek(addr);
+                self.status.set(self.status.get() & !TIMER_IRQ);
+                status
+            },
+            0x4031 => {
+                self.status.set(self.status.get() & !BYTE_TRANSFER);
+                self.read_data
+            },
+            _ => self.peek(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | value as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | (value as u16) << 8,
+            0x4022 => {
+                self.timer_control = value & 3;
+                if value & 2 != 0 {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.status.set(self.status.get() & !TIMER_IRQ);
+                }
+            },
+            0x4023 => {
+                self.io_enable = value;
+                if value & 1 == 0 {
+                    self.timer_control &= !2;
+                    self.status.set(self.status.get() & !TIMER_IRQ);
+                }
+            },
+            0x4024 => {
+                self.write_data = value;
+                self.status.set(self.status.get() & !BYTE_TRANSFER);
+            },
+            0x4025 => self.control = value,
+            0x6000..=0xDFFF => self.ram[addr as usize - 0x6000] = value,
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x4030 => self.status.get() & (TIMER_IRQ | BYTE_TRANSFER) | ((self.side.is_some_and(|side| self.head >= self.disks[side].len()) as u8) << 6),
+            0x4031 => self.read_data,
+            0x4032 => self.drive_status(),
+            0x4033 => 0x80, // battery good
+            0x6000..=0xDFFF => self.ram[addr as usize - 0x6000],
+            0xE000..=0xFFFF => self.bios.get(addr as usize - 0xE000).copied().unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+impl Clocked for Fds {
+    fn catch_up(&mut self, cycles: u64) {
+        while self.clocked < cycles {
+            self.clock_timer();
+            self.clock_drive();
+            self.clocked += 1;
+        }
+    }
+}
+
+impl Snapshot for Fds {
+    fn save(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.ram);
+        put_u8(out, self.disks.len() as u8);
+        for disk in self.disks.iter() {
+            put_u32(out, disk.len() as u32);
+            out.extend_from_slice(disk);
+        }
+        put_u8(out, self.side.map(|side| side as u8).unwrap_or(0xFF));
+        put_u16(out, self.timer_reload);
+        put_u16(out, self.timer_counter);
+        for register in [self.timer_control, self.io_enable, self.control, self.write_data, self.read_data, self.status.get(), self.gap_ended as u8, self.ready as u8].iter() {
+            put_u8(out, *register);
+        }
+        put_u32(out, self.head as u32);
+        put_u64(out, self.byte_timer);
+        put_u64(out, self.clocked);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.ram.copy_from_slice(take_bytes(input, RAM_SIZE)?);
+        if take_u8(input)? as usize != self.disks.len() {
+            return Err(mismatch("disk sides"));
+        }
+        for disk in self.disks.iter_mut() {
+            if take_u32(input)? as usize != disk.len() {
+                return Err(mismatch("disk side size"));
+            }
+            let size = disk.len();
+            disk.copy_from_slice(take_bytes(input, size)?);
+        }
+        self.side = match take_u8(input)? {
+            0xFF => None,
+            side => Some(side as usize),
+        };
+        self.timer_reload = take_u16(input)?;
+        self.timer_counter = take_u16(input)?;
+        self.timer_control = take_u8(input)?;
+        self.io_enable = take_u8(input)?;
+        self.control = take_u8(input)?;
+        self.write_data = take_u8(input)?;
+        self.read_data = take_u8(input)?;
+        self.status.set(take_u8(input)?);
+        self.gap_ended = take_u8(input)? != 0;
+        self.ready = take_u8(input)? != 0;
+        self.head = take_u32(input)? as usize;
+        self.byte_timer = take_u64(input)?;
+        self.clocked = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Fds {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::bytes("bios", &self.bios),
+            StateNode::bytes("ram", &self.ram),
+            StateNode::value("sides", self.disks.len()),
+            StateNode::value("side", self.side.map(|side| ((b'A' + side as u8) as char).to_string()).unwrap_or_else(|| "ejected".to_string())),
+            StateNode::word("timer reload", self.timer_reload),
+            StateNode::word("timer counter", self.timer_counter),
+            StateNode::byte("timer control", self.timer_control),
+            StateNode::byte("io enable", self.io_enable),
+            StateNode::byte("control", self.control),
+            StateNode::byte("write data", self.write_data),
+            StateNode::byte("read data", self.read_data),
+            StateNode::byte("status", self.status.get()),
+            StateNode::value("head", self.head),
+            StateNode::value("byte timer", self.byte_timer),
+            StateNode::value("gap ended", self.gap_ended),
+            StateNode::value("ready", self.ready),
+            StateNode::value("clocked to", self.clocked),
+        ]
+    }
+}
@@ -0,0 +1,105 @@
+use std::cell::Cell;
+use std::io::Error;
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u8, take_u8};
+use super::inspect::{DebugState, StateNode};
+
+// reads 17 to 24 of each register, telling games a four score is attached
+const SIGNATURES: [[u8; 8]; 2] = [
+    [0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, 0, 0],
+];
+
+// four player adapter on both ports : after the $4016 strobe, $4016 shifts out pad 1,
+// then pad 3, then its signature, $4017 does the same with pads 2 and 4
+#[derive(Debug, Clone, Default)]
+pub struct FourScore {
+    pub buttons : [u8; 4],
+    pub strobe  : bool,
+    reads       : [Cell<u8>; 2],
+}
+
+impl FourScore {
+    pub fn new() -> FourScore {
+        FourScore {
+            buttons : [0; 4],
+            strobe  : false,
+            reads   : [Cell::new(0), Cell::new(0)],
+        }
+    }
+
+    fn bit(&self, port: usize, read: u8) -> u8 {
+        match read {
+            0..=7   => self.buttons[port] >> read & 1,
+            8..=15  => self.buttons[port + 2] >> (read - 8) & 1,
+            16..=23 => SIGNATURES[port][read as usize - 16],
+            _       => 1,
+        }
+    }
+
+    fn port(addr: u16) -> usize {
+        (addr == 0x4017) as usize
+    }
+}
+
+impl DeviceOps for FourScore {
+    fn within_range(&self, addr: u16) -> bool {
+        addr == 0x4016 || addr == 0x4017
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let port = FourScore::port(addr);
+        let read = if self.strobe { 0 } else { self.reads[port].get() };
+        if !self.strobe && read < 24 {
+            self.reads[port].set(read + 1);
+        }
+        0x40 | self.bit(port, read)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr != 0x4016 {
+            return;
+        }
+        self.strobe = value & 1 != 0;
+        if self.strobe {
+            self.reads.iter().for_each(|reads| reads.set(0));
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        let port = FourScore::port(addr);
+        0x40 | self.bit(port, if self.strobe { 0 } else { self.reads[port].get() })
+    }
+}
+
+impl Snapshot for FourScore {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.buttons.iter().for_each(|buttons| put_u8(out, *buttons));
+        put_u8(out, self.strobe as u8);
+        self.reads.iter().for_each(|reads| put_u8(out, reads.get()));
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        for buttons in self.buttons.iter_mut() {
+            *buttons = take_u8(input)?;
+        }
+        self.strobe = take_u8(input)? != 0;
+        for reads in self.reads.iter() {
+            reads.set(take_u8(input)?);
+        }
+        Ok(())
+    }
+}
+
+impl DebugState for FourScore {
+    fn debug_state(&self) -> Vec<StateNode> {
+        let mut state = self.buttons.iter().enumerate()
+            .map(|(pad, buttons)| StateNode::byte(&format!("pad {} buttons", pad + 1), *buttons))
+            .collect::<Vec<StateNode>>();
+        state.push(StateNode::value("strobe", self.strobe));
+        state.push(StateNode::value("reads $4016", self.reads[0].get()));
+        state.push(StateNode::value("reads $4017", self.reads[1].get()));
+        state
+    }
+}
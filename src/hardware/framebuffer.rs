@@ -0,0 +1,74 @@
+use super::palette::Palette;
+
+pub const WIDTH  : usize = 256;
+pub const HEIGHT : usize = 240;
+
+// double buffered 0x00RRGGBB pixels, the renderer draws into the back buffer and the
+// buffers trade places at vblank, so frontends can read the last complete frame
+// straight out of front() without copying or allocating. the frame before it is kept
+// too, for blending the two
+#[derive(Debug, Clone)]
+pub struct FrameBuffer {
+    pub width   : usize,
+    pub height  : usize,
+    pub frame   : u64, // number of completed frames
+    pub palette : Palette, // what the ppu's color indices stand for
+    pub drawn   : bool, // a pixel was set, until then every frame is blank and hashes the same
+    front       : Vec<u32>,
+    back        : Vec<u32>,
+    previous    : Vec<u32>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> FrameBuffer {
+        FrameBuffer {
+            width,
+            height,
+            frame    : 0,
+            palette  : Palette::default(),
+            drawn    : false,
+            front    : vec![0; width * height],
+            back     : vec![0; width * height],
+            previous : vec![0; width * height],
+        }
+    }
+
+    pub fn front(&self) -> &[u32] {
+        &self.front
+    }
+
+    pub fn back(&self) -> &[u32] {
+        &self.back
+    }
+
+    // the frame completed before front()
+    pub fn previous(&self) -> &[u32] {
+        &self.previous
+    }
+
+    pub fn back_mut(&mut self) -> &mut [u32] {
+        self.drawn = true;
+        &mut self.back
+    }
+
+    // nothing calls these yet, the ppu draws nothing and every frame stays blank
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.drawn = true;
+            self.back[y * self.width + x] = color;
+        }
+    }
+
+    // a pixel by its color index, as the ppu puts them out
+    pub fn set_color(&mut self, x: usize, y: usize, index: u8) {
+        let color = self.palette.color(index);
+        self.set_pixel(x, y, color);
+    }
+
+    // the three buffers rotate, the oldest frame is drawn over next
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.front);
+        std::mem::swap(&mut self.front, &mut self.back);
+        self.frame += 1;
+    }
+}
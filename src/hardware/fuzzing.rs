@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::bus::Bus;
+use super::cpu::{Cpu, EVENT_IRQ, EVENT_NMI};
+use super::device::Device;
+use super::interfaces::DeviceOps;
+use super::opcodes::Opcode;
+use super::ram::Ram;
+
+pub const MAX_INSTRUCTIONS : usize = 4_096;
+// the longest instruction is 7 cycles and a page crossing branch 4, anything past this is stuck
+const MAX_TICKS            : u32 = 16;
+const HEADER               : usize = 6;
+const PROGRAM              : u16 = 0x8000;
+const PROGRAM_SIZE         : usize = 0x7FFA; // up to the vectors
+
+// the cpu under inputs nobody would write, for the cargo-fuzz targets in fuzz/ and the tests :
+// the first bytes are a, x, y, sp and p to start with and how often nmi is pulsed (every n
+// instructions, 0 never), the rest is the program at $8000 and, repeated, what ram holds
+// below it, all three vectors lead to $8000. each instruction has to finish within a bounded
+// number of cycles without the cycle counter going below zero, and leave the stack pointer
+// where the instruction (or the interrupt taken in its place) puts it, wrapping around the page
+pub fn run_cpu(data: &[u8]) -> Result<(), String> {
+    if data.len() <= HEADER {
+        return Ok(());
+    }
+    let (header, rest) = data.split_at(HEADER);
+    let bus = Rc::new(RefCell::new(Bus::new()));
+    bus.borrow_mut().add_device(Rc::new(RefCell::new(Device::Ram(Ram::new()))));
+    {
+        let mut bus = bus.borrow_mut();
+        for (address, byte) in (0..PROGRAM).zip(rest.iter().cycle()) {
+            bus.write(address, *byte);
+        }
+        for (offset, byte) in rest.iter().take(PROGRAM_SIZE).enumerate() {
+            bus.write(PROGRAM + offset as u16, *byte);
+        }
+        for vector in (0xFFFA..=0xFFFE).step_by(2) {
+            bus.write(vector, PROGRAM as u8);
+            bus.write(vector + 1, (PROGRAM >> 8) as u8);
+        }
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.bus = Some(bus.clone());
+    cpu.reset();
+    while cpu.cycle > 0 {
+        cpu.tick();
+    }
+    cpu.registers.a = header[0];
+    cpu.registers.x = header[1];
+    cpu.registers.y = header[2];
+    cpu.registers.sp = header[3];
+    cpu.registers.flags = header[4];
+    let nmi_every = header[5] as usize;
+
+    for index in 0..MAX_INSTRUCTIONS {
+        if nmi_every != 0 && index % nmi_every == 0 {
+            let mut bus = bus.borrow_mut();
+            bus.nmi.assert(0);
+            bus.nmi.deassert(0);
+        }
+        let before = cpu.registers;
+        let started = cpu.total_cycles;
+        let mut ticks = 0;
+        loop {
+            cpu.tick();
+            ticks += 1;
+            if cpu.cycle < 0 {
+                return Err(format!("cycle counter at {} after ${:02X} at ${:04X}", cpu.cycle, cpu.opcode, before.pc));
+            }
+            if cpu.cycle == 0 {
+                break;
+            }
+            if ticks > MAX_TICKS {
+                return Err(format!("${:02X} at ${:04X} still running after {} cycles", cpu.opcode, before.pc, ticks));
+            }
+        }
+        if cpu.total_cycles != started + ticks as u64 {
+            return Err(format!("{} cycles counted for {} ticks of ${:02X} at ${:04X}", cpu.total_cycles - started, ticks, cpu.opcode, before.pc));
+        }
+
+        let expected = if cpu.events.get() & (EVENT_NMI | EVENT_IRQ) != 0 {
+            before.sp.wrapping_sub(3)
+        } else {
+            match cpu.instruction_set[cpu.opcode as usize].mnemonic {
+                Opcode::BRK => before.sp.wrapping_sub(3),
+                Opcode::RTI => before.sp.wrapping_add(3),
+                Opcode::JSR => before.sp.wrapping_sub(2),
+                Opcode::RTS => before.sp.wrapping_add(2),
+                Opcode::PHA | Opcode::PHP => before.sp.wrapping_sub(1),
+                Opcode::PLA | Opcode::PLP => before.sp.wrapping_add(1),
+                Opcode::TXS => before.x,
+                _ => before.sp,
+            }
+        };
+        if cpu.registers.sp != expected {
+            return Err(format!("sp {:02X} after ${:02X} at ${:04X} with sp {:02X}, expected {:02X}", cpu.registers.sp, cpu.opcode, before.pc, before.sp, expected));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+use super::machine::Machine;
+
+// the machine as the state explorer shows it : a tree of named values, a device's parts as
+// groups under it. every device gives its own through DebugState, private fields included,
+// so one added to the bus shows up without the debugger knowing about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateNode {
+    pub name     : String,
+    pub value    : String, // empty for groups
+    pub children : Vec<StateNode>,
+}
+
+impl StateNode {
+    pub fn value(name: &str, value: impl Display) -> StateNode {
+        StateNode { name : name.to_string(), value : value.to_string(), children : Vec::new() }
+    }
+
+    pub fn byte(name: &str, value: u8) -> StateNode {
+        StateNode::value(name, format!("${:02X}", value))
+    }
+
+    pub fn word(name: &str, value: u16) -> StateNode {
+        StateNode::value(name, format!("${:04X}", value))
+    }
+
+    // memory is only sized here, the memory pane shows its bytes
+    pub fn bytes(name: &str, bytes: &[u8]) -> StateNode {
+        StateNode::value(name, format!("{} bytes", bytes.len()))
+    }
+
+    pub fn group(name: &str, children: Vec<StateNode>) -> StateNode {
+        StateNode { name : name.to_string(), value : String::new(), children }
+    }
+}
+
+pub trait DebugState {
+    fn debug_state(&self) -> Vec<StateNode>;
+}
+
+// the cpu, the interrupt lines, then every device on the bus with where it is mapped
+pub fn machine_tree(machine: &Machine) -> Vec<StateNode> {
+    let bus = machine.bus.borrow();
+    let mut tree = vec![
+        StateNode::group("cpu", machine.cpu.borrow().debug_state()),
+        StateNode::group("interrupts", vec![
+            StateNode::group("irq", bus.irq.debug_state()),
+            StateNode::group("nmi", bus.nmi.debug_state()),
+        ]),
+    ];
+    for mapping in bus.devices.iter() {
+        let device = mapping.device.borrow();
+        let regions = mapping.regions.iter()
+            .map(|region| format!("${:04X}-${:04X}", region.start(), region.end()))
+            .collect::<Vec<String>>();
+        let mut node = StateNode::group(device.name(), device.debug_state());
+        node.value = regions.join(" ");
+        tree.push(node);
+    }
+    tree
+}
@@ -1,31 +1,23 @@
-use super::{address_mode::AddressMode, cpu::Cpu, opcodes::Opcode, registers::Flag, interfaces::DeviceOps};
+use super::{address_mode::AddressMode, call_stack::{Frame, FrameKind}, cpu::{Cpu, EVENT_BRK, EVENT_RTI}, opcodes::Opcode, registers::Flag, interfaces::DeviceOps};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Instructions {
     pub mnemonic  : Opcode,
     pub opcode    : u8,
     pub cycles    : u8,
     pub address_mode : AddressMode,
+    pub legal     : bool, // an official opcode, the others run as a nop
 }
 
 impl Instructions {
-    pub fn new(mnemonic: Opcode, opcode: u8, cycles: u8, address_mode: AddressMode) -> Instructions {
-        Instructions {
-            mnemonic : mnemonic,
-            opcode    : opcode,
-            cycles    : cycles,
-            address_mode,
-        }
-    }
-
     pub fn operation(&self, cpu_ref: &mut Cpu) -> bool {
         let jump_to_relative_address = |cpu_ref: &mut Cpu| {
             cpu_ref.cycle += 1;
 
             let value = {
                 let target_address = (cpu_ref.address_mode.address_rel & 0x00FF) as i8;
-                cpu_ref.registers.pc as i16 + (target_address as i16)
-            } as u16;
+                cpu_ref.registers.pc.wrapping_add(target_address as i16 as u16)
+            };
             
             cpu_ref.address_mode.address_abs = value;
             if cpu_ref.registers.pc >> 8 != cpu_ref.address_mode.address_abs >> 8 {
@@ -54,7 +46,7 @@ impl Instructions {
                 cpu_ref.fetch();
 
                 let value = cpu_ref.registers.fetched as u16;
-                let result = cpu_ref.registers.a as u16 - value - (1 - cpu_ref.registers.get_flag(Flag::C) as u16);
+                let result = (cpu_ref.registers.a as u16).wrapping_sub(value).wrapping_sub(1 - cpu_ref.registers.get_flag(Flag::C) as u16);
                 
                 cpu_ref.registers.set_flag(Flag::C, result > 255);
                 cpu_ref.registers.set_flag(Flag::Z, (result & 0x00FF) == 0);
@@ -215,7 +207,7 @@ impl Instructions {
             Opcode::CMP => {
                 cpu_ref.fetch();
                 
-                let value = cpu_ref.registers.a as u16 - cpu_ref.registers.fetched as u16;
+                let value = (cpu_ref.registers.a as u16).wrapping_sub(cpu_ref.registers.fetched as u16);
                 cpu_ref.registers.set_flag(Flag::C, cpu_ref.registers.a >= cpu_ref.registers.fetched);
                 cpu_ref.registers.set_flag(Flag::Z, value == 0);
                 cpu_ref.registers.set_flag(Flag::N, value & 0x0080 != 0);
@@ -225,7 +217,7 @@ impl Instructions {
             Opcode::CPX => {
                 cpu_ref.fetch();
                 
-                let value = cpu_ref.registers.x as u16 - cpu_ref.registers.fetched as u16;
+                let value = (cpu_ref.registers.x as u16).wrapping_sub(cpu_ref.registers.fetched as u16);
                 cpu_ref.registers.set_flag(Flag::C, cpu_ref.registers.x >= cpu_ref.registers.fetched);
                 cpu_ref.registers.set_flag(Flag::Z, value == 0);
                 cpu_ref.registers.set_flag(Flag::N, value & 0x0080 != 0);
@@ -235,7 +227,7 @@ impl Instructions {
             Opcode::CPY => {
                 cpu_ref.fetch();
                 
-                let value = cpu_ref.registers.y as u16 - cpu_ref.registers.fetched as u16;
+                let value = (cpu_ref.registers.y as u16).wrapping_sub(cpu_ref.registers.fetched as u16);
                 cpu_ref.registers.set_flag(Flag::C, cpu_ref.registers.y >= cpu_ref.registers.fetched);
                 cpu_ref.registers.set_flag(Flag::Z, value == 0);
                 cpu_ref.registers.set_flag(Flag::N, value & 0x0080 != 0);
@@ -244,7 +236,7 @@ impl Instructions {
             },
             Opcode::DEC => {
                 cpu_ref.fetch();
-                let value = cpu_ref.registers.fetched - 1;
+                let value = cpu_ref.registers.fetched.wrapping_sub(1);
                 cpu_ref.write(cpu_ref.address_mode.address_abs, value as u8);
                 cpu_ref.registers.set_flag(Flag::Z, value == 0);
                 cpu_ref.registers.set_flag(Flag::N, value & 0x0080 != 0);
@@ -252,32 +244,32 @@ impl Instructions {
             },
             Opcode::INC => {
                 cpu_ref.fetch();
-                let value = cpu_ref.registers.fetched + 1;
+                let value = cpu_ref.registers.fetched.wrapping_add(1);
                 cpu_ref.write(cpu_ref.address_mode.address_abs, value as u8);
                 cpu_ref.registers.set_flag(Flag::Z, value == 0);
                 cpu_ref.registers.set_flag(Flag::N, value & 0x0080 != 0);
                 false
             },
             Opcode::DEX => {
-                cpu_ref.registers.x -= 1;
+                cpu_ref.registers.x = cpu_ref.registers.x.wrapping_sub(1);
                 cpu_ref.registers.set_flag(Flag::Z, cpu_ref.registers.x == 0);
                 cpu_ref.registers.set_flag(Flag::N, cpu_ref.registers.x & 0x0080 != 0);
                 false
             },
             Opcode::INX => {
-                cpu_ref.registers.x += 1;
+                cpu_ref.registers.x = cpu_ref.registers.x.wrapping_add(1);
                 cpu_ref.registers.set_flag(Flag::Z, cpu_ref.registers.x == 0);
                 cpu_ref.registers.set_flag(Flag::N, cpu_ref.registers.x & 0x0080 != 0);
                 false
             },
             Opcode::DEY => {
-                cpu_ref.registers.y -= 1;
+                cpu_ref.registers.y = cpu_ref.registers.y.wrapping_sub(1);
                 cpu_ref.registers.set_flag(Flag::Z, cpu_ref.registers.y == 0);
                 cpu_ref.registers.set_flag(Flag::N, cpu_ref.registers.y & 0x0080 != 0);
                 false
             },
             Opcode::INY => {
-                cpu_ref.registers.y += 1;
+                cpu_ref.registers.y = cpu_ref.registers.y.wrapping_add(1);
                 cpu_ref.registers.set_flag(Flag::Z, cpu_ref.registers.y == 0);
                 cpu_ref.registers.set_flag(Flag::N, cpu_ref.registers.y & 0x0080 != 0);
                 false
@@ -379,13 +371,11 @@ impl Instructions {
                 false
             },
             Opcode::PHA => {
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16, cpu_ref.registers.a);
-                cpu_ref.registers.sp -= 1;
+                cpu_ref.push(cpu_ref.registers.a);
                 false
             },
             Opcode::PLA => {
-                cpu_ref.registers.sp += 1;
-                cpu_ref.registers.a =cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16);
+                cpu_ref.registers.a = cpu_ref.pull();
                 cpu_ref.registers.set_flag(Flag::Z, cpu_ref.registers.a == 0);
                 cpu_ref.registers.set_flag(Flag::N, cpu_ref.registers.a & 0x0080 != 0);
                 false
@@ -393,70 +383,71 @@ impl Instructions {
             Opcode::PHP => {
                 cpu_ref.registers.set_flag(Flag::B, true);
                 cpu_ref.registers.set_flag(Flag::U, true);
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16, cpu_ref.registers.status);
+                cpu_ref.push(cpu_ref.registers.flags);
                 cpu_ref.registers.set_flag(Flag::B, false);
                 cpu_ref.registers.set_flag(Flag::U, false);
-                cpu_ref.registers.sp -= 1;
                 false
             },
             Opcode::PLP => {
-                cpu_ref.registers.sp += 1;
-                cpu_ref.registers.status = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16);
+                cpu_ref.registers.flags = cpu_ref.pull();
                 cpu_ref.registers.set_flag(Flag::U, false);
                 false
             },
+            // the irq sequence with B set in the pushed status, through the irq vector
             Opcode::BRK => {
-                cpu_ref.registers.pc += 1;
-                cpu_ref.registers.set_flag(Flag::I, true);
-                
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16 - 0, (cpu_ref.registers.pc >> 8) as u8);
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16 - 1, cpu_ref.registers.pc as u8);
-                cpu_ref.registers.sp -= 2;
+                let sp = cpu_ref.registers.sp;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_add(1);
+                cpu_ref.push((cpu_ref.registers.pc >> 8) as u8);
+                cpu_ref.push(cpu_ref.registers.pc as u8);
 
                 cpu_ref.registers.set_flag(Flag::B, true);
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16, cpu_ref.registers.status as u8);
-                cpu_ref.registers.sp -= 1;
+                cpu_ref.push(cpu_ref.registers.flags);
                 cpu_ref.registers.set_flag(Flag::B, false);
+                cpu_ref.registers.set_flag(Flag::I, true);
 
+                let return_address = cpu_ref.registers.pc;
                 let lo = cpu_ref.read(0xFFFE) as u16;
                 let hi = cpu_ref.read(0xFFFF) as u16;
-                cpu_ref.registers.pc += (hi << 8) | lo;
+                cpu_ref.registers.pc = (hi << 8) | lo;
 
+                let caller = return_address.wrapping_sub(2);
+                cpu_ref.signal(EVENT_BRK);
+                cpu_ref.calls.enter(Frame { kind: FrameKind::Brk, entry: cpu_ref.registers.pc, caller, return_address, sp });
                 false
             },
             Opcode::JSR => {
-                cpu_ref.registers.pc -= 1;
+                let sp = cpu_ref.registers.sp;
+                cpu_ref.registers.pc = cpu_ref.registers.pc.wrapping_sub(1);
+                let caller = cpu_ref.registers.pc.wrapping_sub(2);
+                let return_address = cpu_ref.registers.pc.wrapping_add(1);
 
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16 - 0, (cpu_ref.registers.pc >> 8) as u8);    
-                cpu_ref.write(0x0100 + cpu_ref.registers.sp as u16 - 1, (cpu_ref.registers.pc) as u8);    
-                cpu_ref.registers.sp -= 2;
+                cpu_ref.push((cpu_ref.registers.pc >> 8) as u8);
+                cpu_ref.push(cpu_ref.registers.pc as u8);
 
                 cpu_ref.registers.pc = cpu_ref.address_mode.address_abs;
+                cpu_ref.calls.enter(Frame { kind: FrameKind::Call, entry: cpu_ref.registers.pc, caller, return_address, sp });
                 false
             },
             Opcode::RTS => {
-                cpu_ref.registers.sp += 1;
-                let lo = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16) as u16;    
-
-                cpu_ref.registers.sp += 1;
-                let hi = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16) as u16;    
+                let lo = cpu_ref.pull() as u16;
+                let hi = cpu_ref.pull() as u16;
 
-                cpu_ref.registers.pc = (hi << 8) | lo; 
+                // jsr pushed the address of its last byte
+                cpu_ref.registers.pc = ((hi << 8) | lo).wrapping_add(1);
+                cpu_ref.calls.unwind(cpu_ref.registers.sp);
                 false
             },
             Opcode::RTI => {
-                cpu_ref.registers.sp += 1;
-                cpu_ref.registers.status = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16);
+                cpu_ref.registers.flags = cpu_ref.pull();
                 cpu_ref.registers.set_flag(Flag::B, false);
                 cpu_ref.registers.set_flag(Flag::U, false);
 
-                cpu_ref.registers.sp += 1;
-                let lo = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16) as u16;    
-
-                cpu_ref.registers.sp += 1;
-                let hi = cpu_ref.read(0x0100 + cpu_ref.registers.sp as u16) as u16;    
+                let lo = cpu_ref.pull() as u16;
+                let hi = cpu_ref.pull() as u16;
 
                 cpu_ref.registers.pc = (hi << 8) | lo; 
+                cpu_ref.calls.unwind(cpu_ref.registers.sp);
+                cpu_ref.signal(EVENT_RTI);
                 false
             }
         }
@@ -1,3 +1,5 @@
+use super::dirty::WriteTracker;
+
 pub trait DeviceOps {
     fn within_range(&self, _: u16) -> bool {
         true
@@ -5,4 +7,21 @@ pub trait DeviceOps {
 
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, value: u8) -> ();
-}
\ No newline at end of file
+
+    // inspection path for the debugger, devices whose reads have side effects
+    // (status registers, latches, fifos) must override this to leave their state untouched
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    // stateful devices (ram, writable program banks) keep track of the pages written
+    fn write_tracker(&self) -> Option<&WriteTracker> {
+        None
+    }
+}
+
+// devices running on their own clock, brought up to the given cpu cycle lazily : when
+// the cpu accesses their registers, samples the irq line, or the mixer samples them
+pub trait Clocked {
+    fn catch_up(&mut self, cycles: u64);
+}
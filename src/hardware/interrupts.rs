@@ -0,0 +1,76 @@
+use super::inspect::{DebugState, StateNode};
+
+// how a line gets the cpu's attention : irq while held, nmi once per assertion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    Level,
+    Edge,
+}
+
+// an open collector line shared by every device wired to it : asserted while any source
+// holds it, sources are numbered by whoever drives them (the bus uses mapping positions),
+// an edge triggered line latches each new assertion until the cpu takes it
+#[derive(Debug, Clone)]
+pub struct InterruptLine {
+    pub trigger : Trigger,
+    sources     : u64,
+    latched     : bool,
+}
+
+impl InterruptLine {
+    pub fn new(trigger: Trigger) -> InterruptLine {
+        InterruptLine { trigger, sources: 0, latched: false }
+    }
+
+    pub fn assert(&mut self, source: usize) {
+        if self.sources == 0 && self.trigger == Trigger::Edge {
+            self.latched = true;
+        }
+        self.sources |= 1 << source.min(63);
+    }
+
+    pub fn deassert(&mut self, source: usize) {
+        self.sources &= !(1 << source.min(63));
+    }
+
+    pub fn set(&mut self, source: usize, asserted: bool) {
+        if asserted {
+            self.assert(source);
+        } else {
+            self.deassert(source);
+        }
+    }
+
+    pub fn holds(&self, source: usize) -> bool {
+        self.sources & 1 << source.min(63) != 0
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.sources != 0
+    }
+
+    // what the cpu would act on at the next instruction boundary
+    pub fn is_pending(&self) -> bool {
+        match self.trigger {
+            Trigger::Level => self.is_asserted(),
+            Trigger::Edge => self.latched,
+        }
+    }
+
+    // sampled by the cpu, an edge is consumed
+    pub fn take(&mut self) -> bool {
+        let pending = self.is_pending();
+        self.latched = false;
+        pending
+    }
+}
+
+impl DebugState for InterruptLine {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("trigger", format!("{:?}", self.trigger)),
+            StateNode::value("sources", format!("{:#b}", self.sources)),
+            StateNode::value("latched", self.latched),
+        ]
+    }
+}
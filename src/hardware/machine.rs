@@ -0,0 +1,416 @@
+use std::{cell::RefCell, io::Error, ops::RangeInclusive, rc::Rc};
+
+use super::{
+    apu::Apu,
+    audio::{Mixer, SAMPLE_RATE},
+    bus::{Bus, Mapping},
+    controller::{Button, Controller, Peripheral},
+    cpu::Cpu,
+    device::Device,
+    four_score::FourScore,
+    framebuffer::{FrameBuffer, HEIGHT, WIDTH},
+    interfaces::DeviceOps,
+    mapper::Bank,
+    profile::{IoDevice, MachineProfile},
+    ram::{PowerOnPattern, Ram},
+    rom::Rom,
+    serial::Serial,
+    pia::Pia,
+    ppu::{Decay, Ppu},
+    via::Via,
+    acia::Acia,
+    plugin::Plugin,
+    snapshot::Snapshot,
+    timing,
+    zapper::Zapper,
+};
+
+// the console without any frontend : bus, cpu and video output
+pub struct Machine<'a> {
+    pub bus   : Rc<RefCell<Bus<'a>>>,
+    pub cpu   : Rc<RefCell<Cpu<'a>>>,
+    pub video : Rc<RefCell<FrameBuffer>>, // output only, shared between snapshots
+    pub audio : Rc<RefCell<Mixer>>,       // same
+}
+
+impl<'a> Machine<'a> {
+    pub fn new() -> Machine<'a> {
+        Machine::power_on(PowerOnPattern::Fill(0))
+    }
+
+    pub fn power_on(pattern: PowerOnPattern) -> Machine<'a> {
+        let ram = Rc::new(RefCell::new(Device::Ram(Ram::power_on(pattern))));
+        let bus = Rc::new(RefCell::new(Bus::new()));
+        let cpu = Rc::new(RefCell::new(Cpu::new()));
+
+        bus.borrow_mut().add_device(ram);
+        Machine::plug(&mut bus.borrow_mut(), Peripheral::Controller);
+        bus.borrow_mut().map_device(Rc::new(RefCell::new(Device::Ppu(Ppu::new()))), Ppu::regions(), 1);
+        bus.borrow_mut().map_device(Rc::new(RefCell::new(Device::Apu(Apu::new()))), Apu::regions(), 1);
+        (*cpu).borrow_mut().bus = Some(bus.clone());
+
+        (*bus).borrow_mut().write(0xFFFC, 0x00);
+        (*bus).borrow_mut().write(0xFFFC + 1, 0x80);
+        (*cpu).borrow_mut().reset();
+
+        Machine {
+            bus,
+            cpu,
+            video : Rc::new(RefCell::new(FrameBuffer::new(WIDTH, HEIGHT))),
+            audio : Rc::new(RefCell::new(Mixer::new(SAMPLE_RATE))),
+        }
+    }
+
+    // the console is power_on, other boards only get ram where their description puts it,
+    // with their firmware and devices on top, whatever is left reads as 0
+    pub fn build(profile: &MachineProfile, pattern: PowerOnPattern) -> Machine<'a> {
+        let machine = if profile.nes {
+            Machine::power_on(pattern)
+        } else {
+            let bus = Rc::new(RefCell::new(Bus::new()));
+            let cpu = Rc::new(RefCell::new(Cpu::new()));
+            let ram = Rc::new(RefCell::new(Device::Ram(Ram::power_on(pattern))));
+            bus.borrow_mut().map_device(ram, profile.ram.clone(), 0);
+            (*cpu).borrow_mut().bus = Some(bus.clone());
+            Machine {
+                bus,
+                cpu,
+                video : Rc::new(RefCell::new(FrameBuffer::new(WIDTH, HEIGHT))),
+                audio : Rc::new(RefCell::new(Mixer::new(SAMPLE_RATE))),
+            }
+        };
+
+        {
+            let mut bus = machine.bus.borrow_mut();
+            for (address, bytes) in profile.roms.iter() {
+                let rom = Rom::new(*address, bytes.clone());
+                let region = rom.region();
+                bus.map_device(Rc::new(RefCell::new(Device::Rom(rom))), vec![region], 1);
+            }
+            for device in profile.devices.iter() {
+                let (device, region) = match device {
+                    IoDevice::Serial(base) => {
+                        let serial = Serial::new(*base);
+                        let region = serial.region();
+                        (Device::Serial(serial), region)
+                    },
+                    IoDevice::Pia(base) => {
+                        let pia = Pia::new(*base);
+                        let region = pia.region();
+                        (Device::Pia(pia), region)
+                    },
+                    IoDevice::Via(base) => {
+                        let via = Via::new(*base);
+                        let region = via.region();
+                        (Device::Via(via), region)
+                    },
+                    IoDevice::Acia(base, bridge) => {
+                        let acia = Acia::new(*base, bridge.clone());
+                        let region = acia.region();
+                        (Device::Acia(acia), region)
+                    },
+                    IoDevice::Plugin(plugin) => {
+                        bus.map_device(Rc::new(RefCell::new(Device::Plugin(plugin.clone()))), plugin.regions(), 1);
+                        continue;
+                    },
+                };
+                bus.map_device(Rc::new(RefCell::new(device)), vec![region], 1);
+            }
+        }
+        machine.cpu.borrow_mut().reset();
+        machine
+    }
+
+    // input devices sit above ram so the ports are not shadowed by it, the second port
+    // sees the $4016 strobe writes too, its reads at $4016 never reach it since the first
+    // port's controller is mapped before it, the same way the apu only gets the $4017
+    // writes : inputs always go back where they were, ahead of it
+    fn plug(bus: &mut Bus<'a>, peripheral: Peripheral) {
+        let at = bus.devices.iter().position(|mapping| Machine::is_input(&mapping.device.borrow())).unwrap_or(bus.devices.len());
+        bus.devices.retain(|mapping| !Machine::is_input(&mapping.device.borrow()));
+
+        let input = |device: Device<'a>, region: RangeInclusive<u16>| Mapping {
+            device   : Rc::new(RefCell::new(device)),
+            regions  : vec![region],
+            priority : 1,
+        };
+        let inputs = match peripheral {
+            Peripheral::FourScore => vec![input(Device::FourScore(FourScore::new()), 0x4016..=0x4017)],
+            Peripheral::Controller => vec![
+                input(Device::Controller(Controller::on_port(0)), 0x4016..=0x4016),
+                input(Device::Controller(Controller::on_port(1)), 0x4016..=0x4017),
+            ],
+            Peripheral::Zapper => vec![
+                input(Device::Controller(Controller::on_port(0)), 0x4016..=0x4016),
+                input(Device::Zapper(Zapper::new()), 0x4017..=0x4017),
+            ],
+        };
+        bus.devices.splice(at..at, inputs);
+    }
+
+    fn is_input(device: &Device) -> bool {
+        matches!(device, Device::Controller(_) | Device::Zapper(_) | Device::FourScore(_))
+    }
+
+    // a device from outside the crate, on the regions given or when there are none the ones
+    // it asks for, above ram like the built in ones. returns its place on the bus
+    pub fn attach(&self, plugin: Box<dyn Plugin>, regions: Vec<RangeInclusive<u16>>) -> usize {
+        let regions = if regions.is_empty() { plugin.regions() } else { regions };
+        self.bus.borrow_mut().map_device(Rc::new(RefCell::new(Device::Plugin(plugin))), regions, 1)
+    }
+
+    pub fn connect(&self, peripheral: Peripheral) {
+        Machine::plug(&mut self.bus.borrow_mut(), peripheral);
+    }
+
+    pub fn peripheral(&self) -> Peripheral {
+        self.bus.borrow().devices.iter()
+            .find_map(|mapping| match &*mapping.device.borrow() {
+                Device::Zapper(_) => Some(Peripheral::Zapper),
+                Device::FourScore(_) => Some(Peripheral::FourScore),
+                _ => None,
+            })
+            .unwrap_or(Peripheral::Controller)
+    }
+
+    // the ppu's dot when the cpu starts, 0 to timing::ALIGNMENTS - 1, chosen at power on
+    pub fn align(&self, alignment: u8) {
+        self.cpu.borrow_mut().alignment = alignment % timing::ALIGNMENTS;
+        for mapping in self.bus.borrow().devices.iter() {
+            if let Device::Ppu(ppu) = &mut *mapping.device.borrow_mut() {
+                ppu.alignment = alignment % timing::ALIGNMENTS;
+            }
+        }
+    }
+
+    // what of the ppu fades when left alone, the config's [ppu] section
+    pub fn set_decay(&self, decay: Decay) {
+        for mapping in self.bus.borrow().devices.iter() {
+            if let Device::Ppu(ppu) = &mut *mapping.device.borrow_mut() {
+                ppu.decay = decay;
+            }
+        }
+    }
+
+    // whether a dmc fetch landing on a read of $4016 / $4017 repeats it as the console does,
+    // off to rule out lost button bits while debugging input code
+    pub fn set_read_glitch(&self, on: bool) {
+        for mapping in self.bus.borrow().devices.iter() {
+            if let Device::Apu(apu) = &mut *mapping.device.borrow_mut() {
+                apu.read_glitch = on;
+            }
+        }
+    }
+
+    // memory and the program are kept, only the cpu goes through the reset sequence
+    pub fn soft_reset(&self) {
+        self.cpu.borrow_mut().soft_reset();
+    }
+
+    // pads 0 and 1 are the ports, 2 and 3 only exist behind a four score,
+    // a zapper on the second port takes the A button as its trigger
+    pub fn set_buttons(&self, port: u16, buttons: u8) {
+        for mapping in self.bus.borrow().devices.iter() {
+            match &mut *mapping.device.borrow_mut() {
+                Device::Controller(controller) if controller.port == port => controller.buttons = buttons,
+                Device::Zapper(zapper) if port == 1 => zapper.trigger = buttons & Button::A as u8 != 0,
+                Device::FourScore(four_score) if port < 4 => four_score.buttons[port as usize] = buttons,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn zapper(&self) -> Option<Zapper> {
+        self.bus.borrow().devices.iter().find_map(|mapping| match &*mapping.device.borrow() {
+            Device::Zapper(zapper) => Some(zapper.clone()),
+            _ => None,
+        })
+    }
+
+    pub fn aim_zapper(&self, aim: Option<(u16, u16)>) {
+        for mapping in self.bus.borrow().devices.iter() {
+            if let Device::Zapper(zapper) = &mut *mapping.device.borrow_mut() {
+                zapper.aim = aim;
+            }
+        }
+    }
+
+    // the inserted disk side and the number of sides, when a disk system is plugged in
+    pub fn disk(&self) -> Option<(Option<usize>, usize)> {
+        self.bus.borrow().devices.iter().find_map(|mapping| match &*mapping.device.borrow() {
+            Device::Fds(fds) => Some((fds.side, fds.disks.len())),
+            _ => None,
+        })
+    }
+
+    // ejected, side A, side B, ... and back to ejected
+    pub fn flip_disk(&self) {
+        for mapping in self.bus.borrow().devices.iter() {
+            if let Device::Fds(fds) = &mut *mapping.device.borrow_mut() {
+                let next = match fds.side {
+                    None => Some(0),
+                    Some(side) => Some(side + 1),
+                };
+                fds.insert(next);
+            }
+        }
+    }
+
+    // everything the serial ports and displays printed, in mapping order
+    pub fn terminal_output(&self) -> Option<Vec<u8>> {
+        let outputs = self.bus.borrow().devices.iter()
+            .filter_map(|mapping| match &*mapping.device.borrow() {
+                Device::Serial(serial) => Some(serial.output.clone()),
+                Device::Pia(pia) => Some(pia.display.clone()),
+                Device::Acia(acia) => Some(acia.output.clone()),
+                _ => None,
+            })
+            .collect::<Vec<Vec<u8>>>();
+        if outputs.is_empty() { None } else { Some(outputs.concat()) }
+    }
+
+    // how many bytes terminal_output would give, without copying them
+    pub fn terminal_length(&self) -> usize {
+        self.bus.borrow().devices.iter()
+            .map(|mapping| match &*mapping.device.borrow() {
+                Device::Serial(serial) => serial.output.len(),
+                Device::Pia(pia) => pia.display.len(),
+                Device::Acia(acia) => acia.output.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    // the banks the inserted board switches, as it has them now
+    pub fn banks(&self) -> Vec<Bank> {
+        self.bus.borrow().devices.iter()
+            .flat_map(|mapping| mapping.device.borrow().banks())
+            .collect()
+    }
+
+    // typed into the first serial port or keyboard
+    pub fn type_text(&self, text: &str) {
+        for mapping in self.bus.borrow().devices.iter() {
+            match &*mapping.device.borrow() {
+                Device::Serial(serial) => serial.type_text(text),
+                Device::Pia(pia) => pia.type_text(text),
+                Device::Acia(acia) => acia.type_text(text),
+                _ => continue,
+            }
+            return;
+        }
+    }
+
+    // one cpu cycle, returns true when the beam entered vblank and the frame buffers were swapped
+    pub fn tick(&self) -> bool {
+        let mut cpu = self.cpu.borrow_mut();
+        let before = cpu.dots();
+        cpu.tick();
+        let after = cpu.dots();
+
+        if before / timing::DOTS_PER_SCANLINE != after / timing::DOTS_PER_SCANLINE {
+            let scanline = cpu.raster().scanline;
+            let video = self.video.borrow();
+            for mapping in self.bus.borrow().devices.iter() {
+                if let Device::Zapper(zapper) = &mut *mapping.device.borrow_mut() {
+                    zapper.sense(&video, scanline);
+                }
+            }
+        }
+
+        let cycles = cpu.total_cycles;
+        let mut audio = self.audio.borrow_mut();
+        if audio.is_due(cycles) {
+            let mut level = 0.0;
+            let mut channels = Vec::new();
+            // sample points double as the catch up points of devices with their own clock
+            for mapping in self.bus.borrow().devices.iter() {
+                let mut device = mapping.device.borrow_mut();
+                device.tick(cycles);
+                if let Some(source) = device.audio_source() {
+                    level += source.level();
+                    if audio.has_stems() {
+                        channels.extend(source.channels());
+                    }
+                }
+            }
+            audio.push_channels(&channels);
+            audio.push(level);
+        }
+
+        let vblank = timing::next_dot_at(before, timing::VBLANK_SCANLINE as u16, 1);
+        if vblank <= after {
+            self.video.borrow_mut().swap();
+            return true;
+        }
+        false
+    }
+
+    // run-ahead : a copy of the machine runs `frames` frames further with the same input,
+    // so the frame buffer shows a frame that far ahead and a button press shows up that many
+    // frames sooner. it starts over from the machine every frame, new input included. the
+    // sound and the frame count stay the machine's own
+    pub fn run_ahead(&self, frames: u32) {
+        if frames == 0 {
+            return;
+        }
+        let audio = self.audio.borrow().clone();
+        let frame = self.video.borrow().frame;
+        let ahead = self.clone();
+        for _ in 0..frames {
+            while !ahead.tick() {}
+        }
+        *self.audio.borrow_mut() = audio;
+        self.video.borrow_mut().frame = frame;
+    }
+
+    // a machine of its own from this one's state : unlike a clone, which shares the frame
+    // buffer and the mixer with the machine it was taken from as snapshots do, nothing it runs
+    // shows in this one's output, so both can run side by side in the same process
+    pub fn fork(&self) -> Machine<'a> {
+        let mut fork = self.clone();
+        fork.video = Rc::new(RefCell::new(self.video.borrow().clone()));
+        fork.audio = Rc::new(RefCell::new(self.audio.borrow().clone()));
+        fork
+    }
+
+    // finishes the current instruction then executes the next one
+    pub fn step(&self) {
+        while self.cpu.borrow().cycle > 0 {
+            self.tick();
+        }
+        self.tick();
+    }
+}
+
+impl Default for Machine<'_> {
+    fn default() -> Self {
+        Machine::new()
+    }
+}
+
+impl<'a> Clone for Machine<'a> {
+    fn clone(&self) -> Self {
+        let bus = self.bus.borrow().clone_state();
+        let mut cpu = self.cpu.borrow().clone();
+        cpu.bus = Some(bus.clone());
+        Machine {
+            bus,
+            cpu   : Rc::new(RefCell::new(cpu)),
+            video : self.video.clone(),
+            audio : self.audio.clone(),
+        }
+    }
+}
+
+impl Snapshot for Machine<'_> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.cpu.borrow().save(out);
+        self.bus.borrow().save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.cpu.borrow_mut().restore(input)?;
+        self.bus.borrow_mut().restore(input)
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::{Error, ErrorKind};
+
+use super::cartridge::Cartridge;
+use super::device::Device;
+use super::vrc6::{self, Vrc6, Vrc6Variant};
+use crate::loader::ines::RomImage;
+
+const PRG_BANK: usize = 0x4000;
+const PRG_RAM_WINDOW: usize = 0x2000; // $6000 - $7FFF
+
+// which memory a bank window maps, the cpu's or the ppu's
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BankMemory {
+    Prg,
+    Chr,
+}
+
+// a window of a board's memory and the bank it shows, as the board's registers set it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bank {
+    pub memory  : BankMemory,
+    pub address : u16, // where the window starts, in the cpu's or the ppu's address space
+    pub bank    : u16,
+}
+
+// the ram a board carries next to its roms, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoardRam {
+    pub prg : Option<usize>, // None for the board's usual amount
+    pub chr : usize,         // allocated when there is no chr rom
+}
+
+impl BoardRam {
+    pub fn of(image: &RomImage) -> BoardRam {
+        BoardRam { prg: image.prg_ram, chr: image.chr_ram }
+    }
+}
+
+// the cartridge device for a dumped board, by iNES mapper number
+pub fn create<'a>(mapper: u16, prg: Vec<u8>, chr: Vec<u8>, ram: BoardRam) -> Result<Device<'a>, Error> {
+    let chr = if chr.is_empty() { vec![0; ram.chr] } else { chr };
+    match mapper {
+        // NROM : 16k images are mirrored into both halves, prg ram only when the header asks
+        0 if !prg.is_empty() => {
+            let low = prg[..PRG_BANK.min(prg.len())].to_vec();
+            let high = prg[prg.len().saturating_sub(PRG_BANK)..].to_vec();
+            let prg_ram = vec![0; ram.prg.unwrap_or(0).min(PRG_RAM_WINDOW)];
+            Ok(Device::Cartridge(Cartridge::new(vec![(0x6000, prg_ram), (0x8000, low), (0xC000, high)])))
+        },
+        24 => Ok(Device::Vrc6(Vrc6::new(Vrc6Variant::A, prg, chr, ram.prg.unwrap_or(vrc6::PRG_RAM_SIZE)))),
+        26 => Ok(Device::Vrc6(Vrc6::new(Vrc6Variant::B, prg, chr, ram.prg.unwrap_or(vrc6::PRG_RAM_SIZE)))),
+        _ => Err(Error::new(ErrorKind::InvalidData, format!("Unsupported mapper {}", mapper))),
+    }
+}
@@ -0,0 +1,35 @@
+use crate::loader::ines::Mirroring;
+
+// the console's 2k of nametable ram, four screen boards add 2k of their own
+pub const CONSOLE_VRAM     : usize = 0x0800;
+pub const FOUR_SCREEN_VRAM : usize = 0x1000;
+const NAMETABLE            : usize = 0x0400;
+
+// the layout in effect : a four screen board has its own ram for all four tables whatever its
+// mapper says, otherwise a board switching the layout itself wins over the header's
+pub fn effective(board: Option<Mirroring>, header: Mirroring) -> Mirroring {
+    match (header, board) {
+        (Mirroring::FourScreen, _) => Mirroring::FourScreen,
+        (_, Some(board)) => board,
+        (header, None) => header,
+    }
+}
+
+pub fn vram_size(mirroring: Mirroring) -> usize {
+    if mirroring == Mirroring::FourScreen { FOUR_SCREEN_VRAM } else { CONSOLE_VRAM }
+}
+
+// where a ppu address in $2000 - $3EFF lands in the nametable ram, the four 1k tables at
+// $2000, $2400, $2800 and $2C00 folded onto the pages the layout gives them
+pub fn vram_offset(mirroring: Mirroring, address: u16) -> usize {
+    let address = address.wrapping_sub(0x2000) as usize & 0x0FFF;
+    let (table, offset) = (address / NAMETABLE, address % NAMETABLE);
+    let page = match mirroring {
+        Mirroring::Horizontal => table / 2,
+        Mirroring::Vertical => table % 2,
+        Mirroring::SingleScreenLow => 0,
+        Mirroring::SingleScreenHigh => 1,
+        Mirroring::FourScreen => table,
+    };
+    page * NAMETABLE + offset
+}
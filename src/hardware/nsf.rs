@@ -0,0 +1,118 @@
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use crate::loader::nsf::{NsfImage, EXPANSION_VRC6};
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, mismatch, put_u8, take_bytes, take_u8};
+use super::vrc6::Vrc6Audio;
+use super::inspect::{DebugState, StateNode};
+
+const BANK_SIZE : usize = 0x1000;
+const RAM_SIZE  : usize = 0x2000;
+
+// the board an nsf player provides : 8k of ram at $6000 and the tune's data in 4k
+// banks at $8000 - $FFFF, switched through $5FF8 - $5FFF, files without bankswitching
+// get their data laid out from the load address, expansion sound chips take their
+// register writes in the rom area
+#[derive(Debug, Clone)]
+pub struct Nsf {
+    pub data         : Vec<u8>,
+    pub banks        : [u8; 8],
+    pub bankswitched : bool,
+    pub ram          : Vec<u8>,
+    pub vrc6         : Option<Vrc6Audio>,
+}
+
+impl Nsf {
+    pub fn new(image: &NsfImage) -> Nsf {
+        let bankswitched = image.bankswitched();
+        let padding = if bankswitched { image.load as usize % BANK_SIZE } else { (image.load as usize).saturating_sub(0x8000) };
+        let mut data = vec![0; padding];
+        data.extend_from_slice(&image.data);
+        data.resize(data.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+
+        Nsf {
+            data,
+            banks        : if bankswitched { image.banks } else { [0, 1, 2, 3, 4, 5, 6, 7] },
+            bankswitched,
+            ram          : vec![0; RAM_SIZE],
+            vrc6         : if image.expansion & EXPANSION_VRC6 != 0 { Some(Vrc6Audio::default()) } else { None },
+        }
+    }
+
+    pub fn regions() -> Vec<RangeInclusive<u16>> {
+        vec![0x5FF8..=0x5FFF, 0x6000..=0xFFFF]
+    }
+}
+
+impl DeviceOps for Nsf {
+    fn within_range(&self, addr: u16) -> bool {
+        addr >= 0x5FF8
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.ram[addr as usize - 0x6000],
+            0x8000..=0xFFFF => {
+                let slot = (addr as usize - 0x8000) / BANK_SIZE;
+                let bank = self.banks[slot] as usize;
+                self.data.get(bank * BANK_SIZE + addr as usize % BANK_SIZE).copied().unwrap_or(0)
+            },
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5FF8..=0x5FFF if self.bankswitched => self.banks[addr as usize - 0x5FF8] = value,
+            0x6000..=0x7FFF => self.ram[addr as usize - 0x6000] = value,
+            0x8000..=0xFFFF => if let Some(vrc6) = self.vrc6.as_mut() {
+                vrc6.write(addr & 0xF003, value);
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for Nsf {
+    fn save(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.ram);
+        self.banks.iter().for_each(|bank| put_u8(out, *bank));
+        put_u8(out, self.vrc6.is_some() as u8);
+        if let Some(vrc6) = &self.vrc6 {
+            vrc6.save(out);
+        }
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.ram.copy_from_slice(take_bytes(input, RAM_SIZE)?);
+        for bank in self.banks.iter_mut() {
+            *bank = take_u8(input)?;
+        }
+        if (take_u8(input)? != 0) != self.vrc6.is_some() {
+            return Err(mismatch("expansion audio"));
+        }
+        match self.vrc6.as_mut() {
+            Some(vrc6) => vrc6.restore(input),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DebugState for Nsf {
+    fn debug_state(&self) -> Vec<StateNode> {
+        let mut state = vec![
+            StateNode::bytes("data", &self.data),
+            StateNode::value("bankswitched", self.bankswitched),
+            StateNode::group("banks", self.banks.iter().enumerate()
+                .map(|(index, bank)| StateNode::byte(&format!("${:04X}", 0x8000 + index * 0x1000), *bank))
+                .collect()),
+            StateNode::bytes("ram", &self.ram),
+        ];
+        if let Some(vrc6) = &self.vrc6 {
+            state.push(StateNode::group("vrc6 audio", vrc6.debug_state()));
+        }
+        state
+    }
+}
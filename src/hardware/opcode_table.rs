@@ -0,0 +1,7 @@
+use super::address_mode::AddressMode;
+use super::instructions::Instructions;
+use super::opcodes::Opcode;
+
+// every opcode, generated by build.rs from src/instructions.txt : the cpu runs from this table
+// and the disassembler reads it, so the two cannot disagree
+include!(concat!(env!("OUT_DIR"), "/opcode_table.rs"));
@@ -1,7 +1,7 @@
 use std::{fmt::Display};
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Opcode {
     BRK, ORA, ASL, PHP, BPL, 
     CLC, JSR, AND, BIT, ROL, 
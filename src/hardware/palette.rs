@@ -0,0 +1,97 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+// the 64 colors the ppu can put out, as 0x00RRGGBB. the built in palettes are decoded from
+// the 2C02's composite signal the way a tv does : each color is a square wave between two
+// voltage levels, the luminance its average and the hue its phase against the color burst.
+// tvs differ in the axes their demodulators use, which is all that tells the palettes apart.
+// others (FBX, Nestopia's, ...) are loaded from their .pal files
+pub const COLORS        : usize = 64;
+// a .pal file has the 64 colors as rgb bytes, or 8 sets of them, one per emphasis combination
+const PAL_SIZE          : usize = COLORS * 3;
+const PAL_SIZE_EMPHASIS : usize = PAL_SIZE * 8;
+
+// voltages relative to sync, measured on a 2C02 : the low and high level of each luminance
+const LOW   : [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const HIGH  : [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK : f32 = 0.518;
+const WHITE : f32 = 1.962;
+
+// a demodulator axis : its angle from B-Y in degrees and its gain
+type Axis = (f32, f32);
+
+// R-Y, G-Y and B-Y axes
+const BUILT_IN : [(&str, [Axis; 3]); 2] = [
+    // the fcc's, an ideal decoder
+    ("2c02", [(90.0, 1.140), (235.8, 0.703), (0.0, 2.032)]),
+    // the us mode of the chip in many sony trinitrons, hotter reds and a wider green axis
+    ("cxa2025as", [(112.0, 1.687), (252.0, 0.610), (0.0, 2.032)]),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name   : String,
+    pub colors : [u32; COLORS],
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::decoded(BUILT_IN[0].0, &BUILT_IN[0].1)
+    }
+}
+
+impl Palette {
+    pub fn built_in() -> Vec<&'static str> {
+        BUILT_IN.iter().map(|(name, _)| *name).collect()
+    }
+
+    // a built in palette by name, otherwise a .pal file
+    pub fn named(name: &str) -> Result<Palette, Error> {
+        let name = name.trim();
+        match BUILT_IN.iter().find(|(built_in, _)| built_in.eq_ignore_ascii_case(name)) {
+            Some((built_in, axes)) => Ok(Palette::decoded(built_in, axes)),
+            None => Palette::load(name),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Palette, Error> {
+        let bytes = fs::read(path).map_err(|err| Error::new(err.kind(), format!("Palette {}: {}", path, err)))?;
+        if bytes.len() != PAL_SIZE && bytes.len() != PAL_SIZE_EMPHASIS {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid palette file, {} bytes instead of {} or {}", bytes.len(), PAL_SIZE, PAL_SIZE_EMPHASIS)));
+        }
+        let mut colors = [0; COLORS];
+        for (color, rgb) in colors.iter_mut().zip(bytes.chunks(3)) {
+            *color = (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32;
+        }
+        Ok(Palette { name : path.to_string(), colors })
+    }
+
+    fn decoded(name: &str, axes: &[Axis; 3]) -> Palette {
+        let mut colors = [0; COLORS];
+        for (index, color) in colors.iter_mut().enumerate() {
+            let hue = index & 0x0F;
+            // $xE and $xF are black whatever the luminance
+            let level = if hue > 0x0D { 1 } else { index >> 4 };
+            let low = if hue == 0x00 { HIGH[level] } else { LOW[level] };
+            let high = if hue < 0x0D { HIGH[level] } else { LOW[level] };
+            // twelve samples per subcarrier cycle, the wave is high for six of them starting
+            // at a phase set by the hue, $x8 is in phase with the burst
+            let samples = (0..12).map(|phase| {
+                let voltage = if (hue + phase) % 12 < 6 { high } else { low };
+                ((voltage - BLACK) / (WHITE - BLACK), -(PI / 6.0) * (phase as f32 - 0.5))
+            }).collect::<Vec<(f32, f32)>>();
+            let luma = samples.iter().map(|(signal, _)| signal).sum::<f32>() / 12.0;
+            let channel = |(angle, gain): Axis| {
+                let difference = samples.iter().map(|(signal, phase)| signal * (phase - angle.to_radians()).cos()).sum::<f32>() / 6.0;
+                ((luma + gain * difference) * 255.0).round().clamp(0.0, 255.0) as u32
+            };
+            *color = channel(axes[0]) << 16 | channel(axes[1]) << 8 | channel(axes[2]);
+        }
+        Palette { name : name.to_string(), colors }
+    }
+
+    pub fn color(&self, index: u8) -> u32 {
+        self.colors[index as usize % COLORS]
+    }
+}
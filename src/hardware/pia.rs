@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u8, put_u32, take_bytes, take_u8, take_u32};
+use super::inspect::{DebugState, StateNode};
+
+// what is printed is kept up to this, older output is dropped
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+// control register bits : 2 picks the data register over the direction register,
+// 7 is the strobe flag, the rest only configure the interrupt lines
+const CONTROL_DATA   : u8 = 0x04;
+const CONTROL_STROBE : u8 = 0x80;
+const CONTROL_MASK   : u8 = 0x3F;
+
+// a 6821 wired the way the apple I wires it : port A reads the keyboard, with bit 7 always
+// set and the strobe flag up while a key waits, port B writes to the display, which is
+// never busy, registers are port A, control A, port B, control B from base
+#[derive(Debug, Clone, Default)]
+pub struct Pia {
+    pub base      : u16,
+    pub ddr_a     : u8,
+    pub ddr_b     : u8,
+    pub control_a : u8,
+    pub control_b : u8,
+    pub output_b  : u8,
+    pub keys      : RefCell<VecDeque<u8>>,
+    pub display   : Vec<u8>,
+}
+
+impl Pia {
+    pub fn new(base: u16) -> Pia {
+        Pia { base, ..Default::default() }
+    }
+
+    pub fn region(&self) -> RangeInclusive<u16> {
+        self.base..=self.base.wrapping_add(3)
+    }
+
+    // the keyboard only has capitals
+    pub fn type_text(&self, text: &str) {
+        self.keys.borrow_mut().extend(text.bytes().map(|key| key.to_ascii_uppercase()));
+    }
+
+    fn control_a(&self) -> u8 {
+        let strobe = if self.keys.borrow().is_empty() { 0 } else { CONTROL_STROBE };
+        self.control_a | strobe
+    }
+
+    fn key(&self) -> u8 {
+        self.keys.borrow().front().copied().unwrap_or(0) | 0x80
+    }
+}
+
+impl DeviceOps for Pia {
+    fn within_range(&self, addr: u16) -> bool {
+        self.region().contains(&addr)
+    }
+
+    // reading port A takes the key and drops the strobe
+    fn read(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        if addr.wrapping_sub(self.base) == 0 && self.control_a & CONTROL_DATA != 0 {
+            self.keys.borrow_mut().pop_front();
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr.wrapping_sub(self.base) {
+            0 if self.control_a & CONTROL_DATA == 0 => self.ddr_a = value,
+            1 => self.control_a = value & CONTROL_MASK,
+            2 if self.control_b & CONTROL_DATA == 0 => self.ddr_b = value,
+            2 => {
+                self.output_b = value;
+                self.display.push(value & 0x7F);
+                if self.display.len() > OUTPUT_LIMIT {
+                    self.display.drain(..OUTPUT_LIMIT / 2);
+                }
+            },
+            3 => self.control_b = value & CONTROL_MASK,
+            _ => {},
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr.wrapping_sub(self.base) {
+            0 if self.control_a & CONTROL_DATA == 0 => self.ddr_a,
+            0 => self.key(),
+            1 => self.control_a(),
+            2 if self.control_b & CONTROL_DATA == 0 => self.ddr_b,
+            2 => self.output_b & 0x7F,
+            _ => self.control_b,
+        }
+    }
+}
+
+impl Snapshot for Pia {
+    fn save(&self, out: &mut Vec<u8>) {
+        for value in [self.ddr_a, self.ddr_b, self.control_a, self.control_b, self.output_b] {
+            put_u8(out, value);
+        }
+        let keys = self.keys.borrow();
+        put_u32(out, keys.len() as u32);
+        out.extend(keys.iter());
+        put_u32(out, self.display.len() as u32);
+        out.extend_from_slice(&self.display);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        for value in [&mut self.ddr_a, &mut self.ddr_b, &mut self.control_a, &mut self.control_b, &mut self.output_b] {
+            *value = take_u8(input)?;
+        }
+        let length = take_u32(input)? as usize;
+        *self.keys.borrow_mut() = take_bytes(input, length)?.iter().copied().collect();
+        let length = take_u32(input)? as usize;
+        self.display = take_bytes(input, length)?.to_vec();
+        Ok(())
+    }
+}
+
+impl DebugState for Pia {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::word("base", self.base),
+            StateNode::byte("ddr a", self.ddr_a),
+            StateNode::byte("ddr b", self.ddr_b),
+            StateNode::byte("control a", self.control_a),
+            StateNode::byte("control b", self.control_b),
+            StateNode::byte("output b", self.output_b),
+            StateNode::value("keys queued", self.keys.borrow().len()),
+            StateNode::bytes("display", &self.display),
+        ]
+    }
+}
@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+
+use super::{audio::AudioSource, inspect::DebugState, interfaces::{Clocked, DeviceOps}, snapshot::Snapshot};
+
+// peripherals from outside the crate (a sound chip, an sd card interface) : DeviceOps for
+// the bus (read, write, peek), Snapshot for savestates and rewind, DebugState for the state
+// explorer, and the optional parts the built in devices have, a clock, an irq output and a
+// sound output. a plugin is attached with Machine::attach, or registered under a kind so
+// machine descriptions can place it with `plugin = "kind $address options"`
+pub trait Plugin: DeviceOps + Snapshot + DebugState + PluginClone {
+    // shown in logs and the state explorer, and checked when a savestate is restored
+    fn name(&self) -> &'static str;
+
+    // where it is mapped when placed by a description or attached without regions
+    fn regions(&self) -> Vec<RangeInclusive<u16>>;
+
+    fn clocked(&mut self) -> Option<&mut dyn Clocked> {
+        None
+    }
+
+    fn audio_source(&mut self) -> Option<&mut dyn AudioSource> {
+        None
+    }
+
+    fn irq(&self) -> bool {
+        false
+    }
+}
+
+// machines are cloned for run-ahead, rewind and netplay, a plugin with them
+pub trait PluginClone {
+    fn box_clone(&self) -> Box<dyn Plugin>;
+}
+
+impl<T: Plugin + Clone + 'static> PluginClone for T {
+    fn box_clone(&self) -> Box<dyn Plugin> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Plugin> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+impl fmt::Debug for dyn Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Plugin({})", self.name())
+    }
+}
+
+// builds a plugin from the base address and the rest of its description entry
+pub type Constructor = fn(base: u16, options: &str) -> Result<Box<dyn Plugin>, Error>;
+
+thread_local! {
+    static REGISTRY : RefCell<HashMap<String, Constructor>> = RefCell::new(HashMap::new());
+}
+
+// registering a kind again replaces its constructor
+pub fn register(kind: &str, constructor: Constructor) {
+    REGISTRY.with(|registry| registry.borrow_mut().insert(kind.to_string(), constructor));
+}
+
+pub fn registered() -> Vec<String> {
+    let mut kinds = REGISTRY.with(|registry| registry.borrow().keys().cloned().collect::<Vec<String>>());
+    kinds.sort();
+    kinds
+}
+
+pub fn build(kind: &str, base: u16, options: &str) -> Result<Box<dyn Plugin>, Error> {
+    let constructor = REGISTRY.with(|registry| registry.borrow().get(kind).copied())
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No device plugin registered as {}, known : {}", kind, registered().join(", "))))?;
+    constructor(base, options)
+}
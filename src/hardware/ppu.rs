@@ -0,0 +1,300 @@
+use std::cell::Cell;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::{Clocked, DeviceOps};
+use super::snapshot::{Snapshot, put_u8, put_u64, take_bytes, take_u8, take_u64};
+use super::inspect::{DebugState, StateNode};
+use super::timing::{self, DOTS_PER_FRAME, DOTS_PER_SCANLINE, VBLANK_SCANLINE};
+
+// the ppu's registers as the cpu sees them, without rendering : $2000 and $2001 as written,
+// the vblank flag of $2002 following the beam, nmi at its start when $2000 bit 7 allows it,
+// sprite memory through $2003 and $2004. the registers repeat every 8 bytes up to $3FFF,
+// write only ones read back the io latch
+const PRE_RENDER_SCANLINE : u64 = 261;
+// the flag is set on dot 1 of the first vblank line and cleared on dot 1 of the pre-render one
+const SET_DOT             : u64 = VBLANK_SCANLINE * DOTS_PER_SCANLINE + 1;
+const CLEAR_DOT           : u64 = PRE_RENDER_SCANLINE * DOTS_PER_SCANLINE + 1;
+// the nmi output follows the flag this many dots late, reads of $2002 inside that window
+// clear the flag before the cpu sees the nmi
+const NMI_DELAY           : u64 = 2;
+
+// a bit of the io latch fades to 0 about 600ms after it was last driven
+const LATCH_DECAY         : u64 = 36 * DOTS_PER_FRAME;
+// sprite memory is dram refreshed by rendering, a row left alone longer than this while
+// rendering is off loses its contents, read back here as $FF where real chips settle on no
+// fixed pattern
+const OAM_DECAY           : u64 = 3000 * timing::DOTS_PER_CPU_CYCLE;
+const OAM_SIZE            : usize = 256;
+const OAM_ROW             : usize = 8;
+const OAM_ROWS            : usize = OAM_SIZE / OAM_ROW;
+
+const NMI_ENABLE : u8 = 0x80;
+const VBLANK     : u8 = 0x80;
+const RENDERING  : u8 = 0x18; // $2001 background and sprites
+// the bits $2002 drives onto the io latch, the others are left to fade
+const STATUS_BITS : u8 = 0xE0;
+// bits 2 - 4 of a sprite's attribute byte do not exist and read 0
+const ATTRIBUTE_BITS : u8 = 0xE3;
+
+// what fades when left alone : the io latch is on by default, sprite memory off, a game
+// relying on it is rare and tests probe it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decay {
+    pub open_bus : bool,
+    pub oam      : bool,
+}
+
+impl Default for Decay {
+    fn default() -> Self {
+        Decay { open_bus : true, oam : false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    pub ctrl      : u8, // $2000
+    pub mask      : u8, // $2001
+    pub alignment : u8, // the dot the ppu was at when the cpu started, see timing::ALIGNMENTS
+    pub decay     : Decay,
+    pub oam       : [u8; OAM_SIZE],
+    pub oam_address : u8, // $2003
+    vblank        : Cell<bool>,
+    latch         : Cell<u8>,
+    latch_driven  : Cell<[u64; 8]>,        // the dot each bit of the latch was last driven on
+    oam_refreshed : Cell<[u64; OAM_ROWS]>, // the dot each row of sprite memory was last touched on
+    suppressed    : Cell<Option<u64>>, // the frame whose flag a read one dot early kept from being set
+    set_at        : u64,               // the dot the flag was last set on
+    dots          : u64,               // caught up to
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu {
+            ctrl       : 0,
+            mask       : 0,
+            alignment  : 0,
+            decay      : Decay::default(),
+            oam        : [0; OAM_SIZE],
+            oam_address : 0,
+            vblank     : Cell::new(false),
+            latch      : Cell::new(0),
+            latch_driven  : Cell::new([0; 8]),
+            oam_refreshed : Cell::new([0; OAM_ROWS]),
+            suppressed : Cell::new(None),
+            set_at     : 0,
+            dots       : 0,
+        }
+    }
+
+    pub fn regions() -> Vec<RangeInclusive<u16>> {
+        vec![0x2000..=0x3FFF]
+    }
+
+    // the level of the ppu's nmi output, the line latches its rising edge
+    pub fn nmi(&self) -> bool {
+        self.ctrl & NMI_ENABLE != 0 && self.vblank.get() && self.dots >= self.set_at + NMI_DELAY
+    }
+
+    // the last time the beam went through dot `at` of a frame, after `after` and up to `until`
+    fn passed(at: u64, after: u64, until: u64) -> Option<u64> {
+        let frame_start = until - until % DOTS_PER_FRAME;
+        let event = if frame_start + at <= until { frame_start + at } else { (frame_start + at).checked_sub(DOTS_PER_FRAME)? };
+        if event > after { Some(event) } else { None }
+    }
+
+    // the io latch as read back, bits not driven for a while faded
+    pub fn latch(&self) -> u8 {
+        if !self.decay.open_bus {
+            return self.latch.get();
+        }
+        let driven = self.latch_driven.get();
+        (0..8).filter(|bit| self.dots < driven[*bit] + LATCH_DECAY)
+            .fold(0, |value, bit| value | self.latch.get() & 1 << bit)
+    }
+
+    // a register access puts a value on the latch, `bits` of it driven
+    fn drive(&self, value: u8, bits: u8) {
+        let mut driven = self.latch_driven.get();
+        for (bit, at) in driven.iter_mut().enumerate().filter(|(bit, _)| bits & 1 << bit != 0) {
+            *at = self.dots;
+            self.latch.set(self.latch.get() & !(1 << bit) | value & 1 << bit);
+        }
+        self.latch_driven.set(driven);
+    }
+
+    fn status(&self) -> u8 {
+        // the low bits are whatever the io latch last held
+        let vblank = if self.vblank.get() { VBLANK } else { 0 };
+        vblank | self.latch() & !STATUS_BITS
+    }
+
+    fn rendering(&self) -> bool {
+        self.mask & RENDERING != 0
+    }
+
+    fn touch_oam(&self, address: u8) {
+        let mut refreshed = self.oam_refreshed.get();
+        refreshed[address as usize / OAM_ROW] = self.dots;
+        self.oam_refreshed.set(refreshed);
+    }
+
+    fn oam_byte(&self, address: u8) -> u8 {
+        let value = self.oam[address as usize];
+        if address % 4 == 2 { value & ATTRIBUTE_BITS } else { value }
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
+
+// the flag is only looked at when the cpu reads it or samples nmi, so crossing both edges
+// between two looks leaves it as the later one put it
+impl Clocked for Ppu {
+    fn catch_up(&mut self, cycles: u64) {
+        let target = timing::dots(cycles) + self.alignment as u64;
+        if target <= self.dots {
+            return;
+        }
+        let set = Ppu::passed(SET_DOT, self.dots, target);
+        let clear = Ppu::passed(CLEAR_DOT, self.dots, target);
+        match (set, clear) {
+            (Some(set), clear) if clear.is_none_or(|clear| set > clear) => {
+                if self.suppressed.get() != Some(set / DOTS_PER_FRAME) {
+                    self.vblank.set(true);
+                }
+                self.set_at = set;
+            },
+            (_, Some(_)) => self.vblank.set(false),
+            _ => {},
+        }
+        self.dots = target;
+
+        // rendering refreshes every row, the ones left alone otherwise fade
+        let mut refreshed = self.oam_refreshed.get();
+        if self.rendering() {
+            refreshed = [target; OAM_ROWS];
+        } else if self.decay.oam {
+            for (row, at) in refreshed.iter_mut().enumerate().filter(|(_, at)| target >= **at + OAM_DECAY) {
+                self.oam[row * OAM_ROW..(row + 1) * OAM_ROW].fill(0xFF);
+                *at = target;
+            }
+        }
+        self.oam_refreshed.set(refreshed);
+    }
+}
+
+impl DeviceOps for Ppu {
+    fn within_range(&self, addr: u16) -> bool {
+        (0x2000..=0x3FFF).contains(&addr)
+    }
+
+    // reading $2002 one dot before the flag is set reads it clear and keeps it from being set
+    // that frame, reading it on the dot it is set or the next reads it set and clears it before
+    // the nmi gets out, both the way the vblank tests probe it
+    fn read(&self, addr: u16) -> u8 {
+        match addr & 7 {
+            2 => {},
+            4 => {
+                let value = self.oam_byte(self.oam_address);
+                self.touch_oam(self.oam_address);
+                self.drive(value, 0xFF);
+                return value;
+            },
+            _ => return self.latch(),
+        }
+        let frame_start = self.dots - self.dots % DOTS_PER_FRAME;
+        if self.dots + 1 == frame_start + SET_DOT {
+            self.suppressed.set(Some(frame_start / DOTS_PER_FRAME));
+        }
+        let value = self.status();
+        self.vblank.set(false);
+        self.drive(value, STATUS_BITS);
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.drive(value, 0xFF);
+        match addr & 7 {
+            0 => self.ctrl = value,
+            1 => {
+                // the rows are fresh from the rendering being turned off
+                if self.rendering() {
+                    self.oam_refreshed.set([self.dots; OAM_ROWS]);
+                }
+                self.mask = value;
+            },
+            3 => self.oam_address = value,
+            4 => {
+                self.oam[self.oam_address as usize] = value;
+                self.touch_oam(self.oam_address);
+                self.oam_address = self.oam_address.wrapping_add(1);
+            },
+            _ => {},
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr & 7 {
+            2 => self.status(),
+            4 => self.oam_byte(self.oam_address),
+            _ => self.latch(),
+        }
+    }
+}
+
+impl Snapshot for Ppu {
+    fn save(&self, out: &mut Vec<u8>) {
+        for value in [self.ctrl, self.mask, self.vblank.get() as u8, self.latch.get(), self.oam_address].iter() {
+            put_u8(out, *value);
+        }
+        out.extend_from_slice(&self.oam);
+        self.latch_driven.get().iter().for_each(|at| put_u64(out, *at));
+        self.oam_refreshed.get().iter().for_each(|at| put_u64(out, *at));
+        put_u64(out, self.suppressed.get().unwrap_or(u64::MAX));
+        put_u64(out, self.set_at);
+        put_u64(out, self.dots);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.ctrl = take_u8(input)?;
+        self.mask = take_u8(input)?;
+        self.vblank.set(take_u8(input)? != 0);
+        self.latch.set(take_u8(input)?);
+        self.oam_address = take_u8(input)?;
+        self.oam.copy_from_slice(take_bytes(input, OAM_SIZE)?);
+        let mut driven = [0; 8];
+        for at in driven.iter_mut() {
+            *at = take_u64(input)?;
+        }
+        self.latch_driven.set(driven);
+        let mut refreshed = [0; OAM_ROWS];
+        for at in refreshed.iter_mut() {
+            *at = take_u64(input)?;
+        }
+        self.oam_refreshed.set(refreshed);
+        self.suppressed.set(Some(take_u64(input)?).filter(|frame| *frame != u64::MAX));
+        self.set_at = take_u64(input)?;
+        self.dots = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Ppu {
+    fn debug_state(&self) -> Vec<StateNode> {
+        let position = timing::RasterPosition::from_dots(self.dots);
+        vec![
+            StateNode::byte("ctrl", self.ctrl),
+            StateNode::byte("mask", self.mask),
+            StateNode::value("vblank", self.vblank.get()),
+            StateNode::value("nmi", self.nmi()),
+            StateNode::byte("latch", self.latch()),
+            StateNode::byte("oam address", self.oam_address),
+            StateNode::bytes("oam", &self.oam),
+            StateNode::value("beam", format!("frame {} line {} dot {}", position.frame, position.scanline, position.dot)),
+        ]
+    }
+}
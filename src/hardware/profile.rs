@@ -0,0 +1,280 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::config::Config;
+use crate::loader::loader::parse_address;
+
+use super::audio::CPU_CLOCK;
+use super::bridge::{Bridge, Link};
+use super::plugin::{self, Plugin};
+
+#[derive(Debug, Clone)]
+pub enum IoDevice {
+    Serial(u16), // base address
+    Pia(u16),
+    Via(u16),
+    Acia(u16, Option<Rc<Bridge>>), // opened with the description, kept across power cycles
+    Plugin(Box<dyn Plugin>),       // built with the description, a copy goes on each machine
+}
+
+// what surrounds the cpu : the console by default, or a board read from a description
+// with its own clock, memory map, firmware and devices
+#[derive(Debug, Clone)]
+pub struct MachineProfile {
+    pub name         : String,
+    pub clock        : f64,  // cpu cycles per second
+    pub nes          : bool, // the console's ram, ports, apu and video timing underneath
+    pub ram          : Vec<RangeInclusive<u16>>,
+    pub roms         : Vec<(u16, Vec<u8>)>,
+    pub load_address : Option<u16>, // where programs go unless told otherwise
+    pub devices      : Vec<IoDevice>,
+}
+
+impl Default for MachineProfile {
+    fn default() -> Self {
+        MachineProfile::nes()
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
+
+// $0000-$3FFF
+fn parse_range(text: &str) -> Option<RangeInclusive<u16>> {
+    let (start, end) = text.split_once('-')?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if start > end {
+        return None;
+    }
+    Some(start..=end)
+}
+
+// $4000, 0x4000, 16384 or 16k
+fn parse_size(text: &str) -> Option<usize> {
+    let text = text.trim();
+    if let Some(digits) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        return usize::from_str_radix(digits, 16).ok();
+    }
+    match text.strip_suffix(['k', 'K']) {
+        Some(kilobytes) => kilobytes.trim().parse::<usize>().ok().map(|size| size * 1024),
+        None => text.parse().ok(),
+    }
+}
+
+// from a base address and a size, all of it inside the address space
+fn sized_range(base: u16, size: usize) -> Option<RangeInclusive<u16>> {
+    let end = base as usize + size.checked_sub(1)?;
+    if end > 0xFFFF {
+        return None;
+    }
+    Some(base..=end as u16)
+}
+
+// comma separated entries, empty when the key is missing
+fn list<'c>(config: &'c Config, key: &str) -> Vec<&'c str> {
+    config.get(key)
+        .map(|value| value.split(',').map(str::trim).filter(|entry| !entry.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+impl MachineProfile {
+    pub fn nes() -> MachineProfile {
+        MachineProfile {
+            name         : "nes".to_string(),
+            clock        : CPU_CLOCK,
+            nes          : true,
+            ram          : vec![0x0000..=0xFFFF],
+            roms         : Vec::new(),
+            load_address : None,
+            devices      : Vec::new(),
+        }
+    }
+
+    // the apple I with its 8k of ram split the usual way, the keyboard and display pia
+    // at $D010, and the top page as ram rather than the monitor rom, so programs can set
+    // their own reset vector, describe the board with the rom mapped at $FF00 to boot the monitor
+    pub fn apple1() -> MachineProfile {
+        MachineProfile {
+            name         : "apple1".to_string(),
+            clock        : 1_022_727.0,
+            nes          : false,
+            ram          : vec![0x0000..=0x0FFF, 0xE000..=0xEFFF, 0xFF00..=0xFFFF],
+            roms         : Vec::new(),
+            load_address : Some(0x0280),
+            devices      : vec![IoDevice::Pia(0xD010)],
+        }
+    }
+
+    // a built in name or a description file
+    pub fn load(name: &str) -> Result<MachineProfile, Error> {
+        match name.trim() {
+            "nes" => Ok(MachineProfile::nes()),
+            "apple1" => Ok(MachineProfile::apple1()),
+            path => {
+                let content = fs::read(path)?;
+                let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+                MachineProfile::parse(&String::from_utf8_lossy(&content), directory)
+            },
+        }
+    }
+
+    // a [machine] section :
+    //   name, clock (hz), nes (true keeps the console underneath),
+    //   ram = "$0000-$3FFF, ...", rom = "file@$E000, ..." (files relative to the description),
+    //   load_address, serial = "$5000, ...", pia = "$D010, ...", via = "$6000, ...",
+    //   acia = "$8000, $8010 pty, $8020 tcp:6551" (a link to the host after the address),
+    //   plugin = "kind $C000 options, ..." (devices registered with plugin::register)
+    // then a [device.<label>] section per device, see MachineProfile::parse_device
+    pub fn parse(content: &str, directory: &Path) -> Result<MachineProfile, Error> {
+        let config = Config::parse(content)?;
+        let nes = config.get_or("machine.nes", false);
+        let mut profile = MachineProfile {
+            name  : config.get_or("machine.name", "custom".to_string()),
+            clock : config.get_or("machine.clock", CPU_CLOCK),
+            nes,
+            ram   : if nes { vec![0x0000..=0xFFFF] } else { Vec::new() },
+            ..MachineProfile::nes()
+        };
+        if profile.clock.is_nan() || profile.clock <= 0.0 {
+            return Err(invalid(format!("machine clock must be positive: {}", profile.clock)));
+        }
+
+        for entry in list(&config, "machine.ram") {
+            profile.ram.push(parse_range(entry).ok_or_else(|| invalid(format!("machine ram: invalid range {}", entry)))?);
+        }
+        for entry in list(&config, "machine.rom") {
+            let (file, address) = entry.split_once('@')
+                .and_then(|(file, address)| Some((file.trim(), parse_address(address)?)))
+                .ok_or_else(|| invalid(format!("machine rom: expected file@address, got {}", entry)))?;
+            let bytes = fs::read(directory.join(file))?;
+            profile.roms.push((address, bytes));
+        }
+        if let Some(address) = config.get("machine.load_address") {
+            profile.load_address = Some(parse_address(address).ok_or_else(|| invalid(format!("machine load_address: invalid address {}", address)))?);
+        }
+        for entry in list(&config, "machine.serial") {
+            profile.devices.push(IoDevice::Serial(parse_address(entry).ok_or_else(|| invalid(format!("machine serial: invalid address {}", entry)))?));
+        }
+        for entry in list(&config, "machine.pia") {
+            profile.devices.push(IoDevice::Pia(parse_address(entry).ok_or_else(|| invalid(format!("machine pia: invalid address {}", entry)))?));
+        }
+        for entry in list(&config, "machine.via") {
+            profile.devices.push(IoDevice::Via(parse_address(entry).ok_or_else(|| invalid(format!("machine via: invalid address {}", entry)))?));
+        }
+        for entry in list(&config, "machine.acia") {
+            let mut parts = entry.split_whitespace();
+            let address = parts.next().unwrap_or("");
+            let base = parse_address(address).ok_or_else(|| invalid(format!("machine acia: invalid address {}", address)))?;
+            let bridge = match parts.next() {
+                Some(link) => {
+                    let link = Link::parse(link).ok_or_else(|| invalid(format!("machine acia: invalid link {}, expected pty or tcp:<port>", link)))?;
+                    Some(Rc::new(Bridge::open(link)?))
+                },
+                None => None,
+            };
+            profile.devices.push(IoDevice::Acia(base, bridge));
+        }
+        for entry in list(&config, "machine.plugin") {
+            let mut parts = entry.splitn(3, char::is_whitespace);
+            let kind = parts.next().unwrap_or("");
+            let address = parts.next().unwrap_or("");
+            let base = parse_address(address).ok_or_else(|| invalid(format!("machine plugin: expected kind $address, got {}", entry)))?;
+            let device = plugin::build(kind, base, parts.next().unwrap_or("").trim())
+                .map_err(|err| Error::new(err.kind(), format!("machine plugin {}: {}", kind, err)))?;
+            profile.devices.push(IoDevice::Plugin(device));
+        }
+
+        // sections are placed by label, the order of the file is not kept
+        let mut labels = config.values.keys()
+            .filter_map(|key| key.strip_prefix("device.")?.rsplit_once('.').map(|(label, _)| label.to_string()))
+            .collect::<Vec<String>>();
+        labels.sort();
+        labels.dedup();
+        for label in labels {
+            profile.parse_device(&config, &label, directory)
+                .map_err(|err| Error::new(err.kind(), format!("device {}: {}", label, err)))?;
+        }
+        Ok(profile)
+    }
+
+    // a [device.<label>] section, one device with its parameters :
+    //   type = "ram", base, size              memory, on top of the ram key's ranges
+    //   type = "rom", base, file, size        a file (relative to the description), cut or
+    //                                         padded with $FF to size when it is given
+    //   type = "serial" (or "uart"), "pia", "via", base
+    //   type = "acia", base, link             link as for the acia key, optional
+    //   type = "plugin", kind, base, options  a device registered with plugin::register
+    fn parse_device(&mut self, config: &Config, label: &str, directory: &Path) -> Result<(), Error> {
+        let key = |name: &str| config.get(&format!("device.{}.{}", label, name));
+        let base = || {
+            let text = key("base").ok_or_else(|| invalid("missing base address".to_string()))?;
+            parse_address(text).ok_or_else(|| invalid(format!("invalid base address {}", text)))
+        };
+        let size = || key("size").map(|text| parse_size(text).ok_or_else(|| invalid(format!("invalid size {}", text)))).transpose();
+
+        match key("type").unwrap_or("") {
+            "ram" => {
+                let size = size()?.ok_or_else(|| invalid("missing size".to_string()))?;
+                let base = base()?;
+                self.ram.push(sized_range(base, size).ok_or_else(|| invalid(format!("{} bytes at ${:04X} do not fit in the address space", size, base)))?);
+            },
+            "rom" => {
+                let file = key("file").ok_or_else(|| invalid("missing file".to_string()))?;
+                let mut bytes = fs::read(directory.join(file))?;
+                if let Some(size) = size()? {
+                    bytes.resize(size, 0xFF);
+                }
+                let base = base()?;
+                if sized_range(base, bytes.len()).is_none() {
+                    return Err(invalid(format!("{} bytes at ${:04X} do not fit in the address space", bytes.len(), base)));
+                }
+                self.roms.push((base, bytes));
+            },
+            "serial" | "uart" => self.devices.push(IoDevice::Serial(base()?)),
+            "pia" => self.devices.push(IoDevice::Pia(base()?)),
+            "via" => self.devices.push(IoDevice::Via(base()?)),
+            "acia" => {
+                let base = base()?;
+                let bridge = match key("link") {
+                    Some(link) => {
+                        let link = Link::parse(link).ok_or_else(|| invalid(format!("invalid link {}, expected pty or tcp:<port>", link)))?;
+                        Some(Rc::new(Bridge::open(link)?))
+                    },
+                    None => None,
+                };
+                self.devices.push(IoDevice::Acia(base, bridge));
+            },
+            "plugin" => {
+                let kind = key("kind").ok_or_else(|| invalid("missing kind".to_string()))?;
+                self.devices.push(IoDevice::Plugin(plugin::build(kind, base()?, key("options").unwrap_or(""))?));
+            },
+            "" => return Err(invalid("missing type".to_string())),
+            kind => return Err(invalid(format!("unknown type {}, expected ram, rom, serial, uart, pia, via, acia or plugin", kind))),
+        }
+        Ok(())
+    }
+
+    // where the bridged ports can be reached from the host
+    pub fn bridges(&self) -> Vec<String> {
+        self.devices.iter()
+            .filter_map(|device| match device {
+                IoDevice::Acia(_, Some(bridge)) => Some(bridge.name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // a serial port or a display to show in the terminal pane
+    pub fn has_terminal(&self) -> bool {
+        self.devices.iter().any(|device| matches!(device, IoDevice::Serial(_) | IoDevice::Pia(_) | IoDevice::Acia(..)))
+    }
+
+    // the slice of time run as one frame by frame advance and real time pacing
+    pub fn cycles_per_frame(&self) -> u64 {
+        (self.clock / 60.0).max(1.0) as u64
+    }
+}
@@ -1,14 +1,71 @@
+use std::io::Error;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use super::dirty::{PAGE_SIZE, PAGES, WriteTracker};
 use super::interfaces::{DeviceOps};
+use super::rng::SeededRng;
+use super::snapshot::{Snapshot, take_bytes};
+use super::inspect::{DebugState, StateNode};
+
+type Page = [u8; PAGE_SIZE];
 
-#[derive(Debug, Clone, Copy)]
+// pages are shared between copies of the ram until one of them writes there, so the
+// snapshots kept for stepping back and rewinding only copy the pages that changed
+#[derive(Debug, Clone)]
 pub struct Ram {
-    pub data : [u8; 0xFFFF + 1]
+    pub pages   : [Rc<Page>; PAGES],
+    pub tracker : WriteTracker,
+}
+
+// what ram holds at power on, real consoles come up with mostly but not reliably
+// $00 or $FF depending on the chips, some programs only work with one of them,
+// random contents come from a seed so a failing run can be replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerOnPattern {
+    Fill(u8),
+    Random(u64),
+}
+
+impl FromStr for PowerOnPattern {
+    type Err = Error;
+
+    // `random` (seeded from the clock), or the fill byte in hex ($FF, 0xFF or FF)
+    fn from_str(text: &str) -> Result<PowerOnPattern, Error> {
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("random") {
+            return Ok(PowerOnPattern::Random(SeededRng::clock_seed()));
+        }
+        let digits = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+        u8::from_str_radix(digits, 16)
+            .map(PowerOnPattern::Fill)
+            .map_err(|_| Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid power on pattern: {}", text)))
+    }
 }
 
 impl Ram {
     pub fn new() -> Ram {
+        let blank = Rc::new([0; PAGE_SIZE]);
         Ram {
-            data : [0; 0xFFFF + 1]
+            pages   : std::array::from_fn(|_| blank.clone()),
+            tracker : WriteTracker::new(),
+        }
+    }
+
+    pub fn power_on(pattern: PowerOnPattern) -> Ram {
+        let mut data = vec![0; PAGE_SIZE * PAGES];
+        match pattern {
+            PowerOnPattern::Fill(value) => data.fill(value),
+            PowerOnPattern::Random(seed) => SeededRng::new(seed).fill(&mut data),
+        }
+        let mut ram = Ram::new();
+        ram.fill(&data);
+        ram
+    }
+
+    fn fill(&mut self, data: &[u8]) {
+        for (page, bytes) in self.pages.iter_mut().zip(data.chunks(PAGE_SIZE)) {
+            Rc::make_mut(page).copy_from_slice(bytes);
         }
     }
 }
@@ -20,10 +77,38 @@ impl DeviceOps for Ram {
     }
 
     fn read(&self, addr: u16) -> u8 {
-        self.data[addr as usize] 
+        self.pages[addr as usize / PAGE_SIZE][addr as usize % PAGE_SIZE]
     }
 
     fn write(&mut self, addr: u16, value: u8) -> () {
-        self.data[addr as usize] = value
+        Rc::make_mut(&mut self.pages[addr as usize / PAGE_SIZE])[addr as usize % PAGE_SIZE] = value;
+        self.tracker.record(addr);
+    }
+
+    fn write_tracker(&self) -> Option<&WriteTracker> {
+        Some(&self.tracker)
+    }
+}
+
+impl Snapshot for Ram {
+    fn save(&self, out: &mut Vec<u8>) {
+        for page in self.pages.iter() {
+            out.extend_from_slice(&page[..]);
+        }
     }
-}
\ No newline at end of file
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.fill(take_bytes(input, PAGE_SIZE * PAGES)?);
+        self.tracker.record_all();
+        Ok(())
+    }
+}
+
+impl DebugState for Ram {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("size", format!("{} bytes", self.pages.len() * PAGE_SIZE)),
+            StateNode::value("writes", self.tracker.writes),
+        ]
+    }
+}
@@ -1,3 +1,7 @@
+use std::io::Error;
+
+use super::snapshot::{Snapshot, put_u8, put_u16, take_u8, take_u16};
+
 #[derive(Debug, Clone, Copy)]
 pub enum Flag {
     C = 1 << 0, // Carry
@@ -18,7 +22,7 @@ pub struct Registers {
     pub pc      : u16,
     pub sp      : u8,
     pub flags   : u8, 
-    pub status  : u8,
+    pub status  : u8, // unused, flags is the live P register, kept for the savestate layout
     pub fetched : u8,
 }
 
@@ -61,4 +65,29 @@ impl Registers {
             Flag::N => if value { self.flags | (Flag::N as u8) } else { self.flags & !(Flag::N as u8) },
         }
     }
-}
\ No newline at end of file
+}
+
+impl Snapshot for Registers {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.x);
+        put_u8(out, self.y);
+        put_u8(out, self.a);
+        put_u16(out, self.pc);
+        put_u8(out, self.sp);
+        put_u8(out, self.flags);
+        put_u8(out, self.status);
+        put_u8(out, self.fetched);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.x       = take_u8(input)?;
+        self.y       = take_u8(input)?;
+        self.a       = take_u8(input)?;
+        self.pc      = take_u16(input)?;
+        self.sp      = take_u8(input)?;
+        self.flags   = take_u8(input)?;
+        self.status  = take_u8(input)?;
+        self.fetched = take_u8(input)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// band limited synthesis, as blip_buf does it : the sources are sampled far above the output
+// rate and every change of level is added to the output as a step with its high frequencies
+// cut off (a windowed sinc, integrated when samples are read), placed where the change
+// happened to a fraction of a sample. square waves then alias no more than a filter lets
+// through, where point sampling folds all their harmonics back into the audible range
+const TAPS   : usize = 16;
+const PHASES : usize = 32;
+// of the output's nyquist frequency, what is above is cut
+const CUTOFF : f64 = 0.9;
+// the steps are centred this many samples late, so none reaches back before the input
+pub const DELAY : usize = TAPS / 2;
+
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    kernel   : Vec<[f32; TAPS]>, // per phase, the taps of a unit step's derivative
+    pending  : VecDeque<f32>,    // what the samples from `position` on got so far
+    position : u64,              // first output sample still open to changes
+    level    : f32,              // input level at the last input
+    sum      : f64,              // output level, the integral of what was read
+}
+
+impl Resampler {
+    pub fn new() -> Resampler {
+        let kernel = (0..=PHASES).map(|phase| {
+            let fraction = phase as f64 / PHASES as f64;
+            let mut taps = [0.0; TAPS];
+            for (tap, weight) in taps.iter_mut().enumerate() {
+                let x = tap as f64 + 1.0 - fraction - DELAY as f64;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * CUTOFF * x).sin() / (PI * CUTOFF * x) };
+                // blackman window over the taps
+                let w = (x + DELAY as f64) / TAPS as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                *weight = CUTOFF * sinc * window;
+            }
+            // each step adds up to its full height whatever its phase
+            let total = taps.iter().sum::<f64>();
+            let mut normalized = [0.0; TAPS];
+            for (weight, tap) in normalized.iter_mut().zip(taps.iter()) {
+                *weight = (tap / total) as f32;
+            }
+            normalized
+        }).collect();
+        Resampler {
+            kernel,
+            pending  : VecDeque::new(),
+            position : 0,
+            level    : 0.0,
+            sum      : 0.0,
+        }
+    }
+
+    // for an input joining a running output, its samples are counted from there
+    pub fn starting_at(position: u64) -> Resampler {
+        Resampler { position, ..Resampler::new() }
+    }
+
+    // samples already written out, the next one out is this one
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    // the level the input has from `time`, in output samples since the start. samples no
+    // change can reach anymore are handed to `output`, times must not go backwards
+    pub fn input(&mut self, time: f64, level: f32, output: &mut impl FnMut(f32)) {
+        let whole = time.floor().max(0.0) as u64;
+        while self.position <= whole {
+            self.sum += self.pending.pop_front().unwrap_or(0.0) as f64;
+            output(self.sum as f32);
+            self.position += 1;
+        }
+        let delta = level - self.level;
+        if delta == 0.0 {
+            return;
+        }
+        self.level = level;
+        let phase = ((time - whole as f64) * PHASES as f64).round() as usize;
+        // the first tap lands on the sample after `whole`, the next to be written out
+        if self.pending.len() < TAPS {
+            self.pending.resize(TAPS, 0.0);
+        }
+        for (sample, weight) in self.pending.iter_mut().zip(self.kernel[phase].iter()) {
+            *sample += delta * weight;
+        }
+    }
+
+    // starting over at sample 0, the output settles on the input level at once
+    pub fn restart(&mut self) {
+        self.pending.clear();
+        self.sum = self.level as f64;
+        self.position = 0;
+    }
+}
+
+impl Default for Resampler {
+    fn default() -> Resampler {
+        Resampler::new()
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, mismatch, put_u32, take_u32};
+use super::inspect::{DebugState, StateNode};
+
+// firmware soldered on the board : mapped from the machine description, not removable
+// like a cartridge and writes to it are lost
+#[derive(Debug, Clone, Default)]
+pub struct Rom {
+    pub address : u16,
+    pub bytes   : Vec<u8>,
+}
+
+impl Rom {
+    pub fn new(address: u16, mut bytes: Vec<u8>) -> Rom {
+        bytes.truncate(0x10000 - address as usize);
+        Rom { address, bytes }
+    }
+
+    pub fn region(&self) -> RangeInclusive<u16> {
+        self.address..=self.address + self.bytes.len().saturating_sub(1) as u16
+    }
+}
+
+impl DeviceOps for Rom {
+    fn within_range(&self, addr: u16) -> bool {
+        self.region().contains(&addr)
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.bytes.get(addr.wrapping_sub(self.address) as usize).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, _: u16, _: u8) {}
+}
+
+// the contents come from the description, only their size is checked
+impl Snapshot for Rom {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u32(out, self.bytes.len() as u32);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        if take_u32(input)? as usize != self.bytes.len() {
+            return Err(mismatch("rom size"));
+        }
+        Ok(())
+    }
+}
+
+impl DebugState for Rom {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::word("address", self.address),
+            StateNode::bytes("size", &self.bytes),
+        ]
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+
+use super::device::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    UninitializedRead, // ram read before anything was written there since power on
+    RomWrite,          // a write landing on firmware or a program image
+    NonCode,           // an instruction fetched outside the disassembled program
+}
+
+impl Check {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Check::UninitializedRead => "uninitialized read",
+            Check::RomWrite => "rom write",
+            Check::NonCode => "non code executed",
+        }
+    }
+}
+
+// the bus side of the sanitizer : which ram bytes were written, and what the accesses
+// since the debugger last looked did wrong. the coverage check needs the disassembly,
+// the debugger does it on its own
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    written      : RefCell<Box<[u64; 0x10000 / 64]>>,
+    pub findings : RefCell<Vec<(Check, u16)>>,
+}
+
+impl Sanitizer {
+    pub fn new() -> Sanitizer {
+        Sanitizer { written: RefCell::new(Box::new([0; 0x10000 / 64])), findings: RefCell::new(Vec::new()) }
+    }
+
+    fn is_written(&self, addr: u16) -> bool {
+        self.written.borrow()[addr as usize / 64] & 1 << (addr % 64) != 0
+    }
+
+    pub fn read(&self, addr: u16, device: &Device) {
+        if matches!(device, Device::Ram(_)) && !self.is_written(addr) {
+            self.findings.borrow_mut().push((Check::UninitializedRead, addr));
+        }
+    }
+
+    pub fn write(&self, addr: u16, device: &Device) {
+        match device {
+            Device::Ram(_) => self.written.borrow_mut()[addr as usize / 64] |= 1 << (addr % 64),
+            Device::Rom(_) | Device::Cartridge(_) => self.findings.borrow_mut().push((Check::RomWrite, addr)),
+            _ => {},
+        }
+    }
+
+    // restored contents come from elsewhere, all of it counts as written
+    pub fn written_all(&self) {
+        self.written.borrow_mut().fill(u64::MAX);
+    }
+}
+
+impl Default for Sanitizer {
+    fn default() -> Sanitizer {
+        Sanitizer::new()
+    }
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u32, take_bytes, take_u32};
+use super::inspect::{DebugState, StateNode};
+
+// what is printed is kept up to this, older output is dropped
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+pub const STATUS_RECEIVED : u8 = 0x01;
+pub const STATUS_READY    : u8 = 0x02;
+
+// a character port like the ones hobby boards wire to a terminal : base is the data
+// register, a read takes the next typed character and a write prints one, base + 1 is the
+// status, STATUS_RECEIVED while characters wait and STATUS_READY, always set
+#[derive(Debug, Clone, Default)]
+pub struct Serial {
+    pub base   : u16,
+    pub input  : RefCell<VecDeque<u8>>,
+    pub output : Vec<u8>,
+}
+
+impl Serial {
+    pub fn new(base: u16) -> Serial {
+        Serial { base, ..Default::default() }
+    }
+
+    pub fn region(&self) -> RangeInclusive<u16> {
+        self.base..=self.base.wrapping_add(1)
+    }
+
+    pub fn type_text(&self, text: &str) {
+        self.input.borrow_mut().extend(text.bytes());
+    }
+
+    fn status(&self) -> u8 {
+        let received = if self.input.borrow().is_empty() { 0 } else { STATUS_RECEIVED };
+        received | STATUS_READY
+    }
+}
+
+impl DeviceOps for Serial {
+    fn within_range(&self, addr: u16) -> bool {
+        self.region().contains(&addr)
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        if addr == self.base {
+            self.input.borrow_mut().pop_front().unwrap_or(0)
+        } else {
+            self.status()
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr != self.base {
+            return;
+        }
+        self.output.push(value);
+        if self.output.len() > OUTPUT_LIMIT {
+            self.output.drain(..OUTPUT_LIMIT / 2);
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        if addr == self.base {
+            self.input.borrow().front().copied().unwrap_or(0)
+        } else {
+            self.status()
+        }
+    }
+}
+
+impl Snapshot for Serial {
+    fn save(&self, out: &mut Vec<u8>) {
+        let input = self.input.borrow();
+        put_u32(out, input.len() as u32);
+        out.extend(input.iter());
+        put_u32(out, self.output.len() as u32);
+        out.extend_from_slice(&self.output);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        let length = take_u32(input)? as usize;
+        *self.input.borrow_mut() = take_bytes(input, length)?.iter().copied().collect();
+        let length = take_u32(input)? as usize;
+        self.output = take_bytes(input, length)?.to_vec();
+        Ok(())
+    }
+}
+
+impl DebugState for Serial {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::word("base", self.base),
+            StateNode::value("input queued", self.input.borrow().len()),
+            StateNode::bytes("output", &self.output),
+        ]
+    }
+}
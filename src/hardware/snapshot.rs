@@ -0,0 +1,59 @@
+use std::io::{Error, ErrorKind};
+
+// machine state to and from a flat little endian byte stream,
+// restoring expects the same device layout that was saved
+pub trait Snapshot {
+    fn save(&self, out: &mut Vec<u8>);
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error>;
+}
+
+pub fn put_u8(out: &mut Vec<u8>, value: u8) {
+    out.push(value);
+}
+
+pub fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn take_bytes<'b>(input: &mut &'b [u8], count: usize) -> Result<&'b [u8], Error> {
+    if input.len() < count {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated snapshot"));
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+pub fn take_u8(input: &mut &[u8]) -> Result<u8, Error> {
+    Ok(take_bytes(input, 1)?[0])
+}
+
+pub fn take_u16(input: &mut &[u8]) -> Result<u16, Error> {
+    let mut bytes = [0; 2];
+    bytes.copy_from_slice(take_bytes(input, 2)?);
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn take_u32(input: &mut &[u8]) -> Result<u32, Error> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(take_bytes(input, 4)?);
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn take_u64(input: &mut &[u8]) -> Result<u64, Error> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(take_bytes(input, 8)?);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn mismatch(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Snapshot does not match this machine: {}", what))
+}
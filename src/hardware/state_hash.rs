@@ -0,0 +1,64 @@
+use super::dirty::PAGE_SIZE;
+use super::interfaces::DeviceOps;
+use super::machine::Machine;
+
+const FNV_OFFSET : u32 = 0x811C_9DC5;
+const FNV_PRIME  : u32 = 0x0100_0193;
+
+fn fnv(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u32).wrapping_mul(FNV_PRIME))
+}
+
+// a hash of the machine carried from frame to frame : each frame folds the registers,
+// the cycle count and the pages written since the previous frame into the previous
+// value, so two runs agree on a frame only if they agreed on every frame before it,
+// without hashing all of memory every time
+#[derive(Debug, Clone)]
+pub struct StateHash {
+    pub value  : u32,
+    pub frame  : u64, // frames folded so far
+    counts     : Vec<u64>,
+}
+
+impl StateHash {
+    pub fn new() -> StateHash {
+        StateHash { value: FNV_OFFSET, frame: 0, counts: Vec::new() }
+    }
+
+    pub fn fold(&mut self, machine: &Machine) -> u32 {
+        let mut hash = fnv(FNV_OFFSET, &self.value.to_le_bytes());
+        {
+            let cpu = machine.cpu.borrow();
+            let registers = cpu.registers;
+            hash = fnv(hash, &[registers.a, registers.x, registers.y, registers.sp, registers.flags]);
+            hash = fnv(hash, &registers.pc.to_le_bytes());
+            hash = fnv(hash, &cpu.total_cycles.to_le_bytes());
+        }
+
+        let bus = machine.bus.borrow();
+        for (at, mapping) in bus.devices.iter().enumerate() {
+            let device = mapping.device.borrow();
+            let tracker = match device.write_tracker() {
+                Some(tracker) => tracker,
+                None => continue,
+            };
+            for page in tracker.dirty_since(self.counts.get(at).copied().unwrap_or(0)).pages() {
+                let base = (page as usize * PAGE_SIZE) as u16;
+                let bytes: Vec<u8> = (0..PAGE_SIZE as u16).map(|offset| device.peek(base + offset)).collect();
+                hash = fnv(hash, &[at as u8, page]);
+                hash = fnv(hash, &bytes);
+            }
+        }
+
+        self.counts = bus.write_counts();
+        self.value = hash;
+        self.frame += 1;
+        hash
+    }
+}
+
+impl Default for StateHash {
+    fn default() -> StateHash {
+        StateHash::new()
+    }
+}
@@ -0,0 +1,43 @@
+// NTSC raster timing, the beam position is derived from the CPU cycle count
+// (the odd frame skipped dot is not modelled)
+pub const DOTS_PER_CPU_CYCLE  : u64 = 3;
+pub const DOTS_PER_SCANLINE   : u64 = 341;
+pub const SCANLINES_PER_FRAME : u64 = 262;
+pub const DOTS_PER_FRAME      : u64 = DOTS_PER_SCANLINE * SCANLINES_PER_FRAME;
+pub const VBLANK_SCANLINE     : u64 = 241;
+// the ppu's dot when the cpu starts its first cycle : a console powers up in one of four
+// alignments that differ by master clock, below the dot the beam is counted in, what is left
+// of them here is which of the three dots of a cpu cycle the ppu starts on
+pub const ALIGNMENTS          : u8 = DOTS_PER_CPU_CYCLE as u8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterPosition {
+    pub frame    : u64,
+    pub scanline : u16,
+    pub dot      : u16,
+}
+
+impl RasterPosition {
+    pub fn from_cycles(cycles: u64) -> RasterPosition {
+        RasterPosition::from_dots(dots(cycles))
+    }
+
+    pub fn from_dots(dots: u64) -> RasterPosition {
+        RasterPosition {
+            frame    : dots / DOTS_PER_FRAME,
+            scanline : ((dots % DOTS_PER_FRAME) / DOTS_PER_SCANLINE) as u16,
+            dot      : (dots % DOTS_PER_SCANLINE) as u16,
+        }
+    }
+}
+
+pub fn dots(cycles: u64) -> u64 {
+    cycles * DOTS_PER_CPU_CYCLE
+}
+
+// absolute dot of the next time the beam reaches scanline:dot, strictly after `from`
+pub fn next_dot_at(from: u64, scanline: u16, dot: u16) -> u64 {
+    let frame_start = from - from % DOTS_PER_FRAME;
+    let target = frame_start + scanline as u64 * DOTS_PER_SCANLINE + dot as u64;
+    if target > from { target } else { target + DOTS_PER_FRAME }
+}
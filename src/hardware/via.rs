@@ -0,0 +1,349 @@
+use std::cell::Cell;
+use std::io::Error;
+use std::ops::RangeInclusive;
+
+use super::interfaces::{Clocked, DeviceOps};
+use super::snapshot::{Snapshot, put_u8, put_u16, put_u64, take_u8, take_u16, take_u64};
+use super::inspect::{DebugState, StateNode};
+
+// interrupt flag and enable bits
+pub const FLAG_CA2   : u8 = 1 << 0;
+pub const FLAG_CA1   : u8 = 1 << 1;
+pub const FLAG_SHIFT : u8 = 1 << 2;
+pub const FLAG_CB2   : u8 = 1 << 3;
+pub const FLAG_CB1   : u8 = 1 << 4;
+pub const FLAG_T2    : u8 = 1 << 5;
+pub const FLAG_T1    : u8 = 1 << 6;
+const FLAG_ANY       : u8 = 1 << 7;
+
+// auxiliary control bits
+const T1_FREE_RUN   : u8 = 1 << 6;
+const T1_PB7        : u8 = 1 << 7;
+const T2_PULSES     : u8 = 1 << 5;
+const SHIFT_MODE    : u8 = 0x1C;
+
+// a 6522 versatile interface adapter : two 8 bit ports with their data direction registers,
+// timer 1 (one shot or free running, optionally toggling PB7), timer 2 (one shot, it
+// does not count PB6 pulses as nothing drives them), the shift register clocked by timer 2
+// or the system clock, and the interrupt flags holding irq while enabled. registers are
+// the usual 16 from base
+#[derive(Debug, Clone)]
+pub struct Via {
+    pub base          : u16,
+    pub input_a       : u8, // levels driven on the pins by whatever is wired to them
+    pub input_b       : u8,
+    pub cb2_input     : bool, // the bit shifted in
+    pub output_a      : u8,
+    pub output_b      : u8,
+    pub ddr_a         : u8,
+    pub ddr_b         : u8,
+    pub timer1        : u16,
+    pub timer1_latch  : u16,
+    pub timer2        : u16,
+    pub timer2_latch  : u8,
+    pub shift         : u8,
+    pub aux_control   : u8,
+    pub peripheral    : u8,
+    pub enable        : u8,
+    flags             : Cell<u8>,
+    shift_count       : Cell<u8>, // bits left in the current shift
+    timer1_armed      : bool,
+    timer2_armed      : bool,
+    pb7               : bool,
+    clocked           : u64,
+}
+
+impl Via {
+    pub fn new(base: u16) -> Via {
+        Via {
+            base,
+            input_a      : 0xFF,
+            input_b      : 0xFF,
+            cb2_input    : true,
+            output_a     : 0,
+            output_b     : 0,
+            ddr_a        : 0,
+            ddr_b        : 0,
+            timer1       : 0xFFFF,
+            timer1_latch : 0xFFFF,
+            timer2       : 0xFFFF,
+            timer2_latch : 0xFF,
+            shift        : 0,
+            aux_control  : 0,
+            peripheral   : 0,
+            enable       : 0,
+            flags        : Cell::new(0),
+            shift_count  : Cell::new(0),
+            timer1_armed : false,
+            timer2_armed : false,
+            pb7          : true,
+            clocked      : 0,
+        }
+    }
+
+    pub fn region(&self) -> RangeInclusive<u16> {
+        self.base..=self.base.wrapping_add(15)
+    }
+
+    pub fn irq(&self) -> bool {
+        self.flags.get() & self.enable & 0x7F != 0
+    }
+
+    fn flag(&self, flags: u8) {
+        self.flags.set(self.flags.get() | flags);
+    }
+
+    fn clear(&self, flags: u8) {
+        self.flags.set(self.flags.get() & !flags);
+    }
+
+    fn port_a(&self) -> u8 {
+        (self.output_a & self.ddr_a) | (self.input_a & !self.ddr_a)
+    }
+
+    fn port_b(&self) -> u8 {
+        let value = (self.output_b & self.ddr_b) | (self.input_b & !self.ddr_b);
+        if self.aux_control & T1_PB7 != 0 {
+            (value & 0x7F) | ((self.pb7 as u8) << 7)
+        } else {
+            value
+        }
+    }
+
+    fn shift_mode(&self) -> u8 {
+        (self.aux_control & SHIFT_MODE) >> 2
+    }
+
+    // modes 1 - 3 shift in from cb2, 4 - 7 shift out rotating, 4 never stops
+    fn shift_bit(&mut self) {
+        let mode = self.shift_mode();
+        if mode == 0 || (mode != 4 && self.shift_count.get() == 0) {
+            return;
+        }
+        self.shift = if mode < 4 {
+            (self.shift << 1) | self.cb2_input as u8
+        } else {
+            self.shift.rotate_left(1)
+        };
+        if mode != 4 {
+            self.shift_count.set(self.shift_count.get() - 1);
+            if self.shift_count.get() == 0 {
+                self.flag(FLAG_SHIFT);
+            }
+        }
+    }
+
+    fn restart_shift(&self) {
+        self.clear(FLAG_SHIFT);
+        self.shift_count.set(8);
+    }
+
+    fn clock(&mut self) {
+        if self.timer1 == 0 {
+            if self.timer1_armed {
+                self.flag(FLAG_T1);
+                self.pb7 = !self.pb7;
+                self.timer1_armed = self.aux_control & T1_FREE_RUN != 0;
+            }
+            self.timer1 = if self.aux_control & T1_FREE_RUN != 0 { self.timer1_latch } else { 0xFFFF };
+        } else {
+            self.timer1 -= 1;
+        }
+
+        if self.aux_control & T2_PULSES == 0 {
+            // the shift register runs off the low half of timer 2 timing out
+            let shift_on_t2 = matches!(self.shift_mode(), 1 | 4 | 5);
+            if shift_on_t2 && self.timer2 & 0xFF == 0 {
+                self.shift_bit();
+                self.timer2 = (self.timer2 & 0xFF00) | self.timer2_latch as u16;
+            } else if self.timer2 == 0 {
+                if self.timer2_armed {
+                    self.flag(FLAG_T2);
+                    self.timer2_armed = false;
+                }
+                self.timer2 = 0xFFFF;
+            } else {
+                self.timer2 -= 1;
+            }
+        }
+
+        if matches!(self.shift_mode(), 2 | 6) {
+            self.shift_bit();
+        }
+    }
+}
+
+impl DeviceOps for Via {
+    fn within_range(&self, addr: u16) -> bool {
+        self.region().contains(&addr)
+    }
+
+    // port reads drop their handshake flags, timer low bytes and the shift register theirs
+    fn read(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        match addr.wrapping_sub(self.base) & 0x0F {
+            0x0 => self.clear(FLAG_CB1 | FLAG_CB2),
+            0x1 => self.clear(FLAG_CA1 | FLAG_CA2),
+            0x4 => self.clear(FLAG_T1),
+            0x8 => self.clear(FLAG_T2),
+            0xA => self.restart_shift(),
+            _ => {},
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr.wrapping_sub(self.base) & 0x0F {
+            0x0 => {
+                self.output_b = value;
+                self.clear(FLAG_CB1 | FLAG_CB2);
+            },
+            0x1 => {
+                self.output_a = value;
+                self.clear(FLAG_CA1 | FLAG_CA2);
+            },
+            0x2 => self.ddr_b = value,
+            0x3 => self.ddr_a = value,
+            0x4 | 0x6 => self.timer1_latch = (self.timer1_latch & 0xFF00) | value as u16,
+            0x5 => {
+                self.timer1_latch = (self.timer1_latch & 0x00FF) | (value as u16) << 8;
+                self.timer1 = self.timer1_latch;
+                self.timer1_armed = true;
+                self.pb7 = false;
+                self.clear(FLAG_T1);
+            },
+            0x7 => {
+                self.timer1_latch = (self.timer1_latch & 0x00FF) | (value as u16) << 8;
+                self.clear(FLAG_T1);
+            },
+            0x8 => self.timer2_latch = value,
+            0x9 => {
+                self.timer2 = (value as u16) << 8 | self.timer2_latch as u16;
+                self.timer2_armed = true;
+                self.clear(FLAG_T2);
+            },
+            0xA => {
+                self.shift = value;
+                self.restart_shift();
+            },
+            0xB => self.aux_control = value,
+            0xC => self.peripheral = value,
+            0xD => self.clear(value & 0x7F),
+            0xE => {
+                if value & 0x80 != 0 {
+                    self.enable |= value & 0x7F;
+                } else {
+                    self.enable &= !value;
+                }
+            },
+            _ => self.output_a = value,
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr.wrapping_sub(self.base) & 0x0F {
+            0x0 => self.port_b(),
+            0x1 | 0xF => self.port_a(),
+            0x2 => self.ddr_b,
+            0x3 => self.ddr_a,
+            0x4 => self.timer1 as u8,
+            0x5 => (self.timer1 >> 8) as u8,
+            0x6 => self.timer1_latch as u8,
+            0x7 => (self.timer1_latch >> 8) as u8,
+            0x8 => self.timer2 as u8,
+            0x9 => (self.timer2 >> 8) as u8,
+            0xA => self.shift,
+            0xB => self.aux_control,
+            0xC => self.peripheral,
+            0xD => self.flags.get() | if self.irq() { FLAG_ANY } else { 0 },
+            _ => self.enable | 0x80,
+        }
+    }
+}
+
+impl Clocked for Via {
+    fn catch_up(&mut self, cycles: u64) {
+        while self.clocked < cycles {
+            self.clock();
+            self.clocked += 1;
+        }
+    }
+}
+
+impl Snapshot for Via {
+    fn save(&self, out: &mut Vec<u8>) {
+        for register in [self.input_a, self.input_b, self.cb2_input as u8, self.output_a, self.output_b, self.ddr_a, self.ddr_b,
+                         self.timer2_latch, self.shift, self.aux_control, self.peripheral, self.enable, self.flags.get(),
+                         self.shift_count.get(), self.timer1_armed as u8, self.timer2_armed as u8, self.pb7 as u8] {
+            put_u8(out, register);
+        }
+        for counter in [self.timer1, self.timer1_latch, self.timer2] {
+            put_u16(out, counter);
+        }
+        put_u64(out, self.clocked);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.input_a = take_u8(input)?;
+        self.input_b = take_u8(input)?;
+        self.cb2_input = take_u8(input)? != 0;
+        self.output_a = take_u8(input)?;
+        self.output_b = take_u8(input)?;
+        self.ddr_a = take_u8(input)?;
+        self.ddr_b = take_u8(input)?;
+        self.timer2_latch = take_u8(input)?;
+        self.shift = take_u8(input)?;
+        self.aux_control = take_u8(input)?;
+        self.peripheral = take_u8(input)?;
+        self.enable = take_u8(input)?;
+        self.flags.set(take_u8(input)?);
+        self.shift_count.set(take_u8(input)?);
+        self.timer1_armed = take_u8(input)? != 0;
+        self.timer2_armed = take_u8(input)? != 0;
+        self.pb7 = take_u8(input)? != 0;
+        self.timer1 = take_u16(input)?;
+        self.timer1_latch = take_u16(input)?;
+        self.timer2 = take_u16(input)?;
+        self.clocked = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Via {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::word("base", self.base),
+            StateNode::group("port a", vec![
+                StateNode::byte("input", self.input_a),
+                StateNode::byte("output", self.output_a),
+                StateNode::byte("ddr", self.ddr_a),
+            ]),
+            StateNode::group("port b", vec![
+                StateNode::byte("input", self.input_b),
+                StateNode::byte("output", self.output_b),
+                StateNode::byte("ddr", self.ddr_b),
+                StateNode::value("pb7", self.pb7),
+            ]),
+            StateNode::group("timer 1", vec![
+                StateNode::word("counter", self.timer1),
+                StateNode::word("latch", self.timer1_latch),
+                StateNode::value("armed", self.timer1_armed),
+            ]),
+            StateNode::group("timer 2", vec![
+                StateNode::word("counter", self.timer2),
+                StateNode::byte("latch", self.timer2_latch),
+                StateNode::value("armed", self.timer2_armed),
+            ]),
+            StateNode::group("shift register", vec![
+                StateNode::byte("value", self.shift),
+                StateNode::value("bits left", self.shift_count.get()),
+                StateNode::value("cb2 input", self.cb2_input),
+            ]),
+            StateNode::byte("aux control", self.aux_control),
+            StateNode::byte("peripheral control", self.peripheral),
+            StateNode::byte("interrupt flags", self.flags.get()),
+            StateNode::byte("interrupt enable", self.enable),
+            StateNode::value("clocked to", self.clocked),
+        ]
+    }
+}
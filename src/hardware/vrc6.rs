@@ -0,0 +1,442 @@
+use std::io::Error;
+
+use super::audio::AudioSource;
+use super::interfaces::{Clocked, DeviceOps};
+use super::snapshot::{Snapshot, mismatch, put_u8, put_u16, put_u64, take_bytes, take_u8, take_u16, take_u64};
+use crate::loader::ines::Mirroring;
+use super::inspect::{DebugState, StateNode};
+use super::mapper::{Bank, BankMemory};
+
+pub const PRG_RAM_SIZE: usize = 0x2000; // when the header does not say
+
+// mapper 24 wires the cpu address lines straight, mapper 26 swaps A0 and A1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vrc6Variant {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pulse {
+    pub duty    : u8,
+    pub volume  : u8,
+    pub mode    : bool, // constant volume, ignores the duty
+    pub period  : u16,
+    pub enabled : bool,
+    timer       : u16,
+    step        : u8,
+}
+
+impl Pulse {
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && (self.mode || self.step <= self.duty) { self.volume } else { 0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sawtooth {
+    pub rate    : u8,
+    pub period  : u16,
+    pub enabled : bool,
+    timer       : u16,
+    step        : u8,
+    accumulator : u8,
+}
+
+impl Sawtooth {
+    // the accumulator takes the rate every other step and is cleared after the seventh add
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step += 1;
+            if self.step == 14 {
+                self.step = 0;
+                self.accumulator = 0;
+            } else if self.step.is_multiple_of(2) {
+                self.accumulator = self.accumulator.wrapping_add(self.rate);
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+// Konami VRC6 : 16k bank at $8000, 8k bank at $C000, last 8k fixed at $E000, 8k of
+// prg ram at $6000 and three extra sound channels, chr banks and the irq counter are
+// kept as registers until there is a ppu to clock them
+#[derive(Debug, Clone)]
+pub struct Vrc6 {
+    pub variant     : Vrc6Variant,
+    pub prg         : Vec<u8>,
+    pub chr         : Vec<u8>,
+    pub prg_ram     : Vec<u8>,
+    pub prg_16k     : u8,
+    pub prg_8k      : u8,
+    pub chr_banks   : [u8; 8],
+    pub control     : u8, // $B003 : prg ram enable, mirroring and chr layout
+    pub irq_latch   : u8,
+    pub irq_control : u8,
+    pub audio       : Vrc6Audio,
+}
+
+// the three sound channels at $9000 - $B002, also found on their own in nsf files
+#[derive(Debug, Clone, Default)]
+pub struct Vrc6Audio {
+    pub halt     : bool,
+    pub shift    : u8, // $9003 frequency scaling, 0, 4 or 8
+    pub pulses   : [Pulse; 2],
+    pub sawtooth : Sawtooth,
+    clocked      : u64, // cpu cycle the channels have been run to
+}
+
+impl Vrc6Audio {
+    // register number as $x000 to $x003, returns false for anything not audio
+    pub fn write(&mut self, register: u16, value: u8) -> bool {
+        match register {
+            0x9000..=0x9002 => Vrc6Audio::write_pulse(&mut self.pulses[0], register, value),
+            0x9003 => {
+                self.halt = value & 1 != 0;
+                self.shift = if value & 4 != 0 { 8 } else if value & 2 != 0 { 4 } else { 0 };
+            },
+            0xA000..=0xA002 => Vrc6Audio::write_pulse(&mut self.pulses[1], register, value),
+            0xB000 => self.sawtooth.rate = value & 0x3F,
+            0xB001 => self.sawtooth.period = (self.sawtooth.period & 0x0F00) | value as u16,
+            0xB002 => {
+                self.sawtooth.period = (self.sawtooth.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.sawtooth.enabled = value & 0x80 != 0;
+                if !self.sawtooth.enabled {
+                    self.sawtooth.step = 0;
+                    self.sawtooth.accumulator = 0;
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn write_pulse(pulse: &mut Pulse, register: u16, value: u8) {
+        match register & 3 {
+            0 => {
+                pulse.mode = value & 0x80 != 0;
+                pulse.duty = (value >> 4) & 7;
+                pulse.volume = value & 0x0F;
+            },
+            1 => pulse.period = (pulse.period & 0x0F00) | value as u16,
+            _ => {
+                pulse.period = (pulse.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                pulse.enabled = value & 0x80 != 0;
+                if !pulse.enabled {
+                    pulse.step = 15;
+                }
+            },
+        }
+    }
+}
+
+impl Vrc6 {
+    pub fn new(variant: Vrc6Variant, prg: Vec<u8>, chr: Vec<u8>, prg_ram: usize) -> Vrc6 {
+        Vrc6 {
+            variant,
+            prg,
+            chr,
+            prg_ram     : vec![0; prg_ram.min(PRG_RAM_SIZE)],
+            prg_16k     : 0,
+            prg_8k      : 0,
+            chr_banks   : [0; 8],
+            control     : 0,
+            irq_latch   : 0,
+            irq_control : 0,
+            audio       : Vrc6Audio::default(),
+        }
+    }
+
+    // register number as $x000 to $x003, whatever the board wiring
+    fn register(&self, addr: u16) -> u16 {
+        let register = addr & 0xF003;
+        match self.variant {
+            Vrc6Variant::A => register,
+            Vrc6Variant::B => (register & 0xF000) | (register & 1) << 1 | (register & 2) >> 1,
+        }
+    }
+
+    fn prg_byte(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        self.prg[(bank * bank_size + offset) % self.prg.len()]
+    }
+
+    // less than 8k repeats through $6000 - $7FFF, none reads as 0
+    fn prg_ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0 && !self.prg_ram.is_empty()
+    }
+
+    fn prg_ram_index(&self, addr: u16) -> usize {
+        (addr as usize - 0x6000) % self.prg_ram.len()
+    }
+
+    // 16k at $8000, 8k at $C000, the last 8k is fixed, and the eight 1k chr slots
+    pub fn banks(&self) -> Vec<Bank> {
+        let prg = vec![(0x8000, self.prg_16k), (0xC000, self.prg_8k)].into_iter()
+            .map(|(address, bank)| Bank { memory: BankMemory::Prg, address, bank: bank as u16 });
+        let chr = self.chr_banks.iter().enumerate()
+            .map(|(slot, bank)| Bank { memory: BankMemory::Chr, address: slot as u16 * 0x400, bank: *bank as u16 });
+        prg.chain(chr).collect()
+    }
+
+    // $B003 bits 2 and 3
+    pub fn mirroring(&self) -> Mirroring {
+        match (self.control >> 2) & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLow,
+            _ => Mirroring::SingleScreenHigh,
+        }
+    }
+
+}
+
+impl DeviceOps for Vrc6 {
+    fn within_range(&self, addr: u16) -> bool {
+        addr >= 0x6000
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram[self.prg_ram_index(addr)],
+            0x8000..=0xBFFF => self.prg_byte(0x4000, self.prg_16k as usize, addr as usize - 0x8000),
+            0xC000..=0xDFFF => self.prg_byte(0x2000, self.prg_8k as usize, addr as usize - 0xC000),
+            0xE000..=0xFFFF => self.prg[self.prg.len() - 0x2000 + (addr as usize - 0xE000)],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            if self.prg_ram_enabled() {
+                let index = self.prg_ram_index(addr);
+                self.prg_ram[index] = value;
+            }
+            return;
+        }
+        let register = self.register(addr);
+        if self.audio.write(register, value) {
+            return;
+        }
+        match register {
+            0x8000..=0x8003 => {
+                self.prg_16k = value & 0x0F;
+                crate::log_event!(Mapper, Debug, "vrc6 16k prg bank {} at $8000", self.prg_16k);
+            },
+            0xB003 => self.control = value,
+            0xC000..=0xC003 => {
+                self.prg_8k = value & 0x1F;
+                crate::log_event!(Mapper, Debug, "vrc6 8k prg bank {} at $C000", self.prg_8k);
+            },
+            0xD000..=0xE003 if register & 0x0FFF <= 3 => {
+                let slot = if register >= 0xE000 { 4 } else { 0 } + (register & 3) as usize;
+                self.chr_banks[slot] = value;
+                crate::log_event!(Mapper, Debug, "vrc6 1k chr bank {} in slot {}", value, slot);
+            },
+            0xF000 => self.irq_latch = value,
+            0xF001 => self.irq_control = value & 7,
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Vrc6Audio {
+    // after a rewind the machine is behind the channels, which then wait for it
+    fn catch_up(&mut self, cycles: u64) {
+        while self.clocked < cycles {
+            if !self.halt {
+                self.pulses[0].clock(self.shift);
+                self.pulses[1].clock(self.shift);
+                self.sawtooth.clock(self.shift);
+            }
+            self.clocked += 1;
+        }
+    }
+}
+
+impl AudioSource for Vrc6Audio {
+    // pulses are 4 bit, the sawtooth 5 bit, summed linearly like the chip's dac
+    fn level(&self) -> f32 {
+        let sum = self.pulses[0].output() as f32 + self.pulses[1].output() as f32 + self.sawtooth.output() as f32;
+        sum / 61.0
+    }
+
+    fn channels(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("vrc6_pulse1", self.pulses[0].output() as f32 / 15.0),
+            ("vrc6_pulse2", self.pulses[1].output() as f32 / 15.0),
+            ("vrc6_sawtooth", self.sawtooth.output() as f32 / 31.0),
+        ]
+    }
+}
+
+impl Clocked for Vrc6 {
+    fn catch_up(&mut self, cycles: u64) {
+        self.audio.catch_up(cycles);
+    }
+}
+
+impl AudioSource for Vrc6 {
+    fn level(&self) -> f32 {
+        self.audio.level()
+    }
+
+    fn channels(&self) -> Vec<(&'static str, f32)> {
+        self.audio.channels()
+    }
+}
+
+impl Snapshot for Vrc6 {
+    // the rom banks are not saved, only what the program can change
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u16(out, self.prg_ram.len() as u16);
+        out.extend_from_slice(&self.prg_ram);
+        for register in [self.prg_16k, self.prg_8k, self.control, self.irq_latch, self.irq_control].iter() {
+            put_u8(out, *register);
+        }
+        self.chr_banks.iter().for_each(|bank| put_u8(out, *bank));
+        self.audio.save(out);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        if take_u16(input)? as usize != self.prg_ram.len() {
+            return Err(mismatch("prg ram size"));
+        }
+        let size = self.prg_ram.len();
+        self.prg_ram.copy_from_slice(take_bytes(input, size)?);
+        self.prg_16k = take_u8(input)?;
+        self.prg_8k = take_u8(input)?;
+        self.control = take_u8(input)?;
+        self.irq_latch = take_u8(input)?;
+        self.irq_control = take_u8(input)?;
+        for bank in self.chr_banks.iter_mut() {
+            *bank = take_u8(input)?;
+        }
+        self.audio.restore(input)
+    }
+}
+
+impl Snapshot for Vrc6Audio {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.halt as u8);
+        put_u8(out, self.shift);
+        for pulse in self.pulses.iter() {
+            for value in [pulse.duty, pulse.volume, pulse.mode as u8, pulse.enabled as u8, pulse.step].iter() {
+                put_u8(out, *value);
+            }
+            put_u16(out, pulse.period);
+            put_u16(out, pulse.timer);
+        }
+        let saw = &self.sawtooth;
+        for value in [saw.rate, saw.enabled as u8, saw.step, saw.accumulator].iter() {
+            put_u8(out, *value);
+        }
+        put_u16(out, saw.period);
+        put_u16(out, saw.timer);
+        put_u64(out, self.clocked);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.halt = take_u8(input)? != 0;
+        self.shift = take_u8(input)?;
+        for pulse in self.pulses.iter_mut() {
+            pulse.duty = take_u8(input)?;
+            pulse.volume = take_u8(input)?;
+            pulse.mode = take_u8(input)? != 0;
+            pulse.enabled = take_u8(input)? != 0;
+            pulse.step = take_u8(input)?;
+            pulse.period = take_u16(input)?;
+            pulse.timer = take_u16(input)?;
+        }
+        self.sawtooth.rate = take_u8(input)?;
+        self.sawtooth.enabled = take_u8(input)? != 0;
+        self.sawtooth.step = take_u8(input)?;
+        self.sawtooth.accumulator = take_u8(input)?;
+        self.sawtooth.period = take_u16(input)?;
+        self.sawtooth.timer = take_u16(input)?;
+        self.clocked = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Pulse {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("duty", self.duty),
+            StateNode::value("volume", self.volume),
+            StateNode::value("mode", self.mode),
+            StateNode::word("period", self.period),
+            StateNode::value("enabled", self.enabled),
+            StateNode::word("timer", self.timer),
+            StateNode::value("step", self.step),
+        ]
+    }
+}
+
+impl DebugState for Sawtooth {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("rate", self.rate),
+            StateNode::word("period", self.period),
+            StateNode::value("enabled", self.enabled),
+            StateNode::word("timer", self.timer),
+            StateNode::value("step", self.step),
+            StateNode::value("accumulator", self.accumulator),
+        ]
+    }
+}
+
+impl DebugState for Vrc6Audio {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("halt", self.halt),
+            StateNode::value("shift", self.shift),
+            StateNode::group("pulse 1", self.pulses[0].debug_state()),
+            StateNode::group("pulse 2", self.pulses[1].debug_state()),
+            StateNode::group("sawtooth", self.sawtooth.debug_state()),
+            StateNode::value("clocked to", self.clocked),
+        ]
+    }
+}
+
+impl DebugState for Vrc6 {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("variant", format!("{:?}", self.variant)),
+            StateNode::bytes("prg", &self.prg),
+            StateNode::bytes("chr", &self.chr),
+            StateNode::bytes("prg ram", &self.prg_ram),
+            StateNode::byte("prg 16k bank", self.prg_16k),
+            StateNode::byte("prg 8k bank", self.prg_8k),
+            StateNode::group("chr banks", self.chr_banks.iter().enumerate()
+                .map(|(index, bank)| StateNode::byte(&index.to_string(), *bank))
+                .collect()),
+            StateNode::byte("control", self.control),
+            StateNode::value("mirroring", format!("{:?}", self.mirroring())),
+            StateNode::byte("irq latch", self.irq_latch),
+            StateNode::byte("irq control", self.irq_control),
+            StateNode::group("audio", self.audio.debug_state()),
+        ]
+    }
+}
@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        }
+    }
+}
+
+// the bus side of the data breakpoints : the addresses watched for reads, writes or both,
+// and the accesses to them since the debugger last looked with the value read or written.
+// what the value has to be is the debugger's business
+#[derive(Debug, Clone, Default)]
+pub struct Watches {
+    pub watched  : Vec<(RangeInclusive<u16>, Option<Access>)>, // none watches both
+    pub accesses : RefCell<Vec<(Access, u16, u8)>>,
+}
+
+impl Watches {
+    pub fn new(watched: Vec<(RangeInclusive<u16>, Option<Access>)>) -> Watches {
+        Watches { watched, accesses: RefCell::new(Vec::new()) }
+    }
+
+    pub fn access(&self, access: Access, addr: u16, value: u8) {
+        let watched = self.watched.iter()
+            .any(|(range, only)| range.contains(&addr) && only.is_none_or(|only| only == access));
+        if watched {
+            self.accesses.borrow_mut().push((access, addr, value));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::Error;
+
+use super::framebuffer::FrameBuffer;
+use super::interfaces::DeviceOps;
+use super::snapshot::{Snapshot, put_u8, put_u16, take_u8, take_u16};
+use super::inspect::{DebugState, StateNode};
+
+// the photodiode keeps reporting light for a while after the beam went over a bright spot
+pub const LIGHT_SCANLINES  : u16 = 26;
+pub const LIGHT_BRIGHTNESS : u32 = 0xC0;
+
+// light gun on the second port : bit 3 reads 0 while light is sensed, bit 4 is the trigger
+#[derive(Debug, Clone, Default)]
+pub struct Zapper {
+    pub aim     : Option<(u16, u16)>, // x, y on the frame buffer, None when pointed off screen
+    pub trigger : bool,
+    pub light   : bool,
+}
+
+impl Zapper {
+    pub fn new() -> Zapper {
+        Zapper {
+            aim     : None,
+            trigger : false,
+            light   : false,
+        }
+    }
+
+    // called as the beam starts a scanline, the pixel under the aim is taken from the
+    // frame being drawn, which the beam has already gone over when it is below the aim
+    pub fn sense(&mut self, video: &FrameBuffer, scanline: u16) {
+        self.light = match self.aim {
+            Some((x, y)) if (x as usize) < video.width && (y as usize) < video.height && scanline >= y && scanline - y < LIGHT_SCANLINES => {
+                let pixel = video.back()[y as usize * video.width + x as usize];
+                let brightness = ((pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF)) / 3;
+                brightness >= LIGHT_BRIGHTNESS
+            },
+            _ => false,
+        };
+    }
+}
+
+impl DeviceOps for Zapper {
+    fn within_range(&self, addr: u16) -> bool {
+        addr == 0x4017
+    }
+
+    fn read(&self, _: u16) -> u8 {
+        0x40 | if self.light { 0 } else { 1 << 3 } | if self.trigger { 1 << 4 } else { 0 }
+    }
+
+    fn write(&mut self, _: u16, _: u8) {}
+}
+
+impl Snapshot for Zapper {
+    fn save(&self, out: &mut Vec<u8>) {
+        let (x, y) = self.aim.unwrap_or((0, 0));
+        put_u8(out, self.aim.is_some() as u8);
+        put_u16(out, x);
+        put_u16(out, y);
+        put_u8(out, self.trigger as u8);
+        put_u8(out, self.light as u8);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        let aimed = take_u8(input)? != 0;
+        let (x, y) = (take_u16(input)?, take_u16(input)?);
+        self.aim = if aimed { Some((x, y)) } else { None };
+        self.trigger = take_u8(input)? != 0;
+        self.light = take_u8(input)? != 0;
+        Ok(())
+    }
+}
+
+impl DebugState for Zapper {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![
+            StateNode::value("aim", self.aim.map(|(x, y)| format!("{},{}", x, y)).unwrap_or_else(|| "off screen".to_string())),
+            StateNode::value("trigger", self.trigger),
+            StateNode::value("light", self.light),
+        ]
+    }
+}
@@ -0,0 +1,16 @@
+pub mod hardware;
+pub mod debugger;
+pub mod loader;
+pub mod cli;
+pub mod config;
+pub mod player;
+pub mod remote;
+pub mod emulator;
+pub mod events;
+pub mod logging;
+pub mod test_rom;
+pub mod test_runner;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "libretro")]
+pub mod libretro;
\ No newline at end of file
@@ -0,0 +1,378 @@
+// the libretro api (libretro.h, RETRO_API_VERSION 1), so the core loads in retroarch
+// and other libretro frontends. the frontend calls in from one thread, the core and its
+// callbacks live in thread locals
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use crate::emulator::Emulator;
+use crate::hardware::audio::{CPU_CLOCK, SAMPLE_RATE};
+use crate::hardware::controller::Button;
+use crate::hardware::framebuffer::{HEIGHT, WIDTH};
+use crate::hardware::timing;
+use crate::player::video::VideoOptions;
+
+const RETRO_API_VERSION                       : u32 = 1;
+const RETRO_DEVICE_JOYPAD                     : u32 = 1;
+const RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY  : u32 = 9;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT      : u32 = 10;
+const RETRO_ENVIRONMENT_SET_SUPPORT_NO_GAME   : u32 = 18;
+const RETRO_PIXEL_FORMAT_XRGB8888             : u32 = 1;
+const RETRO_REGION_NTSC                       : u32 = 0;
+const PADS                                    : u32 = 2;
+
+// retro joypad ids for the pad's buttons, in the pad's bit order
+const JOYPAD_IDS: [(u32, Button); 8] = [
+    (8, Button::A), (0, Button::B), (2, Button::Select), (3, Button::Start),
+    (4, Button::Up), (5, Button::Down), (6, Button::Left), (7, Button::Right),
+];
+
+// X and Y are turbo A and turbo B, the layout of the usual nes cores
+const TURBO_IDS: [(u32, Button); 2] = [(9, Button::A), (1, Button::B)];
+
+type EnvironmentFn = unsafe extern "C" fn(command: u32, data: *mut c_void) -> bool;
+type VideoRefreshFn = unsafe extern "C" fn(data: *const c_void, width: u32, height: u32, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn = unsafe extern "C" fn(port: u32, device: u32, index: u32, id: u32) -> i16;
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    library_name     : *const c_char,
+    library_version  : *const c_char,
+    valid_extensions : *const c_char,
+    need_fullpath    : bool,
+    block_extract    : bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    base_width   : u32,
+    base_height  : u32,
+    max_width    : u32,
+    max_height   : u32,
+    aspect_ratio : f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    fps         : f64,
+    sample_rate : f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    geometry : RetroGameGeometry,
+    timing   : RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    path : *const c_char,
+    data : *const c_void,
+    size : usize,
+    meta : *const c_char,
+}
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment        : Option<EnvironmentFn>,
+    video_refresh      : Option<VideoRefreshFn>,
+    audio_sample_batch : Option<AudioSampleBatchFn>,
+    input_poll         : Option<InputPollFn>,
+    input_state        : Option<InputStateFn>,
+}
+
+thread_local! {
+    static CORE: RefCell<Option<Emulator<'static>>> = const { RefCell::new(None) };
+    static CALLBACKS: RefCell<Callbacks> = const { RefCell::new(Callbacks {
+        environment        : None,
+        video_refresh      : None,
+        audio_sample_batch : None,
+        input_poll         : None,
+        input_state        : None,
+    }) };
+}
+
+// copied out, so a callback calling back into the core finds nothing borrowed
+fn callbacks() -> Callbacks {
+    CALLBACKS.with(|callbacks| *callbacks.borrow())
+}
+
+// a panic cannot unwind into the frontend, the call fails instead : false, 0 or nothing
+fn guarded<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(failed)
+}
+
+// the disk system bios is looked up as disksys.rom in the frontend's system directory
+unsafe fn disk_system_bios(environment: EnvironmentFn) -> Option<Vec<u8>> {
+    let mut directory: *const c_char = ptr::null();
+    if !environment(RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY, &mut directory as *mut *const c_char as *mut c_void) || directory.is_null() {
+        return None;
+    }
+    let directory = CStr::from_ptr(directory).to_string_lossy().to_string();
+    fs::read(Path::new(&directory).join("disksys.rom")).ok()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> u32 {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().environment = Some(callback));
+    let mut no_game = false;
+    unsafe {
+        callback(RETRO_ENVIRONMENT_SET_SUPPORT_NO_GAME, &mut no_game as *mut bool as *mut c_void);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().video_refresh = Some(callback));
+}
+
+// samples go out a frame at a time through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().audio_sample_batch = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().input_poll = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().input_state = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    guarded((), || {
+        CORE.with(|core| *core.borrow_mut() = None);
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    guarded((), || {
+        if let Some(info) = info.as_mut() {
+            *info = RetroSystemInfo {
+                library_name     : b"Brick Station\0".as_ptr() as *const c_char,
+                library_version  : concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+                valid_extensions : b"nes|fds|bin|rom|prg|hex|ihx|ihex|s19|s28|s37|srec|mot|txt\0".as_ptr() as *const c_char,
+                need_fullpath    : false,
+                block_extract    : false,
+            };
+        }
+    })
+}
+
+// the frontend does its own scaling, the core only crops the overscan and asks for tv pixels
+fn video_options() -> VideoOptions {
+    VideoOptions { aspect : true, ..VideoOptions::default() }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    guarded((), || {
+        if let Some(info) = info.as_mut() {
+            let cycles_per_frame = timing::DOTS_PER_FRAME as f64 / timing::DOTS_PER_CPU_CYCLE as f64;
+            let video = video_options();
+            let (_, _, width, height) = video.crop(WIDTH, HEIGHT);
+            *info = RetroSystemAvInfo {
+                geometry : RetroGameGeometry {
+                    base_width   : width as u32,
+                    base_height  : height as u32,
+                    max_width    : WIDTH as u32,
+                    max_height   : HEIGHT as u32,
+                    aspect_ratio : video.aspect_ratio(WIDTH, HEIGHT),
+                },
+                timing   : RetroSystemTiming {
+                    fps         : CPU_CLOCK / cycles_per_frame,
+                    sample_rate : SAMPLE_RATE as f64,
+                },
+            };
+        }
+    })
+}
+
+// only standard pads are offered
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_: u32, _: u32) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    guarded((), || {
+        CORE.with(|core| {
+            if let Some(emulator) = core.borrow_mut().as_mut() {
+                emulator.reset();
+            }
+        });
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    guarded((), || {
+        let Callbacks { video_refresh, audio_sample_batch, input_poll, input_state, .. } = callbacks();
+        if let Some(input_poll) = input_poll {
+            input_poll();
+        }
+        CORE.with(|core| {
+            let core = core.borrow();
+            let emulator = match core.as_ref() {
+                Some(emulator) => emulator,
+                None => return,
+            };
+            if let Some(input_state) = input_state {
+                for port in 0..PADS {
+                    let held = |ids: &[(u32, Button)]| ids.iter()
+                        .filter(|(id, _)| input_state(port, RETRO_DEVICE_JOYPAD, 0, *id) != 0)
+                        .fold(0u8, |buttons, (_, button)| buttons | *button as u8);
+                    emulator.set_buttons(port as u16, held(&JOYPAD_IDS));
+                    emulator.set_turbo_buttons(port as u16, held(&TURBO_IDS));
+                }
+            }
+
+            emulator.run_frame();
+
+            if let Some(video_refresh) = video_refresh {
+                // cropped in place, the pitch skips the columns left out
+                let (width, height, pixels) = emulator.framebuffer();
+                let (left, top, visible_width, visible_height) = video_options().crop(width, height);
+                let visible = &pixels[top * width + left..];
+                video_refresh(visible.as_ptr() as *const c_void, visible_width as u32, visible_height as u32, width * 4);
+            }
+            if let Some(audio_sample_batch) = audio_sample_batch {
+                // mono to interleaved stereo
+                let samples = emulator.audio().iter()
+                    .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+                    .flat_map(|sample| [sample, sample])
+                    .collect::<Vec<i16>>();
+                let mut sent = 0;
+                while sent < samples.len() / 2 {
+                    let taken = audio_sample_batch(samples[sent * 2..].as_ptr(), samples.len() / 2 - sent);
+                    if taken == 0 {
+                        break;
+                    }
+                    sent += taken;
+                }
+            }
+        });
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    guarded(0, || {
+        CORE.with(|core| core.borrow().as_ref().map(|emulator| emulator.save_state().len()).unwrap_or(0))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    guarded(false, || {
+        CORE.with(|core| match core.borrow().as_ref() {
+            Some(emulator) if !data.is_null() => {
+                let state = emulator.save_state();
+                if state.len() > size {
+                    return false;
+                }
+                ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+                true
+            },
+            _ => false,
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    guarded(false, || {
+        CORE.with(|core| match core.borrow_mut().as_mut() {
+            Some(emulator) if !data.is_null() => emulator.load_state(slice::from_raw_parts(data as *const u8, size)).is_ok(),
+            _ => false,
+        })
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_: u32, _: bool, _: *const c_char) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    guarded(false, || {
+        let environment = callbacks().environment;
+        if let Some(environment) = environment {
+            let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+            if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut u32 as *mut c_void) {
+                return false;
+            }
+        }
+
+        let mut emulator = Emulator::default();
+        if let Some(bios) = environment.and_then(|environment| disk_system_bios(environment)) {
+            let _ = emulator.set_disk_system_bios(bios);
+        }
+        if let Some(game) = game.as_ref() {
+            if game.data.is_null() {
+                return false;
+            }
+            let name = if game.path.is_null() { String::new() } else { CStr::from_ptr(game.path).to_string_lossy().to_string() };
+            if emulator.load_bytes(&name, slice::from_raw_parts(game.data as *const u8, game.size)).is_err() {
+                return false;
+            }
+        }
+        CORE.with(|core| *core.borrow_mut() = Some(emulator));
+        true
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_: u32, _: *const RetroGameInfo, _: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    guarded((), || {
+        CORE.with(|core| *core.borrow_mut() = None);
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> u32 {
+    RETRO_REGION_NTSC
+}
+
+// memory lives behind the bus, there is no flat buffer to hand out
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_: u32) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_: u32) -> usize {
+    0
+}
@@ -0,0 +1,14 @@
+pub mod program;
+#[allow(clippy::module_inception)]
+pub mod loader;
+pub mod hex_text;
+pub mod binary;
+pub mod intel_hex;
+pub mod srec;
+pub mod rom_database;
+pub mod patch;
+pub mod archive;
+pub mod inflate;
+pub mod ines;
+pub mod fds;
+pub mod nsf;
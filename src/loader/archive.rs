@@ -0,0 +1,151 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use super::inflate::inflate;
+use super::loader::Format;
+use super::rom_database::crc32;
+
+// programs inside .zip archives, as rom sets are kept : `games.zip` opens the first .nes
+// entry (or the first entry of any format the loader knows when there is no .nes), and
+// `games.zip#Game (U).nes` names the entry. entries are stored or deflated, zip64 and
+// encrypted archives are refused. 7z archives are recognised but not unpacked
+pub const SEPARATOR : char = '#';
+const EXTENSION     : &str = ".zip";
+
+const END_SIGNATURE     : u32 = 0x0605_4B50;
+const CENTRAL_SIGNATURE : u32 = 0x0201_4B50;
+const LOCAL_SIGNATURE   : u32 = 0x0403_4B50;
+const END_SIZE          : usize = 22;
+const MAX_COMMENT       : usize = 0xFFFF;
+const SEVEN_ZIP_MAGIC   : &[u8] = b"7z\xBC\xAF\x27\x1C";
+
+const STORED   : u16 = 0;
+const DEFLATED : u16 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name        : String,
+    pub size        : usize,
+    method          : u16,
+    compressed_size : usize,
+    crc32           : u32,
+    local_offset    : usize,
+    encrypted       : bool,
+}
+
+fn invalid(reason: String) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
+
+fn u16_at(content: &[u8], offset: usize) -> Result<u16, Error> {
+    content.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| invalid("Truncated zip archive".to_string()))
+}
+
+fn u32_at(content: &[u8], offset: usize) -> Result<u32, Error> {
+    content.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid("Truncated zip archive".to_string()))
+}
+
+// the archive and the entry a path names, `games.zip#entry`
+pub fn split(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim();
+    match path.to_ascii_lowercase().find(&format!("{}{}", EXTENSION, SEPARATOR)) {
+        Some(position) => {
+            let end = position + EXTENSION.len();
+            (&path[..end], Some(&path[end + 1..]))
+        },
+        None => (path, None),
+    }
+}
+
+pub fn is_archive(path: &str) -> bool {
+    split(path).0.to_ascii_lowercase().ends_with(EXTENSION)
+}
+
+// the central directory, found from the record closing the archive
+pub fn entries(content: &[u8]) -> Result<Vec<Entry>, Error> {
+    if content.starts_with(SEVEN_ZIP_MAGIC) {
+        return Err(Error::new(ErrorKind::InvalidInput, "7z archives are not supported, extract the program or repack it as a zip"));
+    }
+    let lowest = content.len().saturating_sub(END_SIZE + MAX_COMMENT);
+    let end = (lowest..=content.len().saturating_sub(END_SIZE)).rev()
+        .find(|offset| u32_at(content, *offset).is_ok_and(|signature| signature == END_SIGNATURE))
+        .ok_or_else(|| invalid("Not a zip archive".to_string()))?;
+    let count = u16_at(content, end + 10)? as usize;
+    let mut offset = u32_at(content, end + 16)? as usize;
+    if count == 0xFFFF || offset == 0xFFFF_FFFF {
+        return Err(invalid("Zip64 archives are not supported".to_string()));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(content, offset)? != CENTRAL_SIGNATURE {
+            return Err(invalid("Corrupted zip directory".to_string()));
+        }
+        let name_length = u16_at(content, offset + 28)? as usize;
+        let name = content.get(offset + 46..offset + 46 + name_length).ok_or_else(|| invalid("Truncated zip archive".to_string()))?;
+        entries.push(Entry {
+            name            : String::from_utf8_lossy(name).to_string(),
+            size            : u32_at(content, offset + 24)? as usize,
+            method          : u16_at(content, offset + 10)?,
+            compressed_size : u32_at(content, offset + 20)? as usize,
+            crc32           : u32_at(content, offset + 16)?,
+            local_offset    : u32_at(content, offset + 42)? as usize,
+            encrypted       : u16_at(content, offset + 8)? & 1 != 0,
+        });
+        offset += 46 + name_length + u16_at(content, offset + 30)? as usize + u16_at(content, offset + 32)? as usize;
+    }
+    Ok(entries)
+}
+
+// entries the loader knows the format of, the .nes ones first, directories left out
+pub fn programs(entries: &[Entry]) -> Vec<&Entry> {
+    let known = |entry: &&Entry| !entry.name.ends_with('/') && Format::from_extension(Path::new(&entry.name)).is_some();
+    let is_nes = |entry: &&Entry| Format::from_extension(Path::new(&entry.name)) == Some(Format::INes);
+    entries.iter().filter(known).filter(is_nes)
+        .chain(entries.iter().filter(known).filter(|entry| !is_nes(entry)))
+        .collect()
+}
+
+pub fn extract(content: &[u8], entry: &Entry) -> Result<Vec<u8>, Error> {
+    if entry.encrypted {
+        return Err(invalid(format!("{} is encrypted", entry.name)));
+    }
+    if u32_at(content, entry.local_offset)? != LOCAL_SIGNATURE {
+        return Err(invalid("Corrupted zip entry".to_string()));
+    }
+    let start = entry.local_offset + 30 + u16_at(content, entry.local_offset + 26)? as usize + u16_at(content, entry.local_offset + 28)? as usize;
+    let data = content.get(start..start + entry.compressed_size).ok_or_else(|| invalid("Truncated zip archive".to_string()))?;
+    let output = match entry.method {
+        STORED => data.to_vec(),
+        DEFLATED => inflate(data, entry.size).map_err(|err| invalid(format!("{}: {}", entry.name, err)))?,
+        method => return Err(invalid(format!("{} is compressed with method {}, only stored and deflated entries are supported", entry.name, method))),
+    };
+    if output.len() != entry.size || crc32(&output) != entry.crc32 {
+        return Err(invalid(format!("{} does not match its crc32, the archive is damaged", entry.name)));
+    }
+    Ok(output)
+}
+
+// what a path names : the file itself, or the entry of an archive it picks. the name
+// returned has the entry's extension so the format is detected from it
+pub fn read(path: &str) -> Result<(String, Vec<u8>), Error> {
+    let (archive_path, entry_name) = split(path);
+    let content = fs::read(archive_path)?;
+    if entry_name.is_none() && !is_archive(path) && !content.starts_with(SEVEN_ZIP_MAGIC) {
+        return Ok((path.trim().to_string(), content));
+    }
+    let entries = entries(&content)?;
+    let entry = match entry_name {
+        Some(name) => entries.iter().find(|entry| entry.name == name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No {} in {}", name, archive_path)))?,
+        None => *programs(&entries).first()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No program in {}", archive_path)))?,
+    };
+    crate::log_event!(Loader, Info, "{} : {} from the archive, {} bytes", archive_path, entry.name, entry.size);
+    Ok((entry.name.clone(), extract(&content, entry)?))
+}
@@ -0,0 +1,10 @@
+use std::io::{Error, ErrorKind};
+
+use super::program::Program;
+
+pub fn parse(content: &[u8], origin: u16) -> Result<Program, Error> {
+    if origin as usize + content.len() > 0xFFFF + 1 {
+        return Err(Error::new(ErrorKind::InvalidData, format!("{} bytes do not fit at ${:04X}", content.len(), origin)));
+    }
+    Ok(Program::from_bytes(origin, content.to_vec()))
+}
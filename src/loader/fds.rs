@@ -0,0 +1,36 @@
+use std::io::{Error, ErrorKind};
+
+use super::program::Program;
+
+const HEADER_MAGIC : &[u8; 4] = b"FDS\x1A";
+const HEADER_SIZE  : usize = 16;
+pub const SIDE_SIZE: usize = 65_500;
+// every disk side starts with its info block, "*NINTENDO-HVC*" after the block code
+const SIDE_MAGIC   : &[u8; 15] = b"\x01*NINTENDO-HVC*";
+
+// disk sides as stored in .fds files : the blocks back to back, without gaps or crcs
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskImage {
+    pub sides: Vec<Vec<u8>>,
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    content.starts_with(HEADER_MAGIC) || content.starts_with(SIDE_MAGIC)
+}
+
+// the disk carries no code the cpu can see before the bios loads it,
+// so the program only holds the image
+pub fn parse(content: &[u8]) -> Result<Program, Error> {
+    let body = if content.starts_with(HEADER_MAGIC) { &content[HEADER_SIZE.min(content.len())..] } else { content };
+    let sides = body.chunks(SIDE_SIZE)
+        .filter(|side| side.starts_with(SIDE_MAGIC))
+        .map(|side| side.to_vec())
+        .collect::<Vec<Vec<u8>>>();
+    if sides.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a Famicom Disk System image"));
+    }
+
+    let mut program = Program::new();
+    program.disk = Some(DiskImage { sides });
+    Ok(program)
+}
@@ -0,0 +1,16 @@
+use std::io::Error;
+
+use super::program::Program;
+
+// whitespace separated hex bytes, anything that does not parse as a byte is skipped
+pub fn parse(content: &[u8], origin: u16) -> Result<Program, Error> {
+    let text = String::from_utf8_lossy(content);
+    let bytes = text.split_whitespace()
+        .filter_map(|token| u8::from_str_radix(token, 16).ok())
+        .collect::<Vec<u8>>();
+    Ok(Program::from_bytes(origin, bytes))
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    content.iter().all(|c| c.is_ascii_hexdigit() || c.is_ascii_whitespace())
+}
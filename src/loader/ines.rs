@@ -0,0 +1,103 @@
+use std::io::{Error, ErrorKind};
+
+use super::program::Program;
+
+const MAGIC       : &[u8; 4] = b"NES\x1A";
+const HEADER_SIZE : usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_UNIT    : usize = 0x4000;
+const CHR_UNIT    : usize = 0x2000;
+const PRG_RAM_UNIT: usize = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    SingleScreenLow,  // both nametables on the first 1k of the console's vram, boards switch to these
+    SingleScreenHigh, // on the second
+}
+
+// a cartridge dump, kept whole since banked images do not fit the flat address space
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomImage {
+    pub mapper    : u16,
+    pub prg       : Vec<u8>,
+    pub chr       : Vec<u8>,
+    pub mirroring : Mirroring,
+    pub battery   : bool,
+    pub prg_ram   : Option<usize>, // bytes at $6000, None when the header leaves it to the board
+    pub chr_ram   : usize,         // bytes, for boards without chr rom
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+// the prg and chr data after the header and the trainer
+pub fn data(content: &[u8]) -> &[u8] {
+    if content.len() < HEADER_SIZE || !looks_like(content) {
+        return content;
+    }
+    let start = HEADER_SIZE + if content[6] & 0x04 != 0 { TRAINER_SIZE } else { 0 };
+    &content[start.min(content.len())..]
+}
+
+// iNES and the NES 2.0 mapper extension, the program segments are what the cpu sees
+// at power on : the first 16k at $8000 and the last 16k at $C000
+pub fn parse(content: &[u8]) -> Result<Program, Error> {
+    let invalid = |reason: &str| Error::new(ErrorKind::InvalidData, reason.to_string());
+    if content.len() < HEADER_SIZE || !looks_like(content) {
+        return Err(invalid("Not an iNES image"));
+    }
+
+    let flags6 = content[6];
+    let flags7 = content[7];
+    let nes2 = flags7 & 0x0C == 0x08;
+    let mut mapper = (flags6 >> 4) as u16 | (flags7 & 0xF0) as u16;
+    if nes2 {
+        mapper |= ((content[8] & 0x0F) as u16) << 8;
+    }
+    let mirroring = if flags6 & 0x08 != 0 {
+        Mirroring::FourScreen
+    } else if flags6 & 0x01 != 0 {
+        Mirroring::Vertical
+    } else {
+        Mirroring::Horizontal
+    };
+
+    let prg_size = content[4] as usize * PRG_UNIT;
+    let chr_size = content[5] as usize * CHR_UNIT;
+    let prg_start = HEADER_SIZE + if flags6 & 0x04 != 0 { TRAINER_SIZE } else { 0 };
+    let chr_start = prg_start + prg_size;
+    if prg_size == 0 || content.len() < chr_start + chr_size {
+        return Err(invalid("Truncated iNES image"));
+    }
+
+    // NES 2.0 gives both rams as shift counts, volatile and battery backed apart, iNES only the
+    // prg ram in 8k units with 0 for whatever the board has, and chr ram when there is no chr rom
+    let shifted = |count: u8| if count == 0 { 0 } else { 64usize << count };
+    let (prg_ram, chr_ram) = if nes2 {
+        (Some(shifted(content[10] & 0x0F) + shifted(content[10] >> 4)), shifted(content[11] & 0x0F) + shifted(content[11] >> 4))
+    } else {
+        (Some(content[8] as usize * PRG_RAM_UNIT).filter(|size| *size > 0), if chr_size == 0 { CHR_UNIT } else { 0 })
+    };
+
+    let image = RomImage {
+        mapper,
+        prg       : content[prg_start..chr_start].to_vec(),
+        chr       : content[chr_start..chr_start + chr_size].to_vec(),
+        mirroring,
+        battery   : flags6 & 0x02 != 0,
+        prg_ram,
+        chr_ram,
+    };
+
+    let mut program = Program::new();
+    program.push(0x8000, &image.prg[..PRG_UNIT]);
+    program.push(0xC000, &image.prg[image.prg.len() - PRG_UNIT..]);
+    let vector = &image.prg[image.prg.len() - 4..image.prg.len() - 2];
+    program.entry = Some(u16::from_le_bytes([vector[0], vector[1]]));
+    program.image = Some(image);
+    Ok(program)
+}
@@ -0,0 +1,175 @@
+use std::io::{Error, ErrorKind};
+
+// deflate decoding (rfc 1951) for zip entries : stored, fixed and dynamic huffman blocks.
+// codes are decoded a bit at a time as puff does, slow next to a table driven decoder but
+// roms are small and this runs once per load
+const LENGTH_BASES  : [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA  : [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// the order code length code lengths come in, in a dynamic block's header
+const CODE_LENGTH_ORDER : [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const MAX_BITS : usize = 15;
+
+fn invalid(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid compressed data, {}", reason))
+}
+
+struct BitReader<'a> {
+    input  : &'a [u8],
+    offset : usize,
+    buffer : u32,
+    bits   : u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, Error> {
+        while self.bits < count {
+            let byte = *self.input.get(self.offset).ok_or_else(|| invalid("truncated"))?;
+            self.offset += 1;
+            self.buffer |= (byte as u32) << self.bits;
+            self.bits += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.bits -= count;
+        Ok(value)
+    }
+
+    // stored blocks start on a byte
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.bits = 0;
+    }
+}
+
+// canonical huffman code : how many codes of each length, and the symbols by code
+struct Huffman {
+    counts  : [u16; MAX_BITS + 1],
+    symbols : Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..=MAX_BITS {
+            symbols.extend((0..lengths.len()).filter(|symbol| lengths[*symbol] as usize == length).map(|symbol| symbol as u16));
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad code"))
+    }
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic(reader: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat with nothing before"))?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("too many code lengths"));
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+pub fn inflate(input: &[u8], size_hint: usize) -> Result<Vec<u8>, Error> {
+    let mut reader = BitReader { input, offset : 0, buffer : 0, bits : 0 };
+    let mut output = Vec::with_capacity(size_hint);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = input.get(reader.offset..reader.offset + 4).ok_or_else(|| invalid("truncated"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(invalid("stored block length"));
+                }
+                let start = reader.offset + 4;
+                output.extend_from_slice(input.get(start..start + length as usize).ok_or_else(|| invalid("truncated"))?);
+                reader.offset = start + length as usize;
+            },
+            kind @ 1..=2 => {
+                let (literal_code, distance_code) = if kind == 1 { fixed() } else { dynamic(&mut reader)? };
+                loop {
+                    let symbol = literal_code.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        output.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let index = symbol - 257;
+                    if index >= LENGTH_BASES.len() {
+                        return Err(invalid("bad length"));
+                    }
+                    let length = LENGTH_BASES[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                    let index = distance_code.decode(&mut reader)? as usize;
+                    if index >= DISTANCE_BASES.len() {
+                        return Err(invalid("bad distance"));
+                    }
+                    let distance = DISTANCE_BASES[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                    if distance > output.len() {
+                        return Err(invalid("distance too far back"));
+                    }
+                    // a byte at a time, the match may overlap what it copies
+                    let start = output.len() - distance;
+                    for offset in 0..length {
+                        output.push(output[start + offset]);
+                    }
+                }
+            },
+            _ => return Err(invalid("bad block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
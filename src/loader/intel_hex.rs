@@ -0,0 +1,66 @@
+use std::io::{Error, ErrorKind};
+
+use super::program::Program;
+
+fn invalid(line: usize, reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Intel HEX line {}: {}", line, reason))
+}
+
+fn decode(record: &str, line: usize) -> Result<Vec<u8>, Error> {
+    if !record.len().is_multiple_of(2) {
+        return Err(invalid(line, "odd number of digits"));
+    }
+    (0..record.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&record[i..i + 2], 16).map_err(|_| invalid(line, "bad hex digit")))
+        .collect()
+}
+
+// :LLAAAATT[DD..]CC records, addresses beyond the 16 bit space are rejected
+pub fn parse(content: &[u8]) -> Result<Program, Error> {
+    let text = String::from_utf8_lossy(content);
+    let mut program = Program::new();
+    let mut base : u32 = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !line.is_ascii() {
+            return Err(invalid(line_number, "not ascii"));
+        }
+        let record = line.strip_prefix(':').ok_or_else(|| invalid(line_number, "missing ':'"))?;
+        let bytes = decode(record, line_number)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid(line_number, "length mismatch"));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid(line_number, "checksum mismatch"));
+        }
+
+        let offset = ((bytes[1] as u32) << 8) | bytes[2] as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => {
+                let address = base + offset;
+                if address as usize + data.len() > 0xFFFF + 1 {
+                    return Err(invalid(line_number, "address outside of 16 bit space"));
+                }
+                program.push(address as u16, data);
+            },
+            0x01 => break,
+            0x02 if data.len() == 2 => base = (((data[0] as u32) << 8) | data[1] as u32) << 4,
+            0x04 if data.len() == 2 => base = (((data[0] as u32) << 8) | data[1] as u32) << 16,
+            0x03 if data.len() == 4 => program.entry = Some(((data[2] as u16) << 8) | data[3] as u16),
+            0x05 if data.len() == 4 => program.entry = Some(((data[2] as u16) << 8) | data[3] as u16),
+            _ => return Err(invalid(line_number, "unsupported record")),
+        }
+    }
+
+    Ok(program)
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    content.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b':')
+}
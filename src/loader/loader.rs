@@ -0,0 +1,114 @@
+use std::{io::Error, path::Path};
+
+use super::{archive, binary, fds, hex_text, ines, intel_hex, patch, program::Program, srec};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    pub load_address       : u16,  // placement of formats without addresses of their own
+    pub patch_reset_vector : bool, // point $FFFC at the program instead of trusting the image
+}
+
+impl LoadOptions {
+    pub fn new() -> LoadOptions {
+        LoadOptions {
+            load_address       : 0x8000,
+            patch_reset_vector : true,
+        }
+    }
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    HexText,  // whitespace separated hex bytes
+    Binary,   // raw image
+    IntelHex,
+    SRecord,
+    INes,     // cartridge dump with its header
+    Fds,      // famicom disk system sides
+}
+
+impl Format {
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "bin" | "rom" | "prg" => Some(Format::Binary),
+            "nes"                 => Some(Format::INes),
+            "fds"                 => Some(Format::Fds),
+            "hex" | "ihx" | "ihex" => Some(Format::IntelHex),
+            "s19" | "s28" | "s37" | "srec" | "mot" => Some(Format::SRecord),
+            _ => None,
+        }
+    }
+
+    pub fn from_content(content: &[u8]) -> Format {
+        if ines::looks_like(content) {
+            Format::INes
+        } else if fds::looks_like(content) {
+            Format::Fds
+        } else if intel_hex::looks_like(content) {
+            Format::IntelHex
+        } else if srec::looks_like(content) {
+            Format::SRecord
+        } else if hex_text::looks_like(content) {
+            Format::HexText
+        } else {
+            Format::Binary
+        }
+    }
+
+    pub fn detect(path: &Path, content: &[u8]) -> Format {
+        Format::from_extension(path).unwrap_or_else(|| Format::from_content(content))
+    }
+}
+
+// origin is where formats without addresses of their own (hex text, raw binary) get placed
+pub fn parse(format: Format, content: &[u8], origin: u16) -> Result<Program, Error> {
+    match format {
+        Format::HexText  => hex_text::parse(content, origin),
+        Format::Binary   => binary::parse(content, origin),
+        Format::IntelHex => intel_hex::parse(content),
+        Format::SRecord  => srec::parse(content),
+        Format::INes     => ines::parse(content),
+        Format::Fds      => fds::parse(content),
+    }
+}
+
+pub fn load(path: &str, origin: u16) -> Result<Program, Error> {
+    load_patched(path, origin, &[])
+}
+
+// the file, or the archive entry it names, with the ips or bps patches applied over it in
+// order. the name is the entry's for archives, the format is told by its extension
+pub fn read_patched(path: &str, patches: &[String]) -> Result<(String, Vec<u8>), Error> {
+    let (name, mut content) = archive::read(path)?;
+    for patch_path in patches {
+        content = patch::apply_file(patch_path, &content)?;
+        crate::log_event!(Loader, Info, "{} : patched by {}, {} bytes", path.trim(), patch_path.trim(), content.len());
+    }
+    Ok((name, content))
+}
+
+pub fn load_patched(path: &str, origin: u16, patches: &[String]) -> Result<Program, Error> {
+    let (name, content) = read_patched(path, patches)?;
+    let name = Path::new(&name);
+    let format = Format::detect(name, &content);
+    crate::log_event!(Loader, Info, "{} : {:?} by its {}, {} bytes", path.trim(), format,
+        if Format::from_extension(name).is_some() { "extension" } else { "content" }, content.len());
+    parse(format, &content, origin)
+}
+
+// accepts $8000, 0x8000 and plain 8000
+pub fn parse_address(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+const MAGIC       : &[u8; 5] = b"NESM\x1A";
+const HEADER_SIZE : usize = 0x80;
+// the ntsc frame rate, used when a file leaves its play speed at zero
+const DEFAULT_PLAY_PERIOD: u16 = 16_639;
+
+// expansion sound chips, bits of header byte $7B
+pub const EXPANSION_VRC6: u8 = 1 << 0;
+
+// a tune collection : code and data loaded at a fixed address, an init routine taking
+// the song number in A and a play routine called once per frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct NsfImage {
+    pub songs       : u8,
+    pub start_song  : u8, // 1 based, as in the header
+    pub load        : u16,
+    pub init        : u16,
+    pub play        : u16,
+    pub title       : String,
+    pub artist      : String,
+    pub copyright   : String,
+    pub play_period : u16, // microseconds between play calls
+    pub banks       : [u8; 8], // initial 4k banks at $8000 - $FFFF, all zero without bankswitching
+    pub expansion   : u8,
+    pub data        : Vec<u8>,
+}
+
+impl NsfImage {
+    pub fn bankswitched(&self) -> bool {
+        self.banks.iter().any(|bank| *bank != 0)
+    }
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+pub fn load(path: &str) -> Result<NsfImage, Error> {
+    parse(&fs::read(path.trim())?)
+}
+
+pub fn parse(content: &[u8]) -> Result<NsfImage, Error> {
+    if !looks_like(content) || content.len() < HEADER_SIZE {
+        return Err(Error::new(ErrorKind::InvalidData, "Not an NSF file"));
+    }
+    let word = |at: usize| u16::from_le_bytes([content[at], content[at + 1]]);
+    let text = |at: usize| {
+        let field = &content[at..at + 32];
+        let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..end]).trim().to_string()
+    };
+
+    let load = word(0x08);
+    if load < 0x6000 {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Invalid NSF load address: {:04X}", load)));
+    }
+    let mut banks = [0; 8];
+    banks.copy_from_slice(&content[0x70..0x78]);
+    let play_period = match word(0x6E) {
+        0 => DEFAULT_PLAY_PERIOD,
+        period => period,
+    };
+
+    Ok(NsfImage {
+        songs       : content[0x06].max(1),
+        start_song  : content[0x07].max(1),
+        load,
+        init        : word(0x0A),
+        play        : word(0x0C),
+        title       : text(0x0E),
+        artist      : text(0x2E),
+        copyright   : text(0x4E),
+        play_period,
+        banks,
+        expansion   : content[0x7B],
+        data        : content[HEADER_SIZE..].to_vec(),
+    })
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+use super::rom_database::crc32;
+
+// rom patches, applied to the file as read before its format is detected, so a patch
+// made against a .nes dump covers its header too :
+// IPS : "PATCH", records of a 3 byte offset, a 2 byte size and that many bytes (or, with
+//   size 0, a 2 byte count and the byte repeated), "EOF" and an optional 3 byte length to
+//   truncate to. there is no checksum, a patch for another rom goes on silently
+// BPS : "BPS1", then the source, target and metadata sizes, actions copying from the
+//   source, the patch or what was written already, and the crc32 of the source, the target
+//   and the patch itself, all three are checked
+const IPS_MAGIC : &[u8] = b"PATCH";
+const IPS_END   : &[u8] = b"EOF";
+const BPS_MAGIC : &[u8] = b"BPS1";
+const BPS_FOOTER : usize = 12;
+
+fn invalid(reason: String) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
+
+pub fn apply_file(path: &str, rom: &[u8]) -> Result<Vec<u8>, Error> {
+    let patch = fs::read(path.trim())?;
+    apply(&patch, rom).map_err(|err| Error::new(err.kind(), format!("{}: {}", path.trim(), err)))
+}
+
+pub fn apply(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(patch, rom)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(patch, rom)
+    } else {
+        Err(invalid("Not an IPS or BPS patch".to_string()))
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], length: usize) -> Result<&'a [u8], Error> {
+    if input.len() < length {
+        return Err(invalid("Truncated patch".to_string()));
+    }
+    let (taken, rest) = input.split_at(length);
+    *input = rest;
+    Ok(taken)
+}
+
+fn big_endian(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |value, byte| value << 8 | *byte as usize)
+}
+
+fn apply_ips(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut output = rom.to_vec();
+    let mut input = &patch[IPS_MAGIC.len()..];
+    loop {
+        if input.starts_with(IPS_END) && (input.len() == IPS_END.len() || input.len() == IPS_END.len() + 3) {
+            break;
+        }
+        let offset = big_endian(take(&mut input, 3)?);
+        let size = big_endian(take(&mut input, 2)?);
+        let bytes = if size == 0 {
+            let count = big_endian(take(&mut input, 2)?);
+            vec![take(&mut input, 1)?[0]; count]
+        } else {
+            take(&mut input, size)?.to_vec()
+        };
+        if output.len() < offset + bytes.len() {
+            output.resize(offset + bytes.len(), 0);
+        }
+        output[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    let mut input = &input[IPS_END.len()..];
+    if !input.is_empty() {
+        output.truncate(big_endian(take(&mut input, 3)?));
+    }
+    Ok(output)
+}
+
+// beyond the first byte each continuation adds one, so every number has a single encoding
+fn varint(input: &mut &[u8]) -> Result<usize, Error> {
+    let too_large = || invalid("Number too large in patch".to_string());
+    let mut value = 0usize;
+    let mut shift = 1usize;
+    loop {
+        let byte = take(input, 1)?[0] as usize;
+        value = (byte & 0x7F).checked_mul(shift).and_then(|part| value.checked_add(part)).ok_or_else(too_large)?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_mul(0x80).ok_or_else(too_large)?;
+        value = value.checked_add(shift).ok_or_else(too_large)?;
+    }
+}
+
+// a relative offset : the lowest bit is the sign
+fn relative(input: &mut &[u8], position: &mut usize) -> Result<(), Error> {
+    let data = varint(input)?;
+    let distance = data >> 1;
+    *position = if data & 1 != 0 { position.checked_sub(distance) } else { position.checked_add(distance) }
+        .ok_or_else(|| invalid("Copy out of bounds in patch".to_string()))?;
+    Ok(())
+}
+
+fn footer(patch: &[u8], index: usize) -> u32 {
+    let start = patch.len() - BPS_FOOTER + index * 4;
+    u32::from_le_bytes([patch[start], patch[start + 1], patch[start + 2], patch[start + 3]])
+}
+
+fn apply_bps(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER {
+        return Err(invalid("Truncated patch".to_string()));
+    }
+    if crc32(&patch[..patch.len() - 4]) != footer(patch, 2) {
+        return Err(invalid("Corrupted patch, its crc32 does not match".to_string()));
+    }
+    if crc32(rom) != footer(patch, 0) {
+        return Err(invalid(format!("Patch made for another rom, crc32 {:08X} expected, {:08X} found", footer(patch, 0), crc32(rom))));
+    }
+    let mut input = &patch[BPS_MAGIC.len()..patch.len() - BPS_FOOTER];
+    let source_size = varint(&mut input)?;
+    let target_size = varint(&mut input)?;
+    let metadata_size = varint(&mut input)?;
+    take(&mut input, metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid(format!("Patch made for a rom of {} bytes, not {}", source_size, rom.len())));
+    }
+
+    // the size is the patch's word until the actions write it, so only so much is reserved
+    let mut output = Vec::with_capacity(target_size.min(rom.len() + patch.len() * 4));
+    let (mut source_offset, mut target_offset) = (0, 0);
+    let out_of_bounds = || invalid("Copy out of bounds in patch".to_string());
+    while !input.is_empty() {
+        let action = varint(&mut input)?;
+        let length = (action >> 2) + 1;
+        if output.len() + length > target_size {
+            return Err(out_of_bounds());
+        }
+        match action & 3 {
+            // source read : the source's bytes at the same place
+            0 => output.extend_from_slice(rom.get(output.len()..output.len() + length).ok_or_else(out_of_bounds)?),
+            // target read : bytes from the patch
+            1 => output.extend_from_slice(take(&mut input, length)?),
+            // source copy : from anywhere in the source
+            2 => {
+                relative(&mut input, &mut source_offset)?;
+                output.extend_from_slice(rom.get(source_offset..source_offset + length).ok_or_else(out_of_bounds)?);
+                source_offset += length;
+            },
+            // target copy : from what was written, a byte at a time as the runs may overlap
+            _ => {
+                relative(&mut input, &mut target_offset)?;
+                for _ in 0..length {
+                    let byte = *output.get(target_offset).ok_or_else(out_of_bounds)?;
+                    output.push(byte);
+                    target_offset += 1;
+                }
+            },
+        }
+    }
+    if output.len() != target_size {
+        return Err(invalid(format!("Patch wrote {} bytes instead of {}", output.len(), target_size)));
+    }
+    if crc32(&output) != footer(patch, 1) {
+        return Err(invalid("Patched rom does not match the patch's crc32".to_string()));
+    }
+    Ok(output)
+}
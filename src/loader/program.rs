@@ -0,0 +1,61 @@
+use super::fds::DiskImage;
+use super::ines::RomImage;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub address : u16,
+    pub bytes   : Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub segments : Vec<Segment>,
+    pub entry    : Option<u16>,
+    pub image    : Option<RomImage>, // set for cartridge dumps, which need a mapper
+    pub disk     : Option<DiskImage>, // set for disk images, which need the disk system bios
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program {
+            segments : Vec::new(),
+            entry    : None,
+            image    : None,
+            disk     : None,
+        }
+    }
+
+    pub fn from_bytes(address: u16, bytes: Vec<u8>) -> Program {
+        Program {
+            segments : vec![Segment { address, bytes }],
+            entry    : None,
+            image    : None,
+            disk     : None,
+        }
+    }
+
+    // appends data, extending the last segment when the record is contiguous with it
+    pub fn push(&mut self, address: u16, bytes: &[u8]) {
+        if let Some(last) = self.segments.last_mut() {
+            if last.address as usize + last.bytes.len() == address as usize {
+                last.bytes.extend_from_slice(bytes);
+                return;
+            }
+        }
+        self.segments.push(Segment { address, bytes: bytes.to_vec() });
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|segment| segment.bytes.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Program::new()
+    }
+}
@@ -0,0 +1,284 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::config::Config;
+use super::ines::{self, Mirroring};
+use super::loader::{self, LoadOptions};
+use super::program::Program;
+
+const EMBEDDED_DATABASE: &str = include_str!("rom_database.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RomIdentity {
+    pub crc32 : u32,
+    pub sha1  : [u8; 20],
+}
+
+impl RomIdentity {
+    // a cartridge dump is hashed without its header and trainer, as NesCart records are, so
+    // a bad header still matches
+    pub fn of(content: &[u8]) -> RomIdentity {
+        let content = ines::data(content);
+        RomIdentity { crc32: crc32(content), sha1: sha1(content) }
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        self.sha1.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RomEntry {
+    pub crc32     : u32,
+    pub sha1      : Option<String>, // lowercase hex, checked as well when present
+    pub name      : String,
+    pub overrides : Config,
+}
+
+#[derive(Debug, Clone)]
+pub struct RomDatabase {
+    pub entries: Vec<RomEntry>,
+}
+
+impl RomDatabase {
+    pub fn embedded() -> RomDatabase {
+        RomDatabase::parse(EMBEDDED_DATABASE).expect("embedded rom database is malformed")
+    }
+
+    // `crc32 | sha1 | name | key = value, key = value` per line, # starts a comment line
+    pub fn parse(content: &str) -> Result<RomDatabase, Error> {
+        let mut entries = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::new(ErrorKind::InvalidData, format!("rom database line {}: expected crc32 | sha1 | name | overrides", index + 1));
+            let fields = line.split('|').map(str::trim).collect::<Vec<&str>>();
+            if fields.len() < 3 {
+                return Err(invalid());
+            }
+            let crc32 = u32::from_str_radix(fields[0], 16).map_err(|_| invalid())?;
+            let sha1 = Some(fields[1].to_ascii_lowercase()).filter(|sha1| !sha1.is_empty());
+            let overrides = Config::parse(&fields.get(3).unwrap_or(&"").replace(',', "\n"))?;
+            entries.push(RomEntry { crc32, sha1, name: fields[2].to_string(), overrides });
+        }
+        Ok(RomDatabase { entries })
+    }
+
+    // a NesCart xml export (or nestopia's NstDatabase.xml) : a record per <cartridge crc sha1>,
+    // named by its <game name> when it has one, its <board mapper>, <wram size battery> and
+    // <pad h v> turned into header keys. what is not understood is passed over
+    pub fn parse_nescart(content: &str) -> RomDatabase {
+        let mut entries = Vec::new();
+        let mut name = String::new();
+        let mut cartridge: Option<(RomEntry, usize)> = None; // with its prg ram added up, in k
+        for tag in content.split('<').skip(1).filter_map(|tag| tag.split_once('>').map(|(tag, _)| tag)) {
+            let element = tag.trim_end_matches('/').split_whitespace().next().unwrap_or("");
+            let attributes = xml_attributes(tag);
+            let attribute = |key: &str| attributes.iter().find(|(name, _)| *name == key).map(|(_, value)| value.as_str());
+            match (element, cartridge.as_mut()) {
+                ("game", _) => name = attribute("name").unwrap_or("").to_string(),
+                ("cartridge", _) => cartridge = attribute("crc").and_then(|crc| u32::from_str_radix(crc, 16).ok()).map(|crc32| (RomEntry {
+                    crc32,
+                    sha1      : attribute("sha1").map(str::to_ascii_lowercase).filter(|sha1| !sha1.is_empty()),
+                    name      : if name.is_empty() { format!("{:08X}", crc32) } else { name.clone() },
+                    overrides : Config::default(),
+                }, 0)),
+                ("board", Some((entry, _))) => if let Some(mapper) = attribute("mapper") {
+                    entry.overrides.values.insert("header.mapper".to_string(), mapper.to_string());
+                },
+                ("wram", Some((entry, prg_ram))) => {
+                    *prg_ram += attribute("size").and_then(|size| size.trim_end_matches(['k', 'K']).parse::<usize>().ok()).unwrap_or(0);
+                    if attribute("battery") == Some("1") {
+                        entry.overrides.values.insert("header.battery".to_string(), "true".to_string());
+                    }
+                },
+                ("pad", Some((entry, _))) => {
+                    let mirroring = match (attribute("h"), attribute("v")) {
+                        (Some("1"), _) => "horizontal",
+                        (_, Some("1")) => "vertical",
+                        _ => continue,
+                    };
+                    entry.overrides.values.insert("header.mirroring".to_string(), mirroring.to_string());
+                },
+                ("/cartridge", Some(_)) => if let Some((mut entry, prg_ram)) = cartridge.take() {
+                    if prg_ram > 0 {
+                        entry.overrides.values.insert("header.prg_ram".to_string(), prg_ram.to_string());
+                    }
+                    entries.push(entry);
+                },
+                _ => {},
+            }
+        }
+        RomDatabase { entries }
+    }
+
+    pub fn lookup(&self, identity: &RomIdentity) -> Option<&RomEntry> {
+        let sha1 = identity.sha1_hex();
+        self.entries.iter()
+            .find(|entry| entry.crc32 == identity.crc32 && entry.sha1.as_ref().is_none_or(|expected| *expected == sha1))
+    }
+}
+
+// `key="value"` pairs of a tag, the five entities of xml read back
+fn xml_attributes(tag: &str) -> Vec<(&str, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    while let Some((key, value)) = rest.split_once('=') {
+        let value = value.trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let (text, after) = match value[1..].split_once(quote) {
+            Some(split) => split,
+            None => break,
+        };
+        let key = key.split_whitespace().last().unwrap_or("");
+        let text = text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&");
+        attributes.push((key, text));
+        rest = after;
+    }
+    attributes
+}
+
+// what is known about the loaded image : its hashes, the database match if any (the embedded
+// one, then the NesCart export `games.nescart` names), and the settings layered from the
+// database entry then `<games.directory>/<CRC32>.toml` (or `<program name>.toml`)
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    pub identity : RomIdentity,
+    pub name     : Option<String>,
+    pub settings : Config,
+}
+
+impl GameSettings {
+    pub fn identify(config: &Config, program_path: &str, content: &[u8]) -> Result<GameSettings, Error> {
+        let identity = RomIdentity::of(content);
+        let entry = match RomDatabase::embedded().lookup(&identity) {
+            Some(entry) => Some(entry.clone()),
+            None => match config.get("games.nescart") {
+                Some(path) => {
+                    let content = fs::read(path).map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))?;
+                    RomDatabase::parse_nescart(&String::from_utf8_lossy(&content)).lookup(&identity).cloned()
+                },
+                None => None,
+            },
+        };
+
+        let directory = Path::new(config.get("games.directory").unwrap_or("games")).to_path_buf();
+        let stem = Path::new(program_path.trim()).file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+        let by_hash = directory.join(format!("{:08X}.toml", identity.crc32));
+        let by_name = directory.join(format!("{}.toml", stem));
+        let game_file = if by_hash.exists() { by_hash } else { by_name };
+
+        let mut settings = entry.as_ref().map(|entry| entry.overrides.clone()).unwrap_or_default();
+        settings.overlay(&Config::load(&game_file.to_string_lossy())?);
+
+        Ok(GameSettings { identity, name: entry.map(|entry| entry.name), settings })
+    }
+
+    pub fn load_options(&self, base: LoadOptions) -> LoadOptions {
+        LoadOptions {
+            load_address       : self.settings.get("load.address").and_then(loader::parse_address).unwrap_or(base.load_address),
+            patch_reset_vector : self.settings.get_or("load.reset_vector", base.patch_reset_vector),
+        }
+    }
+
+    // the configuration the game runs with : its input and video keys (input.port2,
+    // video.overscan_top, ..) over the ones of the config file
+    pub fn overlaid(&self, config: &Config) -> Config {
+        let mut overlaid = config.clone();
+        overlaid.overlay(&self.settings);
+        overlaid
+    }
+
+    // the header keys fix what a bad iNES header says about the board
+    pub fn correct(&self, program: &mut Program) -> Result<(), Error> {
+        let image = match program.image.as_mut() {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        let invalid = |key: &str, value: &str| Error::new(ErrorKind::InvalidData, format!("{}: invalid {} {}", self.settings_name(), key, value));
+        if let Some(mapper) = self.settings.get("header.mapper") {
+            image.mapper = mapper.parse().map_err(|_| invalid("header.mapper", mapper))?;
+        }
+        if let Some(mirroring) = self.settings.get("header.mirroring") {
+            image.mirroring = match mirroring.to_ascii_lowercase().as_str() {
+                "horizontal"  => Mirroring::Horizontal,
+                "vertical"    => Mirroring::Vertical,
+                "four_screen" => Mirroring::FourScreen,
+                _ => return Err(invalid("header.mirroring", mirroring)),
+            };
+        }
+        if let Some(size) = self.settings.get("header.prg_ram") {
+            let size = size.parse::<usize>().map_err(|_| invalid("header.prg_ram", size))?;
+            image.prg_ram = Some(size * 1024).filter(|size| *size > 0);
+        }
+        image.battery = self.settings.get_or("header.battery", image.battery);
+        Ok(())
+    }
+
+    fn settings_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{:08X}", self.identity.crc32))
+    }
+}
+
+pub fn crc32(content: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in content {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn sha1(content: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+
+    let mut message = content.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((content.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            words[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19  => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _       => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *value = value.wrapping_add(*add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, value) in digest.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,72 @@
+use std::io::{Error, ErrorKind};
+
+use super::program::Program;
+
+fn invalid(line: usize, reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("S-record line {}: {}", line, reason))
+}
+
+fn decode(record: &str, line: usize) -> Result<Vec<u8>, Error> {
+    if !record.len().is_multiple_of(2) {
+        return Err(invalid(line, "odd number of digits"));
+    }
+    (0..record.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&record[i..i + 2], 16).map_err(|_| invalid(line, "bad hex digit")))
+        .collect()
+}
+
+// Motorola S-records, S1/S2/S3 carry data, S7/S8/S9 the start address
+pub fn parse(content: &[u8]) -> Result<Program, Error> {
+    let text = String::from_utf8_lossy(content);
+    let mut program = Program::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !line.is_ascii() {
+            return Err(invalid(line_number, "not ascii"));
+        }
+        if line.len() < 2 || !line.starts_with('S') {
+            return Err(invalid(line_number, "missing 'S'"));
+        }
+        let kind = line.as_bytes()[1];
+        let bytes = decode(&line[2..], line_number)?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(invalid(line_number, "length mismatch"));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+            return Err(invalid(line_number, "checksum mismatch"));
+        }
+
+        let address_width = match kind {
+            b'0' | b'1' | b'5' | b'9' => 2,
+            b'2' | b'6' | b'8' => 3,
+            b'3' | b'7' => 4,
+            _ => return Err(invalid(line_number, "unsupported record")),
+        };
+        if bytes.len() < address_width + 2 {
+            return Err(invalid(line_number, "record too short"));
+        }
+        let address = bytes[1..=address_width].iter().fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+        let data = &bytes[address_width + 1..bytes.len() - 1];
+        if address as usize + data.len() > 0xFFFF + 1 {
+            return Err(invalid(line_number, "address outside of 16 bit space"));
+        }
+
+        match kind {
+            b'1' | b'2' | b'3' => program.push(address as u16, data),
+            b'7' | b'8' | b'9' => program.entry = Some(address as u16),
+            _ => {},
+        }
+    }
+
+    Ok(program)
+}
+
+pub fn looks_like(content: &[u8]) -> bool {
+    let mut meaningful = content.iter().skip_while(|c| c.is_ascii_whitespace());
+    meaningful.next() == Some(&b'S') && meaningful.next().is_some_and(|c| c.is_ascii_digit())
+}
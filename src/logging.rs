@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+// the environment variable read when --log is not given
+pub const LOG_ENV: &str = "BRICK_STATION_LOG";
+// lines the log pane keeps
+const PANE_LINES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Level, Error> {
+        [Level::Off, Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace].iter().copied()
+            .find(|level| level.name() == text.trim().to_ascii_lowercase())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid log level: {}", text)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subsystem {
+    Bus,    // devices mapped, removed, cartridges inserted
+    Mapper, // bank switches
+    Irq,    // interrupt lines and the interrupts the cpu takes
+    Loader, // formats detected and how programs were placed
+}
+
+pub const SUBSYSTEMS: [Subsystem; 4] = [Subsystem::Bus, Subsystem::Mapper, Subsystem::Irq, Subsystem::Loader];
+
+impl Subsystem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::Bus => "bus",
+            Subsystem::Mapper => "mapper",
+            Subsystem::Irq => "irq",
+            Subsystem::Loader => "loader",
+        }
+    }
+}
+
+// one level per subsystem, read on every event before anything is formatted
+static LEVELS: [AtomicU8; 4] = [AtomicU8::new(Level::Warn as u8), AtomicU8::new(Level::Warn as u8), AtomicU8::new(Level::Warn as u8), AtomicU8::new(Level::Warn as u8)];
+
+struct Sinks {
+    file : Option<BufWriter<File>>,
+    pane : VecDeque<String>,
+}
+
+static SINKS: Mutex<Sinks> = Mutex::new(Sinks { file: None, pane: VecDeque::new() });
+
+// `info` for every subsystem, `bus=debug,irq=trace` for some, or both : `warn,mapper=debug`
+pub fn configure(spec: &str) -> Result<(), Error> {
+    let mut levels = SUBSYSTEMS.iter().map(|subsystem| (*subsystem, level(*subsystem))).collect::<Vec<(Subsystem, Level)>>();
+    for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('=') {
+            Some((name, value)) => {
+                let subsystem = SUBSYSTEMS.iter()
+                    .find(|subsystem| subsystem.name() == name.trim().to_ascii_lowercase())
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unknown log subsystem: {}", name)))?;
+                let value = value.parse()?;
+                levels.iter_mut().filter(|(known, _)| known == subsystem).for_each(|(_, level)| *level = value);
+            },
+            None => {
+                let value = part.parse()?;
+                levels.iter_mut().for_each(|(_, level)| *level = value);
+            },
+        }
+    }
+    for (subsystem, value) in levels {
+        LEVELS[subsystem as usize].store(value as u8, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+pub fn level(subsystem: Subsystem) -> Level {
+    match LEVELS[subsystem as usize].load(Ordering::Relaxed) {
+        0 => Level::Off,
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+pub fn enabled(subsystem: Subsystem, level: Level) -> bool {
+    level != Level::Off && level as u8 <= LEVELS[subsystem as usize].load(Ordering::Relaxed)
+}
+
+// events go to the pane always and to the file when there is one
+pub fn log_to_file(path: &str) -> Result<(), Error> {
+    let file = BufWriter::new(File::create(path)?);
+    if let Ok(mut sinks) = SINKS.lock() {
+        sinks.file = Some(file);
+    }
+    Ok(())
+}
+
+pub fn record(subsystem: Subsystem, level: Level, message: fmt::Arguments) {
+    let line = format!("{:<5} {:<6} {}", level.name(), subsystem.name(), message);
+    if let Ok(mut sinks) = SINKS.lock() {
+        if let Some(file) = sinks.file.as_mut() {
+            let _ = writeln!(file, "{}", line);
+            let _ = file.flush();
+        }
+        if sinks.pane.len() == PANE_LINES {
+            sinks.pane.pop_front();
+        }
+        sinks.pane.push_back(line);
+    }
+}
+
+// the last lines logged, oldest first
+pub fn recent(count: usize) -> Vec<String> {
+    SINKS.lock()
+        .map(|sinks| sinks.pane.iter().skip(sinks.pane.len().saturating_sub(count)).cloned().collect())
+        .unwrap_or_default()
+}
+
+// formats only when the subsystem logs at that level
+#[macro_export]
+macro_rules! log_event {
+    ($subsystem:ident, $level:ident, $($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Subsystem::$subsystem, $crate::logging::Level::$level) {
+            $crate::logging::record($crate::logging::Subsystem::$subsystem, $crate::logging::Level::$level, format_args!($($arg)*));
+        }
+    };
+}
@@ -1,8 +1,33 @@
-use debugger::debugger::State;
+use brick_station::cli::{Arguments, DeterminismArguments, NsfArguments, TestArguments, TraceDiffArguments};
+use brick_station::debugger::debugger::State;
+use brick_station::debugger::{determinism, trace_diff};
+use brick_station::player::nsf_player;
+use brick_station::test_runner;
 
-pub mod hardware;
-pub mod debugger;
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    State::start(args[1].clone()).unwrap();
-}
\ No newline at end of file
+    if args.get(1).map(String::as_str) == Some("nsf") {
+        let arguments = NsfArguments::parse(&args[2..]).unwrap();
+        nsf_player::start(arguments).unwrap();
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("trace-diff") {
+        let arguments = TraceDiffArguments::parse(&args[2..]).unwrap();
+        trace_diff::start(arguments).unwrap();
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("determinism") {
+        let arguments = DeterminismArguments::parse(&args[2..]).unwrap();
+        determinism::start(arguments).unwrap();
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("test") {
+        let arguments = TestArguments::parse(&args[2..]).unwrap();
+        if !test_runner::start(arguments).unwrap() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let arguments = Arguments::parse(&args[1..]).unwrap();
+    State::start(arguments).unwrap();
+}
@@ -0,0 +1,6 @@
+pub mod wav;
+pub mod nsf_player;
+pub mod png;
+pub mod frames;
+pub mod video;
+pub mod ntsc;
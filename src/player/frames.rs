@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use crate::hardware::framebuffer::FrameBuffer;
+use crate::loader::rom_database::crc32;
+
+use super::png;
+use super::video::VideoOptions;
+
+// animations are kept in memory until written, ten seconds of frames at most
+pub const CLIP_LIMIT: usize = 600;
+
+// crc32 of the front buffer as rgb bytes, the same bytes a screenshot stores, so
+// screenshot based tests can compare hashes instead of images once the ppu draws
+pub fn frame_hash(video: &FrameBuffer) -> u32 {
+    let rgb = video.front().iter()
+        .flat_map(|pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])
+        .collect::<Vec<u8>>();
+    crc32(&rgb)
+}
+
+// every completed frame from the front buffer : a directory gets one png per frame
+// (frame_000000.png, ...), a path ending in .png collects a short clip into an animated
+// png, written when the recording is finished. frames are stored as the video options present them
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    pub target  : String,
+    pub written : u64, // frames captured so far
+    pub video   : VideoOptions,
+    animated    : bool,
+    clip        : Vec<Vec<u32>>,
+    size        : (usize, usize),
+    last_frame  : Option<u64>,
+}
+
+impl FrameRecorder {
+    pub fn new(target: &str, video: VideoOptions) -> Result<FrameRecorder, Error> {
+        let target = target.trim().to_string();
+        let animated = target.to_ascii_lowercase().ends_with(".png");
+        if !animated {
+            fs::create_dir_all(&target)?;
+        }
+        Ok(FrameRecorder {
+            target,
+            written    : 0,
+            video,
+            animated,
+            clip       : Vec::new(),
+            size       : (0, 0),
+            last_frame : None,
+        })
+    }
+
+    // a frame already captured is skipped, so calling this more often than frames
+    // complete is harmless
+    pub fn capture(&mut self, video: &FrameBuffer) -> Result<(), Error> {
+        if video.frame == 0 || self.last_frame == Some(video.frame) {
+            return Ok(());
+        }
+        self.last_frame = Some(video.frame);
+        let (width, height, pixels) = self.video.present(video);
+        self.size = (width, height);
+        if self.animated {
+            if self.clip.len() < CLIP_LIMIT {
+                self.clip.push(pixels);
+                self.written += 1;
+            }
+            return Ok(());
+        }
+        let path = Path::new(&self.target).join(format!("frame_{:06}.png", self.written));
+        fs::write(path, png::encode(width, height, &pixels))?;
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.animated && !self.clip.is_empty() {
+            fs::write(&self.target, png::encode_animation(self.size.0, self.size.1, &self.clip, (1, 60)))?;
+            self.clip.clear();
+        }
+        Ok(())
+    }
+}
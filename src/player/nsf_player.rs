@@ -0,0 +1,222 @@
+use std::io;
+use std::io::Error;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::*;
+use tui::backend::CrosstermBackend;
+use tui::layout::*;
+use tui::style::*;
+use tui::text::*;
+use tui::widgets::*;
+use tui::Terminal;
+
+use crate::cli::NsfArguments;
+use crate::hardware::audio::{CPU_CLOCK, SAMPLE_RATE};
+use crate::hardware::device::Device;
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use crate::hardware::nsf::Nsf;
+use crate::loader::nsf::{self, NsfImage};
+
+use super::wav::WavRecording;
+
+// the cpu waits in a jmp to itself between play calls, routines return into it
+const IDLE: u16 = 0x5000;
+// init routines get this long to return before the song starts anyway
+const INIT_BUDGET: u64 = 4 * CPU_CLOCK as u64;
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const METER_WIDTH: usize = 48;
+
+// drives a tune the way the player hardware does : init once with the song number,
+// then play every play period, the output is whatever the mixer sampled meanwhile
+pub struct NsfPlayer<'a> {
+    pub image    : NsfImage,
+    pub machine  : Machine<'a>,
+    pub song     : u8, // 0 based
+    frame_cycles : f64,
+    next_frame   : f64,
+}
+
+impl<'a> NsfPlayer<'a> {
+    pub fn new(image: NsfImage) -> NsfPlayer<'a> {
+        let song = image.start_song - 1;
+        let mut player = NsfPlayer {
+            frame_cycles : image.play_period as f64 * CPU_CLOCK / 1_000_000.0,
+            next_frame   : 0.0,
+            machine      : Machine::new(),
+            image,
+            song,
+        };
+        player.select(song);
+        player
+    }
+
+    // every song starts from a fresh machine with silenced channels, as init expects
+    pub fn select(&mut self, song: u8) {
+        self.song = song % self.image.songs;
+        self.machine = Machine::new();
+        {
+            let mut bus = self.machine.bus.borrow_mut();
+            bus.insert_cartridge(Device::Nsf(Nsf::new(&self.image)));
+            for (i, byte) in [0x4C, IDLE as u8, (IDLE >> 8) as u8].iter().enumerate() {
+                bus.write(IDLE + i as u16, *byte);
+            }
+            for addr in 0x4000..=0x4013 {
+                bus.write(addr, 0);
+            }
+            bus.write(0x4015, 0x0F);
+            bus.write(0x4017, 0x40);
+        }
+        self.machine.cpu.borrow_mut().registers.pc = IDLE;
+
+        // X selects ntsc
+        self.call(self.image.init, self.song, 0);
+        let start = self.cycles();
+        while !self.idle() && self.cycles() - start < INIT_BUDGET {
+            self.machine.tick();
+        }
+        self.next_frame = self.cycles() as f64;
+        self.machine.audio.borrow_mut().drain();
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.machine.cpu.borrow().total_cycles
+    }
+
+    fn idle(&self) -> bool {
+        let cpu = self.machine.cpu.borrow();
+        cpu.cycle == 0 && cpu.registers.pc == IDLE
+    }
+
+    // a jsr from the idle loop
+    fn call(&self, routine: u16, a: u8, x: u8) {
+        let mut cpu = self.machine.cpu.borrow_mut();
+        let sp = cpu.registers.sp;
+        cpu.write(0x0100 + sp as u16, ((IDLE - 1) >> 8) as u8);
+        cpu.write(0x0100 + sp.wrapping_sub(1) as u16, (IDLE - 1) as u8);
+        cpu.registers.sp = sp.wrapping_sub(2);
+        cpu.registers.a = a;
+        cpu.registers.x = x;
+        cpu.registers.pc = routine;
+    }
+
+    // a play routine running late delays the next call rather than piling them up
+    pub fn run(&mut self, cycles: u64) {
+        let end = self.cycles() + cycles;
+        while self.cycles() < end {
+            if self.idle() && self.cycles() as f64 >= self.next_frame {
+                self.call(self.image.play, 0, 0);
+                self.next_frame = (self.next_frame + self.frame_cycles).max(self.cycles() as f64);
+            }
+            self.machine.tick();
+        }
+    }
+
+    pub fn render(&mut self, recording: &mut WavRecording) {
+        let slice = (CPU_CLOCK / 60.0) as u64;
+        self.machine.audio.borrow_mut().split_stems(recording.split);
+        while !recording.is_complete() {
+            self.run(slice);
+            let mut audio = self.machine.audio.borrow_mut();
+            recording.append(audio.drain(), audio.drain_stems());
+        }
+    }
+
+    fn describe(&self) -> String {
+        let title = if self.image.title.is_empty() { "<untitled>" } else { &self.image.title };
+        format!("{} - track {}/{}", title, self.song + 1, self.image.songs)
+    }
+}
+
+// `<stem>-<track>.wav` next to the tune
+fn track_wav_path(path: &str, song: u8) -> String {
+    let path = Path::new(path.trim());
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("track");
+    path.with_file_name(format!("{}-{}.wav", stem, song + 1)).to_string_lossy().to_string()
+}
+
+fn record(image: &NsfImage, song: u8, seconds: f64, path: &str, stems: bool) -> Result<Vec<String>, Error> {
+    let mut recorder = NsfPlayer::new(image.clone());
+    recorder.select(song);
+    let mut recording = WavRecording::new(path, SAMPLE_RATE, Some(seconds), stems);
+    recorder.render(&mut recording);
+    recording.finish()
+}
+
+fn build_view<B: tui::backend::Backend>(f: &mut tui::Frame<B>, player: &NsfPlayer, level: f32, paused: bool, status: &str) {
+    let size = f.size();
+    let seconds = player.cycles() as f64 / CPU_CLOCK;
+    let filled = ((level * 2.0).min(1.0) * METER_WIDTH as f32) as usize;
+    let lines = vec![
+        Spans::from(Span::styled(player.describe(), Style::default().add_modifier(Modifier::BOLD))),
+        Spans::from(format!("Artist    : {}", player.image.artist)),
+        Spans::from(format!("Copyright : {}", player.image.copyright)),
+        Spans::from(""),
+        Spans::from(format!("{:02}:{:02} {}", seconds as u64 / 60, seconds as u64 % 60, if paused { "(paused)" } else { "" })),
+        Spans::from(format!("[{}{}]", "#".repeat(filled), " ".repeat(METER_WIDTH - filled))),
+        Spans::from(""),
+        Spans::from("Left / Right : track, Space : pause, w : write the track to wav, q : quit"),
+        Spans::from(status.to_string()),
+    ];
+    let view = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("NSF"))
+        .style(Style::default().bg(Color::White).fg(Color::Black));
+    f.render_widget(view, Rect::new(size.x, size.y, size.width.min(80), size.height.min(11)));
+}
+
+pub fn start(arguments: NsfArguments) -> Result<(), Error> {
+    let image = nsf::load(&arguments.path)?;
+    let mut player = NsfPlayer::new(image);
+    if let Some(track) = arguments.track {
+        player.select(track.saturating_sub(1));
+    }
+
+    // headless : render the track and leave
+    if let Some(path) = &arguments.wav_path {
+        let written = record(&player.image, player.song, arguments.seconds, path, arguments.stems)?;
+        println!("{} : {:.0} seconds written to {}", player.describe(), arguments.seconds, written.join(", "));
+        return Ok(());
+    }
+
+    // there is no sound output device, playback is shown and can be written to wav
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal.clear()?;
+    terminal.hide_cursor()?;
+    let mut paused = false;
+    let mut level = 0.0;
+    let mut status = String::new();
+    loop {
+        let started = Instant::now();
+        terminal.draw(|f| build_view(f, &player, level, paused, &status))?;
+
+        if !paused {
+            player.run((CPU_CLOCK / 60.0) as u64);
+            let samples = player.machine.audio.borrow_mut().drain();
+            level = samples.iter().copied().fold(0.0, f32::max);
+        }
+
+        if poll(Duration::from_millis(0)).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = read() {
+                match key.code {
+                    KeyCode::Right => player.select(player.song + 1),
+                    KeyCode::Left => player.select((player.song + player.image.songs - 1) % player.image.songs),
+                    KeyCode::Char(' ') => paused = !paused,
+                    KeyCode::Char('w') => {
+                        let path = track_wav_path(&arguments.path, player.song);
+                        status = match record(&player.image, player.song, arguments.seconds, &path, arguments.stems) {
+                            Ok(written) => format!("written {}", written.join(", ")),
+                            Err(err) => format!("cannot write {}: {}", path, err),
+                        };
+                    },
+                    KeyCode::Esc | KeyCode::Char('q') => break,
+                    _ => {}
+                }
+            }
+        }
+        thread::sleep(FRAME_TIME.saturating_sub(started.elapsed()));
+    }
+    terminal.show_cursor()?;
+    Ok(())
+}
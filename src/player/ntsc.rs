@@ -0,0 +1,66 @@
+// the picture as an ntsc tv gets it through the composite cable : each pixel becomes a few
+// samples of luma with the chroma riding on the 3.58 MHz subcarrier, which a tv separates
+// again by averaging over a cycle. fine luma detail leaks into the colors and colors bleed
+// into their neighbours, the dithering and artifact colors many games were drawn for
+use std::f32::consts::PI;
+
+// the nes pixel clock is 1.5 times the subcarrier, so a pixel spans 240 degrees of it
+const SAMPLES_PER_PIXEL : isize = 4;
+const SAMPLES_PER_CYCLE : isize = 6;  // 60 degrees apart
+// each scanline starts a third of a cycle later, odd frames half a cycle on (dot crawl)
+const LINE_SHIFT        : isize = 2;
+const FRAME_SHIFT       : isize = 3;
+// luma is averaged over one subcarrier cycle, chroma is demodulated over two
+const LUMA_WINDOW       : isize = SAMPLES_PER_CYCLE;
+const CHROMA_WINDOW     : isize = SAMPLES_PER_CYCLE * 2;
+
+fn to_yiq(pixel: u32) -> [f32; 3] {
+    let (r, g, b) = (((pixel >> 16) & 0xFF) as f32, ((pixel >> 8) & 0xFF) as f32, (pixel & 0xFF) as f32);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.596 * r - 0.274 * g - 0.322 * b,
+        0.211 * r - 0.523 * g + 0.312 * b,
+    ]
+}
+
+fn to_rgb([y, i, q]: [f32; 3]) -> u32 {
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u32;
+    channel(y + 0.956 * i + 0.621 * q) << 16
+        | channel(y - 0.272 * i - 0.647 * q) << 8
+        | channel(y - 1.106 * i + 1.703 * q)
+}
+
+// the same size as the frame, `frame` moves the subcarrier phase as the console does
+pub fn filter(width: usize, height: usize, pixels: &[u32], frame: u64) -> Vec<u32> {
+    let carrier = (0..SAMPLES_PER_CYCLE)
+        .map(|step| (step as f32 * 2.0 * PI / SAMPLES_PER_CYCLE as f32).sin_cos())
+        .collect::<Vec<(f32, f32)>>();
+    let mut output = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &pixels[(y * width).min(pixels.len())..((y + 1) * width).min(pixels.len())];
+        if row.is_empty() {
+            output.resize(output.len() + width, 0);
+            continue;
+        }
+        let yiq = row.iter().map(|pixel| to_yiq(*pixel)).collect::<Vec<[f32; 3]>>();
+        let start = y as isize * LINE_SHIFT + (frame % 2) as isize * FRAME_SHIFT;
+        // samples past either end repeat the pixel at the edge, carrier running on
+        let sample = |t: isize| {
+            let [luma, i, q] = yiq[t.div_euclid(SAMPLES_PER_PIXEL).clamp(0, yiq.len() as isize - 1) as usize];
+            let (sin, cos) = carrier[(start + t).rem_euclid(SAMPLES_PER_CYCLE) as usize];
+            (luma + i * cos + q * sin, sin, cos)
+        };
+        for x in 0..width as isize {
+            let center = x * SAMPLES_PER_PIXEL + SAMPLES_PER_PIXEL / 2;
+            let luma = (center - LUMA_WINDOW / 2..center + LUMA_WINDOW / 2)
+                .map(|t| sample(t).0)
+                .sum::<f32>() / LUMA_WINDOW as f32;
+            let (i, q) = (center - CHROMA_WINDOW / 2..center + CHROMA_WINDOW / 2)
+                .map(sample)
+                .fold((0.0, 0.0), |(i, q), (signal, sin, cos)| (i + signal * cos, q + signal * sin));
+            let scale = 2.0 / CHROMA_WINDOW as f32;
+            output.push(to_rgb([luma, i * scale, q * scale]));
+        }
+    }
+    output
+}
@@ -0,0 +1,198 @@
+use crate::loader::rom_database::crc32;
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+// deflate length and distance codes, base values and extra bits
+const LENGTH_BASES  : [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA  : [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+const WINDOW    : usize = 32 * 1024;
+const MAX_MATCH : usize = 258;
+const HASH_BITS : u32 = 15;
+
+struct BitWriter {
+    out    : Vec<u8>,
+    buffer : u32,
+    bits   : u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // huffman codes go most significant bit first
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write(reversed, length);
+    }
+
+    // the fixed literal / length code of deflate
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+// a single fixed huffman block with greedy matches, frames are mostly runs of a few
+// colors so that already gets most of what a full encoder would
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { out: Vec::new(), buffer: 0, bits: 0 };
+    writer.write(1, 1); // final block
+    writer.write(1, 2); // fixed codes
+
+    let hash = |at: usize| {
+        let key = (data[at] as u32) << 16 | (data[at + 1] as u32) << 8 | data[at + 2] as u32;
+        (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut at = 0;
+    while at < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+        if at + 3 <= data.len() {
+            let key = hash(at);
+            let candidate = last_seen[key];
+            last_seen[key] = at;
+            if candidate != usize::MAX && at - candidate <= WINDOW {
+                let limit = MAX_MATCH.min(data.len() - at);
+                while length < limit && data[candidate + length] == data[at + length] {
+                    length += 1;
+                }
+                distance = at - candidate;
+            }
+        }
+
+        if length < 3 {
+            writer.write_symbol(data[at] as u16);
+            at += 1;
+            continue;
+        }
+
+        let code = LENGTH_BASES.iter().rposition(|base| *base as usize <= length).unwrap();
+        writer.write_symbol(257 + code as u16);
+        writer.write((length - LENGTH_BASES[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+        let code = DISTANCE_BASES.iter().rposition(|base| *base as usize <= distance).unwrap();
+        writer.write_code(code as u32, 5);
+        writer.write((distance - DISTANCE_BASES[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+
+        for skipped in at + 1..(at + length).min(data.len().saturating_sub(2)) {
+            last_seen[hash(skipped)] = skipped;
+        }
+        at += length;
+    }
+    writer.write_symbol(256);
+    writer.finish()
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// 0x00RRGGBB pixels as 8 bit rgb rows, each behind a filter byte (none)
+fn scanlines(width: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(pixels.len() * 3 + pixels.len() / width.max(1));
+    for row in pixels.chunks(width.max(1)) {
+        raw.push(0);
+        for pixel in row {
+            raw.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8]);
+        }
+    }
+    raw
+}
+
+fn header(width: usize, height: usize) -> Vec<u8> {
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8 bit rgb, no interlace
+    ihdr
+}
+
+pub fn encode(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header(width, height));
+    chunk(&mut out, b"IDAT", &zlib(&scanlines(width, pixels)));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+// an animated png, every frame full size and shown for delay seconds (numerator, denominator),
+// viewers without apng support show the first frame
+pub fn encode_animation(width: usize, height: usize, frames: &[Vec<u32>], delay: (u16, u16)) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header(width, height));
+    let mut actl = (frames.len() as u32).to_be_bytes().to_vec();
+    actl.extend_from_slice(&0u32.to_be_bytes()); // loop forever
+    chunk(&mut out, b"acTL", &actl);
+
+    let mut sequence = 0u32;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut fctl = sequence.to_be_bytes().to_vec();
+        fctl.extend_from_slice(&(width as u32).to_be_bytes());
+        fctl.extend_from_slice(&(height as u32).to_be_bytes());
+        fctl.extend_from_slice(&[0; 8]); // x and y offsets
+        fctl.extend_from_slice(&delay.0.to_be_bytes());
+        fctl.extend_from_slice(&delay.1.to_be_bytes());
+        fctl.extend_from_slice(&[0, 0]); // no dispose, source blend
+        chunk(&mut out, b"fcTL", &fctl);
+        sequence += 1;
+
+        let data = zlib(&scanlines(width, frame));
+        if i == 0 {
+            chunk(&mut out, b"IDAT", &data);
+        } else {
+            let mut fdat = sequence.to_be_bytes().to_vec();
+            fdat.extend(data);
+            chunk(&mut out, b"fdAT", &fdat);
+            sequence += 1;
+        }
+    }
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
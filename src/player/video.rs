@@ -0,0 +1,160 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::hardware::framebuffer::FrameBuffer;
+
+use super::ntsc;
+
+// how a frame is shown once it leaves the machine : the rows and columns an ntsc tv hides
+// behind its bezel are cropped, the rest scaled by a whole number, and widened to the 8:7
+// pixels of the tv when asked, after the filter. hashes are still taken from the whole frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoOptions {
+    pub blend    : bool,  // each frame averaged with the one before, sprites drawn every other frame stop flickering
+    pub filter   : Filter,
+    pub overscan : Overscan,
+    pub scale    : usize, // 1 to 8
+    pub aspect   : bool,  // 8:7 pixel aspect ratio
+}
+
+// pixels cropped from each edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overscan {
+    pub top    : usize,
+    pub bottom : usize,
+    pub left   : usize,
+    pub right  : usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    None,
+    Composite, // see ntsc.rs
+}
+
+impl Filter {
+    pub fn next(self) -> Filter {
+        match self {
+            Filter::None => Filter::Composite,
+            Filter::Composite => Filter::None,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::None => write!(f, "none"),
+            Filter::Composite => write!(f, "composite"),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Filter, Error> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "none" | "off" => Ok(Filter::None),
+            "composite" | "ntsc" => Ok(Filter::Composite),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid video filter: {}", text))),
+        }
+    }
+}
+
+pub const MAX_SCALE    : usize = 8;
+pub const PIXEL_ASPECT : f32 = 8.0 / 7.0;
+
+impl Default for VideoOptions {
+    fn default() -> VideoOptions {
+        VideoOptions {
+            blend    : false,
+            filter   : Filter::None,
+            overscan : Overscan { top : 8, bottom : 8, left : 0, right : 0 },
+            scale    : 1,
+            aspect   : false,
+        }
+    }
+}
+
+impl VideoOptions {
+    pub fn from_config(config: &Config) -> VideoOptions {
+        let default = VideoOptions::default();
+        VideoOptions {
+            blend    : config.get_or("video.frame_blend", default.blend),
+            filter   : config.get_or("video.filter", default.filter),
+            overscan : Overscan {
+                top    : config.get_or("video.overscan_top", default.overscan.top),
+                bottom : config.get_or("video.overscan_bottom", default.overscan.bottom),
+                left   : config.get_or("video.overscan_left", default.overscan.left),
+                right  : config.get_or("video.overscan_right", default.overscan.right),
+            },
+            scale    : config.get_or("video.scale", default.scale).clamp(1, MAX_SCALE),
+            aspect   : config.get_or("video.aspect_correction", default.aspect),
+        }
+    }
+
+    // the visible part of a width x height frame : x, y, width, height. an overscan larger
+    // than the frame leaves at least one pixel
+    pub fn crop(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
+        let left = self.overscan.left.min(width.saturating_sub(1));
+        let top = self.overscan.top.min(height.saturating_sub(1));
+        let visible_width = width.saturating_sub(left + self.overscan.right).max(1).min(width - left);
+        let visible_height = height.saturating_sub(top + self.overscan.bottom).max(1).min(height - top);
+        (left, top, visible_width, visible_height)
+    }
+
+    // size of the presented frame
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        let (_, _, width, height) = self.crop(width, height);
+        let scaled = width * self.scale;
+        let scaled = if self.aspect { (scaled as f32 * PIXEL_ASPECT).round() as usize } else { scaled };
+        (scaled, height * self.scale)
+    }
+
+    // width over height of the picture on screen, for frontends doing their own scaling
+    pub fn aspect_ratio(&self, width: usize, height: usize) -> f32 {
+        let (_, _, width, height) = self.crop(width, height);
+        let ratio = width as f32 / height as f32;
+        if self.aspect { ratio * PIXEL_ASPECT } else { ratio }
+    }
+
+    // blended, filtered, cropped, then scaled with nearest neighbour : rows are repeated
+    // `scale` times and columns picked so the 8:7 widening spreads evenly over the line
+    pub fn present(&self, video: &FrameBuffer) -> (usize, usize, Vec<u32>) {
+        let (width, height) = (video.width, video.height);
+        let blended;
+        let pixels = if self.blend {
+            blended = video.front().iter().zip(video.previous()).map(|(a, b)| blend(*a, *b)).collect::<Vec<u32>>();
+            &blended
+        } else {
+            video.front()
+        };
+        let filtered;
+        let pixels = match self.filter {
+            Filter::None => pixels,
+            Filter::Composite => {
+                filtered = ntsc::filter(width, height, pixels, video.frame);
+                &filtered
+            },
+        };
+        let (left, top, visible_width, _) = self.crop(width, height);
+        let (output_width, output_height) = self.size(width, height);
+        let columns = (0..output_width)
+            .map(|x| left + x * visible_width / output_width)
+            .collect::<Vec<usize>>();
+        let mut output = Vec::with_capacity(output_width * output_height);
+        for y in 0..output_height {
+            let row = (top + y / self.scale) * width;
+            output.extend(columns.iter().map(|x| pixels.get(row + x).copied().unwrap_or(0)));
+        }
+        (output_width, output_height, output)
+    }
+}
+
+// the average of each channel, rounded down
+fn blend(a: u32, b: u32) -> u32 {
+    (a & b) + (((a ^ b) & 0xFEFEFE) >> 1)
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+// 16 bit mono pcm, samples are clamped to -1.0 .. 1.0
+pub fn encode(sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_size as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_size).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    out
+}
+
+pub fn write(path: &str, sample_rate: u32, samples: &[f32]) -> Result<(), Error> {
+    fs::write(path.trim(), encode(sample_rate, samples))
+}
+
+// mixed output, and each channel on its own when stems are asked for, collected from
+// the mixer until the duration is reached or the recording is stopped
+#[derive(Debug, Clone)]
+pub struct WavRecording {
+    pub path        : String,
+    pub sample_rate : u32,
+    pub limit       : Option<usize>, // in samples, none records until stopped
+    pub split       : bool,          // stems wanted
+    pub mixed       : Vec<f32>,
+    pub stems       : Vec<(&'static str, Vec<f32>)>,
+}
+
+impl WavRecording {
+    pub fn new(path: &str, sample_rate: u32, seconds: Option<f64>, split: bool) -> WavRecording {
+        WavRecording {
+            path        : path.trim().to_string(),
+            sample_rate,
+            limit       : seconds.map(|seconds| (seconds * sample_rate as f64) as usize),
+            split,
+            mixed       : Vec::new(),
+            stems       : Vec::new(),
+        }
+    }
+
+    pub fn append(&mut self, mixed: Vec<f32>, stems: Vec<(&'static str, Vec<f32>)>) {
+        let room = self.limit.map(|limit| limit.saturating_sub(self.mixed.len())).unwrap_or(usize::MAX);
+        let start = self.mixed.len();
+        self.mixed.extend(mixed.into_iter().take(room));
+        for (name, samples) in stems {
+            let at = match self.stems.iter().position(|(stem, _)| *stem == name) {
+                Some(at) => at,
+                None => {
+                    self.stems.push((name, vec![0.0; start]));
+                    self.stems.len() - 1
+                },
+            };
+            self.stems[at].1.extend(samples.into_iter().take(room));
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.limit.is_some_and(|limit| self.mixed.len() >= limit)
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.mixed.len() as f64 / self.sample_rate as f64
+    }
+
+    // `<name>-<channel>.wav` next to the mixed file for each stem, returns the files written
+    pub fn finish(&self) -> Result<Vec<String>, Error> {
+        write(&self.path, self.sample_rate, &self.mixed)?;
+        let mut written = vec![self.path.clone()];
+        let path = Path::new(&self.path);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("recording");
+        for (name, samples) in self.stems.iter() {
+            let stem_path = path.with_file_name(format!("{}-{}.wav", stem, name)).to_string_lossy().to_string();
+            write(&stem_path, self.sample_rate, samples)?;
+            written.push(stem_path);
+        }
+        Ok(written)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod json;
+pub mod websocket;
+pub mod server;
\ No newline at end of file
@@ -0,0 +1,236 @@
+use std::io::Error;
+use std::net::TcpListener;
+
+use crate::cli::Arguments;
+use crate::debugger::debugger::App;
+use crate::hardware::interfaces::DeviceOps;
+use crate::loader::loader;
+use crate::player::{frames, png};
+
+use super::json::Json;
+use super::websocket::{base64, WebSocket};
+
+// json-rpc 2.0 error codes
+const PARSE_ERROR      : i64 = -32700;
+const INVALID_REQUEST  : i64 = -32600;
+const METHOD_NOT_FOUND : i64 = -32601;
+const INVALID_PARAMS   : i64 = -32602;
+const MACHINE_ERROR    : i64 = -32000;
+
+pub const METHODS: [&str; 15] = [
+    "load", "reset", "power_cycle", "registers", "step", "run_frames", "continue",
+    "read_memory", "write_memory", "set_breakpoint", "clear_breakpoint", "breakpoints",
+    "set_buttons", "framebuffer", "state_hash",
+];
+
+struct Failure(i64, String);
+
+fn invalid_params(reason: &str) -> Failure {
+    Failure(INVALID_PARAMS, reason.to_string())
+}
+
+// --remote : no interface, the machine is driven over a websocket on localhost, one
+// client at a time, with json-rpc requests (see METHODS), the program given on the
+// command line is loaded up front when it exists
+pub fn serve(arguments: Arguments, port: u16) -> Result<(), Error> {
+    let mut app = App::new(&arguments)?;
+    if let Ok(program) = app.load_current_program() {
+        app.load_program(&program);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("remote control on ws://127.0.0.1:{}", port);
+    for stream in listener.incoming() {
+        let mut socket = match stream.and_then(WebSocket::accept) {
+            Ok(socket) => socket,
+            Err(_) => continue,
+        };
+        while let Ok(Some(message)) = socket.receive() {
+            if let Some(response) = respond(&mut app, &message) {
+                if socket.send(&response.to_string()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// the response to a message, None for notifications, batches answer as an array
+fn respond(app: &mut App, message: &str) -> Option<Json> {
+    match Json::parse(message) {
+        Ok(Json::Array(requests)) if !requests.is_empty() => {
+            let responses = requests.iter().filter_map(|request| answer(app, request)).collect::<Vec<Json>>();
+            if responses.is_empty() { None } else { Some(Json::Array(responses)) }
+        },
+        Ok(request) => answer(app, &request),
+        Err(err) => Some(reply(Json::Null, Err(Failure(PARSE_ERROR, err.to_string())))),
+    }
+}
+
+fn answer(app: &mut App, request: &Json) -> Option<Json> {
+    let id = request.get("id").cloned();
+    let result = match (request.get("jsonrpc").and_then(Json::as_str), request.get("method").and_then(Json::as_str)) {
+        (Some("2.0"), Some(method)) => call(app, method, request.get("params").unwrap_or(&Json::Null)),
+        _ => Err(Failure(INVALID_REQUEST, "expected a json-rpc 2.0 request".to_string())),
+    };
+    id.map(|id| reply(id, result))
+}
+
+fn reply(id: Json, result: Result<Json, Failure>) -> Json {
+    match result {
+        Ok(result) => Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+        Err(Failure(code, message)) => Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("error", Json::object(vec![("code", Json::Number(code as f64)), ("message", message.into())])),
+        ]),
+    }
+}
+
+// numbers, or strings the debugger resolves : hex ($C000, 0xC000) or file:line with debug info
+fn address(app: &App, params: &Json) -> Result<u16, Failure> {
+    match params.get("address") {
+        Some(Json::Number(_)) => params.get("address").and_then(Json::as_u64)
+            .filter(|address| *address <= 0xFFFF)
+            .map(|address| address as u16)
+            .ok_or_else(|| invalid_params("address out of range")),
+        Some(Json::String(target)) => app.resolve_location(target).ok_or_else(|| invalid_params("unknown address")),
+        _ => Err(invalid_params("missing address")),
+    }
+}
+
+fn count(params: &Json, key: &str) -> Result<u64, Failure> {
+    match params.get(key) {
+        None => Ok(1),
+        Some(value) => value.as_u64().ok_or_else(|| invalid_params(&format!("{} must be a positive integer", key))),
+    }
+}
+
+fn registers(app: &App) -> Json {
+    let state = (*app.inner_machine_state).borrow();
+    let cpu = state.machine.cpu.borrow();
+    let frame = state.machine.video.borrow().frame;
+    Json::object(vec![
+        ("a", (cpu.registers.a as u64).into()),
+        ("x", (cpu.registers.x as u64).into()),
+        ("y", (cpu.registers.y as u64).into()),
+        ("sp", (cpu.registers.sp as u64).into()),
+        ("pc", (cpu.registers.pc as u64).into()),
+        ("p", (cpu.registers.flags as u64).into()),
+        ("cycles", cpu.total_cycles.into()),
+        ("frame", frame.into()),
+    ])
+}
+
+fn call(app: &mut App, method: &str, params: &Json) -> Result<Json, Failure> {
+    match method {
+        "load" => {
+            let path = params.get("path").and_then(Json::as_str).ok_or_else(|| invalid_params("missing path"))?;
+            loader::load(path, app.load_options.load_address).map_err(|err| Failure(MACHINE_ERROR, err.to_string()))?;
+            app.open_program(path);
+            Ok(registers(app))
+        },
+        "reset" => {
+            app.soft_reset();
+            Ok(registers(app))
+        },
+        "power_cycle" => {
+            app.power_cycle();
+            Ok(registers(app))
+        },
+        "registers" => Ok(registers(app)),
+        // whole instructions, wherever the program counter is
+        "step" => {
+            for _ in 0..count(params, "count")? {
+                (*app.inner_machine_state).borrow().machine.step();
+            }
+            Ok(registers(app))
+        },
+        "run_frames" => {
+            app.run_frames(count(params, "count")?);
+            Ok(registers(app))
+        },
+        // until a breakpoint, leaving the program or the run limit
+        "continue" => {
+            app.run_until_breakpoint();
+            Ok(registers(app))
+        },
+        "read_memory" => {
+            let start = address(app, params)?;
+            let length = count(params, "length")?.min(0x10000);
+            let state = (*app.inner_machine_state).borrow();
+            let bus = state.machine.bus.borrow();
+            let bytes = (0..length).map(|i| Json::from(bus.peek(start.wrapping_add(i as u16)) as u64)).collect();
+            Ok(Json::Array(bytes))
+        },
+        "write_memory" => {
+            let start = address(app, params)?;
+            let bytes = params.get("bytes").and_then(Json::as_array).ok_or_else(|| invalid_params("missing bytes"))?;
+            let bytes = bytes.iter()
+                .map(|byte| byte.as_u64().filter(|byte| *byte <= 0xFF).map(|byte| byte as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| invalid_params("bytes must be 0 - 255"))?;
+            let state = (*app.inner_machine_state).borrow();
+            let mut bus = state.machine.bus.borrow_mut();
+            for (i, byte) in bytes.iter().enumerate() {
+                bus.write(start.wrapping_add(i as u16), *byte);
+            }
+            Ok(Json::from(bytes.len() as u64))
+        },
+        // breakpoint classes go by name in place of the address
+        "set_breakpoint" => {
+            if let Some(class) = params.get("address").and_then(Json::as_str) {
+                if app.set_break_class(class, Some(true)).is_some() {
+                    return Ok(Json::from(class.trim().to_lowercase()));
+                }
+            }
+            let address = address(app, params)?;
+            app.breakpoints.insert(address);
+            Ok(Json::from(address as u64))
+        },
+        "clear_breakpoint" => {
+            if let Some(class) = params.get("address").and_then(Json::as_str) {
+                let set = app.break_classes().contains(&class.trim().to_lowercase().as_str());
+                if app.set_break_class(class, Some(false)).is_some() {
+                    return Ok(Json::Bool(set));
+                }
+            }
+            let address = address(app, params)?;
+            Ok(Json::Bool(app.breakpoints.remove(&address)))
+        },
+        "breakpoints" => {
+            let addresses = app.breakpoints.iter().map(|address| Json::from(*address as u64));
+            let classes = app.break_classes().into_iter().map(Json::from);
+            Ok(Json::Array(addresses.chain(classes).collect()))
+        },
+        // held from the next frame on, bits as in the controller report : A B Select Start Up Down Left Right
+        "set_buttons" => {
+            let port = params.get("port").and_then(Json::as_u64).filter(|port| *port < 4).ok_or_else(|| invalid_params("port must be 0 - 3"))?;
+            let buttons = params.get("buttons").and_then(Json::as_u64).filter(|buttons| *buttons <= 0xFF).ok_or_else(|| invalid_params("buttons must be 0 - 255"))?;
+            app.held_buttons[port as usize] = buttons as u8;
+            Ok(Json::Null)
+        },
+        // the last completed frame, as a base64 png and the hash --run-frames prints
+        "framebuffer" => {
+            let state = (*app.inner_machine_state).borrow();
+            let video = state.machine.video.borrow();
+            Ok(Json::object(vec![
+                ("frame", video.frame.into()),
+                ("width", (video.width as u64).into()),
+                ("height", (video.height as u64).into()),
+                ("crc32", format!("{:08X}", frames::frame_hash(&video)).into()),
+                ("png", base64(&png::encode(video.width, video.height, video.front())).into()),
+            ]))
+        },
+        // the hash folded at the start of the last frame run, as --trace-states writes it
+        "state_hash" => match app.state_hash.frame {
+            0 => Ok(Json::Null),
+            frames => Ok(Json::object(vec![
+                ("frame", (frames - 1).into()),
+                ("hash", format!("{:08X}", app.state_hash.value).into()),
+            ])),
+        },
+        _ => Err(Failure(METHOD_NOT_FOUND, format!("unknown method {}, expected one of {}", method, METHODS.join(", ")))),
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// messages above this are refused rather than buffered
+const MAX_MESSAGE: u64 = 16 * 1024 * 1024;
+
+const OPCODE_CONTINUATION : u8 = 0x0;
+const OPCODE_TEXT         : u8 = 0x1;
+const OPCODE_BINARY       : u8 = 0x2;
+const OPCODE_CLOSE        : u8 = 0x8;
+const OPCODE_PING         : u8 = 0x9;
+const OPCODE_PONG         : u8 = 0xA;
+
+// the server side of rfc 6455, text messages only, one connection at a time
+pub struct WebSocket {
+    stream : BufReader<TcpStream>,
+}
+
+impl WebSocket {
+    // reads the http upgrade request and answers it
+    pub fn accept(stream: TcpStream) -> Result<WebSocket, Error> {
+        let mut stream = BufReader::new(stream);
+        let mut key = None;
+        let mut line = String::new();
+        stream.read_line(&mut line)?;
+        if !line.starts_with("GET ") {
+            return Err(Error::new(ErrorKind::InvalidData, "not a websocket request"));
+        }
+        loop {
+            line.clear();
+            if stream.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                    key = Some(value.trim().to_string());
+                }
+            }
+        }
+        let key = match key {
+            Some(key) => key,
+            None => {
+                stream.get_mut().write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+                return Err(Error::new(ErrorKind::InvalidData, "missing Sec-WebSocket-Key"));
+            },
+        };
+        let accept = base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()));
+        let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept);
+        stream.get_mut().write_all(response.as_bytes())?;
+        Ok(WebSocket { stream })
+    }
+
+    // the next text message, None once the client closed, pings are answered on the way
+    pub fn receive(&mut self) -> Result<Option<String>, Error> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0u8; 2];
+            if let Err(err) = self.stream.read_exact(&mut header) {
+                return if err.kind() == ErrorKind::UnexpectedEof { Ok(None) } else { Err(err) };
+            }
+            let finished = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0F;
+            let masked = header[1] & 0x80 != 0;
+            let length = match header[1] & 0x7F {
+                126 => {
+                    let mut length = [0u8; 2];
+                    self.stream.read_exact(&mut length)?;
+                    u16::from_be_bytes(length) as u64
+                },
+                127 => {
+                    let mut length = [0u8; 8];
+                    self.stream.read_exact(&mut length)?;
+                    u64::from_be_bytes(length)
+                },
+                length => length as u64,
+            };
+            if length > MAX_MESSAGE - message.len() as u64 {
+                return Err(Error::new(ErrorKind::InvalidData, "message too large"));
+            }
+            let mut mask = [0u8; 4];
+            if masked {
+                self.stream.read_exact(&mut mask)?;
+            }
+            let mut payload = vec![0u8; length as usize];
+            self.stream.read_exact(&mut payload)?;
+            if masked {
+                for (i, byte) in payload.iter_mut().enumerate() {
+                    *byte ^= mask[i % 4];
+                }
+            }
+
+            match opcode {
+                OPCODE_CLOSE => {
+                    let _ = self.frame(OPCODE_CLOSE, &payload);
+                    return Ok(None);
+                },
+                OPCODE_PING => self.frame(OPCODE_PONG, &payload)?,
+                OPCODE_PONG => {},
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    message.extend(payload);
+                    if finished {
+                        return String::from_utf8(message)
+                            .map(Some)
+                            .map_err(|_| Error::new(ErrorKind::InvalidData, "message is not utf-8"));
+                    }
+                },
+                _ => return Err(Error::new(ErrorKind::InvalidData, "unknown opcode")),
+            }
+        }
+    }
+
+    pub fn send(&mut self, text: &str) -> Result<(), Error> {
+        self.frame(OPCODE_TEXT, text.as_bytes())
+    }
+
+    // server frames are never masked
+    fn frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), Error> {
+        let mut out = vec![0x80 | opcode];
+        match payload.len() {
+            length if length < 126 => out.push(length as u8),
+            length if length <= 0xFFFF => {
+                out.push(126);
+                out.extend_from_slice(&(length as u16).to_be_bytes());
+            },
+            length => {
+                out.push(127);
+                out.extend_from_slice(&(length as u64).to_be_bytes());
+            },
+        }
+        out.extend_from_slice(payload);
+        self.stream.get_mut().write_all(&out)
+    }
+}
+
+// only for the handshake, where the protocol asks for it
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for i in 0..16 {
+            words[i] = u32::from_be_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = next;
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, value) in state.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::emulator::Emulator;
+use crate::hardware::interfaces::DeviceOps;
+use crate::hardware::machine::Machine;
+use crate::player::frames;
+
+// the status protocol of blargg's test roms : once $6001 - $6003 hold the signature, $6000 is
+// $80 while the test runs, $81 when it wants the reset button pressed, and the result code
+// after that, 0 for a pass. $6004 holds the text the rom printed, up to a zero byte
+const STATUS            : u16 = 0x6000;
+const SIGNATURE         : [u8; 3] = [0xDE, 0xB0, 0x61];
+const TEXT              : u16 = 0x6004;
+const TEXT_LIMIT        : u16 = 0x1000;
+const RUNNING           : u8 = 0x80;
+const RESET_REQUESTED   : u8 = 0x81;
+// the roms ask for the reset to come at least 100ms after they request it
+const RESET_DELAY       : u64 = 10;
+const DEFAULT_DIRECTORY : &str = "tests/roms";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed(u8),
+    TimedOut,     // still running when the frame limit was reached
+    NoSignature,  // never wrote the signature, or not a rom of the protocol
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub name    : String,
+    pub outcome : Outcome,
+    pub frames  : u64,
+    pub text    : String,
+    pub hash    : Option<u32>, // of the last frame, as --run-frames prints it, None while nothing drew into it
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "passed"),
+            Outcome::Failed(code) => write!(f, "failed with code {}", code),
+            Outcome::TimedOut => write!(f, "timed out"),
+            Outcome::NoSignature => write!(f, "no status"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = Error;
+
+    // as it is displayed
+    fn from_str(text: &str) -> Result<Outcome, Error> {
+        match text.trim() {
+            "passed" => Ok(Outcome::Passed),
+            "timed out" => Ok(Outcome::TimedOut),
+            "no status" => Ok(Outcome::NoSignature),
+            other => other.strip_prefix("failed with code ")
+                .and_then(|code| code.parse().ok())
+                .map(Outcome::Failed)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid outcome: {}", text))),
+        }
+    }
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.outcome == Outcome::Passed
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<24} {} after {} frames", self.name, self.outcome, self.frames)?;
+        for line in self.text.lines().filter(|line| !line.trim().is_empty()) {
+            write!(f, "\n    {}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// Some(status) once the rom wrote the signature
+pub fn status(machine: &Machine) -> Option<u8> {
+    let bus = machine.bus.borrow();
+    let signature = [bus.peek(STATUS + 1), bus.peek(STATUS + 2), bus.peek(STATUS + 3)];
+    if signature == SIGNATURE { Some(bus.peek(STATUS)) } else { None }
+}
+
+pub fn describe(status: u8) -> String {
+    match status {
+        RUNNING => "running".to_string(),
+        RESET_REQUESTED => "asks for a reset".to_string(),
+        0 => Outcome::Passed.to_string(),
+        code => Outcome::Failed(code).to_string(),
+    }
+}
+
+fn text_bytes(machine: &Machine) -> Vec<u8> {
+    let bus = machine.bus.borrow();
+    (TEXT..TEXT + TEXT_LIMIT)
+        .map(|address| bus.peek(address))
+        .take_while(|byte| *byte != 0)
+        .collect()
+}
+
+pub fn text(machine: &Machine) -> String {
+    String::from_utf8_lossy(&text_bytes(machine)).to_string()
+}
+
+// what one frame of a rom of the protocol brought
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub text    : String, // printed since the last frame
+    pub reset   : bool,   // the reset button is to be pressed now
+    pub outcome : Option<Outcome>,
+}
+
+// follows a running rom frame by frame : streams its text, times the reset it asks for
+#[derive(Debug, Clone, Default)]
+pub struct Watch {
+    printed  : usize,
+    frames   : u64,
+    reset_at : Option<u64>,
+}
+
+impl Watch {
+    pub fn frame(&mut self, machine: &Machine) -> Progress {
+        self.frames += 1;
+        let mut progress = Progress::default();
+        let status = match status(machine) {
+            Some(status) => status,
+            None => return progress,
+        };
+        let text = text_bytes(machine);
+        // cleared by the rom after a reset, printed again from the start
+        if text.len() < self.printed {
+            self.printed = 0;
+        }
+        progress.text = String::from_utf8_lossy(&text[self.printed..]).to_string();
+        self.printed = text.len();
+        match status {
+            RUNNING => {},
+            RESET_REQUESTED => match self.reset_at {
+                Some(frame) if self.frames >= frame => {
+                    progress.reset = true;
+                    self.reset_at = None;
+                },
+                Some(_) => {},
+                None => self.reset_at = Some(self.frames + RESET_DELAY),
+            },
+            0 => progress.outcome = Some(Outcome::Passed),
+            code => progress.outcome = Some(Outcome::Failed(code)),
+        }
+        progress
+    }
+}
+
+// runs the rom headlessly, frame by frame, until it reports a result or the limit is reached
+pub fn run(path: &Path, frame_limit: u64) -> Result<Report, Error> {
+    let mut emulator = Emulator::default();
+    emulator.load(&path.to_string_lossy())?;
+    let mut watch = Watch::default();
+    let mut outcome = None;
+    let mut frames = 0;
+    while frames < frame_limit && outcome.is_none() {
+        emulator.run_frame();
+        frames += 1;
+        let progress = watch.frame(&emulator.machine);
+        if progress.reset {
+            emulator.reset();
+        }
+        outcome = progress.outcome;
+    }
+    let outcome = outcome.unwrap_or(if status(&emulator.machine).is_some() { Outcome::TimedOut } else { Outcome::NoSignature });
+    let video = emulator.machine.video.borrow();
+    let hash = Some(frames::frame_hash(&video)).filter(|_| video.drawn);
+    Ok(Report {
+        name : path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string()),
+        outcome,
+        frames,
+        text : text(&emulator.machine),
+        hash,
+    })
+}
+
+// where the roms are looked for, they are not part of the repository
+pub fn directory() -> PathBuf {
+    env::var_os("BRICK_STATION_TEST_ROMS").map_or_else(|| PathBuf::from(DEFAULT_DIRECTORY), PathBuf::from)
+}
+
+// a suite is either its combined rom or a directory of single test roms, sorted by name
+pub fn suite(directory: &Path, name: &str) -> Vec<PathBuf> {
+    let combined = directory.join(name).with_extension("nes");
+    if combined.is_file() {
+        return vec![combined];
+    }
+    let mut singles = fs::read_dir(directory.join(name)).into_iter().flatten()
+        .chain(fs::read_dir(directory.join(name).join("rom_singles")).into_iter().flatten())
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "nes"))
+        .collect::<Vec<PathBuf>>();
+    singles.sort();
+    singles
+}
+
+// the outcomes a suite is expected to keep, `<suite>/<rom> | <outcome>` per line, so a change
+// in timing that makes a rom pass or fail differently shows up even while some still fail
+pub fn read_golden(path: &Path) -> Result<BTreeMap<String, Outcome>, Error> {
+    let mut golden = BTreeMap::new();
+    for line in fs::read_to_string(path)?.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let (rom, outcome) = line.split_once('|')
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid golden result: {}", line)))?;
+        golden.insert(rom.trim().to_string(), outcome.parse()?);
+    }
+    Ok(golden)
+}
+
+// the comments at the top of the file are kept
+pub fn write_golden(path: &Path, results: &BTreeMap<String, Outcome>) -> Result<(), Error> {
+    let header = fs::read_to_string(path).unwrap_or_default().lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+    let lines = results.iter().map(|(rom, outcome)| format!("{} | {}\n", rom, outcome)).collect::<String>();
+    fs::write(path, header + &lines)
+}
+
+// runs the roms of the suites that are found and holds them to the outcomes recorded in the
+// golden file, or records them again under BRICK_STATION_BLESS. the roms whose outcome
+// changed, None when no rom was found and nothing could be checked
+pub fn hold_to_golden(suites: &[&str], frame_limit: u64, golden_path: &Path) -> Result<Option<Vec<String>>, Error> {
+    let mut golden = read_golden(golden_path)?;
+    let mut results = BTreeMap::new();
+    for suite in suites {
+        for rom in self::suite(&directory(), suite) {
+            let report = run(&rom, frame_limit).map_err(|err| Error::new(err.kind(), format!("{}: {}", rom.display(), err)))?;
+            println!("{}", report);
+            results.insert(format!("{}/{}", suite, report.name), report.outcome);
+        }
+    }
+    if results.is_empty() {
+        return Ok(None);
+    }
+
+    if env::var_os("BRICK_STATION_BLESS").is_some() {
+        golden.extend(results);
+        write_golden(golden_path, &golden)?;
+        return Ok(Some(Vec::new()));
+    }
+    Ok(Some(results.iter()
+        .filter(|(rom, outcome)| golden.get(*rom) != Some(outcome))
+        .map(|(rom, outcome)| match golden.get(rom) {
+            Some(expected) => format!("{} {}, was {}", rom, outcome, expected),
+            None => format!("{} {}, not recorded", rom, outcome),
+        })
+        .collect()))
+}
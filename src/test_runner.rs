@@ -0,0 +1,238 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::cli::TestArguments;
+use crate::config::Config;
+use crate::remote::json::Json;
+use crate::test_rom::{self, Outcome, Report};
+
+pub const MANIFEST       : &str = "manifest.toml";
+pub const DEFAULT_FRAMES : u64 = 60 * 30;
+
+// what a rom is expected to end with : the outcome of the $6000 protocol, "no status" for
+// the roms that only draw, and the hash of the last frame when the picture matters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectation {
+    pub name    : String,
+    pub rom     : PathBuf,
+    pub outcome : Outcome,
+    pub frames  : u64, // run at most, the roms of the protocol stop once they report
+    pub hash    : Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub expectation : Expectation,
+    pub report      : Result<Report, String>, // the error when the rom did not load or the run panicked
+    pub seconds     : f64,
+}
+
+impl TestResult {
+    // what differs from the expectation, empty when the test passed
+    pub fn failures(&self) -> Vec<String> {
+        let report = match &self.report {
+            Ok(report) => report,
+            Err(err) => return vec![err.clone()],
+        };
+        let mut failures = Vec::new();
+        if report.outcome != self.expectation.outcome {
+            failures.push(format!("{}, expected {}", report.outcome, self.expectation.outcome));
+        }
+        match (report.hash, self.expectation.hash) {
+            (_, None) => {},
+            (None, Some(expected)) => failures.push(format!("nothing drew into the frame, expected hash {:08X}", expected)),
+            (Some(hash), Some(expected)) if hash != expected => failures.push(format!("frame hash {:08X}, expected {:08X}", hash, expected)),
+            _ => {},
+        }
+        failures
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures().is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        let frames = self.report.as_ref().map_or(String::new(), |report| format!(" after {} frames", report.frames));
+        match self.failures() {
+            failures if failures.is_empty() => format!("ok     {}{}", self.expectation.name, frames),
+            failures => format!("FAILED {}{} : {}", self.expectation.name, frames, failures.join(", ")),
+        }
+    }
+}
+
+fn hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.trim().trim_start_matches('$').trim_start_matches("0x"), 16).ok()
+}
+
+// the roms under the directory, its subdirectories included, sorted
+fn roms(directory: &Path) -> Vec<PathBuf> {
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            roms.extend(self::roms(&path));
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("nes")) {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+    roms
+}
+
+// the tests of a directory : its manifest's [test.<name>] sections, each with
+//   rom = "file"              relative to the directory, <name>.nes when left out
+//   expect = "passed"         passed, failed with code <n>, timed out or no status
+//   frames = <n>              the time limit, the manifest's own frames key else
+//   hash = "B77D18AB"         the hash of the last frame, as --run-frames prints it, a
+//                             frame nothing drew into fails it (the ppu draws nothing yet)
+// then every other .nes under the directory, expected to pass in the default time
+pub fn manifest(directory: &Path, frames: Option<u64>) -> Result<Vec<Expectation>, Error> {
+    let config = Config::load(&directory.join(MANIFEST).to_string_lossy())?;
+    let invalid = |name: &str, reason: String| Error::new(ErrorKind::InvalidData, format!("{} test {}: {}", MANIFEST, name, reason));
+    let frames = frames.unwrap_or_else(|| config.get_or("frames", DEFAULT_FRAMES));
+
+    // sections are sorted by name, the order of the file is not kept
+    let mut names = config.values.keys()
+        .filter_map(|key| key.strip_prefix("test.")?.rsplit_once('.').map(|(name, _)| name.to_string()))
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+    let mut tests = Vec::new();
+    for name in names {
+        let key = |field: &str| config.get(&format!("test.{}.{}", name, field));
+        let rom = directory.join(key("rom").map_or_else(|| format!("{}.nes", name), str::to_string));
+        let outcome = key("expect").map_or(Ok(Outcome::Passed), str::parse).map_err(|err| invalid(&name, err.to_string()))?;
+        let frames = match key("frames") {
+            Some(text) => text.parse().map_err(|_| invalid(&name, format!("invalid frames {}", text)))?,
+            None => frames,
+        };
+        let hash = match key("hash") {
+            Some(text) => Some(hex(text).ok_or_else(|| invalid(&name, format!("invalid hash {}", text)))?),
+            None => None,
+        };
+        tests.push(Expectation { name, rom, outcome, frames, hash });
+    }
+
+    let listed = tests.iter().map(|test| test.rom.clone()).collect::<Vec<PathBuf>>();
+    for rom in roms(directory).into_iter().filter(|rom| !listed.contains(rom)) {
+        let name = rom.strip_prefix(directory).unwrap_or(&rom).with_extension("").to_string_lossy().replace('\\', "/");
+        tests.push(Expectation { name, rom, outcome: Outcome::Passed, frames, hash: None });
+    }
+    Ok(tests)
+}
+
+// one rom on a machine of its own, a panic in the emulator kept to its test
+pub fn run(expectation: &Expectation) -> TestResult {
+    let started = Instant::now();
+    let report = panic::catch_unwind(AssertUnwindSafe(|| test_rom::run(&expectation.rom, expectation.frames)))
+        .unwrap_or_else(|_| Err(Error::other("the emulator panicked")))
+        .map_err(|err| format!("{}: {}", expectation.rom.display(), err));
+    TestResult { expectation: expectation.clone(), report, seconds: started.elapsed().as_secs_f64() }
+}
+
+// `jobs` threads take the next rom as they finish one, the results come back in the
+// order of the tests
+pub fn run_all(tests: &[Expectation], jobs: usize, done: impl Fn(&TestResult) + Sync) -> Vec<TestResult> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, tests.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let test = match tests.get(index) {
+                    Some(test) => test,
+                    None => break,
+                };
+                let result = run(test);
+                done(&result);
+                results.lock().unwrap_or_else(|err| err.into_inner()).push((index, result));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap_or_else(|err| err.into_inner());
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// a junit testsuite, the roms that did not load or panicked as errors, what they printed as
+// their system-out
+pub fn junit(results: &[TestResult]) -> String {
+    let failures = results.iter().filter(|result| result.report.is_ok() && !result.passed()).count();
+    let errors = results.iter().filter(|result| result.report.is_err()).count();
+    let seconds = results.iter().map(|result| result.seconds).sum::<f64>();
+    let mut lines = vec![
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string(),
+        format!("<testsuite name=\"brick_station\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">", results.len(), failures, errors, seconds),
+    ];
+    for result in results {
+        let case = format!("  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"", xml(&result.expectation.name), xml(&result.expectation.rom.to_string_lossy()), result.seconds);
+        let text = result.report.as_ref().map_or("", |report| report.text.as_str());
+        if result.passed() && text.is_empty() {
+            lines.push(format!("{}/>", case));
+            continue;
+        }
+        lines.push(format!("{}>", case));
+        match &result.report {
+            Err(err) => lines.push(format!("    <error message=\"{}\"/>", xml(err))),
+            Ok(_) if !result.passed() => lines.push(format!("    <failure message=\"{}\"/>", xml(&result.failures().join(", ")))),
+            Ok(_) => {},
+        }
+        if !text.is_empty() {
+            lines.push(format!("    <system-out>{}</system-out>", xml(text)));
+        }
+        lines.push("  </testcase>".to_string());
+    }
+    lines.push("</testsuite>".to_string());
+    lines.join("\n") + "\n"
+}
+
+// an object per test, hashes as the hex --run-frames prints
+pub fn json(results: &[TestResult]) -> Json {
+    let hash = |hash: Option<u32>| hash.map_or(Json::Null, |hash| format!("{:08X}", hash).into());
+    Json::Array(results.iter().map(|result| {
+        let report = result.report.as_ref().ok();
+        Json::object(vec![
+            ("name", result.expectation.name.as_str().into()),
+            ("rom", result.expectation.rom.to_string_lossy().to_string().into()),
+            ("passed", Json::Bool(result.passed())),
+            ("expected", result.expectation.outcome.to_string().into()),
+            ("outcome", report.map_or(Json::Null, |report| report.outcome.to_string().into())),
+            ("expected_hash", hash(result.expectation.hash)),
+            ("hash", hash(report.and_then(|report| report.hash))),
+            ("frames", report.map_or(Json::Null, |report| report.frames.into())),
+            ("seconds", Json::Number(result.seconds)),
+            ("failures", Json::Array(result.failures().into_iter().map(Json::from).collect())),
+            ("text", report.map_or(Json::Null, |report| report.text.as_str().into())),
+        ])
+    }).collect())
+}
+
+// prints each test as it ends and a summary, writes the reports asked for. false when a
+// test failed, for the exit status
+pub fn start(arguments: TestArguments) -> Result<bool, Error> {
+    let directory = Path::new(&arguments.directory);
+    if !directory.is_dir() {
+        return Err(Error::new(ErrorKind::NotFound, format!("No test directory {}", directory.display())));
+    }
+    let tests = manifest(directory, arguments.frames)?;
+    let results = run_all(&tests, arguments.jobs, |result| println!("{}", result.describe()));
+    let passed = results.iter().filter(|result| result.passed()).count();
+    println!("{} of {} passed", passed, results.len());
+    if let Some(path) = &arguments.junit {
+        fs::write(path, junit(&results))?;
+    }
+    if let Some(path) = &arguments.json {
+        fs::write(path, json(&results).to_string() + "\n")?;
+    }
+    Ok(passed == results.len())
+}
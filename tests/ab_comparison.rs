@@ -0,0 +1,85 @@
+// a reference machine built from other settings runs alongside and is caught at the first
+// instruction the two disagree on
+use std::env;
+use std::fs;
+
+use brick_station::config::Config;
+use brick_station::debugger::comparison::{Comparison, MachineSettings};
+use brick_station::emulator;
+use brick_station::hardware::machine::Machine;
+use brick_station::hardware::profile::MachineProfile;
+use brick_station::hardware::ram::PowerOnPattern;
+use brick_station::loader::loader::LoadOptions;
+use brick_station::loader::program::{Program, Segment};
+
+// lda $10, ldx $11, jmp $8000 : reads memory nothing wrote
+fn reader() -> Program {
+    let mut program = Program::new();
+    program.segments.push(Segment { address: 0x8000, bytes: vec![0xA5, 0x10, 0xA6, 0x11, 0x4C, 0x00, 0x80] });
+    program
+}
+
+fn machines<'a>(comparison: &Comparison) -> (Machine<'a>, Machine<'a>) {
+    let profile = MachineProfile::nes();
+    let this = MachineSettings::from_config(&Config::default()).build(&profile);
+    let reference = comparison.settings.build(&profile);
+    for machine in [&this, &reference] {
+        emulator::insert_program(machine, &reader(), None, LoadOptions::new());
+    }
+    (this, reference)
+}
+
+// instruction by instruction, the first reason to stop and the cycle it came at
+fn run(comparison: &mut Comparison, this: &Machine, reference: &Machine, instructions: usize) -> Option<(String, u64)> {
+    for _ in 0..instructions {
+        this.step();
+        if let Some(reason) = comparison.check(this, Some(reference), false) {
+            return Some((reason, this.cpu.borrow().total_cycles));
+        }
+    }
+    None
+}
+
+fn comparison(config: &str) -> Comparison {
+    let path = env::temp_dir().join(format!("brick_station_ab_{}_{}.toml", std::process::id(), config.len()));
+    fs::write(&path, config).unwrap();
+    let comparison = Comparison::new(&Config::default(), path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    comparison
+}
+
+#[test]
+fn the_other_config_builds_the_reference() {
+    let comparison = comparison("[power]\nram = \"$FF\"\nalignment = 2\n");
+    assert_eq!(comparison.settings.power_on, PowerOnPattern::Fill(0xFF));
+    assert_eq!(comparison.settings.alignment, 2);
+    assert!(comparison.settings.read_glitch, "what it does not set is the machine's own");
+}
+
+#[test]
+fn a_run_stops_at_the_first_instruction_that_differs() {
+    let mut comparison = comparison("[power]\nram = \"$FF\"\n");
+    let (this, reference) = machines(&comparison);
+    let (reason, cycles) = run(&mut comparison, &this, &reference, 10).expect("lda $10 reads 00 on one side, ff on the other");
+    assert!(reason.starts_with("a/b A, P differ"), "{}", reason);
+    let divergence = comparison.divergence.clone().unwrap();
+    assert_eq!(divergence.cycles, cycles);
+    assert_eq!(divergence.registers.iter().find(|(name, _, _)| *name == "A"), Some(&("A", 0x00, 0xFF)));
+
+    // found once, the reference waits there
+    let waiting = reference.cpu.borrow().total_cycles;
+    assert_eq!(run(&mut comparison, &this, &reference, 10), None);
+    assert_eq!(reference.cpu.borrow().total_cycles, waiting);
+}
+
+#[test]
+fn the_same_settings_never_differ() {
+    let mut comparison = comparison("[ppu]\noam_decay = true\n");
+    let (this, reference) = machines(&comparison);
+    assert_eq!(run(&mut comparison, &this, &reference, 2000), None);
+    for _ in 0..3 {
+        while !this.tick() {}
+        assert_eq!(comparison.check(&this, Some(&reference), true), None, "ram included");
+    }
+    assert_eq!(this.cpu.borrow().total_cycles, reference.cpu.borrow().total_cycles);
+}
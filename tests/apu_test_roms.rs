@@ -0,0 +1,31 @@
+// blargg's apu_test, the dmc tests and the frame counter timing roms, held to the outcomes
+// recorded in tests/apu_test_roms.txt rather than to all passing : a rom that starts passing
+// or failing differently is a change in the audio timing. roms that are not found are skipped,
+// see tests/ppu_test_roms.rs for where they go.
+//   BRICK_STATION_BLESS=1 cargo test --release --test apu_test_roms -- --ignored --nocapture
+// records the outcomes. ignored until they are : without them it would check nothing
+use std::path::Path;
+
+use brick_station::test_rom::{self, Outcome};
+
+const SUITES      : [&str; 3] = ["apu_test", "dmc_tests", "blargg_apu_2005.07.30"];
+const FRAME_LIMIT : u64 = 60 * 40;
+const GOLDEN      : &str = "tests/apu_test_roms.txt";
+
+#[test]
+#[ignore = "no outcomes are recorded in tests/apu_test_roms.txt, the roms were not at hand"]
+fn apu_test_roms() {
+    match test_rom::hold_to_golden(&SUITES, FRAME_LIMIT, Path::new(GOLDEN)).unwrap_or_else(|err| panic!("{}", err)) {
+        None => panic!("no apu test roms in {}, nothing checked", test_rom::directory().display()),
+        Some(changed) => assert!(changed.is_empty(), "{} roms changed:\n{}", changed.len(), changed.join("\n")),
+    }
+}
+
+#[test]
+fn outcomes_read_back_as_written() {
+    let golden = test_rom::read_golden(Path::new(GOLDEN)).unwrap();
+    let outcomes = [Outcome::Passed, Outcome::Failed(3), Outcome::TimedOut, Outcome::NoSignature];
+    for outcome in outcomes.iter().chain(golden.values()) {
+        assert_eq!(outcome.to_string().parse::<Outcome>().unwrap(), *outcome);
+    }
+}
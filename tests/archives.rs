@@ -0,0 +1,72 @@
+// the inflate decoder on raw deflate streams, and zip archives from the small ones in
+// tests/archives : stored and deflated entries, a damaged crc32 and the 7z refusal
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+
+use brick_station::loader::archive;
+use brick_station::loader::inflate::inflate;
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+}
+
+// what went into deflated.zip
+fn fixed_text() -> Vec<u8> {
+    b"A9 00 8D 00 02 ".repeat(4)
+}
+
+fn dynamic_text() -> Vec<u8> {
+    let mut text = (0..600).flat_map(|i| format!("{:02X} ", i * 7 % 256).into_bytes()).collect::<Vec<u8>>();
+    text.extend(b"abc".repeat(100));
+    text
+}
+
+#[test]
+fn stored_and_fixed_blocks() {
+    assert_eq!(inflate(&hex("010500faff68656c6c6f"), 0).unwrap(), b"hello");
+    // the last three repeats are one match reaching back over what it writes
+    assert_eq!(inflate(&hex("73b454303050b070019106460a8ea47001"), 0).unwrap(), fixed_text());
+    // 300 a's : a literal then matches at distance 1
+    assert_eq!(inflate(&hex("4b4c1c05c40200"), 0).unwrap(), vec![b'a'; 300]);
+}
+
+#[test]
+fn broken_streams_are_refused() {
+    let refused = |stream: &str| {
+        let err = inflate(&hex(stream), 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        err.to_string()
+    };
+    assert!(refused("73b454303050b07001").ends_with("truncated"));
+    assert!(refused("010500faff6865").ends_with("truncated"));
+    assert!(refused("010500faef68656c6c6f").ends_with("stored block length"));
+    assert!(refused("07").ends_with("bad block type"));
+}
+
+#[test]
+fn stored_and_deflated_entries() {
+    let (name, content) = archive::read("tests/archives/stored.zip").unwrap();
+    assert_eq!((name.as_str(), content.as_slice()), ("program.bin", &[0x78, 0xA9, 0x01, 0x4C, 0x03, 0x80][..]));
+
+    // a fixed block, and a dynamic one
+    assert_eq!(archive::read("tests/archives/deflated.zip#fixed.txt").unwrap().1, fixed_text());
+    assert_eq!(archive::read("tests/archives/deflated.zip#dynamic.txt").unwrap().1, dynamic_text());
+    assert_eq!(archive::read("tests/archives/deflated.zip#missing.txt").unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn damaged_and_unsupported_archives_are_refused() {
+    let err = archive::read("tests/archives/damaged.zip#program.bin").unwrap_err();
+    assert_eq!(err.to_string(), "program.bin does not match its crc32, the archive is damaged");
+
+    let content = fs::read("tests/archives/stored.zip").unwrap();
+    assert!(archive::entries(&content[..content.len() - 30]).is_err());
+
+    let path = env::temp_dir().join(format!("brick_station_archives_{}.7z", std::process::id()));
+    fs::write(&path, b"7z\xBC\xAF\x27\x1C\x00\x04").unwrap();
+    let err = archive::read(path.to_str().unwrap()).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().starts_with("7z archives are not supported"));
+}
@@ -0,0 +1,72 @@
+// run_until as a future : it runs a slice at a time, giving the executor back in between,
+// until its predicate holds, a breakpoint is reached or it is cancelled
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use brick_station::emulator::{CancelToken, Emulator, Stopped};
+use brick_station::hardware::interfaces::DeviceOps;
+use brick_station::loader::program::Program;
+
+mod common;
+
+// counts in $00 forever, the apu's frame irq kept out
+fn counter() -> Program {
+    common::program(&[(0x8000, &[
+        0x78,             // sei
+        0xE6, 0x00,       // inc $00
+        0x4C, 0x01, 0x80, // jmp $8001
+    ])])
+}
+
+fn emulator() -> Emulator<'static> {
+    common::emulator(&counter())
+}
+
+// polls as an executor would, returns the outcome and how many polls it took
+fn drive<F: Future + Unpin>(mut future: F, limit: usize) -> Option<(F::Output, usize)> {
+    let mut context = Context::from_waker(Waker::noop());
+    (1..=limit).find_map(|polls| match Pin::new(&mut future).poll(&mut context) {
+        Poll::Ready(outcome) => Some((outcome, polls)),
+        Poll::Pending => None,
+    })
+}
+
+#[test]
+fn runs_a_slice_at_a_time_until_the_predicate_holds() {
+    let emulator = emulator();
+    let (outcome, polls) = drive(emulator.run_until(|emulator| emulator.frame() == 2, CancelToken::new()), 100).unwrap();
+    assert_eq!(outcome, Stopped::Reached);
+    assert!(polls > 2, "two frames are more than a slice, {} polls", polls);
+    assert_eq!(emulator.frame(), 2);
+
+    let (outcome, polls) = drive(emulator.run_until(|emulator| emulator.machine.cpu.borrow().registers.pc == 0x8003, CancelToken::new()), 100).unwrap();
+    assert_eq!((outcome, polls), (Stopped::Reached, 1));
+}
+
+#[test]
+fn a_cancelled_run_ends_at_its_next_slice() {
+    let emulator = emulator();
+    let token = CancelToken::new();
+    let mut run = emulator.run_until(|_| false, token.clone());
+    let mut context = Context::from_waker(Waker::noop());
+    assert_eq!(Pin::new(&mut run).poll(&mut context), Poll::Pending);
+    let cycles = emulator.cycles();
+
+    let canceller = token.clone();
+    thread::spawn(move || canceller.cancel()).join().unwrap();
+    assert_eq!(Pin::new(&mut run).poll(&mut context), Poll::Ready(Stopped::Cancelled));
+    assert_eq!(emulator.cycles(), cycles, "nothing ran once cancelled");
+}
+
+#[test]
+fn breakpoints_stop_a_run_and_it_goes_on_past_them() {
+    let mut emulator = emulator();
+    emulator.breakpoints.insert(0x8003);
+    let forever = || emulator.run_until(|_| false, CancelToken::new());
+    assert_eq!(drive(forever(), 10).unwrap().0, Stopped::Breakpoint(0x8003));
+    let counted = emulator.peek(0x00);
+    assert_eq!(drive(forever(), 10).unwrap().0, Stopped::Breakpoint(0x8003));
+    assert_eq!(emulator.peek(0x00), counted + 1);
+}
@@ -0,0 +1,20 @@
+// the programs and machines the embedding tests build theirs from
+use brick_station::emulator::Emulator;
+use brick_station::loader::program::{Program, Segment};
+
+// machine code at each address, starting at the first
+pub fn program(segments: &[(u16, &[u8])]) -> Program {
+    let mut program = Program::new();
+    for (address, bytes) in segments {
+        program.segments.push(Segment { address: *address, bytes: bytes.to_vec() });
+    }
+    program.entry = segments.first().map(|(address, _)| *address);
+    program
+}
+
+// a nes with the program loaded
+pub fn emulator(program: &Program) -> Emulator<'static> {
+    let mut emulator = Emulator::default();
+    emulator.load_program(program);
+    emulator
+}
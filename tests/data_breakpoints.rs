@@ -0,0 +1,96 @@
+// watchpoints : reads or writes of an address stopping the debugger, only when the value
+// holds their condition, with the region the address is in
+use std::env;
+use std::fs;
+
+use brick_station::cli::Arguments;
+use brick_station::debugger::debugger::App;
+use brick_station::debugger::session::Session;
+use brick_station::debugger::trace_filter::Comparison;
+use brick_station::debugger::watchpoints::Watchpoint;
+use brick_station::hardware::interfaces::DeviceOps;
+use brick_station::hardware::watches::Access;
+
+fn watchpoint(text: &str) -> Watchpoint {
+    text.parse().unwrap()
+}
+
+// sei, then stores 3, 2, 1 and 0 in $d0 from $8003 and stays at $8008
+const COUNTDOWN: &str = "78 A2 03 86 D0 CA 10 FB 4C 08 80";
+
+fn app(name: &str) -> App<'static> {
+    let path = env::temp_dir().join(format!("brick_station_watchpoints_{}_{}.txt", std::process::id(), name));
+    fs::write(&path, COUNTDOWN).unwrap();
+    let arguments = Arguments::parse(&[path.to_str().unwrap().to_string()]).unwrap();
+    let mut app = App::new(&arguments).unwrap();
+    let program = app.load_current_program().unwrap();
+    app.load_program(&program);
+    fs::remove_file(&path).unwrap();
+    app
+}
+
+fn d0(app: &App) -> u8 {
+    app.inner_machine_state.borrow().machine.cpu.borrow().peek(0x00D0)
+}
+
+#[test]
+fn watchpoints_read_back_from_their_text() {
+    let spelled = watchpoint("write to $00D0 where value == 0");
+    assert_eq!(spelled, Watchpoint { range: 0x00D0..=0x00D0, access: Some(Access::Write), condition: Some((Comparison::Equal, 0)) });
+    assert_eq!(spelled.to_string(), "w 00D0 =00");
+    assert_eq!(watchpoint("w 00d0 =0"), spelled);
+
+    let range = watchpoint("rw 0200-02ff");
+    assert_eq!((range.access, range.condition, range.to_string()), (None, None, "rw 0200-02FF".to_string()));
+    let status = watchpoint("r $2002 &80");
+    assert_eq!(watchpoint(&status.to_string()), status);
+    assert!(status.matches(Access::Read, 0x2002, 0xC0) && !status.matches(Access::Read, 0x2002, 0x40) && !status.matches(Access::Write, 0x2002, 0x80));
+
+    assert!("w 0300-02ff".parse::<Watchpoint>().is_err());
+    assert!("x 00d0".parse::<Watchpoint>().is_err());
+    assert!("w 00d0 ~5".parse::<Watchpoint>().is_err());
+}
+
+#[test]
+fn a_write_stops_only_with_the_value_asked_for() {
+    let mut app = app("zero");
+    app.regions.push("0000-00ff,zero page".parse().unwrap());
+    app.watchpoints.push(watchpoint("write to $00D0 where value == 0"));
+    app.run_until_breakpoint();
+    assert_eq!(app.stop_reason.as_deref(), Some("write 00 to $00D0 (zero page+$D0) at $8003"));
+    assert_eq!(d0(&app), 0x00);
+
+    app.stop_reason = None;
+    app.step_instruction();
+    assert_eq!(app.stop_reason, None, "dex reads and writes nothing watched");
+}
+
+#[test]
+fn reads_and_writes_are_watched_apart() {
+    let mut app = app("apart");
+    app.watchpoints.push(watchpoint("r 00d0"));
+    app.watchpoints.push(watchpoint("rw 00d0 >01"));
+    app.run_until_breakpoint();
+    assert_eq!(app.stop_reason.as_deref(), Some("write 03 to $00D0 at $8003"));
+    app.run_until_breakpoint();
+    assert_eq!(app.stop_reason.as_deref(), Some("write 02 to $00D0 at $8003"));
+    for _ in 0..3 {
+        app.step_instruction();
+        assert_eq!(app.stop_reason, None, "nothing reads $d0");
+    }
+    assert_eq!(d0(&app), 0x01);
+}
+
+#[test]
+fn sessions_keep_the_watchpoints() {
+    let directory = env::temp_dir().join(format!("brick_station_watchpoints_{}", std::process::id()));
+    let session = Session {
+        breakpoints : vec![0x8000],
+        watchpoints : vec![watchpoint("w 00d0 =00"), watchpoint("rw 0200-02ff"), watchpoint("r 2002 &80")],
+        ..Session::default()
+    };
+    session.save(&directory).unwrap();
+    let loaded = Session::load(&directory).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(loaded.watchpoints, session.watchpoints);
+}
@@ -0,0 +1,55 @@
+// the same program run several times in one process with the same input : runs that
+// come apart are caught at the first frame, with what tells them apart
+use std::env;
+use std::fs;
+
+use brick_station::cli::DeterminismArguments;
+use brick_station::debugger::determinism::{self, Audit, Source, PROBE_SEED};
+use brick_station::debugger::macros::InputMacro;
+
+// a hex text program in a file of its own with a config next to it, audited for 20 frames
+fn audit(name: &str, program: &str, config: &str, inputs: &InputMacro) -> Audit {
+    let directory = env::temp_dir().join(format!("brick_station_determinism_{}_{}", std::process::id(), name));
+    fs::create_dir_all(&directory).unwrap();
+    let (path, config_path) = (directory.join("program.txt"), directory.join("config.toml"));
+    fs::write(&path, program).unwrap();
+    fs::write(&config_path, config).unwrap();
+    let audited = DeterminismArguments::parse(&[path.to_str().unwrap().to_string(), "--config".to_string(), config_path.to_str().unwrap().to_string(), "--frames".to_string(), "20".to_string()]).unwrap();
+    assert_eq!(audited.frames, 20);
+    let this = audited.side(&[]).unwrap();
+    let probe = audited.side(&["--fuzz-seed".to_string(), PROBE_SEED.to_string()]).unwrap();
+    let audit = determinism::audit(&this, &probe, audited.frames, inputs).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    audit
+}
+
+// sei, clears $00 then counts in it forever
+const COUNTER: &str = "78 A9 00 85 00 E6 00 4C 05 80";
+// sei, then lda $10, ldx $11 and loops : reads memory nothing wrote
+const READER: &str = "78 A5 10 A6 11 4C 01 80";
+
+#[test]
+fn a_program_on_its_own_runs_the_same_every_time() {
+    let audit = audit("counter", COUNTER, "", &InputMacro { frames: vec![0x01; 10] });
+    assert_eq!(audit.findings, Vec::new());
+    assert_eq!(audit.report(), "deterministic : no difference in 20 frames");
+}
+
+#[test]
+fn reading_memory_nothing_wrote_shows_with_other_power_on_ram() {
+    let audit = audit("reader", READER, "", &InputMacro::default());
+    assert_eq!(audit.findings.len(), 1, "{}", audit.report());
+    let finding = &audit.findings[0];
+    assert_eq!((finding.source, finding.frame), (Source::PowerOn, Some(1)));
+    assert!(finding.detail.starts_with("A, X"), "{}", finding.detail);
+    assert!(audit.is_deterministic(), "the same ram each time, the same run");
+}
+
+#[test]
+fn ram_seeded_from_the_clock_comes_apart() {
+    let audit = audit("clock", READER, "[power]\nram = \"random\"\n", &InputMacro::default());
+    let sources = audit.findings.iter().map(|finding| finding.source).collect::<Vec<Source>>();
+    assert!(sources.contains(&Source::HostClock) && sources.contains(&Source::Rerun), "{}", audit.report());
+    assert!(!audit.is_deterministic());
+    assert!(audit.report().starts_with("not deterministic in 20 frames"));
+}
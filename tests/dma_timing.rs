@@ -0,0 +1,113 @@
+// the cycles the sprite and dmc dma take from the cpu, alone and together, and the extra
+// controller read a dmc fetch causes when it lands on one
+use brick_station::hardware::controller::{Button, Peripheral};
+use brick_station::hardware::device::Device;
+use brick_station::hardware::dma;
+use brick_station::hardware::interfaces::DeviceOps;
+use brick_station::hardware::machine::Machine;
+
+// the cycle of the next instruction boundary
+fn boundary(machine: &Machine) -> u64 {
+    while machine.cpu.borrow().cycle > 0 {
+        machine.tick();
+    }
+    machine.cpu.borrow().total_cycles
+}
+
+fn load(machine: &Machine, program: &[u8]) {
+    {
+        let mut bus = machine.bus.borrow_mut();
+        for (offset, byte) in program.iter().enumerate() {
+            bus.write(0x8000 + offset as u16, *byte);
+        }
+    }
+    machine.cpu.borrow_mut().reset();
+}
+
+fn oam(machine: &Machine) -> [u8; 256] {
+    machine.bus.borrow().devices.iter()
+        .find_map(|mapping| match &*mapping.device.borrow() {
+            Device::Ppu(ppu) => Some(ppu.oam),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn sprite_dma_copies_a_page_in_513_or_514_cycles() {
+    let machine = Machine::new();
+    for offset in 0..=0xFF {
+        machine.bus.borrow_mut().write(0x0200 + offset, offset as u8 ^ 0x5A);
+    }
+    load(&machine, &[
+        0xA9, 0x02, 0x8D, 0x14, 0x40, // lda #$02, sta $4014
+        0xEA, 0xEA,                   // nop, nop
+    ]);
+    machine.step();
+    machine.step();
+    let start = boundary(&machine);
+    machine.step();
+    let end = boundary(&machine);
+    assert_eq!(end - start, 513 + start % 2);
+    assert_eq!(machine.cpu.borrow().registers.pc, 0x8005, "the nop waits for the dma");
+    assert_eq!(oam(&machine)[0x11], 0x11 ^ 0x5A);
+    assert_eq!(oam(&machine)[0xFF], 0xFF ^ 0x5A);
+}
+
+#[test]
+fn a_dmc_fetch_takes_4_cycles() {
+    let machine = Machine::new();
+    machine.bus.borrow_mut().write(0xC000, 0xA5);
+    load(&machine, &[
+        0xA9, 0x8F, 0x8D, 0x10, 0x40, // lda #$8F, sta $4010 : irq on, fastest rate
+        0xA9, 0x10, 0x8D, 0x15, 0x40, // lda #$10, sta $4015 : $C000, 1 byte
+        0xEA,                         // nop
+    ]);
+    for _ in 0..4 {
+        machine.step();
+    }
+    assert_eq!(machine.bus.borrow().peek(0x4015) & 0x10, 0x10, "a byte left");
+    let start = boundary(&machine);
+    machine.step();
+    assert_eq!(boundary(&machine) - start, dma::DMC_DMA_CYCLES as u64);
+    assert_eq!(machine.bus.borrow().peek(0x4015) & 0x90, 0x80, "the sample is over, irq flagged");
+}
+
+#[test]
+fn a_dmc_fetch_during_sprite_dma_takes_2_more() {
+    let machine = Machine::new();
+    let mut bus = machine.bus.borrow_mut();
+    bus.clock = 100;
+    bus.write(0x4010, 0x0F);
+    bus.write(0x4015, 0x10);
+    bus.write(0x4014, 0x02);
+    assert_eq!(dma::service(&mut bus, None), dma::OAM_DMA_CYCLES + 2);
+    assert_eq!(bus.peek(0x4015) & 0x10, 0, "fetched");
+
+    bus.clock = 1000;
+    bus.write(0x4014, 0x02);
+    assert_eq!(dma::service(&mut bus, None), dma::OAM_DMA_CYCLES, "nothing to fetch, and an even start");
+}
+
+#[test]
+fn a_dmc_fetch_on_a_controller_read_loses_a_bit() {
+    for glitch in [true, false] {
+        let machine = Machine::new();
+        machine.connect(Peripheral::Controller);
+        machine.set_read_glitch(glitch);
+        machine.set_buttons(0, Button::B as u8);
+        let mut bus = machine.bus.borrow_mut();
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        bus.clock = 100;
+        bus.write(0x4015, 0x10);
+        assert_eq!(bus.read(0x4016) & 1, 0, "a");
+        dma::service(&mut bus, Some((0x4016, 100)));
+        let next = bus.read(0x4016) & 1;
+        if glitch {
+            assert_eq!(next, 0, "b was read again by the halted cpu, select comes next");
+        } else {
+            assert_eq!(next, 1, "b");
+        }
+    }
+}
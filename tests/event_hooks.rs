@@ -0,0 +1,108 @@
+// an embedding program hears about frames, interrupts, bank switches, breakpoints and
+// printed text through callbacks or a channel instead of polling the machine
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use brick_station::emulator::Emulator;
+use brick_station::events::{self, Event, EventKind};
+use brick_station::hardware::machine::Machine;
+use brick_station::hardware::mapper::{self, BankMemory, BoardRam};
+use brick_station::hardware::profile::MachineProfile;
+use brick_station::hardware::ram::PowerOnPattern;
+use brick_station::loader::program::Program;
+
+mod common;
+use common::emulator;
+
+// nmi on at every vblank, a handler that only returns, and a loop waiting for it
+fn nmi_loop() -> Program {
+    common::program(&[(0x8000, &[
+        0xA9, 0x80,       // lda #$80
+        0x8D, 0x00, 0x20, // sta $2000
+        0x4C, 0x05, 0x80, // jmp $8005
+        0x40,             // rti, at $8008
+    ]), (0xFFFA, &[0x08, 0x80])])
+}
+
+#[test]
+fn frames_and_interrupts_come_through_a_channel() {
+    let emulator = emulator(&nmi_loop());
+    let receiver = emulator.events(&[EventKind::FrameCompleted, EventKind::NmiRaised]);
+    (0..3).for_each(|_| emulator.run_frame());
+    let received = receiver.try_iter().collect::<Vec<Event>>();
+    let frames = received.iter().filter_map(|event| match event { Event::FrameCompleted { frame } => Some(*frame), _ => None }).collect::<Vec<u64>>();
+    assert_eq!(frames, vec![1, 2, 3]);
+    let interrupts = received.iter().filter(|event| event.kind() == EventKind::NmiRaised).count();
+    assert!(interrupts >= 2, "{:?}", received);
+
+    drop(receiver);
+    emulator.run_frame();
+}
+
+#[test]
+fn a_breakpoint_stops_the_frame_and_is_heard_of() {
+    let mut emulator = emulator(&nmi_loop());
+    let hits = Rc::new(RefCell::new(Vec::new()));
+    let heard = hits.clone();
+    emulator.subscribe(&events::ALL, Box::new(move |event| if let Event::BreakpointHit { pc } = event { heard.borrow_mut().push(*pc) }));
+    emulator.breakpoints.insert(0x8008);
+
+    // the vblank completes the frame before the nmi it raises is taken
+    emulator.run_frame();
+    assert_eq!(emulator.frame(), 1);
+    emulator.run_frame();
+    assert_eq!(emulator.frame(), 1, "stopped before the handler");
+    assert_eq!(emulator.machine.cpu.borrow().registers.pc, 0x8008);
+    emulator.run_frame();
+    assert_eq!(emulator.frame(), 2, "goes on past it");
+    assert_eq!(*hits.borrow(), vec![0x8008]);
+}
+
+#[test]
+fn board_bank_switches_are_heard_of() {
+    // vrc6 : the last 8k is fixed at $E000, the code there switches both prg windows
+    let mut prg = vec![0; 0x8000];
+    prg[0x6000..0x600D].copy_from_slice(&[
+        0xA9, 0x01,       // lda #$01
+        0x8D, 0x00, 0x80, // sta $8000
+        0xA9, 0x03,       // lda #$03
+        0x8D, 0x00, 0xC0, // sta $C000
+        0x4C, 0x0A, 0xE0, // jmp $E00A
+    ]);
+    prg[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0xE0]);
+    let emulator = Emulator::default();
+    let board = mapper::create(24, prg, Vec::new(), BoardRam { prg: None, chr: 0x2000 }).unwrap();
+    emulator.machine.bus.borrow_mut().insert_cartridge(board);
+    emulator.machine.cpu.borrow_mut().reset();
+
+    let receiver = emulator.events(&[EventKind::MapperBankSwitched]);
+    (0..10).for_each(|_| { emulator.step(); });
+    assert_eq!(receiver.try_iter().collect::<Vec<Event>>(), vec![
+        Event::MapperBankSwitched { memory: BankMemory::Prg, address: 0x8000, bank: 1 },
+        Event::MapperBankSwitched { memory: BankMemory::Prg, address: 0xC000, bank: 3 },
+    ]);
+}
+
+#[test]
+fn printed_text_comes_as_it_is_printed() {
+    // the apple I display at $D012, set up as the monitor does
+    let program = common::program(&[(0x0280, &[
+        0xA9, 0xA7,       // lda #$A7
+        0x8D, 0x13, 0xD0, // sta $D013
+        0xA9, 0xC8,       // lda #'H' | $80
+        0x8D, 0x12, 0xD0, // sta $D012
+        0xA9, 0xC9,       // lda #'I' | $80
+        0x8D, 0x12, 0xD0, // sta $D012
+        0x4C, 0x8F, 0x02, // jmp $028F
+    ])]);
+    let mut emulator = Emulator::default();
+    emulator.machine = Machine::build(&MachineProfile::apple1(), PowerOnPattern::Fill(0));
+    emulator.load_program(&program);
+
+    let receiver = emulator.events(&[EventKind::SerialOutput]);
+    emulator.run_frame();
+    assert_eq!(receiver.try_iter().collect::<Vec<Event>>(), vec![
+        Event::SerialOutput { bytes: b"H".to_vec() },
+        Event::SerialOutput { bytes: b"I".to_vec() },
+    ]);
+}
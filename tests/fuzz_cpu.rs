@@ -0,0 +1,34 @@
+// the cpu fuzz target's checks over seeded inputs, for a run without cargo-fuzz
+use brick_station::hardware::fuzzing::run_cpu;
+use brick_station::hardware::rng::SeededRng;
+
+const RUNS : u64 = 100;
+
+fn check(data: &[u8], seed: u64) {
+    if let Err(violation) = run_cpu(data) {
+        panic!("seed {}: {}", seed, violation);
+    }
+}
+
+#[test]
+fn random_instruction_streams() {
+    for seed in 0..RUNS {
+        let mut rng = SeededRng::new(seed);
+        let mut data = vec![0; 6 + 1 + (rng.next_u64() % 512) as usize];
+        rng.fill(&mut data);
+        check(&data, seed);
+    }
+}
+
+#[test]
+fn stack_instructions_across_the_page_ends() {
+    // pushes, pulls, calls and returns only, from a stack pointer next to either end
+    const STACK: [u8; 10] = [0x48, 0x08, 0x68, 0x28, 0x20, 0x60, 0x40, 0x00, 0x9A, 0xBA];
+    for seed in 0..RUNS {
+        let mut rng = SeededRng::new(seed);
+        let sp = [0x00, 0x01, 0x02, 0xFD, 0xFE, 0xFF][seed as usize % 6];
+        let mut data = vec![rng.next_u8(), rng.next_u8(), rng.next_u8(), sp, rng.next_u8(), rng.next_u8() % 8];
+        data.extend((0..256).map(|_| STACK[rng.next_u8() as usize % STACK.len()]));
+        check(&data, seed);
+    }
+}
@@ -0,0 +1,59 @@
+// gamepad inputs and mappings, and the joystick events of a gamepad played from a file
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use brick_station::config::Config;
+use brick_station::debugger::gamepad::{Gamepad, GamepadMapping, Input, TARGETS};
+use brick_station::hardware::controller::Button;
+
+// time, value, type and number as the joystick interface writes them
+fn event(value: i16, kind: u8, number: u8) -> Vec<u8> {
+    let mut bytes = 0u32.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&value.to_le_bytes());
+    bytes.extend_from_slice(&[kind, number]);
+    bytes
+}
+
+#[test]
+fn inputs_read_back_from_their_names() {
+    for input in [Input::Button(7), Input::Axis(1, false), Input::Axis(6, true)] {
+        assert_eq!(input.to_string().parse::<Input>().unwrap(), input);
+    }
+    assert_eq!("axis7-".parse::<Input>().unwrap(), Input::Axis(7, false));
+    assert!("axis7".parse::<Input>().is_err(), "an axis needs its direction");
+    assert!("trigger2".parse::<Input>().is_err());
+}
+
+#[test]
+fn config_sections_map_a_players_targets() {
+    let config = Config::parse("[gamepad2]\na = \"button5\"\nup = \"axis4- button11\"\nstart = \"oops\"\n").unwrap();
+    let mapping = GamepadMapping::from_config(&config, 1);
+    let target = |name: &str| TARGETS.iter().position(|(target, _, _)| *target == name).unwrap();
+    assert_eq!(mapping.inputs[target("a")], [Input::Button(5)]);
+    assert_eq!(mapping.inputs[target("up")], [Input::Axis(4, false), Input::Button(11)]);
+    assert_eq!(mapping.inputs[target("start")], GamepadMapping::default().inputs[target("start")], "left as it was");
+    assert_eq!(GamepadMapping::from_config(&config, 0), GamepadMapping::default());
+}
+
+#[cfg(unix)]
+#[test]
+fn events_press_the_mapped_buttons() {
+    let path = env::temp_dir().join(format!("brick_station_js_{}", std::process::id()));
+    let events = [
+        event(1, 0x81, 7),       // start held when opened : the state, not a press
+        event(1, 0x01, 1),       // east button
+        event(-32767, 0x02, 6),  // d-pad left
+        event(1, 0x01, 2),       // west button, turbo b
+    ].concat();
+    fs::write(&path, events).unwrap();
+    let mut gamepad = Gamepad::open(Path::new(&path)).unwrap();
+    let mut pressed = Vec::new();
+    assert!(gamepad.poll(&mut pressed));
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(pressed, [Input::Button(1), Input::Axis(6, false), Input::Button(2)]);
+    let (buttons, turbo) = GamepadMapping::default().buttons(&gamepad);
+    assert_eq!(buttons, Button::A as u8 | Button::Start as u8 | Button::Left as u8);
+    assert_eq!(turbo, Button::B as u8);
+}
@@ -0,0 +1,66 @@
+// input macros as text, played back frame by frame, and kept with a program's session
+use std::env;
+use std::fs;
+
+use brick_station::cli::Arguments;
+use brick_station::debugger::debugger::{App, State};
+use brick_station::debugger::macros::{self, InputMacro, MacroTake};
+use brick_station::debugger::session::Session;
+use brick_station::hardware::controller::Button;
+
+fn combo() -> InputMacro {
+    let (down, right, a) = (Button::Down as u8, Button::Right as u8, Button::A as u8);
+    InputMacro { frames : vec![down, down, down | right, right, right, a, 0] }
+}
+
+#[test]
+fn macros_read_back_from_their_text() {
+    let text = combo().to_string();
+    assert_eq!(text, ".....D..x2 .....D.R .......Rx2 A....... ........");
+    assert_eq!(text.parse::<InputMacro>().unwrap(), combo());
+    assert!("A......".parse::<InputMacro>().is_err(), "a pad summary is 8 buttons");
+    assert!("A.......xy".parse::<InputMacro>().is_err());
+    assert_eq!(macros::parse_binding(&format!("h{}", text)), Some(('h', combo())));
+}
+
+#[test]
+fn takes_play_one_frame_at_a_time_and_end() {
+    let mut playing = MacroTake::new(0, combo());
+    let played = std::iter::from_fn(|| playing.play()).collect::<Vec<u8>>();
+    assert_eq!(played, combo().frames);
+    assert_eq!(playing.play(), None);
+
+    let mut recording = MacroTake::new(1, InputMacro::default());
+    played.iter().for_each(|buttons| recording.record(*buttons));
+    assert_eq!(recording.frames, combo());
+    assert!((recording.frames.seconds() - 7.0 / 60.0).abs() < 1e-6);
+}
+
+#[test]
+fn sessions_keep_the_bound_macros() {
+    let directory = env::temp_dir().join(format!("brick_station_macros_{}", std::process::id()));
+    let session = Session {
+        breakpoints : vec![0x8000],
+        macros      : vec![('#', combo()), ('h', InputMacro { frames : vec![Button::Start as u8] })],
+        ..Session::default()
+    };
+    session.save(&directory).unwrap();
+    let loaded = Session::load(&directory).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(loaded.macros, session.macros);
+}
+
+#[test]
+fn macros_on_command_keys_are_not_restored() {
+    let session = Session {
+        macros : vec![('q', combo()), ('M', combo()), ('#', combo())],
+        ..Session::default()
+    };
+    let path = env::temp_dir().join(format!("brick_station_macro_keys_{}.txt", std::process::id()));
+    fs::write(&path, "4C 00 80").unwrap();
+    let mut app = App::new(&Arguments::parse(&[path.to_str().unwrap().to_string()]).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    session.apply(&mut app);
+    assert!(State::is_command_key('q') && State::is_command_key('M') && !State::is_command_key('#'));
+    assert_eq!(app.macros.keys().copied().collect::<Vec<char>>(), vec!['#']);
+}
@@ -0,0 +1,24 @@
+// the text formats refuse what they cannot read instead of panicking, and are found by
+// their extensions
+use std::io::ErrorKind;
+use std::path::Path;
+
+use brick_station::loader::loader::{self, Format};
+
+#[test]
+fn text_formats_refuse_characters_outside_ascii() {
+    for (format, content) in [(Format::SRecord, &b"S\xC3\xA9000000"[..]), (Format::IntelHex, &b":a\xC3\xA90"[..])] {
+        let err = loader::parse(format, content, 0x8000).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{:?}", format);
+    }
+}
+
+#[test]
+fn intel_hex_is_found_by_its_extensions() {
+    for name in ["program.hex", "program.ihx", "PROGRAM.IHEX"] {
+        assert_eq!(Format::from_extension(Path::new(name)), Some(Format::IntelHex), "{}", name);
+    }
+    let program = loader::parse(Format::IntelHex, b":0380000078EAEA31\n:00000001FF\n", 0).unwrap();
+    assert_eq!(program.segments[0].address, 0x8000);
+    assert_eq!(program.segments[0].bytes, vec![0x78, 0xEA, 0xEA]);
+}
@@ -0,0 +1,56 @@
+// boards assembled from their description only : the list keys and [device.<label>] sections
+use std::fs;
+use std::path::Path;
+
+use brick_station::hardware::interfaces::DeviceOps;
+use brick_station::hardware::machine::Machine;
+use brick_station::hardware::profile::MachineProfile;
+use brick_station::hardware::ram::PowerOnPattern;
+
+#[test]
+fn device_sections_assemble_the_bus() {
+    let directory = std::env::temp_dir().join(format!("brick_station_descriptions_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("monitor.bin"), [0xEA, 0x4C, 0x00, 0xF0]).unwrap();
+    let description = r#"
+        [machine]
+        ram = "$0000-$00FF"
+
+        [device.work]
+        type = "ram"
+        base = "$0200"
+        size = "1k"
+
+        [device.monitor]
+        type = "rom"
+        base = "$F000"
+        file = "monitor.bin"
+        size = "$1000"
+
+        [device.console]
+        type = "uart"
+        base = "$D000"
+    "#;
+    let profile = MachineProfile::parse(description, &directory).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    let machine = Machine::build(&profile, PowerOnPattern::Fill(0));
+    let mut bus = machine.bus.borrow_mut();
+
+    bus.write(0x05FF, 0x42);
+    assert_eq!(bus.read(0x05FF), 0x42);
+    bus.write(0x0600, 0x42);
+    assert_eq!(bus.read(0x0600), 0, "past the ram section");
+    assert_eq!(bus.read(0xF001), 0x4C);
+    assert_eq!(bus.read(0xFFFF), 0xFF, "the rom is padded to its size");
+    drop(bus);
+    assert_eq!(machine.terminal_output(), Some(Vec::new()), "the uart is a serial port");
+}
+
+#[test]
+fn device_sections_are_checked() {
+    let parse = |section: &str| MachineProfile::parse(&format!("[machine]\n[device.x]\n{}", section), Path::new("")).unwrap_err().to_string();
+    assert!(parse("type = \"ram\"\nbase = \"$F000\"\nsize = \"8k\"").contains("do not fit"));
+    assert!(parse("type = \"ram\"\nbase = \"$F000\"").contains("missing size"));
+    assert!(parse("type = \"via\"").contains("missing base address"));
+    assert!(parse("type = \"sound\"\nbase = \"$4000\"").starts_with("device x: unknown type sound"));
+}
@@ -0,0 +1,306 @@
+// the boards mapper::create knows, through one fixture : prg roms are filled with the number
+// of each 8k bank, so a read tells which bank is mapped there. a mapper added to mapper::create
+// fails every_mapper_is_covered until it has an entry in MAPPERS and tests of its own
+use brick_station::emulator::Emulator;
+use brick_station::hardware::device::Device;
+use brick_station::hardware::interfaces::DeviceOps;
+use brick_station::hardware::mapper::{self, BoardRam};
+use brick_station::hardware::nametables;
+use brick_station::hardware::snapshot::Snapshot;
+use brick_station::hardware::vrc6::Vrc6;
+use brick_station::loader::ines::Mirroring;
+
+const PRG_BANK    : usize = 0x2000;
+const CHR_BANK    : usize = 0x0400;
+// the highest mapper number of NES 2.0 headers
+const LAST_MAPPER : u16 = 0x0FFF;
+
+struct Board {
+    number  : u16,
+    name    : &'static str,
+    prg_max : usize, // 8k banks
+}
+
+const MAPPERS: [Board; 3] = [
+    Board { number: 0, name: "NROM", prg_max: 4 },
+    Board { number: 24, name: "VRC6a", prg_max: 32 },
+    Board { number: 26, name: "VRC6b", prg_max: 32 },
+];
+
+struct Fixture<'a> {
+    board : Device<'a>,
+}
+
+impl<'a> Fixture<'a> {
+    fn new(mapper: u16, prg_banks: usize, chr_banks: usize) -> Fixture<'a> {
+        Fixture::with_ram(mapper, prg_banks, chr_banks, BoardRam::default())
+    }
+
+    fn with_ram(mapper: u16, prg_banks: usize, chr_banks: usize, ram: BoardRam) -> Fixture<'a> {
+        let prg = (0..prg_banks * PRG_BANK).map(|at| (at / PRG_BANK) as u8).collect();
+        let chr = (0..chr_banks * CHR_BANK).map(|at| (at / CHR_BANK) as u8).collect();
+        let board = mapper::create(mapper, prg, chr, ram).unwrap_or_else(|err| panic!("mapper {}: {}", mapper, err));
+        Fixture { board }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.board.write(address, value);
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        self.board.read(address)
+    }
+
+    // the 8k bank at $8000, $A000, $C000 and $E000
+    fn layout(&self) -> [u8; 4] {
+        [self.read(0x8000), self.read(0xA000), self.read(0xC000), self.read(0xE000)]
+    }
+
+    fn run(&mut self, cycles: u64) {
+        self.board.tick(cycles);
+    }
+
+    fn vrc6(&self) -> &Vrc6 {
+        match &self.board {
+            Device::Vrc6(vrc6) => vrc6,
+            _ => panic!("{} is not a vrc6", self.board.name()),
+        }
+    }
+}
+
+// the address space as the cpu sees it, a byte every 64
+fn contents(fixture: &Fixture) -> Vec<u8> {
+    (0x4020..=0xFFFFu32).step_by(64).map(|address| fixture.read(address as u16)).collect()
+}
+
+#[test]
+fn every_mapper_is_covered() {
+    let created = (0..=LAST_MAPPER)
+        .filter(|mapper| mapper::create(*mapper, vec![0; 4 * PRG_BANK], vec![0; 8 * CHR_BANK], BoardRam::default()).is_ok())
+        .collect::<Vec<u16>>();
+    let covered = MAPPERS.iter().map(|board| board.number).collect::<Vec<u16>>();
+    assert_eq!(created, covered);
+}
+
+// what every board has to do : the last 8k stay at $E000 where the vectors are, whatever is
+// written to the registers, and a savestate brings the banks back
+#[test]
+fn last_bank_stays_fixed() {
+    for board in MAPPERS.iter() {
+        let mut fixture = Fixture::new(board.number, board.prg_max, 8);
+        let last = (board.prg_max - 1) as u8;
+        for address in (0x8000..=0xDFFFu16).step_by(0x1000).flat_map(|page| page..=page + 3) {
+            for value in [0x00, 0x01, 0x0F, 0x1F, 0x80, 0xFF] {
+                fixture.write(address, value);
+                assert_eq!(fixture.read(0xE000), last, "{} after ${:02X} to ${:04X}", board.name, value, address);
+                assert_eq!(fixture.read(0xFFFF), last, "{} after ${:02X} to ${:04X}", board.name, value, address);
+            }
+        }
+    }
+}
+
+#[test]
+fn savestates_bring_the_banks_back() {
+    for board in MAPPERS.iter() {
+        let mut fixture = Fixture::new(board.number, board.prg_max, 8);
+        fixture.write(0x8000, 0x03);
+        fixture.write(0xC000, 0x05);
+        fixture.write(0xB003, 0x84);
+        let (saved, mirroring) = (contents(&fixture), fixture.board.mirroring());
+        let mut state = Vec::new();
+        fixture.board.save(&mut state);
+
+        fixture.write(0x8000, 0x01);
+        fixture.write(0xC000, 0x02);
+        fixture.write(0xB003, 0x00);
+        fixture.board.restore(&mut state.as_slice()).unwrap_or_else(|err| panic!("{}: {}", board.name, err));
+        assert_eq!(contents(&fixture), saved, "{}", board.name);
+        assert_eq!(fixture.board.mirroring(), mirroring, "{}", board.name);
+    }
+}
+
+#[test]
+fn nrom_mirrors_16k_images_and_maps_32k_flat() {
+    assert_eq!(Fixture::new(0, 2, 1).layout(), [0, 1, 0, 1]);
+    assert_eq!(Fixture::new(0, 4, 1).layout(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn nrom_has_no_registers() {
+    let mut fixture = Fixture::new(0, 4, 1);
+    for address in (0x8000..=0xFFFFu16).step_by(0x1000) {
+        fixture.write(address, 0x07);
+    }
+    assert_eq!(fixture.layout(), [0x07, 0x07, 0x07, 0x07]);
+    assert_eq!(fixture.read(0x8001), 0);
+    assert_eq!(fixture.read(0xE001), 3);
+    assert_eq!(fixture.board.mirroring(), None);
+    fixture.run(100_000);
+    assert!(!fixture.board.irq());
+}
+
+#[test]
+fn nrom_prg_ram_only_when_the_header_asks() {
+    let mut fixture = Fixture::new(0, 2, 1);
+    fixture.write(0x6000, 0x42);
+    assert_eq!(fixture.read(0x6000), 0);
+    let mut fixture = Fixture::with_ram(0, 2, 1, BoardRam { prg: Some(0x2000), chr: 0 });
+    fixture.write(0x6000, 0x42);
+    fixture.write(0x7FFF, 0x24);
+    assert_eq!((fixture.read(0x6000), fixture.read(0x7FFF)), (0x42, 0x24));
+}
+
+#[test]
+fn chr_ram_without_chr_rom() {
+    for board in MAPPERS.iter().filter(|board| board.number != 0) {
+        let fixture = Fixture::with_ram(board.number, board.prg_max, 0, BoardRam { prg: None, chr: 0x2000 });
+        assert_eq!(fixture.vrc6().chr, vec![0; 0x2000], "{}", board.name);
+        // chr rom wins
+        let fixture = Fixture::with_ram(board.number, board.prg_max, 8, BoardRam { prg: None, chr: 0x2000 });
+        assert_eq!(fixture.vrc6().chr.len(), 8 * CHR_BANK, "{}", board.name);
+    }
+}
+
+#[test]
+fn vrc6_switches_16k_and_8k_banks() {
+    for number in [24, 26] {
+        let mut fixture = Fixture::new(number, 32, 8);
+        assert_eq!(fixture.layout(), [0, 1, 0, 31]);
+        fixture.write(0x8000, 0x05);
+        assert_eq!(fixture.layout(), [10, 11, 0, 31]);
+        fixture.write(0xC000, 0x11);
+        assert_eq!(fixture.layout(), [10, 11, 17, 31]);
+        // the registers keep 4 and 5 bits
+        fixture.write(0x8000, 0xF7);
+        fixture.write(0xC000, 0xE3);
+        assert_eq!(fixture.layout(), [14, 15, 3, 31]);
+        // any of the four addresses of a register
+        fixture.write(0x8003, 0x02);
+        fixture.write(0xC002, 0x09);
+        assert_eq!(fixture.layout(), [4, 5, 9, 31]);
+    }
+}
+
+#[test]
+fn vrc6_banks_wrap_around_smaller_roms() {
+    let mut fixture = Fixture::new(24, 16, 8);
+    fixture.write(0x8000, 0x09);
+    fixture.write(0xC000, 0x13);
+    assert_eq!(fixture.layout(), [2, 3, 3, 15]);
+}
+
+#[test]
+fn vrc6_chr_banks_by_board_wiring() {
+    let registers = [0xD000, 0xD001, 0xD002, 0xD003, 0xE000, 0xE001, 0xE002, 0xE003];
+    // mapper 26 swaps the two low address lines
+    let slots = [(24, [0, 1, 2, 3, 4, 5, 6, 7]), (26, [0, 2, 1, 3, 4, 6, 5, 7])];
+    for (number, slots) in slots {
+        let mut fixture = Fixture::new(number, 32, 8);
+        for (register, value) in registers.iter().zip(0x40..) {
+            fixture.write(*register, value);
+        }
+        let mut banks = [0; 8];
+        for (register, slot) in slots.iter().enumerate() {
+            banks[*slot] = 0x40 + register as u8;
+        }
+        assert_eq!(fixture.vrc6().chr_banks, banks, "mapper {}", number);
+    }
+}
+
+#[test]
+fn vrc6_prg_ram_follows_its_enable_bit() {
+    let mut fixture = Fixture::new(24, 32, 8);
+    fixture.write(0x6000, 0x42);
+    assert_eq!(fixture.read(0x6000), 0);
+    fixture.write(0xB003, 0x80);
+    fixture.write(0x6000, 0x42);
+    fixture.write(0x7FFF, 0x24);
+    assert_eq!((fixture.read(0x6000), fixture.read(0x7FFF)), (0x42, 0x24));
+    // disabled again, the contents are kept
+    fixture.write(0xB003, 0x00);
+    assert_eq!(fixture.read(0x6000), 0);
+    fixture.write(0xB003, 0x80);
+    assert_eq!(fixture.read(0x6000), 0x42);
+}
+
+#[test]
+fn vrc6_prg_ram_size_from_the_header() {
+    // 2k repeat through the 8k window
+    let mut fixture = Fixture::with_ram(24, 32, 8, BoardRam { prg: Some(0x0800), chr: 0 });
+    fixture.write(0xB003, 0x80);
+    fixture.write(0x6001, 0x42);
+    assert_eq!((fixture.read(0x6801), fixture.read(0x7801)), (0x42, 0x42));
+    // none at all
+    let mut fixture = Fixture::with_ram(24, 32, 8, BoardRam { prg: Some(0), chr: 0 });
+    fixture.write(0xB003, 0x80);
+    fixture.write(0x6000, 0x42);
+    assert_eq!(fixture.read(0x6000), 0);
+}
+
+#[test]
+fn vrc6_mirroring_follows_the_control_register() {
+    for number in [24, 26] {
+        let mut fixture = Fixture::new(number, 32, 8);
+        let modes = [Mirroring::Vertical, Mirroring::Horizontal, Mirroring::SingleScreenLow, Mirroring::SingleScreenHigh];
+        for (bits, mode) in modes.iter().enumerate() {
+            fixture.write(0xB003, (bits as u8) << 2 | 0x80);
+            assert_eq!(fixture.board.mirroring(), Some(*mode), "mapper {} with ${:02X}", number, bits << 2);
+        }
+    }
+}
+
+#[test]
+fn vrc6_sound_registers_leave_the_banks_alone() {
+    let mut fixture = Fixture::new(26, 32, 8);
+    fixture.write(0x8000, 0x01);
+    fixture.write(0xC000, 0x04);
+    for register in [0x9000, 0x9001, 0x9002, 0x9003, 0xA000, 0xA001, 0xA002, 0xB000, 0xB001, 0xB002] {
+        fixture.write(register, 0xFF);
+    }
+    assert_eq!(fixture.layout(), [2, 3, 4, 31]);
+}
+
+// the counter is not clocked yet : the latch and control registers are kept, the line stays released
+#[test]
+fn vrc6_irq_registers() {
+    let mut fixture = Fixture::new(24, 32, 8);
+    fixture.write(0xF000, 0xF0);
+    fixture.write(0xF001, 0xFF);
+    assert_eq!((fixture.vrc6().irq_latch, fixture.vrc6().irq_control), (0xF0, 0x07));
+    fixture.run(100_000);
+    assert!(!fixture.board.irq());
+}
+
+#[test]
+fn nametables_fold_onto_the_pages_of_the_layout() {
+    let tables = [0x2000, 0x2400, 0x2800, 0x2C00];
+    let pages = |mirroring| tables.map(|table| nametables::vram_offset(mirroring, table + 0x15) / 0x400);
+    assert_eq!(pages(Mirroring::Horizontal), [0, 0, 1, 1]);
+    assert_eq!(pages(Mirroring::Vertical), [0, 1, 0, 1]);
+    assert_eq!(pages(Mirroring::SingleScreenLow), [0, 0, 0, 0]);
+    assert_eq!(pages(Mirroring::SingleScreenHigh), [1, 1, 1, 1]);
+    assert_eq!(pages(Mirroring::FourScreen), [0, 1, 2, 3]);
+    // $3000 - $3EFF mirrors $2000 - $2EFF
+    assert_eq!(nametables::vram_offset(Mirroring::Vertical, 0x3415), nametables::vram_offset(Mirroring::Vertical, 0x2415));
+    assert_eq!(nametables::vram_offset(Mirroring::FourScreen, 0x2FFF), nametables::FOUR_SCREEN_VRAM - 1);
+}
+
+#[test]
+fn four_screen_wins_over_the_board() {
+    assert_eq!(nametables::effective(Some(Mirroring::SingleScreenLow), Mirroring::FourScreen), Mirroring::FourScreen);
+    assert_eq!(nametables::effective(Some(Mirroring::SingleScreenLow), Mirroring::Vertical), Mirroring::SingleScreenLow);
+    assert_eq!(nametables::effective(None, Mirroring::Vertical), Mirroring::Vertical);
+}
+
+#[test]
+fn mirroring_of_a_loaded_dump() {
+    // a 32k vrc6 image with vertical mirroring in its header
+    let mut image = b"NES\x1A\x02\x00\x81\x10".to_vec();
+    image.resize(16 + 2 * 0x4000, 0);
+    let mut emulator = Emulator::default();
+    assert_eq!(emulator.mirroring(), None);
+    emulator.load_bytes("board.nes", &image).unwrap();
+    assert_eq!(emulator.mirroring(), Some(Mirroring::Vertical));
+    emulator.write(0xB003, 0x0C);
+    assert_eq!(emulator.mirroring(), Some(Mirroring::SingleScreenHigh));
+}
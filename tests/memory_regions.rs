@@ -0,0 +1,64 @@
+// named address ranges : read from their text, the innermost one found for an address,
+// and kept with a program's session
+use std::env;
+use std::fs;
+
+use brick_station::debugger::regions::{self, Region};
+use brick_station::debugger::session::Session;
+use brick_station::hardware::address_mode::AddressMode;
+
+fn region(text: &str) -> Region {
+    text.parse().unwrap()
+}
+
+#[test]
+fn regions_read_back_from_their_text() {
+    let oam = region("$0200-$02ff, red, OAM shadow");
+    assert_eq!((oam.range.clone(), oam.name.as_str(), oam.color), (0x0200..=0x02FF, "OAM shadow", "red"));
+    assert_eq!(oam.to_string(), "0200-02FF,red,OAM shadow");
+    assert_eq!(region(&oam.to_string()), oam);
+
+    let byte = region("0010,frame counter, low");
+    assert_eq!((byte.range.clone(), byte.color, byte.name.as_str()), (0x0010..=0x0010, regions::COLORS[0].0, "frame counter, low"));
+    assert_eq!(region(&byte.to_string()), byte);
+
+    assert!("0300-02ff,backwards".parse::<Region>().is_err());
+    assert!("0200-02ff,red,".parse::<Region>().is_err());
+    assert!("0200".parse::<Region>().is_err(), "a region needs a name");
+}
+
+#[test]
+fn the_innermost_region_names_an_address() {
+    let mut named = Vec::new();
+    regions::annotate(&mut named, region("0000-07ff,gray,work ram"));
+    regions::annotate(&mut named, region("0200-02ff,red,OAM shadow"));
+    assert_eq!(regions::find(&named, 0x0203).map(|region| region.label(0x0203)), Some("OAM shadow+$3".to_string()));
+    assert_eq!(regions::find(&named, 0x0300).map(|region| region.label(0x0300)), Some("work ram+$300".to_string()));
+    assert_eq!(regions::find(&named, 0x0800), None);
+
+    regions::annotate(&mut named, region("0200-02ff,green,sprites"));
+    assert_eq!(named.len(), 2, "the same addresses are named again");
+    assert_eq!(regions::remove(&mut named, 0x0210).map(|region| region.name), Some("sprites".to_string()));
+    assert_eq!(regions::find(&named, 0x0210).map(|region| region.name.as_str()), Some("work ram"));
+}
+
+#[test]
+fn operands_name_the_address_before_indexing() {
+    assert_eq!(regions::operand(&AddressMode::Abx, 0x03, 0x02), Some(0x0203));
+    assert_eq!(regions::operand(&AddressMode::Izy, 0x10, 0xEA), Some(0x0010));
+    assert_eq!(regions::operand(&AddressMode::Imm, 0x10, 0xEA), None);
+}
+
+#[test]
+fn sessions_keep_the_regions() {
+    let directory = env::temp_dir().join(format!("brick_station_regions_{}", std::process::id()));
+    let session = Session {
+        memory_page : 2,
+        regions     : vec![region("0200-02ff,red,OAM shadow"), region("0300-03ff,level data # room 1")],
+        ..Session::default()
+    };
+    session.save(&directory).unwrap();
+    let loaded = Session::load(&directory).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(loaded.regions, session.regions);
+}
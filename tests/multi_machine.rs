@@ -0,0 +1,57 @@
+// machines in the same process run on their own : side by side they end up as they would
+// alone, and a fork's frames and sound never show in the machine it was taken from
+use brick_station::emulator::Emulator;
+use brick_station::loader::program::Program;
+
+mod common;
+
+// counts in $00 and writes it to the first pulse channel's volume, forever
+fn counter(step: u8) -> Program {
+    common::program(&[(0x8000, &[
+        0xA9, 0x01,       // lda #$01
+        0x8D, 0x15, 0x40, // sta $4015
+        0xA5, 0x00,       // lda $00
+        0x18,             // clc
+        0x69, step,       // adc #step
+        0x85, 0x00,       // sta $00
+        0x8D, 0x00, 0x40, // sta $4000
+        0x4C, 0x05, 0x80, // jmp $8005
+    ])])
+}
+
+fn emulator(step: u8) -> Emulator<'static> {
+    common::emulator(&counter(step))
+}
+
+#[test]
+fn machines_side_by_side_end_up_as_they_would_alone() {
+    let alone = emulator(1);
+    (0..3).for_each(|_| alone.run_frame());
+
+    let (this, other) = (emulator(1), emulator(3));
+    for _ in 0..3 {
+        this.run_frame();
+        other.run_frame();
+    }
+    assert_eq!((this.frame(), other.frame()), (3, 3));
+    assert_eq!(this.save_state(), alone.save_state());
+    assert_ne!(this.save_state(), other.save_state());
+    assert_eq!(this.audio().len(), alone.audio().len());
+}
+
+#[test]
+fn forks_keep_their_frames_and_sound_to_themselves() {
+    let emulator = emulator(1);
+    (0..2).for_each(|_| emulator.run_frame());
+    emulator.audio();
+
+    let fork = emulator.fork();
+    assert_eq!(fork.save_state(), emulator.save_state());
+    (0..3).for_each(|_| fork.run_frame());
+    assert_eq!((emulator.frame(), fork.frame()), (2, 5));
+    assert!(emulator.audio().is_empty());
+    assert!(!fork.audio().is_empty());
+
+    (0..3).for_each(|_| emulator.run_frame());
+    assert_eq!(emulator.save_state(), fork.save_state(), "the same frames from the same state");
+}
@@ -0,0 +1,20 @@
+// what terminals type for a file dropped on them, read back as the path at the browser's prompt
+use brick_station::debugger::browser::pasted_path;
+
+#[test]
+fn quotes_and_escapes_are_taken_off() {
+    assert_eq!(pasted_path("  /roms/game.nes \n"), "/roms/game.nes");
+    assert_eq!(pasted_path("'/roms/Super Game (USA).nes' "), "/roms/Super Game (USA).nes");
+    assert_eq!(pasted_path("\"/roms/it's.nes\""), "/roms/it's.nes");
+    assert_eq!(pasted_path("/roms/Super\\ Game\\ \\(USA\\).nes"), "/roms/Super Game (USA).nes");
+    // a backslash before anything else is kept, as in a windows path
+    assert_eq!(pasted_path("C:\\roms\\game.nes"), "C:\\roms\\game.nes");
+}
+
+#[test]
+fn file_urls_are_decoded() {
+    assert_eq!(pasted_path("file:///roms/Super%20Game%20%28USA%29.nes"), "/roms/Super Game (USA).nes");
+    assert_eq!(pasted_path("'file://localhost/roms/game.zip#a%2Bb.nes'"), "/roms/game.zip#a+b.nes");
+    // a % that does not start an escape stays
+    assert_eq!(pasted_path("file:///roms/100%.nes"), "/roms/100%.nes");
+}
@@ -0,0 +1,130 @@
+// ips and bps patches from small hand made vectors : ips records, runs, the "EOF" offset and
+// the truncate extension, bps actions, numbers and its three crc32s
+use std::io::ErrorKind;
+
+use brick_station::loader::patch;
+use brick_station::loader::rom_database::crc32;
+
+fn ips(records: &[u8], end: &[u8]) -> Vec<u8> {
+    [b"PATCH", records, b"EOF", end].concat()
+}
+
+// a bps number : seven bits a byte, the last one flagged, each continuation one less
+fn number(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let low = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(0x80 | low);
+            return bytes;
+        }
+        bytes.push(low);
+        value -= 1;
+    }
+}
+
+fn action(kind: usize, length: usize) -> Vec<u8> {
+    number((length - 1) << 2 | kind)
+}
+
+// the sign in the lowest bit
+fn relative(distance: isize) -> Vec<u8> {
+    number((distance.unsigned_abs() << 1) | (distance < 0) as usize)
+}
+
+fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+    let mut patch = [b"BPS1", &number(source.len())[..], &number(target.len()), &number(0), actions].concat();
+    patch.extend(crc32(source).to_le_bytes());
+    patch.extend(crc32(target).to_le_bytes());
+    patch.extend(crc32(&patch).to_le_bytes());
+    patch
+}
+
+fn refused(patch: &[u8], rom: &[u8]) -> String {
+    let err = patch::apply(patch, rom).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    err.to_string()
+}
+
+#[test]
+fn ips_records_runs_and_growth() {
+    let records = [
+        &[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB][..], // two bytes at 1
+        &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xCC], // a run of three at 4
+        &[0x00, 0x00, 0x0A, 0x00, 0x01, 0xDD],             // past the end, zeros in between
+    ].concat();
+    let patched = patch::apply(&ips(&records, &[]), &[0x11; 8]).unwrap();
+    assert_eq!(patched, vec![0x11, 0xAA, 0xBB, 0x11, 0xCC, 0xCC, 0xCC, 0x11, 0x00, 0x00, 0xDD]);
+    assert_eq!(refused(&ips(&[0x00, 0x00, 0x01, 0x00, 0x04, 0xAA], &[]), &[0; 8]), "Truncated patch");
+}
+
+#[test]
+fn ips_eof_is_an_offset_unless_the_patch_ends_there() {
+    // a record at $454F46 starts with the bytes of "EOF"
+    let patched = patch::apply(&ips(&[0x45, 0x4F, 0x46, 0x00, 0x01, 0x77], &[]), &[0; 4]).unwrap();
+    assert_eq!(patched.len(), 0x454F47);
+    assert_eq!(patched[0x454F46], 0x77);
+
+    // three bytes after "EOF" truncate
+    let patched = patch::apply(&ips(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x99], &[0x00, 0x00, 0x03]), &[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(patched, vec![0x99, 2, 3]);
+}
+
+#[test]
+fn bps_actions_with_overlapping_copies() {
+    let source = (0..200).map(|byte| byte as u8).collect::<Vec<u8>>();
+    let target = [&source[..4], b"hi", b"hihihi", &source[100..103]].concat();
+    let actions = [
+        action(0, 4),                     // source read
+        action(1, 2), b"hi".to_vec(),     // target read
+        action(3, 6), relative(4),        // target copy, over what it writes
+        action(2, 3), relative(100),      // source copy
+    ].concat();
+    // 200 takes two bytes as a number
+    assert_eq!(number(200).len(), 2);
+    assert_eq!(patch::apply(&bps(&source, &target, &actions), &source).unwrap(), target);
+}
+
+#[test]
+fn bps_checks_its_three_crcs() {
+    let source = b"source".to_vec();
+    let target = b"sourcesource".to_vec();
+    let actions = [action(0, 6), action(3, 6), relative(0)].concat();
+    let patch = bps(&source, &target, &actions);
+    assert_eq!(patch::apply(&patch, &source).unwrap(), target);
+
+    let mut corrupted = patch.clone();
+    corrupted[6] ^= 1;
+    assert!(refused(&corrupted, &source).contains("its crc32 does not match"));
+    assert!(refused(&patch, b"SOURCE").starts_with("Patch made for another rom"));
+
+    // the patch's own crc is right, the target it promises is not what the actions write
+    let wrong_target = bps(&source, b"sourcesourcx", &actions);
+    assert_eq!(refused(&wrong_target, &source), "Patched rom does not match the patch's crc32");
+}
+
+#[test]
+fn bps_copies_stay_in_bounds() {
+    let source = b"source".to_vec();
+    let out_of_bounds = "Copy out of bounds in patch";
+    // a source read past the end of the source
+    assert_eq!(refused(&bps(&source, b"sourcesource", &action(0, 12)), &source), out_of_bounds);
+    // a source copy before its start
+    assert_eq!(refused(&bps(&source, b"s", &[action(2, 1), relative(-1)].concat()), &source), out_of_bounds);
+    // a target copy of what is not written yet
+    assert_eq!(refused(&bps(&source, b"ss", &[action(0, 1), action(3, 1), relative(1)].concat()), &source), out_of_bounds);
+    // more than the target's size
+    assert_eq!(refused(&bps(&source, b"so", &action(0, 3)), &source), out_of_bounds);
+}
+
+#[test]
+fn bps_target_sizes_are_not_trusted_up_front() {
+    let source = b"source".to_vec();
+    let size = usize::MAX / 2;
+    let mut patch = [b"BPS1", &number(source.len())[..], &number(size), &number(0), &action(0, 6)].concat();
+    patch.extend(crc32(&source).to_le_bytes());
+    patch.extend(crc32(&source).to_le_bytes());
+    patch.extend(crc32(&patch).to_le_bytes());
+    assert_eq!(refused(&patch, &source), format!("Patch wrote 6 bytes instead of {}", size));
+}
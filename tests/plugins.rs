@@ -0,0 +1,153 @@
+// a peripheral written outside the crate, as a downstream crate would : a latch with a
+// counter clocked by the cpu, attached directly and placed by a machine description
+use std::io::Error;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use brick_station::emulator::Emulator;
+use brick_station::hardware::inspect::{machine_tree, DebugState, StateNode};
+use brick_station::hardware::interfaces::{Clocked, DeviceOps};
+use brick_station::hardware::machine::Machine;
+use brick_station::hardware::plugin::{self, Plugin};
+use brick_station::hardware::profile::MachineProfile;
+use brick_station::hardware::ram::PowerOnPattern;
+use brick_station::hardware::snapshot::{put_u64, put_u8, take_u64, take_u8, Snapshot};
+
+// $base holds the last byte written, $base + 1 reads the cycles seen so far divided by 256
+#[derive(Clone)]
+struct Latch {
+    base   : u16,
+    value  : u8,
+    cycles : u64,
+}
+
+impl DeviceOps for Latch {
+    fn within_range(&self, addr: u16) -> bool {
+        (self.base..=self.base + 1).contains(&addr)
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        if addr == self.base { self.value } else { (self.cycles >> 8) as u8 }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr == self.base {
+            self.value = value;
+        }
+    }
+}
+
+impl Clocked for Latch {
+    fn catch_up(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+}
+
+impl Snapshot for Latch {
+    fn save(&self, out: &mut Vec<u8>) {
+        put_u8(out, self.value);
+        put_u64(out, self.cycles);
+    }
+
+    fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> {
+        self.value = take_u8(input)?;
+        self.cycles = take_u64(input)?;
+        Ok(())
+    }
+}
+
+impl DebugState for Latch {
+    fn debug_state(&self) -> Vec<StateNode> {
+        vec![StateNode::byte("value", self.value), StateNode::value("cycles", self.cycles)]
+    }
+}
+
+impl Plugin for Latch {
+    fn name(&self) -> &'static str {
+        "latch"
+    }
+
+    fn regions(&self) -> Vec<RangeInclusive<u16>> {
+        vec![self.base..=self.base + 1]
+    }
+
+    fn clocked(&mut self) -> Option<&mut dyn Clocked> {
+        Some(self)
+    }
+}
+
+fn latch(base: u16, options: &str) -> Result<Box<dyn Plugin>, Error> {
+    let value = u8::from_str_radix(options.trim_start_matches('$'), 16).unwrap_or(0);
+    Ok(Box::new(Latch { base, value, cycles : 0 }))
+}
+
+#[test]
+fn attached_plugins_sit_on_the_bus() {
+    let mut emulator = Emulator::default();
+    emulator.attach(Box::new(Latch { base : 0x5000, value : 0, cycles : 0 }), Vec::new());
+    emulator.write(0x5000, 0x42);
+    assert_eq!(emulator.read(0x5000), 0x42);
+    assert_eq!(emulator.read(0x5002), 0, "the rest of the page is still ram");
+
+    let state = emulator.save_state();
+    emulator.write(0x5000, 0x17);
+    emulator.load_state(&state).unwrap();
+    assert_eq!(emulator.read(0x5000), 0x42);
+
+    emulator.power_cycle();
+    assert_eq!(emulator.read(0x5000), 0, "a power cycle attaches the plugin as it was given");
+}
+
+#[test]
+fn plugins_are_clocked_and_shown_in_the_explorer() {
+    let machine = Machine::new();
+    machine.attach(Box::new(Latch { base : 0x5000, value : 0, cycles : 0 }), vec![0x6000..=0x6001]);
+    while machine.cpu.borrow().total_cycles < 0x1000 {
+        machine.tick();
+    }
+    assert!(machine.bus.borrow().peek(0x6001) >= 0x08, "catches up with the cpu at the sample points");
+
+    let tree = machine_tree(&machine);
+    let latch = tree.iter().find(|node| node.name == "latch").expect("the latch in the tree");
+    assert_eq!(latch.value, "$6000-$6001");
+    assert_eq!(latch.children[0], StateNode::byte("value", 0));
+}
+
+#[test]
+fn descriptions_place_registered_plugins() {
+    plugin::register("latch", latch);
+    let profile = MachineProfile::parse("[machine]\nram = \"$0000-$FFFF\"\nplugin = \"latch $D000 $5A\"\n", Path::new("")).unwrap();
+    let machine = Machine::build(&profile, PowerOnPattern::Fill(0));
+    assert_eq!(machine.bus.borrow().peek(0xD000), 0x5A);
+
+    let unknown = MachineProfile::parse("[machine]\nplugin = \"sd_card $D000\"\n", Path::new(""));
+    assert!(unknown.unwrap_err().to_string().contains("No device plugin registered as sd_card"));
+}
+
+#[test]
+fn savestates_only_restore_into_the_same_plugin() {
+    #[derive(Clone)]
+    struct Other(Latch);
+    impl DeviceOps for Other {
+        fn read(&self, addr: u16) -> u8 { self.0.read(addr) }
+        fn write(&mut self, addr: u16, value: u8) { self.0.write(addr, value) }
+        fn within_range(&self, addr: u16) -> bool { self.0.within_range(addr) }
+    }
+    impl Snapshot for Other {
+        fn save(&self, out: &mut Vec<u8>) { self.0.save(out) }
+        fn restore(&mut self, input: &mut &[u8]) -> Result<(), Error> { self.0.restore(input) }
+    }
+    impl DebugState for Other {
+        fn debug_state(&self) -> Vec<StateNode> { self.0.debug_state() }
+    }
+    impl Plugin for Other {
+        fn name(&self) -> &'static str { "other" }
+        fn regions(&self) -> Vec<RangeInclusive<u16>> { self.0.regions() }
+    }
+
+    let mut with_latch = Emulator::default();
+    with_latch.attach(Box::new(Latch { base : 0x5000, value : 0, cycles : 0 }), Vec::new());
+    let mut with_other = Emulator::default();
+    with_other.attach(Box::new(Other(Latch { base : 0x5000, value : 0, cycles : 0 })), Vec::new());
+    assert!(with_other.load_state(&with_latch.save_state()).is_err());
+}
@@ -0,0 +1,26 @@
+// blargg's ppu test roms, run headlessly and read through the $6000 status protocol, held to
+// the outcomes recorded in tests/ppu_test_roms.txt as tests/apu_test_roms.rs does : the ppu
+// only has its registers and draws nothing, so most of them cannot pass and what is checked
+// is that none changes, ppu_sprite_hit failing included. the roms are not part of the
+// repository : put them (or their rom_singles directories) in tests/roms or point
+// BRICK_STATION_TEST_ROMS at them, then
+//   BRICK_STATION_BLESS=1 cargo test --release --test ppu_test_roms -- --ignored --nocapture
+// records the outcomes, and without BRICK_STATION_BLESS holds them to it. ignored until
+// outcomes are recorded : without them it would check nothing
+use std::path::Path;
+
+use brick_station::test_rom;
+
+const SUITES      : [&str; 3] = ["ppu_vbl_nmi", "vbl_nmi_timing", "ppu_sprite_hit"];
+// 40 seconds of emulated time
+const FRAME_LIMIT : u64 = 60 * 40;
+const GOLDEN      : &str = "tests/ppu_test_roms.txt";
+
+#[test]
+#[ignore = "no outcomes are recorded in tests/ppu_test_roms.txt, the roms were not at hand"]
+fn ppu_test_roms() {
+    match test_rom::hold_to_golden(&SUITES, FRAME_LIMIT, Path::new(GOLDEN)).unwrap_or_else(|err| panic!("{}", err)) {
+        None => panic!("no ppu test roms in {}, nothing checked", test_rom::directory().display()),
+        Some(changed) => assert!(changed.is_empty(), "{} roms changed:\n{}", changed.len(), changed.join("\n")),
+    }
+}
@@ -0,0 +1,160 @@
+// the vblank flag and nmi around the dot the flag is set, read from each of the three dots
+// a cpu cycle can start on : dot 1 of scanline 241 is 82182, cpu cycle 27394 with the ppu
+// aligned on its first dot
+use brick_station::hardware::interfaces::{Clocked, DeviceOps};
+use brick_station::hardware::machine::Machine;
+use brick_station::hardware::ppu::Ppu;
+
+const SET_CYCLE   : u64 = 27394;
+const FRAME_DOTS  : u64 = 341 * 262;
+const NMI_ENABLE  : u8 = 0x80;
+
+// a ppu caught up to the cycle, with $2000 written before vblank
+fn ppu_at(cycle: u64, alignment: u8, ctrl: u8) -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.alignment = alignment;
+    ppu.write(0x2000, ctrl);
+    ppu.catch_up(cycle);
+    ppu
+}
+
+#[test]
+fn reads_before_vblank_see_the_flag_clear() {
+    let ppu = ppu_at(SET_CYCLE - 1, 0, 0);
+    assert_eq!(ppu.read(0x2002) & 0x80, 0);
+}
+
+#[test]
+fn a_read_one_dot_early_keeps_the_flag_from_being_set() {
+    // cycle 27393 with the ppu two dots ahead starts on dot 82181
+    let mut ppu = ppu_at(SET_CYCLE - 1, 2, NMI_ENABLE);
+    assert_eq!(ppu.read(0x2002) & 0x80, 0);
+    ppu.catch_up(SET_CYCLE + 10);
+    assert_eq!(ppu.read(0x2002) & 0x80, 0, "suppressed for the frame");
+    assert!(!ppu.nmi());
+
+    // the next frame sets it again
+    ppu.catch_up(SET_CYCLE + FRAME_DOTS / 3 + 10);
+    assert_eq!(ppu.peek(0x2002) & 0x80, 0x80);
+}
+
+#[test]
+fn reads_on_the_dot_it_is_set_or_the_next_suppress_nmi() {
+    for alignment in [0, 1] {
+        let mut ppu = ppu_at(SET_CYCLE, alignment, NMI_ENABLE);
+        assert!(!ppu.nmi(), "not out yet at alignment {}", alignment);
+        assert_eq!(ppu.read(0x2002) & 0x80, 0x80, "read set at alignment {}", alignment);
+        ppu.catch_up(SET_CYCLE + 10);
+        assert!(!ppu.nmi(), "cleared before the nmi got out at alignment {}", alignment);
+    }
+}
+
+#[test]
+fn later_reads_come_after_the_nmi() {
+    let mut ppu = ppu_at(SET_CYCLE, 2, NMI_ENABLE);
+    assert!(ppu.nmi());
+    assert_eq!(ppu.read(0x2002) & 0x80, 0x80);
+    assert!(!ppu.nmi(), "reading acknowledges it");
+    ppu.catch_up(SET_CYCLE + 1);
+    assert_eq!(ppu.read(0x2002) & 0x80, 0, "read once only");
+}
+
+#[test]
+fn enabling_nmi_inside_vblank_raises_it() {
+    let mut ppu = ppu_at(SET_CYCLE + 100, 0, 0);
+    assert!(!ppu.nmi());
+    ppu.write(0x2000, NMI_ENABLE);
+    assert!(ppu.nmi());
+}
+
+#[test]
+fn the_pre_render_line_clears_the_flag() {
+    let mut ppu = ppu_at(SET_CYCLE + 10, 0, 0);
+    assert_eq!(ppu.peek(0x2002) & 0x80, 0x80);
+    ppu.catch_up((261 * 341 + 1) / 3 + 1);
+    assert_eq!(ppu.peek(0x2002) & 0x80, 0);
+}
+
+// a program waiting in a loop with nmi enabled, its handler counting frames at $10
+#[test]
+fn the_console_takes_one_nmi_per_frame() {
+    let machine = Machine::new();
+    let program = [
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // lda #$80, sta $2000
+        0x4C, 0x05, 0x80,             // jmp *
+        0xE6, 0x10, 0x40,             // nmi : inc $10, rti
+    ];
+    {
+        let mut bus = machine.bus.borrow_mut();
+        for (offset, byte) in program.iter().enumerate() {
+            bus.write(0x8000 + offset as u16, *byte);
+        }
+        bus.write(0xFFFA, 0x08);
+        bus.write(0xFFFB, 0x80);
+    }
+    machine.cpu.borrow_mut().reset();
+    for _ in 0..3 {
+        while !machine.tick() {}
+    }
+    // the third frame's nmi gets out two dots after the flag
+    for _ in 0..3 {
+        machine.step();
+    }
+    assert_eq!(machine.bus.borrow().peek(0x0010), 3);
+}
+
+#[test]
+fn write_only_registers_read_back_the_fading_latch() {
+    let mut ppu = ppu_at(0, 0, 0);
+    ppu.write(0x2005, 0xA5);
+    assert_eq!(ppu.read(0x2000), 0xA5);
+    assert_eq!(ppu.read(0x3FFD), 0xA5, "mirrored every 8 bytes");
+
+    // $2002 only drives its top bits, the low ones keep fading from the write
+    ppu.catch_up(SET_CYCLE + 10);
+    assert_eq!(ppu.read(0x2002), 0x85);
+    ppu.catch_up(SET_CYCLE + 36 * FRAME_DOTS / 3);
+    assert_eq!(ppu.read(0x2000), 0x80, "read since, bit 7 is still driven");
+    ppu.catch_up(SET_CYCLE + 72 * FRAME_DOTS / 3);
+    assert_eq!(ppu.read(0x2000), 0);
+}
+
+#[test]
+fn the_latch_holds_without_decay() {
+    let mut ppu = ppu_at(0, 0, 0);
+    ppu.decay.open_bus = false;
+    ppu.write(0x2000, 0x5A);
+    ppu.catch_up(100 * FRAME_DOTS / 3);
+    assert_eq!(ppu.read(0x2006), 0x5A);
+}
+
+#[test]
+fn sprite_memory_through_2003_and_2004() {
+    let mut ppu = ppu_at(0, 0, 0);
+    ppu.write(0x2003, 0xFE);
+    for value in [0x11, 0x22, 0x33, 0xFF] {
+        ppu.write(0x2004, value);
+    }
+    assert_eq!(ppu.oam[0xFE..], [0x11, 0x22]);
+    assert_eq!(ppu.oam[..2], [0x33, 0xFF], "the address wraps");
+    ppu.write(0x2003, 0x01);
+    assert_eq!(ppu.read(0x2004), 0xFF);
+    ppu.write(0x2003, 0x02);
+    ppu.write(0x2004, 0xFF);
+    ppu.write(0x2003, 0x02);
+    assert_eq!(ppu.read(0x2004), 0xE3, "attribute bits 2 - 4 do not exist");
+}
+
+#[test]
+fn sprite_memory_fades_while_rendering_is_off() {
+    for (oam_decay, rendering, expected) in [(false, 0x00, 0x42), (true, 0x18, 0x42), (true, 0x00, 0xFF)] {
+        let mut ppu = ppu_at(0, 0, 0);
+        ppu.decay.oam = oam_decay;
+        ppu.write(0x2001, rendering);
+        ppu.write(0x2003, 0x40);
+        ppu.write(0x2004, 0x42);
+        ppu.catch_up(4000);
+        ppu.write(0x2003, 0x40);
+        assert_eq!(ppu.read(0x2004), expected, "oam decay {}, $2001 {:02X}", oam_decay, rendering);
+    }
+}
@@ -0,0 +1,86 @@
+// breaks on the reads or writes of a ppu or apu register by name, on every mirror of it,
+// checked in the state explorer's groups and kept with the session
+use std::env;
+use std::fs;
+
+use brick_station::cli::Arguments;
+use brick_station::debugger::debugger::App;
+use brick_station::debugger::register_breaks::{self, RegisterBreak};
+use brick_station::debugger::session::Session;
+use brick_station::hardware::watches::Access;
+
+fn register_break(text: &str) -> RegisterBreak {
+    text.parse().unwrap()
+}
+
+// sei, $3f to PPUADDR then to PPUDATA through its last mirror, a sprite dma from page 2 and
+// it stays at $800E
+const PPU_WRITES: &str = "78 A9 3F 8D 06 20 8D FF 3F A9 02 8D 14 40 4C 0E 80";
+
+fn app() -> App<'static> {
+    let path = env::temp_dir().join(format!("brick_station_register_breaks_{}.txt", std::process::id()));
+    fs::write(&path, PPU_WRITES).unwrap();
+    let arguments = Arguments::parse(&[path.to_str().unwrap().to_string()]).unwrap();
+    let mut app = App::new(&arguments).unwrap();
+    let program = app.load_current_program().unwrap();
+    app.load_program(&program);
+    fs::remove_file(&path).unwrap();
+    app
+}
+
+#[test]
+fn registers_are_named_and_mirrors_fold_back() {
+    let data = register_break("w ppudata");
+    assert_eq!(data, RegisterBreak { register: 0x2007, access: Access::Write });
+    assert_eq!(data.to_string(), "w PPUDATA");
+    assert_eq!(register_break("read OAMDMA"), RegisterBreak { register: 0x4014, access: Access::Read });
+    assert!("w $2007".parse::<RegisterBreak>().is_err());
+    assert!("x PPUDATA".parse::<RegisterBreak>().is_err());
+
+    assert_eq!(register_breaks::register(0x3FFF), Some(0x2007));
+    assert_eq!(register_breaks::register(0x2012), Some(0x2002));
+    assert_eq!(register_breaks::register(0x4016), None);
+    assert_eq!(register_breaks::at("ppu/break on/PPUDATA $2007"), Some(0x2007));
+    assert_eq!(register_breaks::at("ppu/ctrl"), None);
+}
+
+#[test]
+fn a_write_through_a_mirror_stops_on_the_register() {
+    let mut app = app();
+    app.register_breaks.insert(register_break("w PPUDATA"));
+    app.register_breaks.insert(register_break("w OAMDATA"));
+    app.run_until_breakpoint();
+    assert_eq!(app.stop_reason.as_deref(), Some("write 3F to PPUDATA ($3FFF) at $8006"));
+
+    // the dma's 256 writes to $2004 are one stop, made as the cpu is halted at the start of
+    // the instruction after the write to $4014
+    app.run_until_breakpoint();
+    assert_eq!(app.stop_reason.as_deref(), Some("write 00 to OAMDATA at $800E (256 times)"));
+}
+
+#[test]
+fn the_explorer_shows_the_boxes_under_their_device() {
+    let mut app = app();
+    app.register_breaks.insert(register_break("r PPUSTATUS"));
+    let tree = app.state_tree();
+    let group = |device: &str| tree.iter()
+        .find(|node| node.name == device)
+        .and_then(|node| node.children.iter().find(|child| child.name == register_breaks::GROUP))
+        .unwrap();
+    let status = group("ppu").children.iter().find(|row| row.name == "PPUSTATUS $2002").unwrap();
+    assert_eq!(status.value, "[x] read [ ] write");
+    assert!(group("apu").children.iter().any(|row| row.name == "OAMDMA $4014"));
+}
+
+#[test]
+fn sessions_keep_the_register_breaks() {
+    let directory = env::temp_dir().join(format!("brick_station_register_breaks_{}", std::process::id()));
+    let session = Session {
+        registers : vec![register_break("r PPUSTATUS"), register_break("w OAMDMA")],
+        ..Session::default()
+    };
+    session.save(&directory).unwrap();
+    let loaded = Session::load(&directory).unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(loaded.registers, session.registers);
+}
@@ -0,0 +1,102 @@
+// images are known by their data whatever their header says, and the settings of a game
+// correct its header and reach the machine's own
+use std::fs;
+
+use brick_station::config::Config;
+use brick_station::loader::ines::Mirroring;
+use brick_station::loader::loader::{self, Format};
+use brick_station::loader::rom_database::{GameSettings, RomDatabase, RomIdentity};
+
+// 16k of prg ending on its vectors, no chr : mapper 0, horizontal, no battery
+fn image(flags6: u8) -> Vec<u8> {
+    let mut image = b"NES\x1A\x01\x00".to_vec();
+    image.extend([flags6, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    image.extend(vec![0xEA; 0x4000]);
+    image
+}
+
+fn game(settings: &str, content: &[u8]) -> GameSettings {
+    GameSettings { identity: RomIdentity::of(content), name: None, settings: Config::parse(settings).unwrap() }
+}
+
+#[test]
+fn a_bad_header_does_not_change_the_identity() {
+    assert_eq!(RomIdentity::of(&image(0x00)), RomIdentity::of(&image(0x13)));
+    assert_eq!(RomIdentity::of(&image(0x00)), RomIdentity::of(&vec![0xEA; 0x4000]));
+}
+
+#[test]
+fn header_keys_correct_the_board() {
+    let content = image(0x00);
+    let mut program = loader::parse(Format::INes, &content, 0).unwrap();
+    let settings = "[header]\nmapper = 24\nmirroring = vertical\nprg_ram = 8\nbattery = true\n";
+    game(settings, &content).correct(&mut program).unwrap();
+    let board = program.image.unwrap();
+    assert_eq!((board.mapper, board.mirroring, board.prg_ram, board.battery), (24, Mirroring::Vertical, Some(0x2000), true));
+
+    let mut program = loader::parse(Format::INes, &content, 0).unwrap();
+    assert!(game("[header]\nmirroring = diagonal\n", &content).correct(&mut program).is_err());
+}
+
+#[test]
+fn input_and_video_keys_go_over_the_config() {
+    let config = Config::parse("[input]\nport2 = controller\n[video]\noverscan_top = 8\nscale = 3\n").unwrap();
+    let overlaid = game("[input]\nport2 = zapper\n[video]\noverscan_top = 0\n", &image(0)).overlaid(&config);
+    assert_eq!(overlaid.get("input.port2"), Some("zapper"));
+    assert_eq!(overlaid.get("video.overscan_top"), Some("0"));
+    assert_eq!(overlaid.get("video.scale"), Some("3"));
+}
+
+// as a NesCart export has it, with a second cartridge of the same game and one never loaded
+fn nescart(identity: &RomIdentity) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<database version="1.0">
+<game name="Brick &amp; Mortar" region="USA">
+  <cartridge system="NES-NTSC" crc="{:08X}" sha1="{}" dump="ok">
+    <board type="NES-SNROM" mapper="1">
+      <prg size="16k"/>
+      <wram size="8k" battery="1"/>
+      <pad h="0" v="1"/>
+    </board>
+  </cartridge>
+  <cartridge system="NES-NTSC" crc="12345678" sha1="">
+    <board type="NES-NROM-128" mapper="0"/>
+  </cartridge>
+</game>
+</database>
+"#, identity.crc32, identity.sha1_hex().to_uppercase())
+}
+
+#[test]
+fn nescart_records_become_header_keys() {
+    let content = image(0x00);
+    let identity = RomIdentity::of(&content);
+    let database = RomDatabase::parse_nescart(&nescart(&identity));
+    assert_eq!(database.entries.len(), 2);
+    let entry = database.lookup(&identity).unwrap();
+    assert_eq!(entry.name, "Brick & Mortar");
+    let header = ["header.mapper", "header.mirroring", "header.prg_ram", "header.battery"].iter()
+        .map(|key| entry.overrides.get(key))
+        .collect::<Vec<Option<&str>>>();
+    assert_eq!(header, vec![Some("1"), Some("vertical"), Some("8"), Some("true")]);
+    assert!(database.entries[1].overrides.get("header.prg_ram").is_none());
+
+    // a sha1 that does not match keeps a crc32 collision out
+    assert!(database.lookup(&RomIdentity { sha1: [0; 20], ..identity }).is_none());
+}
+
+#[test]
+fn games_are_looked_up_in_the_nescart_export_given() {
+    let content = image(0x00);
+    let path = std::env::temp_dir().join(format!("brick_station_nescart_{}.xml", std::process::id()));
+    fs::write(&path, nescart(&RomIdentity::of(&content))).unwrap();
+    let config = Config::parse(&format!("[games]\nnescart = \"{}\"\n", path.display())).unwrap();
+    let game = GameSettings::identify(&config, "brick.nes", &content).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(game.name.as_deref(), Some("Brick & Mortar"));
+    let mut program = loader::parse(Format::INes, &content, 0).unwrap();
+    game.correct(&mut program).unwrap();
+    let board = program.image.unwrap();
+    assert_eq!((board.mapper, board.mirroring, board.prg_ram, board.battery), (1, Mirroring::Vertical, Some(0x2000), true));
+}
@@ -0,0 +1,63 @@
+// assemble(disassemble(bytes)) == bytes, for every legal opcode over its operand values
+use brick_station::debugger::assembler::assemble;
+use brick_station::debugger::disassembler::Disassembler;
+use brick_station::hardware::opcode_table::OPCODES;
+
+const ORIGINS: [u16; 4] = [0x0000, 0x8000, 0xC0FE, 0xFFF0];
+
+// the first line, without the cycle count
+fn disassemble(bytes: &[u8], origin: u16) -> String {
+    let line = Disassembler::disassemble_at(bytes, origin).program[0].clone();
+    line.rsplit_once(' ').map_or(line.as_str(), |(text, _)| text).trim_end().to_string()
+}
+
+fn round_trip(bytes: &[u8], origin: u16) {
+    let text = disassemble(bytes, origin);
+    match assemble(&text, origin) {
+        Ok(assembled) => assert_eq!(assembled, bytes, "{:02X?} at ${:04X} reads as `{}`", bytes, origin, text),
+        Err(error) => panic!("{:02X?} at ${:04X} reads as `{}`, which does not assemble : {}", bytes, origin, text, error),
+    }
+}
+
+fn operand_bytes(opcode: u8) -> usize {
+    Disassembler::operand_length(&OPCODES[opcode as usize].address_mode)
+}
+
+#[test]
+fn every_legal_opcode_without_or_with_a_byte_operand() {
+    for instruction in OPCODES.iter().filter(|instruction| instruction.legal && operand_bytes(instruction.opcode) < 2) {
+        for origin in ORIGINS {
+            for byte in 0..=0xFF {
+                let bytes = [instruction.opcode, byte];
+                round_trip(&bytes[..1 + operand_bytes(instruction.opcode)], origin);
+            }
+        }
+    }
+}
+
+#[test]
+fn every_legal_opcode_with_a_word_operand() {
+    // both bytes through all their values, the low one against a few high ones and back
+    let words = (0..=0xFFu16).flat_map(|byte| [byte, byte << 8, byte * 0x0101, byte ^ 0xFF00]);
+    let words = words.collect::<Vec<u16>>();
+    for instruction in OPCODES.iter().filter(|instruction| instruction.legal && operand_bytes(instruction.opcode) == 2) {
+        for word in words.iter() {
+            round_trip(&[instruction.opcode, *word as u8, (*word >> 8) as u8], 0x8000);
+        }
+    }
+}
+
+#[test]
+fn illegal_opcodes_as_bytes() {
+    for instruction in OPCODES.iter().filter(|instruction| !instruction.legal) {
+        round_trip(&[instruction.opcode], 0x8000);
+    }
+}
+
+#[test]
+fn branches_out_of_range_are_refused() {
+    assert!(assemble("BNE $8100", 0x8000).is_err());
+    assert!(assemble("BNE $7F81", 0x8000).is_err());
+    assert_eq!(assemble("BNE $7F82", 0x8000).unwrap(), vec![0xD0, 0x80]);
+    assert_eq!(assemble("BNE $8081", 0x8000).unwrap(), vec![0xD0, 0x7F]);
+}
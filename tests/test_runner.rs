@@ -0,0 +1,111 @@
+// the test subcommand : a directory of roms and its manifest run side by side, each held to
+// its expected outcome and frame hash, with junit and json reports
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use brick_station::remote::json::Json;
+use brick_station::test_rom::Outcome;
+use brick_station::test_runner::{self, Expectation};
+
+// an nrom image of the $6000 protocol : the signature, the text, then the result code
+fn rom(code: u8, text: &str) -> Vec<u8> {
+    let mut program = vec![0x78];
+    let bytes = [0xDE, 0xB0, 0x61].iter().copied().chain(text.bytes()).chain([0]);
+    for (address, value) in (0x6001u16..).zip(bytes) {
+        program.extend([0xA9, value, 0x8D, address as u8, (address >> 8) as u8]);
+    }
+    program.extend([0xA9, code, 0x8D, 0x00, 0x60]);
+    let end = 0xC000 + program.len() as u16;
+    program.extend([0x4C, end as u8, (end >> 8) as u8]);
+
+    let mut prg = vec![0xEA; 0x4000];
+    prg[..program.len()].copy_from_slice(&program);
+    prg[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+    let mut image = b"NES\x1A\x01\x01".to_vec();
+    image.extend([0; 10]);
+    image.extend(prg);
+    image.extend([0; 0x2000]);
+    image
+}
+
+fn directory(name: &str, manifest: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("brick_station_test_runner_{}_{}", std::process::id(), name));
+    fs::create_dir_all(directory.join("singles")).unwrap();
+    fs::write(directory.join("passes.nes"), rom(0, "all good")).unwrap();
+    fs::write(directory.join("singles").join("fails.nes"), rom(3, "<bad> & worse")).unwrap();
+    fs::write(directory.join(test_runner::MANIFEST), manifest).unwrap();
+    directory
+}
+
+const MANIFEST: &str = "frames = 120
+[test.on_purpose]
+rom = \"singles/fails.nes\"
+expect = \"failed with code 3\"
+[test.wrong_picture]
+rom = \"passes.nes\"
+hash = \"12345678\"
+";
+
+#[test]
+fn the_manifest_comes_first_then_the_roms_it_leaves_out() {
+    let directory = directory("manifest", MANIFEST);
+    let tests = test_runner::manifest(&directory, None).unwrap();
+    let unlisted = test_runner::manifest(&directory, Some(7)).unwrap();
+    fs::write(directory.join(test_runner::MANIFEST), "[test.broken]\nexpect = \"sideways\"\n").unwrap();
+    let broken = test_runner::manifest(&directory, None);
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(tests, vec![
+        Expectation { name: "on_purpose".to_string(), rom: directory.join("singles/fails.nes"), outcome: Outcome::Failed(3), frames: 120, hash: None },
+        Expectation { name: "wrong_picture".to_string(), rom: directory.join("passes.nes"), outcome: Outcome::Passed, frames: 120, hash: Some(0x12345678) },
+    ]);
+    assert_eq!(unlisted.iter().map(|test| test.frames).collect::<Vec<u64>>(), vec![7, 7]);
+    assert!(broken.is_err());
+}
+
+#[test]
+fn roms_run_side_by_side_and_come_back_in_order() {
+    let directory = directory("run", "[test.on_purpose]\nrom = \"singles/fails.nes\"\nexpect = \"failed with code 3\"\n");
+    fs::write(directory.join("singles").join("garbage.nes"), b"not a rom").unwrap();
+    let tests = test_runner::manifest(&directory, Some(60)).unwrap();
+    let finished = Mutex::new(Vec::new());
+    let results = test_runner::run_all(&tests, 3, |result| finished.lock().unwrap().push(result.expectation.name.clone()));
+    fs::remove_dir_all(&directory).unwrap();
+
+    let names = results.iter().map(|result| result.expectation.name.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["on_purpose", "passes", "singles/garbage"]);
+    assert_eq!(finished.into_inner().unwrap().len(), 3);
+    assert!(results[0].passed() && results[1].passed(), "{}\n{}", results[0].describe(), results[1].describe());
+    assert_eq!(results[1].report.as_ref().unwrap().text, "all good");
+    assert!(results[2].report.is_err() && !results[2].passed());
+    assert!(results[2].describe().starts_with("FAILED singles/garbage : "));
+}
+
+#[test]
+fn reports_tell_the_failures_apart() {
+    let directory = directory("reports", MANIFEST);
+    fs::write(directory.join("missing.nes"), b"").unwrap();
+    let tests = test_runner::manifest(&directory, None).unwrap();
+    let results = test_runner::run_all(&tests, 2, |_| {});
+    fs::remove_dir_all(&directory).unwrap();
+
+    // the ppu draws nothing, the frame is never drawn into
+    let failure = "nothing drew into the frame, expected hash 12345678";
+    assert_eq!(results[1].report.as_ref().unwrap().hash, None);
+    assert_eq!(results[1].failures(), vec![failure]);
+
+    let junit = test_runner::junit(&results);
+    assert!(junit.contains("<testsuite name=\"brick_station\" tests=\"3\" failures=\"1\" errors=\"1\""), "{}", junit);
+    assert!(junit.contains("<system-out>&lt;bad&gt; &amp; worse</system-out>"), "{}", junit);
+    assert!(junit.contains(&format!("<failure message=\"{}\"/>", failure)), "{}", junit);
+
+    let json = Json::parse(&test_runner::json(&results).to_string()).unwrap();
+    let tests = json.as_array().unwrap();
+    assert_eq!(tests[0].get("outcome").and_then(Json::as_str), Some("failed with code 3"));
+    assert_eq!(tests[1].get("passed"), Some(&Json::Bool(false)));
+    assert_eq!(tests[1].get("expected_hash").and_then(Json::as_str), Some("12345678"));
+    assert_eq!(tests[2].get("outcome"), Some(&Json::Null));
+    assert!(Path::new(tests[2].get("rom").and_then(Json::as_str).unwrap()).ends_with("missing.nes"));
+}
@@ -0,0 +1,59 @@
+// turbo buttons pressed and released in turn from frame to frame, at the rate asked for
+use brick_station::config::Config;
+use brick_station::debugger::debugger::State;
+use brick_station::emulator::Emulator;
+use brick_station::hardware::controller::{self, Button, Turbo};
+use brick_station::hardware::device::Device;
+
+fn pad(emulator: &Emulator, port: u16) -> u8 {
+    emulator.machine.bus.borrow().devices.iter()
+        .find_map(|mapping| match &*mapping.device.borrow() {
+            Device::Controller(pad) if pad.port == port => Some(pad.buttons),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn the_rate_sets_the_frames_pressed_and_released() {
+    let pattern = |turbo: Turbo| (0..8).map(|frame| turbo.pressed(frame)).collect::<Vec<bool>>();
+    assert_eq!(pattern(Turbo::default()), [true, true, false, false, true, true, false, false]);
+    assert_eq!(pattern(Turbo::new(30)), [true, false, true, false, true, false, true, false]);
+    assert_eq!(pattern(Turbo::new(10)), [true, true, true, false, false, false, true, true]);
+    assert_eq!(Turbo::new(0).rate, 1);
+    assert_eq!(Turbo::new(60).rate, Turbo::MAX_RATE);
+}
+
+#[test]
+fn held_buttons_stay_pressed_over_turbo_ones() {
+    let turbo = Turbo::new(30);
+    let (a, b) = (Button::A as u8, Button::B as u8);
+    assert_eq!(turbo.apply(b, a, 0), a | b);
+    assert_eq!(turbo.apply(b, a, 1), b);
+    assert_eq!(turbo.apply(a, a, 1), a, "held as a plain button too");
+    assert_eq!(controller::describe_turbo(b, a), "aB......");
+}
+
+#[test]
+fn frames_run_latch_the_turbo_buttons() {
+    let mut emulator = Emulator::default();
+    emulator.turbo = Turbo::new(30);
+    emulator.set_buttons(0, Button::Start as u8);
+    emulator.set_turbo_buttons(1, Button::B as u8);
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        emulator.run_frame();
+        seen.push((pad(&emulator, 0), pad(&emulator, 1)));
+    }
+    let (start, b) = (Button::Start as u8, Button::B as u8);
+    assert_eq!(seen, [(start, b), (start, 0), (start, b), (start, 0)]);
+}
+
+#[test]
+fn key_bindings_leave_the_command_keys_alone() {
+    let config = Config::parse("[pad1]\na = 1\nturbo_a = c\n[pad2]\nb = x\nstart = 4\n").unwrap();
+    let bindings = State::pad_bindings(&config);
+    assert!(matches!(bindings.get(&'1'), Some((0, Button::A, false))));
+    assert!(matches!(bindings.get(&'4'), Some((1, Button::Start, false))));
+    assert_eq!(bindings.len(), 2);
+}
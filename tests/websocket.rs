@@ -0,0 +1,30 @@
+// a frame announcing more than the message limit is refused before anything is allocated,
+// whatever its 64 bit length
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use brick_station::remote::websocket::WebSocket;
+
+#[test]
+fn huge_lengths_are_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n").unwrap();
+        let mut response = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        response.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 101"), "{}", line);
+        let mut frame = vec![0x81, 0xFF];
+        frame.extend(u64::MAX.to_be_bytes());
+        stream.write_all(&frame).unwrap();
+        stream
+    });
+    let mut socket = WebSocket::accept(listener.accept().unwrap().0).unwrap();
+    let err = socket.receive().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "message too large");
+    drop(client.join().unwrap());
+}